@@ -14,3 +14,12 @@ pub struct SqlParam {
 pub struct WriteParam {
     pub db: String,
 }
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct BackupParam {
+    pub db: String,
+    pub path: String,
+    /// Id of the backup that an incremental backup is based on.
+    pub since: Option<String>,
+}
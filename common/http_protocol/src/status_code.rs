@@ -5,6 +5,8 @@ pub const OK: StatusCode = StatusCode::OK;
 pub const BAD_REQUEST: StatusCode = StatusCode::BAD_REQUEST;
 /// 用户密码错误 或 用户不存在
 pub const _UNAUTHORIZED: StatusCode = StatusCode::UNAUTHORIZED;
+/// 用户没有执行该操作的权限
+pub const FORBIDDEN: StatusCode = StatusCode::FORBIDDEN;
 /// 路径不存在
 pub const NOT_FOUND: StatusCode = StatusCode::NOT_FOUND;
 /// 路径不支持对应的请求方式
@@ -1,6 +1,8 @@
 use once_cell::sync::Lazy;
 use prometheus::Registry;
-use prometheus::{linear_buckets, HistogramOpts, HistogramVec, IntCounter, Opts};
+use prometheus::{
+    linear_buckets, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts,
+};
 use trace::error;
 
 pub const SERVER_NAMESPACE: &str = "server";
@@ -173,6 +175,71 @@ pub static COMPACTION_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
     .expect("tskv metric cannot be created")
 });
 
+pub static RETENTION_DROPPED_FILES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "retention_dropped_files_total",
+            "total num of files dropped by data retention",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(TSKV_SUBSYSTEM),
+        &["db"],
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static RETENTION_DROPPED_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "retention_dropped_bytes_total",
+            "total size of files dropped by data retention",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(TSKV_SUBSYSTEM),
+        &["db"],
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static RETENTION_PURGED_ROWS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "retention_purged_rows_total",
+            "total num of expired rows purged from cache and compaction",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(TSKV_SUBSYSTEM),
+        &["db"],
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static WRITE_STALLS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "write_stalls_total",
+            "total num of writes slowed down or stopped by too many files in level 0",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(TSKV_SUBSYSTEM),
+        &["db", "ts_family", "state"],
+    )
+    .expect("tskv metric cannot be created")
+});
+
+pub static WRITE_STALL_STATE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "write_stall_state",
+            "write stall state of ts_family, 0: normal, 1: slowdown, 2: stop",
+        )
+        .namespace(SERVER_NAMESPACE)
+        .subsystem(TSKV_SUBSYSTEM),
+        &["db", "ts_family"],
+    )
+    .expect("tskv metric cannot be created")
+});
+
 pub fn init_tskv_metrics_recorder() {
     REGISTRY
         .register(Box::new(COMPACTION_SUCCESS.clone()))
@@ -183,6 +250,21 @@ pub fn init_tskv_metrics_recorder() {
     REGISTRY
         .register(Box::new(COMPACTION_DURATION.clone()))
         .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(RETENTION_DROPPED_FILES.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(RETENTION_DROPPED_BYTES.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(RETENTION_PURGED_ROWS.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(WRITE_STALLS.clone()))
+        .expect("tskv metrics collector cannot be registered");
+    REGISTRY
+        .register(Box::new(WRITE_STALL_STATE.clone()))
+        .expect("tskv metrics collector cannot be registered");
 }
 
 pub fn incr_compaction_success() {
@@ -199,6 +281,31 @@ pub fn sample_tskv_compaction_duration(db: &str, ts_family: &str, level: &str, d
         .observe(delta)
 }
 
+pub fn incr_retention_dropped_files(db: &str, files: u64, bytes: u64) {
+    RETENTION_DROPPED_FILES
+        .with_label_values(&[db])
+        .inc_by(files);
+    RETENTION_DROPPED_BYTES
+        .with_label_values(&[db])
+        .inc_by(bytes);
+}
+
+pub fn incr_retention_purged_rows(db: &str, rows: u64) {
+    RETENTION_PURGED_ROWS.with_label_values(&[db]).inc_by(rows);
+}
+
+pub fn incr_write_stalls(db: &str, ts_family: &str, state: &str) {
+    WRITE_STALLS
+        .with_label_values(&[db, ts_family, state])
+        .inc();
+}
+
+pub fn set_write_stall_state(db: &str, ts_family: &str, state: i64) {
+    WRITE_STALL_STATE
+        .with_label_values(&[db, ts_family])
+        .set(state);
+}
+
 pub fn gather_metrics_as_prometheus_string() -> String {
     use prometheus::Encoder;
     let encoder = prometheus::TextEncoder::new();
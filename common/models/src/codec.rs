@@ -1,25 +1,28 @@
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-pub const BIGINT_CODEC: [Encoding; 4] = [
+pub const BIGINT_CODEC: [Encoding; 5] = [
     Encoding::Default,
     Encoding::Null,
     Encoding::Delta,
     Encoding::Quantile,
+    Encoding::Auto,
 ];
 // Because timestamp, bigint, and unsigned bigint are all integers,
 // so their compression algorithms are the same
-pub const TIMESTAMP_CODEC: [Encoding; 4] = BIGINT_CODEC;
-pub const UNSIGNED_BIGINT_CODEC: [Encoding; 4] = BIGINT_CODEC;
+pub const TIMESTAMP_CODEC: [Encoding; 5] = BIGINT_CODEC;
+pub const UNSIGNED_BIGINT_CODEC: [Encoding; 5] = BIGINT_CODEC;
 
-pub const DOUBLE_CODEC: [Encoding; 4] = [
+pub const DOUBLE_CODEC: [Encoding; 6] = [
     Encoding::Default,
     Encoding::Null,
     Encoding::Gorilla,
     Encoding::Quantile,
+    Encoding::Chimp,
+    Encoding::Auto,
 ];
 
-pub const STRING_CODEC: [Encoding; 7] = [
+pub const STRING_CODEC: [Encoding; 9] = [
     Encoding::Default,
     Encoding::Null,
     Encoding::Gzip,
@@ -27,9 +30,16 @@ pub const STRING_CODEC: [Encoding; 7] = [
     Encoding::Zstd,
     Encoding::Snappy,
     Encoding::Zlib,
+    Encoding::Dictionary,
+    Encoding::Auto,
 ];
 
-pub const BOOLEAN_CODEC: [Encoding; 3] = [Encoding::Default, Encoding::Null, Encoding::BitPack];
+pub const BOOLEAN_CODEC: [Encoding; 4] = [
+    Encoding::Default,
+    Encoding::Null,
+    Encoding::BitPack,
+    Encoding::Auto,
+];
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize, Hash)]
 pub enum Encoding {
@@ -44,6 +54,10 @@ pub enum Encoding {
     Zstd = 8,
     Zlib = 9,
     BitPack = 10,
+    Chimp = 11,
+    Dictionary = 12,
+    /// Chooses the smallest codec for each block when it is written.
+    Auto = 13,
     Unknown = 15,
 }
 
@@ -85,6 +99,9 @@ impl Encoding {
             Encoding::Zstd => "ZSTD",
             Encoding::Zlib => "ZLIB",
             Encoding::BitPack => "BITPACK",
+            Encoding::Chimp => "CHIMP",
+            Encoding::Dictionary => "DICTIONARY",
+            Encoding::Auto => "AUTO",
             Encoding::Unknown => "UNKNOWN",
         }
     }
@@ -111,6 +128,9 @@ impl FromStr for Encoding {
             "ZSTD" => Ok(Self::Zstd),
             "ZLIB" => Ok(Self::Zlib),
             "BITPACK" => Ok(Self::BitPack),
+            "CHIMP" => Ok(Self::Chimp),
+            "DICTIONARY" => Ok(Self::Dictionary),
+            "AUTO" => Ok(Self::Auto),
             _ => Err("s".to_string()),
         }
     }
@@ -130,6 +150,9 @@ impl From<u8> for Encoding {
             8 => Encoding::Zstd,
             9 => Encoding::Zlib,
             10 => Encoding::BitPack,
+            11 => Encoding::Chimp,
+            12 => Encoding::Dictionary,
+            13 => Encoding::Auto,
             _ => Encoding::Unknown,
         }
     }
@@ -67,4 +67,13 @@ define_error_codes! {
     /// The sql state code needs to be developed later
     /// and is currently used as a placeholder
     (TskvUnknown, b"0200000");
+    /// New series are rejected because the number of series in the database
+    /// or the table reached the limit
+    (TskvSeriesLimitExceeded, b"0200011");
+    /// Writes are stalled because caches used up the memory limit, and the
+    /// caches are not flushed in time, the write can be retried later
+    (TskvWriteBufferFull, b"0200012");
+    /// Writes to a vnode are stopped because compaction falls behind and there are
+    /// too many files in level 0, the write can be retried later
+    (TskvWriteStalled, b"0200013");
 }
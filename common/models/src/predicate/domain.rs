@@ -12,6 +12,7 @@ use datafusion::{
     arrow::datatypes::DataType, logical_expr::Expr, optimizer::utils::conjunction, prelude::Column,
     scalar::ScalarValue,
 };
+use regex::Regex;
 
 use super::transformation::RowExpressionToDomainsVisitor;
 
@@ -452,10 +453,72 @@ impl EqutableValueSet {
     }
 }
 
+/// A pattern that string values are matched with.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pattern {
+    regex: String,
+    negated: bool,
+}
+
+impl Pattern {
+    /// The regular expression, LIKE patterns are converted to anchored regular expressions.
+    pub fn regex(&self) -> &str {
+        &self.regex
+    }
+
+    /// negated = true means values not matching the regular expression
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+}
+
+/// A set containing string values matching all of the patterns.
+///
+/// Values which can not be compared with patterns (e.g. null or not utf8) are not in the set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternValueSet {
+    patterns: Vec<Pattern>,
+}
+
+impl PatternValueSet {
+    pub fn patterns(&self) -> &[Pattern] {
+        &self.patterns
+    }
+
+    /// Compiles the patterns, returns a matcher checking if a value is in the set.
+    pub fn matcher(&self) -> Result<PatternMatcher> {
+        let regexes = self
+            .patterns
+            .iter()
+            .map(|p| {
+                Regex::new(&p.regex)
+                    .map(|r| (r, p.negated))
+                    .map_err(|e| Error::InvalidPattern { err: e.to_string() })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(PatternMatcher { regexes })
+    }
+}
+
+/// Compiled patterns of a PatternValueSet.
+#[derive(Debug, Clone)]
+pub struct PatternMatcher {
+    regexes: Vec<(Regex, bool)>,
+}
+
+impl PatternMatcher {
+    pub fn is_match(&self, value: &str) -> bool {
+        self.regexes
+            .iter()
+            .all(|(regex, negated)| regex.is_match(value) != *negated)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Domain {
     Range(RangeValueSet),
     Equtable(EqutableValueSet),
+    Pattern(PatternValueSet),
     None,
     All,
 }
@@ -523,6 +586,55 @@ impl Domain {
             entries,
         })
     }
+    /// Construct a set of string values matching the regular expression.
+    ///
+    /// negated = true means not matching the regular expression
+    ///
+    /// Returns an exception if the regular expression is invalid.
+    pub fn of_regex(regex: &str, negated: bool) -> Result<Domain> {
+        Regex::new(regex).map_err(|e| Error::InvalidPattern { err: e.to_string() })?;
+
+        Ok(Domain::Pattern(PatternValueSet {
+            patterns: vec![Pattern {
+                regex: regex.to_string(),
+                negated,
+            }],
+        }))
+    }
+    /// Construct a set of string values matching the LIKE pattern,
+    /// '%' matches any characters and '_' matches one character.
+    ///
+    /// escape_char is the character escaping '%', '_' and itself, default is '\\'.
+    ///
+    /// case_insensitive = true means ILIKE
+    pub fn of_like(
+        pattern: &str,
+        escape_char: Option<char>,
+        case_insensitive: bool,
+        negated: bool,
+    ) -> Result<Domain> {
+        let escape_char = escape_char.unwrap_or('\\');
+        let mut regex = String::from(if case_insensitive { "(?is)^" } else { "(?s)^" });
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                c if c == escape_char => match chars.next() {
+                    Some(c) => regex.push_str(&regex::escape(&c.to_string())),
+                    None => {
+                        return Err(Error::InvalidPattern {
+                            err: format!("LIKE pattern '{}' ends with escape character", pattern),
+                        })
+                    }
+                },
+                '%' => regex.push_str(".*"),
+                '_' => regex.push('.'),
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        regex.push('$');
+
+        Domain::of_regex(&regex, negated)
+    }
     /// Calculates the intersection of two ranges, and returns None if the intersection does not exist
     ///
     /// This method returns the new value without changing the old value
@@ -537,6 +649,26 @@ impl Domain {
             (Self::None, _) | (_, Self::None) => Ok(Self::None),
             (Self::All, _) => Ok(other.clone()),
             (_, Self::All) => Ok(self.clone()),
+            (Self::Pattern(ref self_val_set), Self::Pattern(ref other_val_set)) => {
+                let mut patterns = self_val_set.patterns.clone();
+                patterns.extend(other_val_set.patterns.iter().cloned());
+                // Patterns are sorted so that intersections in any order are equal
+                patterns.sort();
+                patterns.dedup();
+                Ok(Self::Pattern(PatternValueSet { patterns }))
+            }
+            (Self::Pattern(ref pattern_val_set), Self::Equtable(ref val_set))
+            | (Self::Equtable(ref val_set), Self::Pattern(ref pattern_val_set))
+                if val_set.white_list =>
+            {
+                Domain::pattern_value_intersect(pattern_val_set, val_set)
+            }
+            // The intersection can't be represented, take one of them as a superset of it,
+            // patterns are more selective than excluded values but slower than ranges.
+            (Self::Pattern(_), Self::Equtable(_)) => Ok(self.clone()),
+            (Self::Equtable(_), Self::Pattern(_)) => Ok(other.clone()),
+            (Self::Pattern(_), _) => Ok(other.clone()),
+            (_, Self::Pattern(_)) => Ok(self.clone()),
             _ => Err(Error::Internal {
                 err: "mismatched ValueSet type".to_string(),
             }),
@@ -560,6 +692,11 @@ impl Domain {
             (Self::None, _) => Ok(other.clone()),
             (_, Self::None) => Ok(self.clone()),
             (Self::All, _) | (_, Self::All) => Ok(Self::All),
+            (Self::Pattern(ref self_val_set), Self::Pattern(ref other_val_set)) => {
+                Ok(Domain::pattern_union(self_val_set, other_val_set))
+            }
+            // The union can't be represented, take all values as a superset of it.
+            (Self::Pattern(_), _) | (_, Self::Pattern(_)) => Ok(Self::All),
             _ => Err(Error::Internal {
                 err: "mismatched ValueSet type".to_string(),
             }),
@@ -704,6 +841,37 @@ impl Domain {
         Domain::of_ranges(&result)
     }
 
+    /// Keeps values in the equable value set that match the patterns
+    ///
+    /// Note: val_set must be a white list
+    fn pattern_value_intersect(
+        pattern_val_set: &PatternValueSet,
+        val_set: &EqutableValueSet,
+    ) -> Result<Domain> {
+        let matcher = pattern_val_set.matcher()?;
+        let entries: Vec<&ScalarValue> = val_set
+            .entries
+            .iter()
+            .map(|e| &e.value)
+            .filter(|v| utf8_from(v).map(|v| matcher.is_match(v)).unwrap_or(false))
+            .collect();
+
+        Ok(Domain::of_values(&val_set.data_type, true, &entries))
+    }
+    /// Two sets of single not negated pattern are merged into a set of alternative patterns,
+    /// otherwise the union can't be represented, returns Domain::All.
+    fn pattern_union(first: &PatternValueSet, other: &PatternValueSet) -> Domain {
+        match (first.patterns.as_slice(), other.patterns.as_slice()) {
+            ([p1], [p2]) if !p1.negated && !p2.negated => Domain::Pattern(PatternValueSet {
+                patterns: vec![Pattern {
+                    regex: format!("(?:{})|(?:{})", p1.regex, p2.regex),
+                    negated: false,
+                }],
+            }),
+            _ => Domain::All,
+        }
+    }
+
     fn value_union(first: &EqutableValueSet, other: &EqutableValueSet) -> Result<Domain> {
         let result_data_type = &first.data_type;
         let result_white_list: bool;
@@ -1014,4 +1182,70 @@ mod tests {
             }
         };
     }
+
+    #[test]
+    fn test_of_like() {
+        let matcher = |domain: Domain| match domain {
+            Domain::Pattern(val_set) => val_set.matcher().unwrap(),
+            _ => panic!("excepted Domain::Pattern"),
+        };
+
+        let m = matcher(Domain::of_like("web-%", None, false, false).unwrap());
+        assert!(m.is_match("web-1"));
+        assert!(m.is_match("web-"));
+        assert!(!m.is_match("db-web-1"));
+        assert!(!m.is_match("WEB-1"));
+
+        let m = matcher(Domain::of_like("a_c.\\%", None, true, false).unwrap());
+        assert!(m.is_match("ABC.%"));
+        assert!(!m.is_match("abxc.%"));
+        assert!(!m.is_match("abcx%"));
+
+        let m = matcher(Domain::of_like("a!_%", Some('!'), false, true).unwrap());
+        assert!(!m.is_match("a_1"));
+        assert!(m.is_match("ab1"));
+
+        assert!(Domain::of_like("a\\", None, false, false).is_err());
+        assert!(Domain::of_regex("(", false).is_err());
+    }
+
+    #[test]
+    fn test_pattern_intersect_union() {
+        let p1 = Domain::of_regex("^db[0-9]+$", false).unwrap();
+        let p2 = Domain::of_like("%1", None, false, false).unwrap();
+        let utf8 = |s: &str| ScalarValue::Utf8(Some(s.to_string()));
+
+        let domain = p1.intersect(&p2).unwrap();
+        match &domain {
+            Domain::Pattern(val_set) => {
+                assert_eq!(val_set.patterns().len(), 2);
+                let m = val_set.matcher().unwrap();
+                assert!(m.is_match("db1"));
+                assert!(!m.is_match("db2"));
+            }
+            _ => panic!("excepted Domain::Pattern"),
+        }
+
+        let (v1, v2, v3) = (utf8("db1"), utf8("db2"), utf8("web1"));
+        let values = Domain::of_values(&DataType::Utf8, true, &[&v1, &v2, &v3]);
+        assert_eq!(
+            domain.intersect(&values).unwrap(),
+            Domain::of_values(&DataType::Utf8, true, &[&v1])
+        );
+
+        let range = Domain::of_ranges(&[Range::gt(&DataType::Utf8, &v1)]).unwrap();
+        assert_eq!(p1.intersect(&range).unwrap(), range);
+        assert_eq!(p1.union(&range).unwrap(), Domain::All);
+
+        match p1.union(&p2).unwrap() {
+            Domain::Pattern(val_set) => {
+                let m = val_set.matcher().unwrap();
+                assert!(m.is_match("db2"));
+                assert!(m.is_match("web1"));
+                assert!(!m.is_match("web2"));
+            }
+            _ => panic!("excepted Domain::Pattern"),
+        }
+        assert_eq!(domain.union(&p2).unwrap(), Domain::All);
+    }
 }
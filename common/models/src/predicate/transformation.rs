@@ -5,13 +5,13 @@ use datafusion::{
     error::DataFusionError,
     logical_expr::{
         expr_visitor::{ExprVisitable, ExpressionVisitor, Recursion},
-        BinaryExpr, Operator,
+        BinaryExpr, Like, Operator,
     },
     prelude::{Column, Expr},
     scalar::ScalarValue,
 };
 
-use super::domain::{ColumnDomains, Domain, Range};
+use super::domain::{utf8_from, ColumnDomains, Domain, Range};
 
 type Result<T> = result::Result<T, DataFusionError>;
 
@@ -155,9 +155,11 @@ impl ExpressionVisitor for RowExpressionToDomainsVisitor<'_> {
             // | Expr::Wildcard
             // | Expr::QualifiedWildcard { .. }
             // | Expr::GetIndexedField { .. } => {}
-            Expr::Column(_) | Expr::Literal(_) | Expr::BinaryExpr { .. } => {
-                Ok(Recursion::Continue(self))
-            }
+            Expr::Column(_)
+            | Expr::Literal(_)
+            | Expr::BinaryExpr { .. }
+            | Expr::Like(_)
+            | Expr::ILike(_) => Ok(Recursion::Continue(self)),
             // TODO Currently not supported, follow-up support needs to implement the corresponding expression in post_visit
             Expr::Not(_)
             | Expr::IsNotNull(_)
@@ -188,6 +190,37 @@ impl ExpressionVisitor for RowExpressionToDomainsVisitor<'_> {
                             self.ctx, left, op, right,
                         );
                     }
+                    // Pattern matching of tags, the stack is expr, generate a domain
+                    Operator::Like | Operator::NotLike => {
+                        let negated = matches!(op, Operator::NotLike);
+                        Self::construct_pattern_domain_and_push_current_domain_stack(
+                            self.ctx,
+                            left,
+                            right,
+                            |p| Domain::of_like(p, None, false, negated),
+                        );
+                    }
+                    Operator::RegexMatch
+                    | Operator::RegexIMatch
+                    | Operator::RegexNotMatch
+                    | Operator::RegexNotIMatch => {
+                        let case_insensitive =
+                            matches!(op, Operator::RegexIMatch | Operator::RegexNotIMatch);
+                        let negated =
+                            matches!(op, Operator::RegexNotMatch | Operator::RegexNotIMatch);
+                        Self::construct_pattern_domain_and_push_current_domain_stack(
+                            self.ctx,
+                            left,
+                            right,
+                            |p| {
+                                if case_insensitive {
+                                    Domain::of_regex(&format!("(?i){}", p), negated)
+                                } else {
+                                    Domain::of_regex(p, negated)
+                                }
+                            },
+                        );
+                    }
                     // The stack is domain, pop it, and generate a new domain
                     Operator::And => {
                         let domain1_opt = self.ctx.current_domain_stack.pop_back();
@@ -212,6 +245,32 @@ impl ExpressionVisitor for RowExpressionToDomainsVisitor<'_> {
                     _ => {}
                 }
             }
+            Expr::Like(Like {
+                negated,
+                expr,
+                pattern,
+                escape_char,
+            }) => {
+                Self::construct_pattern_domain_and_push_current_domain_stack(
+                    self.ctx,
+                    expr,
+                    pattern,
+                    |p| Domain::of_like(p, *escape_char, false, *negated),
+                );
+            }
+            Expr::ILike(Like {
+                negated,
+                expr,
+                pattern,
+                escape_char,
+            }) => {
+                Self::construct_pattern_domain_and_push_current_domain_stack(
+                    self.ctx,
+                    expr,
+                    pattern,
+                    |p| Domain::of_like(p, *escape_char, true, *negated),
+                );
+            }
             // TODO The stack is the domain, and the domain is generated
             Expr::Not(_) | Expr::Between { .. } | Expr::InList { .. } => {}
             _ => {}
@@ -286,6 +345,25 @@ impl RowExpressionToDomainsVisitor<'_> {
             ctx.current_domain_stack.push_back(domains);
         }
     }
+    /// Construct pattern matching of a column with a utf8 literal as PatternValueSet.
+    ///
+    /// If it's not supported or the pattern is invalid, push ColumnDomains::all() onto the stack.
+    fn construct_pattern_domain_and_push_current_domain_stack(
+        ctx: &mut RowExpressionToDomainsVisitorContext,
+        expr: &Expr,
+        pattern: &Expr,
+        to_domain: impl FnOnce(&str) -> crate::Result<Domain>,
+    ) {
+        let domains = match (expr, pattern) {
+            (Expr::Column(col), Expr::Literal(value)) => utf8_from(value)
+                .and_then(|p| to_domain(p).ok())
+                .map(|domain| ColumnDomains::of(col.to_owned(), &domain)),
+            _ => None,
+        }
+        .unwrap_or_else(ColumnDomains::all);
+
+        ctx.current_domain_stack.push_back(domains);
+    }
 }
 
 #[cfg(test)]
@@ -1121,6 +1199,69 @@ mod tests {
         );
     }
 
+    /// pattern matching
+    /// eg.
+    ///   host like 'web-%' and \
+    ///   region ~ '^rg_[0-9]+$' and \
+    ///   region !~* 'RG_0'
+    ///   ===>
+    ///   host: like 'web-%'
+    ///   region: ~ '^rg_[0-9]+$' and !~* 'RG_0'
+    #[test]
+    fn test_pattern_expr_to_domain() {
+        let host = Expr::Like(Like::new(
+            false,
+            Box::new(col("host")),
+            Box::new(lit("web-%")),
+            None,
+        ));
+        let region_1 = binary_expr(col("region"), Operator::RegexMatch, lit("^rg_[0-9]+$"));
+        let region_2 = binary_expr(col("region"), Operator::RegexNotIMatch, lit("RG_0"));
+
+        let and = and(and(host, region_1), region_2);
+
+        let result = get_domains(&and);
+
+        assert!(
+            result.is_ok(),
+            "convert expr {} to column domains err",
+            &and
+        );
+
+        let column_domain = result.as_ref().unwrap();
+
+        // build except result
+        let host_domain = Domain::of_like("web-%", None, false, false).unwrap();
+        let region_1_domain = Domain::of_regex("^rg_[0-9]+$", false).unwrap();
+        let region_2_domain = Domain::of_regex("(?i)RG_0", true).unwrap();
+
+        let mut except_column_domains = ColumnDomains::of(Column::from_name("host"), &host_domain);
+        except_column_domains.insert_or_intersect(Column::from_name("region"), &region_1_domain);
+        except_column_domains.insert_or_intersect(Column::from_name("region"), &region_2_domain);
+
+        assert!(
+            except_column_domains.eq(column_domain),
+            "convert expr {} to column domains err, excepted {:?}, found {:?}",
+            &and,
+            except_column_domains,
+            column_domain,
+        );
+
+        // pattern is not a literal
+        let like = Expr::ILike(Like::new(
+            false,
+            Box::new(col("host")),
+            Box::new(col("region")),
+            None,
+        ));
+        let result = get_domains(&like);
+        assert!(
+            result.as_ref().unwrap().is_all(),
+            "convert expr {} to column domains err, excepted ColumnDomains::All",
+            &like
+        );
+    }
+
     /// not support push down - 1
     /// eg.
     ///   c1 > 1 or \
@@ -433,6 +433,11 @@ pub struct DatabaseOptions {
     pub replica: u64,
     // timestamp percision
     pub precision: Precision,
+
+    // max number of series in the database, None means the default in config
+    pub max_series_per_database: Option<u64>,
+    // max number of series in a table, None means the default in config
+    pub max_series_per_table: Option<u64>,
 }
 
 impl Default for DatabaseOptions {
@@ -449,6 +454,8 @@ impl Default for DatabaseOptions {
             },
             replica: 1,
             precision: Precision::NS,
+            max_series_per_database: None,
+            max_series_per_table: None,
         }
     }
 }
@@ -471,6 +478,17 @@ impl Precision {
     }
 }
 
+impl Precision {
+    /// Convert a nanosecond timestamp (or duration) to this precision.
+    pub fn convert_nanoseconds(&self, nanos: i64) -> i64 {
+        match self {
+            Precision::MS => nanos / 1_000_000,
+            Precision::US => nanos / 1_000,
+            Precision::NS => nanos,
+        }
+    }
+}
+
 impl fmt::Display for Precision {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -505,6 +523,15 @@ impl fmt::Display for Duration {
 }
 
 impl Duration {
+    pub fn to_nanoseconds(&self) -> i64 {
+        let unit_nanos: i64 = match self.unit {
+            DurationUnit::Minutes => 60 * 1_000_000_000,
+            DurationUnit::Hour => 60 * 60 * 1_000_000_000,
+            DurationUnit::Day => 24 * 60 * 60 * 1_000_000_000,
+        };
+        (self.time_num.min(i64::MAX as u64) as i64).saturating_mul(unit_nanos)
+    }
+
     // with default DurationUnit day
     pub fn new(text: &str) -> Option<Self> {
         if text.is_empty() {
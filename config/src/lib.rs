@@ -1,4 +1,4 @@
-use std::{fs::File, io::prelude::Read};
+use std::{collections::HashMap, fs::File, io::prelude::Read};
 
 use serde::{Deserialize, Serialize};
 use trace::info;
@@ -9,6 +9,8 @@ pub struct Config {
     pub storage: StorageConfig,
     pub wal: WalConfig,
     pub cache: CacheConfig,
+    #[serde(default)]
+    pub object_store: ObjectStoreConfig,
     pub log: LogConfig,
     pub security: SecurityConfig,
     pub reporting_disabled: Option<bool>,
@@ -19,6 +21,7 @@ impl Config {
         self.storage.override_by_env();
         self.wal.override_by_env();
         self.cache.override_by_env();
+        self.object_store.override_by_env();
         self.query.override_by_env();
     }
 }
@@ -42,9 +45,60 @@ pub struct StorageConfig {
     pub dio_max_non_resident: usize,
     pub dio_page_len_scale: usize,
     pub strict_write: bool,
+    #[serde(default = "StorageConfig::default_retention_check_interval")]
+    pub retention_check_interval: u64,
+    #[serde(default = "StorageConfig::default_compaction_picker")]
+    pub compaction_picker: String,
+    #[serde(default = "StorageConfig::default_compaction_time_window")]
+    pub compaction_time_window: u64,
+    #[serde(default = "StorageConfig::default_tombstone_rewrite_percent")]
+    pub tombstone_rewrite_percent: u32,
+    #[serde(default)]
+    pub max_series_per_database: u64,
+    #[serde(default)]
+    pub max_series_per_table: u64,
+    #[serde(default = "StorageConfig::default_l0_slowdown_trigger")]
+    pub l0_slowdown_trigger: u32,
+    #[serde(default = "StorageConfig::default_l0_stop_trigger")]
+    pub l0_stop_trigger: u32,
+    /// Databases can only be backed up to and restored from directories under it.
+    #[serde(default = "StorageConfig::default_backup_path")]
+    pub backup_path: String,
+    #[serde(default)]
+    pub database_compaction_pickers: HashMap<String, String>,
+    #[serde(default)]
+    pub level_paths: HashMap<String, String>,
 }
 
 impl StorageConfig {
+    fn default_retention_check_interval() -> u64 {
+        3600
+    }
+
+    fn default_compaction_picker() -> String {
+        "level".to_string()
+    }
+
+    fn default_compaction_time_window() -> u64 {
+        86400
+    }
+
+    fn default_tombstone_rewrite_percent() -> u32 {
+        30
+    }
+
+    fn default_l0_slowdown_trigger() -> u32 {
+        20
+    }
+
+    fn default_l0_stop_trigger() -> u32 {
+        36
+    }
+
+    fn default_backup_path() -> String {
+        "data/backup".to_string()
+    }
+
     pub fn override_by_env(&mut self) {
         if let Ok(path) = std::env::var("CNOSDB_APPLICATION_PATH") {
             self.path = path;
@@ -76,6 +130,33 @@ impl StorageConfig {
         if let Ok(size) = std::env::var("CNOSDB_STORAGE_STRICT_WRITE") {
             self.strict_write = size.parse::<bool>().unwrap();
         }
+        if let Ok(size) = std::env::var("CNOSDB_STORAGE_RETENTION_CHECK_INTERVAL") {
+            self.retention_check_interval = size.parse::<u64>().unwrap();
+        }
+        if let Ok(picker) = std::env::var("CNOSDB_STORAGE_COMPACTION_PICKER") {
+            self.compaction_picker = picker;
+        }
+        if let Ok(size) = std::env::var("CNOSDB_STORAGE_COMPACTION_TIME_WINDOW") {
+            self.compaction_time_window = size.parse::<u64>().unwrap();
+        }
+        if let Ok(size) = std::env::var("CNOSDB_STORAGE_TOMBSTONE_REWRITE_PERCENT") {
+            self.tombstone_rewrite_percent = size.parse::<u32>().unwrap();
+        }
+        if let Ok(size) = std::env::var("CNOSDB_STORAGE_MAX_SERIES_PER_DATABASE") {
+            self.max_series_per_database = size.parse::<u64>().unwrap();
+        }
+        if let Ok(size) = std::env::var("CNOSDB_STORAGE_MAX_SERIES_PER_TABLE") {
+            self.max_series_per_table = size.parse::<u64>().unwrap();
+        }
+        if let Ok(num) = std::env::var("CNOSDB_STORAGE_L0_SLOWDOWN_TRIGGER") {
+            self.l0_slowdown_trigger = num.parse::<u32>().unwrap();
+        }
+        if let Ok(num) = std::env::var("CNOSDB_STORAGE_L0_STOP_TRIGGER") {
+            self.l0_stop_trigger = num.parse::<u32>().unwrap();
+        }
+        if let Ok(path) = std::env::var("CNOSDB_STORAGE_BACKUP_PATH") {
+            self.backup_path = path;
+        }
     }
 }
 
@@ -84,9 +165,25 @@ pub struct WalConfig {
     pub enabled: bool,
     pub path: String,
     pub sync: bool,
+    #[serde(default)]
+    pub sync_interval: u64,
+    #[serde(default)]
+    pub sync_every_n_bytes: u64,
+    #[serde(default = "WalConfig::default_max_file_size")]
+    pub max_file_size: u64,
+    #[serde(default = "WalConfig::default_recovery_mode")]
+    pub recovery_mode: String,
 }
 
 impl WalConfig {
+    fn default_max_file_size() -> u64 {
+        1024 * 1024 * 1024
+    }
+
+    fn default_recovery_mode() -> String {
+        "tolerate_tail_corruption".to_string()
+    }
+
     pub fn override_by_env(&mut self) {
         if let Ok(enabled) = std::env::var("CNOSDB_WAL_ENABLED") {
             self.enabled = enabled.as_str() == "true";
@@ -97,6 +194,18 @@ impl WalConfig {
         if let Ok(sync) = std::env::var("CNOSDB_WAL_SYNC") {
             self.sync = sync.as_str() == sync;
         }
+        if let Ok(interval) = std::env::var("CNOSDB_WAL_SYNC_INTERVAL") {
+            self.sync_interval = interval.parse::<u64>().unwrap();
+        }
+        if let Ok(size) = std::env::var("CNOSDB_WAL_SYNC_EVERY_N_BYTES") {
+            self.sync_every_n_bytes = size.parse::<u64>().unwrap();
+        }
+        if let Ok(size) = std::env::var("CNOSDB_WAL_MAX_FILE_SIZE") {
+            self.max_file_size = size.parse::<u64>().unwrap();
+        }
+        if let Ok(mode) = std::env::var("CNOSDB_WAL_RECOVERY_MODE") {
+            self.recovery_mode = mode;
+        }
     }
 }
 
@@ -104,9 +213,21 @@ impl WalConfig {
 pub struct CacheConfig {
     pub max_buffer_size: u64,
     pub max_immutable_number: u16,
+    #[serde(default = "CacheConfig::default_max_total_buffer_size")]
+    pub max_total_buffer_size: u64,
+    #[serde(default = "CacheConfig::default_write_stall_timeout")]
+    pub write_stall_timeout: u64,
 }
 
 impl CacheConfig {
+    fn default_max_total_buffer_size() -> u64 {
+        1024 * 1024 * 1024
+    }
+
+    fn default_write_stall_timeout() -> u64 {
+        10000
+    }
+
     pub fn override_by_env(&mut self) {
         if let Ok(size) = std::env::var("CNOSDB_CACHE_MAX_BUFFER_SIZE") {
             self.max_buffer_size = size.parse::<u64>().unwrap();
@@ -114,6 +235,64 @@ impl CacheConfig {
         if let Ok(size) = std::env::var("CNOSDB_CACHE_MAX_IMMUTABLE_NUMBER") {
             self.max_immutable_number = size.parse::<u16>().unwrap();
         }
+        if let Ok(size) = std::env::var("CNOSDB_CACHE_MAX_TOTAL_BUFFER_SIZE") {
+            self.max_total_buffer_size = size.parse::<u64>().unwrap();
+        }
+        if let Ok(timeout) = std::env::var("CNOSDB_CACHE_WRITE_STALL_TIMEOUT") {
+            self.write_stall_timeout = timeout.parse::<u64>().unwrap();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObjectStoreConfig {
+    pub url: String,
+    pub endpoint: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub offload_age: u64,
+    pub offload_check_interval: u64,
+}
+
+impl Default for ObjectStoreConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            endpoint: String::new(),
+            region: String::new(),
+            access_key_id: String::new(),
+            secret_access_key: String::new(),
+            offload_age: 0,
+            offload_check_interval: 3600,
+        }
+    }
+}
+
+impl ObjectStoreConfig {
+    pub fn override_by_env(&mut self) {
+        if let Ok(url) = std::env::var("CNOSDB_OBJECT_STORE_URL") {
+            self.url = url;
+        }
+        if let Ok(endpoint) = std::env::var("CNOSDB_OBJECT_STORE_ENDPOINT") {
+            self.endpoint = endpoint;
+        }
+        if let Ok(region) = std::env::var("CNOSDB_OBJECT_STORE_REGION") {
+            self.region = region;
+        }
+        if let Ok(key) = std::env::var("CNOSDB_OBJECT_STORE_ACCESS_KEY_ID") {
+            self.access_key_id = key;
+        }
+        if let Ok(key) = std::env::var("CNOSDB_OBJECT_STORE_SECRET_ACCESS_KEY") {
+            self.secret_access_key = key;
+        }
+        if let Ok(age) = std::env::var("CNOSDB_OBJECT_STORE_OFFLOAD_AGE") {
+            self.offload_age = age.parse::<u64>().unwrap();
+        }
+        if let Ok(interval) = std::env::var("CNOSDB_OBJECT_STORE_OFFLOAD_CHECK_INTERVAL") {
+            self.offload_check_interval = interval.parse::<u64>().unwrap();
+        }
     }
 }
 
@@ -151,6 +330,15 @@ impl LogConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecurityConfig {
     pub tls_config: Option<TLSConfig>,
+    /// Users allowed to run administrative operations such as backup and restore.
+    #[serde(default = "SecurityConfig::default_admin_users")]
+    pub admin_users: Vec<String>,
+}
+
+impl SecurityConfig {
+    fn default_admin_users() -> Vec<String> {
+        vec!["root".to_string()]
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -176,9 +364,8 @@ pub fn get_config(path: &str) -> Config {
     config
 }
 
-#[test]
-fn test() {
-    let config_str = r#"
+#[cfg(test)]
+const TEST_CONFIG_STR: &str = r#"
 [query]
 max_server_connections = 10240 
 query_sql_limit = 16777216   # 16 * 1024 * 1024
@@ -194,24 +381,133 @@ dio_max_resident = 1024
 dio_max_non_resident = 1024
 dio_page_len_scale = 1
 strict_write = true
+retention_check_interval = 3600
+compaction_picker = 'level'
+compaction_time_window = 86400
+tombstone_rewrite_percent = 30
+max_series_per_database = 0
+max_series_per_table = 0
+l0_slowdown_trigger = 20
+l0_stop_trigger = 36
+backup_path = '/tmp/cnosdb/backup'
+
+[storage.database_compaction_pickers]
+db_metrics = 'time_window'
+
+[storage.level_paths]
+3 = '/tmp/cnosdb/cold'
 
 [wal]
 enabled = true
 path = 'data/wal'
 sync = true
+sync_interval = 0
+sync_every_n_bytes = 0
+max_file_size = 1073741824 # 1024 * 1024 * 1024
+recovery_mode = 'tolerate_tail_corruption'
 
 [cache]
 max_buffer_size = 1048576 # 134217728 # 128 * 1024 * 1024
 max_immutable_number = 4
+max_total_buffer_size = 1073741824 # 1024 * 1024 * 1024
+write_stall_timeout = 10000
+
+[object_store]
+url = 's3://cnosdb/node_1'
+endpoint = 'http://127.0.0.1:9000'
+region = 'us-east-1'
+access_key_id = ''
+secret_access_key = ''
+offload_age = 2592000
+offload_check_interval = 3600
 
 [log]
 level = 'info'
 path = 'data/log'
 
 [security]
+admin_users = ['root', 'admin']
 
 "#;
 
-    let config: Config = toml::from_str(config_str).unwrap();
+#[test]
+fn test() {
+    let config: Config = toml::from_str(TEST_CONFIG_STR).unwrap();
+    assert_eq!(config.storage.backup_path, "/tmp/cnosdb/backup");
+    assert_eq!(config.security.admin_users, vec!["root", "admin"]);
     dbg!(config);
 }
+
+/// Parses `TEST_CONFIG_STR` without the options named by `keys`, like a
+/// configuration file written before these options were added.
+#[cfg(test)]
+fn parse_config_without(keys: &[&str]) -> Config {
+    let config_str: String = TEST_CONFIG_STR
+        .lines()
+        .filter(|line| !keys.iter().any(|k| line.starts_with(&format!("{} =", k))))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    toml::from_str(&config_str).unwrap()
+}
+
+#[test]
+fn test_default_values() {
+    let config = parse_config_without(&[
+        "retention_check_interval",
+        "compaction_picker",
+        "compaction_time_window",
+        "tombstone_rewrite_percent",
+        "max_series_per_database",
+        "max_series_per_table",
+        "l0_slowdown_trigger",
+        "l0_stop_trigger",
+        "max_total_buffer_size",
+        "write_stall_timeout",
+        "sync_interval",
+        "sync_every_n_bytes",
+        "max_file_size",
+        "recovery_mode",
+        "backup_path",
+        "admin_users",
+    ]);
+    assert_eq!(config.storage.retention_check_interval, 3600);
+    assert_eq!(config.storage.compaction_picker, "level");
+    assert_eq!(config.storage.compaction_time_window, 86400);
+    assert_eq!(config.storage.tombstone_rewrite_percent, 30);
+    assert_eq!(config.storage.max_series_per_database, 0);
+    assert_eq!(config.storage.max_series_per_table, 0);
+    assert_eq!(config.storage.l0_slowdown_trigger, 20);
+    assert_eq!(config.storage.l0_stop_trigger, 36);
+    assert_eq!(config.cache.max_total_buffer_size, 1024 * 1024 * 1024);
+    assert_eq!(config.cache.write_stall_timeout, 10000);
+    assert_eq!(config.wal.sync_interval, 0);
+    assert_eq!(config.wal.sync_every_n_bytes, 0);
+    assert_eq!(config.wal.max_file_size, 1024 * 1024 * 1024);
+    assert_eq!(config.wal.recovery_mode, "tolerate_tail_corruption");
+    assert_eq!(config.storage.backup_path, "data/backup");
+    assert_eq!(config.security.admin_users, vec!["root".to_string()]);
+}
+
+#[test]
+fn test_default_object_store() {
+    // Configuration files written before the object store was added.
+    let config_str: String = TEST_CONFIG_STR
+        .split("\n\n")
+        .filter(|section| !section.starts_with("[object_store]"))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    assert!(!config_str.contains("offload_age"));
+    let config: Config = toml::from_str(&config_str).unwrap();
+    assert_eq!(config.object_store.url, "");
+    assert_eq!(config.object_store.endpoint, "");
+    assert_eq!(config.object_store.region, "");
+    assert_eq!(config.object_store.access_key_id, "");
+    assert_eq!(config.object_store.secret_access_key, "");
+    assert_eq!(config.object_store.offload_age, 0);
+    assert_eq!(config.object_store.offload_check_interval, 3600);
+
+    let config = parse_config_without(&["offload_age", "offload_check_interval"]);
+    assert_eq!(config.object_store.url, "s3://cnosdb/node_1");
+    assert_eq!(config.object_store.offload_age, 0);
+    assert_eq!(config.object_store.offload_check_interval, 3600);
+}
@@ -1,8 +1,11 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 use std::{collections::HashMap, convert::Infallible, net::SocketAddr};
 
 use http_protocol::header::{ACCEPT, AUTHORIZATION};
-use http_protocol::parameter::{SqlParam, WriteParam};
+use http_protocol::parameter::{BackupParam, SqlParam, WriteParam};
 use http_protocol::response::ErrorResponse;
+use http_protocol::status_code::OK;
 
 use super::header::Header;
 use super::Error as HttpError;
@@ -55,9 +58,14 @@ pub struct HttpService {
     handle: Option<ServiceHandle<()>>,
     query_body_limit: u64,
     write_body_limit: u64,
+    /// Root directory of backups, paths of backup and restore requests are under it.
+    backup_path: PathBuf,
+    /// Users allowed to back up and restore databases.
+    admin_users: Arc<Vec<String>>,
 }
 
 impl HttpService {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         dbms: DBMSRef,
         kv_inst: EngineRef,
@@ -65,6 +73,8 @@ impl HttpService {
         tls_config: Option<TLSConfig>,
         query_body_limit: u64,
         write_body_limit: u64,
+        backup_path: impl Into<PathBuf>,
+        admin_users: Vec<String>,
     ) -> Self {
         Self {
             tls_config,
@@ -74,6 +84,8 @@ impl HttpService {
             handle: None,
             query_body_limit,
             write_body_limit,
+            backup_path: backup_path.into(),
+            admin_users: Arc::new(admin_users),
         }
     }
 
@@ -98,6 +110,24 @@ impl HttpService {
         let kv_inst = self.kv_inst.clone();
         warp::any().map(move || kv_inst.clone())
     }
+    /// Checks that the request is sent by an admin user, extracts the backup root directory.
+    fn with_admin(&self) -> impl Filter<Extract = (PathBuf,), Error = warp::Rejection> + Clone {
+        let backup_path = self.backup_path.clone();
+        let admin_users = self.admin_users.clone();
+        self.handle_header().and_then(move |header: Header| {
+            let backup_path = backup_path.clone();
+            let admin_users = admin_users.clone();
+            async move {
+                let user_info = header.try_get_basic_auth().map_err(reject::custom)?;
+                if !admin_users.contains(&user_info.user) {
+                    return Err(reject::custom(HttpError::Forbidden {
+                        user: user_info.user,
+                    }));
+                }
+                Ok(backup_path)
+            }
+        })
+    }
 
     fn routes(
         &self,
@@ -106,6 +136,8 @@ impl HttpService {
             .or(self.query())
             .or(self.write_line_protocol())
             .or(self.metrics())
+            .or(self.backup())
+            .or(self.restore())
     }
 
     fn ping(&self) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
@@ -215,6 +247,54 @@ impl HttpService {
             )
     }
 
+    fn backup(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "backup")
+            .and(warp::post())
+            .and(self.with_admin())
+            .and(warp::query::<BackupParam>())
+            .and(self.with_kv_inst())
+            .and_then(
+                |backup_root: PathBuf, param: BackupParam, kv_inst: EngineRef| async move {
+                    let path = backup_path(&backup_root, &param.path).map_err(reject::custom)?;
+                    info!("backup database {} to {}", param.db, path.display());
+                    match kv_inst
+                        .backup_database(&param.db, &path.to_string_lossy(), param.since.as_deref())
+                        .await
+                        .context(TskvSnafu)
+                    {
+                        Ok(manifest) => Ok(ResponseBuilder::new(OK).json(&manifest)),
+                        Err(e) => Err(reject::custom(e)),
+                    }
+                },
+            )
+    }
+
+    fn restore(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "restore")
+            .and(warp::post())
+            .and(self.with_admin())
+            .and(warp::query::<BackupParam>())
+            .and(self.with_kv_inst())
+            .and_then(
+                |backup_root: PathBuf, param: BackupParam, kv_inst: EngineRef| async move {
+                    let path = backup_path(&backup_root, &param.path).map_err(reject::custom)?;
+                    info!("restore database {} from {}", param.db, path.display());
+                    match kv_inst
+                        .restore_database(&param.db, &path.to_string_lossy())
+                        .await
+                        .context(TskvSnafu)
+                    {
+                        Ok(manifest) => Ok(ResponseBuilder::new(OK).json(&manifest)),
+                        Err(e) => Err(reject::custom(e)),
+                    }
+                },
+            )
+    }
+
     fn metrics(
         &self,
     ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
@@ -348,6 +428,22 @@ fn construct_query(req: Bytes, header: &Header, param: SqlParam) -> Result<Query
     ))
 }
 
+/// Returns the directory of a backup under `backup_root`, `path` must be a relative
+/// path without `..`.
+fn backup_path(backup_root: &Path, path: &str) -> Result<PathBuf, HttpError> {
+    let relative = Path::new(path);
+    let valid = relative.components().count() > 0
+        && relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !valid {
+        return Err(HttpError::InvalidBackupPath {
+            path: path.to_string(),
+        });
+    }
+    Ok(backup_root.join(relative))
+}
+
 async fn sql_handle(query: &Query, header: Header, dbms: DBMSRef) -> Result<Response, HttpError> {
     debug!("prepare to execute: {:?}", query.content());
 
@@ -387,8 +483,32 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, std::convert::In
 /**************** bottom *****************/
 #[cfg(test)]
 mod test {
+    use std::path::{Path, PathBuf};
+
     use tokio::time;
 
+    use super::backup_path;
+    use crate::http::Error as HttpError;
+
+    #[test]
+    fn test_backup_path() {
+        let root = Path::new("/data/backup");
+        assert_eq!(
+            backup_path(root, "db1/20221017").unwrap(),
+            PathBuf::from("/data/backup/db1/20221017")
+        );
+        assert_eq!(
+            backup_path(root, "./db1").unwrap(),
+            PathBuf::from("/data/backup/./db1")
+        );
+        for path in ["", "/tmp/db1", "../db1", "db1/../../db2"] {
+            assert!(matches!(
+                backup_path(root, path),
+                Err(HttpError::InvalidBackupPath { .. })
+            ));
+        }
+    }
+
     #[tokio::test]
     async fn test1() {
         use warp::Filter;
@@ -59,6 +59,15 @@ pub enum Error {
 
     #[snafu(display("Fetch result: {}", reason))]
     FetchResult { reason: String },
+
+    #[snafu(display("User '{}' is not an admin user", user))]
+    Forbidden { user: String },
+
+    #[snafu(display(
+        "Invalid backup path '{}', must be a relative path under the backup directory",
+        path
+    ))]
+    InvalidBackupPath { path: String },
 }
 
 impl reject::Reject for Error {}
@@ -78,8 +87,8 @@ impl From<&Error> for Response {
 
                 ResponseBuilder::new(UNPROCESSABLE_ENTITY).json(&error_resp)
             }
-            Error::Tskv { source: _ } => {
-                let error_resp = ErrorResponse::new(ErrorCode::TskvUnknown, error_message);
+            Error::Tskv { source } => {
+                let error_resp = ErrorResponse::new(source.error_code(), error_message);
 
                 ResponseBuilder::new(UNPROCESSABLE_ENTITY).json(&error_resp)
             }
@@ -88,6 +97,16 @@ impl From<&Error> for Response {
 
                 ResponseBuilder::bad_request(&error_resp)
             }
+            Error::InvalidBackupPath { path: _ } => {
+                let error_resp = ErrorResponse::new(ErrorCode::Unknown, error_message);
+
+                ResponseBuilder::bad_request(&error_resp)
+            }
+            Error::Forbidden { user: _ } => {
+                let error_resp = ErrorResponse::new(ErrorCode::Unknown, error_message);
+
+                ResponseBuilder::forbidden(&error_resp)
+            }
             _ => ResponseBuilder::internal_server_error(),
         }
     }
@@ -104,7 +123,10 @@ mod tests {
     use spi::query::QueryError;
     use warp::http::header::{HeaderValue, CONTENT_TYPE};
 
-    use http_protocol::{header::APPLICATION_JSON, status_code::BAD_REQUEST};
+    use http_protocol::{
+        header::APPLICATION_JSON,
+        status_code::{BAD_REQUEST, FORBIDDEN},
+    };
 
     use super::*;
 
@@ -179,4 +201,18 @@ mod tests {
 
         assert_eq!(content_type, HeaderValue::from_static(APPLICATION_JSON));
     }
+
+    #[test]
+    fn test_forbidden_error() {
+        let resp: Response = Error::Forbidden {
+            user: "test".to_string(),
+        }
+        .into();
+
+        assert_eq!(resp.status(), FORBIDDEN);
+
+        let content_type = resp.headers().get(CONTENT_TYPE).unwrap();
+
+        assert_eq!(content_type, HeaderValue::from_static(APPLICATION_JSON));
+    }
 }
@@ -9,6 +9,7 @@ use super::header::IntoHeaderPair;
 use http_protocol::header::APPLICATION_JSON;
 use http_protocol::header::CONTENT_TYPE;
 use http_protocol::status_code::BAD_REQUEST;
+use http_protocol::status_code::FORBIDDEN;
 use http_protocol::status_code::INTERNAL_SERVER_ERROR;
 use http_protocol::status_code::METHOD_NOT_ALLOWED;
 use http_protocol::status_code::NOT_FOUND;
@@ -75,6 +76,13 @@ impl ResponseBuilder {
         Self::new(BAD_REQUEST).json(error_info)
     }
 
+    pub fn forbidden<T>(error_info: &T) -> Response
+    where
+        T: Serialize,
+    {
+        Self::new(FORBIDDEN).json(error_info)
+    }
+
     pub fn not_found() -> Response {
         NOT_FOUND.into_response()
     }
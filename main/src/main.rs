@@ -144,6 +144,8 @@ fn main() -> Result<(), std::io::Error> {
                     global_config.security.tls_config.clone(),
                     global_config.query.query_sql_limit,
                     global_config.query.write_sql_limit,
+                    global_config.storage.backup_path.clone(),
+                    global_config.security.admin_users.clone(),
                 ));
                 let grpc_service = Box::new(GrpcService::new(
                     dbms.clone(),
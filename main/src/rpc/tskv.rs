@@ -121,11 +121,15 @@ impl TskvService for TskvServiceImpl {
                     //     .send(tskv::Task::WritePoints { req, tx })
                     //     .await
                     //     .map_err(|err| Status::internal(err.to_string()));
-                    let ret = self
-                        .kv_engine
-                        .write(req)
-                        .await
-                        .map_err(|err| Status::internal(err.to_string()));
+                    let ret = self.kv_engine.write(req).await.map_err(|err| match err {
+                        tskv::Error::SeriesLimitExceeded { .. } => {
+                            Status::resource_exhausted(err.to_string())
+                        }
+                        tskv::Error::WriteBufferFull { .. } | tskv::Error::WriteStalled { .. } => {
+                            Status::unavailable(err.to_string())
+                        }
+                        _ => Status::internal(err.to_string()),
+                    });
                     // 2. if something wrong when sending Request
                     // if let Err(err) = ret {
                     //     resp_sender.send(Err(err)).await.expect("successful");
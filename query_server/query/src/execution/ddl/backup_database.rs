@@ -0,0 +1,34 @@
+use crate::execution::ddl::DDLDefinitionTask;
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::BackupDatabase;
+
+pub struct BackupDatabaseTask {
+    stmt: BackupDatabase,
+}
+
+impl BackupDatabaseTask {
+    pub fn new(stmt: BackupDatabase) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for BackupDatabaseTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        query_state_machine
+            .catalog
+            .backup_database(
+                &self.stmt.database_name,
+                &self.stmt.path,
+                self.stmt.since.as_deref(),
+            )
+            .await
+            .context(execution::MetadataSnafu)
+    }
+}
@@ -7,9 +7,14 @@ use spi::query::{self, QueryError};
 use spi::query::execution::ExecutionError;
 
 use self::create_table::CreateTableTask;
+use crate::execution::ddl::backup_database::BackupDatabaseTask;
 use crate::execution::ddl::create_database::CreateDatabaseTask;
 use crate::execution::ddl::describe_database::DescribeDatabaseTask;
 use crate::execution::ddl::describe_table::DescribeTableTask;
+use crate::execution::ddl::restore_database::RestoreDatabaseTask;
+use crate::execution::ddl::show_cardinality::{
+    ShowSeriesCardinalityTask, ShowTagValuesCardinalityTask,
+};
 use crate::execution::ddl::show_database::ShowDatabasesTask;
 use crate::execution::ddl::show_table::ShowTablesTask;
 use snafu::ResultExt;
@@ -17,12 +22,15 @@ use snafu::ResultExt;
 use self::create_external_table::CreateExternalTableTask;
 use self::drop_object::DropObjectTask;
 
+mod backup_database;
 mod create_database;
 mod create_external_table;
 mod create_table;
 mod describe_database;
 mod describe_table;
 mod drop_object;
+mod restore_database;
+mod show_cardinality;
 mod show_database;
 mod show_table;
 
@@ -87,6 +95,18 @@ impl DDLDefinitionTaskFactory {
             DDLPlan::DescribeTable(sub_plan) => Box::new(DescribeTableTask::new(sub_plan.clone())),
             DDLPlan::ShowTables(sub_plan) => Box::new(ShowTablesTask::new(sub_plan.clone())),
             DDLPlan::ShowDatabases() => Box::new(ShowDatabasesTask::new()),
+            DDLPlan::ShowSeriesCardinality(sub_plan) => {
+                Box::new(ShowSeriesCardinalityTask::new(sub_plan.clone()))
+            }
+            DDLPlan::ShowTagValuesCardinality(sub_plan) => {
+                Box::new(ShowTagValuesCardinalityTask::new(sub_plan.clone()))
+            }
+            DDLPlan::BackupDatabase(sub_plan) => {
+                Box::new(BackupDatabaseTask::new(sub_plan.clone()))
+            }
+            DDLPlan::RestoreDatabase(sub_plan) => {
+                Box::new(RestoreDatabaseTask::new(sub_plan.clone()))
+            }
         }
     }
 }
@@ -0,0 +1,30 @@
+use crate::execution::ddl::DDLDefinitionTask;
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::RestoreDatabase;
+
+pub struct RestoreDatabaseTask {
+    stmt: RestoreDatabase,
+}
+
+impl RestoreDatabaseTask {
+    pub fn new(stmt: RestoreDatabase) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for RestoreDatabaseTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        query_state_machine
+            .catalog
+            .restore_database(&self.stmt.database_name, &self.stmt.path)
+            .await
+            .context(execution::MetadataSnafu)
+    }
+}
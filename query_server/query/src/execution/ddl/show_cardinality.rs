@@ -0,0 +1,52 @@
+use crate::execution::ddl::DDLDefinitionTask;
+use async_trait::async_trait;
+use snafu::ResultExt;
+use spi::query::execution;
+use spi::query::execution::{ExecutionError, Output, QueryStateMachineRef};
+use spi::query::logical_planner::{ShowSeriesCardinality, ShowTagValuesCardinality};
+
+pub struct ShowSeriesCardinalityTask {
+    stmt: ShowSeriesCardinality,
+}
+
+impl ShowSeriesCardinalityTask {
+    pub fn new(stmt: ShowSeriesCardinality) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for ShowSeriesCardinalityTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        query_state_machine
+            .catalog
+            .show_series_cardinality(&self.stmt.table_name, self.stmt.exact)
+            .context(execution::MetadataSnafu)
+    }
+}
+
+pub struct ShowTagValuesCardinalityTask {
+    stmt: ShowTagValuesCardinality,
+}
+
+impl ShowTagValuesCardinalityTask {
+    pub fn new(stmt: ShowTagValuesCardinality) -> Self {
+        Self { stmt }
+    }
+}
+
+#[async_trait]
+impl DDLDefinitionTask for ShowTagValuesCardinalityTask {
+    async fn execute(
+        &self,
+        query_state_machine: QueryStateMachineRef,
+    ) -> Result<Output, ExecutionError> {
+        query_state_machine
+            .catalog
+            .show_tag_values_cardinality(&self.stmt.table_name, &self.stmt.tag_key, self.stmt.exact)
+            .context(execution::MetadataSnafu)
+    }
+}
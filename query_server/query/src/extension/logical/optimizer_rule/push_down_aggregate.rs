@@ -0,0 +1,252 @@
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::datatypes::IntervalDayTimeType,
+    datasource::source_as_provider,
+    logical_expr::{
+        Aggregate, AggregateFunction, BinaryExpr, BuiltinScalarFunction, Extension, LogicalPlan,
+        Operator, TableScan,
+    },
+    optimizer::{utils::split_conjunction, OptimizerConfig, OptimizerRule},
+    prelude::Expr,
+    scalar::ScalarValue,
+};
+use models::{
+    schema::{ColumnType, TskvTableSchema},
+    ValueType,
+};
+
+use crate::{
+    extension::logical::plan_node::tskv_aggregate::{
+        TskvAggregate, TskvAggregateFunction, TskvAggregatePlanNode, TskvGroupBy,
+    },
+    table::ClusterTable,
+};
+
+use datafusion::error::Result;
+
+const NANOS_PER_DAY: i64 = 86_400_000_000_000;
+const NANOS_PER_MILLI: i64 = 1_000_000;
+
+/// Answer simple aggregates by tskv, which use per-block value statistics instead of
+/// decoding every value.
+///
+/// Triggering conditions:
+/// 1. The input of the aggregate is a scan of ClusterTable, optionally under a filter
+/// 2. Filters only compare the time column with literals, or tag columns with string literals by `=`
+/// 3. Groups are tag columns or at most one `date_bin(interval, time, origin)`
+/// 4. Aggregates are count/min/max/sum of a field without DISTINCT or FILTER, min/max/sum
+///    of string fields and sum of boolean fields are not supported, and `count(*)` is only
+///    supported if the table has exactly one field column
+///
+/// first/last are not pushed down: DataFusion has no such aggregate functions to rewrite,
+/// and block statistics don't record the first and last values of a block.
+pub struct PushDownAggregateToTskv {}
+
+impl OptimizerRule for PushDownAggregateToTskv {
+    fn optimize(
+        &self,
+        plan: &LogicalPlan,
+        optimizer_config: &mut OptimizerConfig,
+    ) -> Result<LogicalPlan> {
+        if let LogicalPlan::Aggregate(Aggregate {
+            input,
+            group_expr,
+            aggr_expr,
+            schema,
+        }) = plan
+        {
+            let (predicate, scan) = match input.as_ref() {
+                LogicalPlan::Filter(filter) => (Some(filter.predicate()), filter.input().as_ref()),
+                other => (None, other),
+            };
+
+            if let LogicalPlan::TableScan(TableScan {
+                table_name,
+                source,
+                filters,
+                fetch: None,
+                ..
+            }) = scan
+            {
+                if let Some(cluster_table) = source_as_provider(source)?
+                    .as_any()
+                    .downcast_ref::<ClusterTable>()
+                {
+                    let table_schema = cluster_table.table_schema();
+
+                    let mut all_filters: Vec<Expr> = filters.clone();
+                    if let Some(predicate) = predicate {
+                        for e in split_conjunction(predicate) {
+                            if !all_filters.contains(e) {
+                                all_filters.push(e.clone());
+                            }
+                        }
+                    }
+
+                    let group_by = group_expr
+                        .iter()
+                        .map(|e| to_tskv_group_by(table_schema, e))
+                        .collect::<Option<Vec<_>>>();
+                    let aggregates = aggr_expr
+                        .iter()
+                        .map(|e| to_tskv_aggregate(table_schema, e))
+                        .collect::<Option<Vec<_>>>();
+
+                    if let (Some(group_by), Some(aggregates)) = (group_by, aggregates) {
+                        let time_buckets = group_by
+                            .iter()
+                            .filter(|g| matches!(g, TskvGroupBy::TimeBucket { .. }))
+                            .count();
+                        if time_buckets <= 1
+                            && all_filters.iter().all(|e| is_exact_filter(table_schema, e))
+                        {
+                            return Ok(LogicalPlan::Extension(Extension {
+                                node: Arc::new(TskvAggregatePlanNode {
+                                    table_name: table_name.clone(),
+                                    source: Arc::new(cluster_table.clone()),
+                                    filters: all_filters,
+                                    group_expr: group_expr.clone(),
+                                    aggr_expr: aggr_expr.clone(),
+                                    group_by,
+                                    aggregates,
+                                    schema: schema.clone(),
+                                }),
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+
+        datafusion::optimizer::utils::optimize_children(self, plan, optimizer_config)
+    }
+
+    fn name(&self) -> &str {
+        "push_down_aggregate_to_tskv"
+    }
+}
+
+/// Returns true if the filter can be evaluated by tskv without any false positive.
+fn is_exact_filter(table_schema: &TskvTableSchema, expr: &Expr) -> bool {
+    let (column, op, value) = match expr {
+        Expr::BinaryExpr(BinaryExpr { left, op, right }) => match (left.as_ref(), right.as_ref()) {
+            (Expr::Column(c), Expr::Literal(v)) => (c, *op, v),
+            (Expr::Literal(v), Expr::Column(c)) => match op.swap() {
+                Some(op) => (c, op, v),
+                None => return false,
+            },
+            _ => return false,
+        },
+        _ => return false,
+    };
+    if value.is_null() {
+        return false;
+    }
+
+    match table_schema.column(&column.name).map(|c| &c.column_type) {
+        Some(ColumnType::Time) => {
+            matches!(value, ScalarValue::TimestampNanosecond(..))
+                && matches!(
+                    op,
+                    Operator::Eq | Operator::Lt | Operator::LtEq | Operator::Gt | Operator::GtEq
+                )
+        }
+        Some(ColumnType::Tag) => matches!(value, ScalarValue::Utf8(_)) && op == Operator::Eq,
+        _ => false,
+    }
+}
+
+fn to_tskv_group_by(table_schema: &TskvTableSchema, expr: &Expr) -> Option<TskvGroupBy> {
+    match expr {
+        Expr::Alias(expr, _) => to_tskv_group_by(table_schema, expr),
+        Expr::Column(c) => match table_schema.column(&c.name)?.column_type {
+            ColumnType::Tag => Some(TskvGroupBy::Tag(c.name.clone())),
+            _ => None,
+        },
+        Expr::ScalarFunction {
+            fun: BuiltinScalarFunction::DateBin,
+            args,
+        } => {
+            let stride = match args.first()? {
+                Expr::Literal(ScalarValue::IntervalDayTime(Some(v))) => {
+                    let (days, millis) = IntervalDayTimeType::to_parts(*v);
+                    (days as i64)
+                        .checked_mul(NANOS_PER_DAY)?
+                        .checked_add((millis as i64).checked_mul(NANOS_PER_MILLI)?)?
+                }
+                _ => return None,
+            };
+            match args.get(1)? {
+                Expr::Column(c) if table_schema.column(&c.name)?.column_type.is_time() => {}
+                _ => return None,
+            }
+            let origin = match args.get(2) {
+                Some(Expr::Literal(ScalarValue::TimestampNanosecond(Some(v), _))) => *v,
+                None => 0,
+                _ => return None,
+            };
+            if stride <= 0 || args.len() > 3 {
+                return None;
+            }
+            Some(TskvGroupBy::TimeBucket { stride, origin })
+        }
+        _ => None,
+    }
+}
+
+fn to_tskv_aggregate(table_schema: &TskvTableSchema, expr: &Expr) -> Option<TskvAggregate> {
+    if let Expr::Alias(expr, _) = expr {
+        return to_tskv_aggregate(table_schema, expr);
+    }
+    if let Expr::AggregateFunction {
+        fun,
+        args,
+        distinct: false,
+        filter: None,
+    } = expr
+    {
+        let function = match fun {
+            AggregateFunction::Count => TskvAggregateFunction::Count,
+            AggregateFunction::Min => TskvAggregateFunction::Min,
+            AggregateFunction::Max => TskvAggregateFunction::Max,
+            AggregateFunction::Sum => TskvAggregateFunction::Sum,
+            _ => return None,
+        };
+        if args.len() != 1 {
+            return None;
+        }
+        match &args[0] {
+            Expr::Column(c) => {
+                let supported = match table_schema.column(&c.name)?.column_type {
+                    ColumnType::Field(
+                        ValueType::Float | ValueType::Integer | ValueType::Unsigned,
+                    ) => true,
+                    ColumnType::Field(ValueType::Boolean) => function != TskvAggregateFunction::Sum,
+                    ColumnType::Field(ValueType::String) => {
+                        function == TskvAggregateFunction::Count
+                    }
+                    _ => false,
+                };
+                supported.then(|| TskvAggregate {
+                    function,
+                    field: Some(c.name.clone()),
+                })
+            }
+            // count(*) counts rows, which is the number of values if there is only one field
+            Expr::Literal(v)
+                if function == TskvAggregateFunction::Count
+                    && !v.is_null()
+                    && table_schema.fields().len() == 1 =>
+            {
+                Some(TskvAggregate {
+                    function,
+                    field: None,
+                })
+            }
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    any::Any,
+    fmt::{self, Debug},
+    sync::Arc,
+};
+
+use datafusion::{
+    common::DFSchemaRef,
+    logical_expr::{LogicalPlan, UserDefinedLogicalNode},
+    prelude::Expr,
+};
+
+use crate::table::ClusterTable;
+
+/// Aggregate function that can be answered by tskv
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TskvAggregateFunction {
+    Count,
+    Min,
+    Max,
+    Sum,
+}
+
+/// Aggregate expression pushed down to tskv
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TskvAggregate {
+    pub function: TskvAggregateFunction,
+    /// Name of the aggregated field column, all rows of the only field column
+    /// are counted if `None` (e.g. `count(*)`).
+    pub field: Option<String>,
+}
+
+/// Group by expression pushed down to tskv
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TskvGroupBy {
+    /// Group by the value of a tag column
+    Tag(String),
+    /// Group by `date_bin(stride, time, origin)`, in nanoseconds
+    TimeBucket { stride: i64, origin: i64 },
+}
+
+#[derive(Clone)]
+pub struct TskvAggregatePlanNode {
+    /// The name of the table
+    pub table_name: String,
+    /// The source of the table
+    pub source: Arc<ClusterTable>,
+    /// Expressions to be used as filters by the table provider,
+    /// all of them must be fully evaluated by tskv
+    pub filters: Vec<Expr>,
+    /// Grouping expressions, in the same order as `group_by`
+    pub group_expr: Vec<Expr>,
+    /// Aggregate expressions, in the same order as `aggregates`
+    pub aggr_expr: Vec<Expr>,
+    pub group_by: Vec<TskvGroupBy>,
+    pub aggregates: Vec<TskvAggregate>,
+    /// The schema description of the aggregate output
+    pub schema: DFSchemaRef,
+}
+
+impl Debug for TskvAggregatePlanNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_for_explain(f)
+    }
+}
+
+impl UserDefinedLogicalNode for TskvAggregatePlanNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![]
+    }
+
+    /// Schema for TskvAggregate is the same as the replaced aggregate
+    fn schema(&self) -> &DFSchemaRef {
+        &self.schema
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "TskvAggregate: table={}, groupBy=[{:?}], aggr=[{:?}], filters={:?}",
+            self.table_name, self.group_expr, self.aggr_expr, self.filters
+        )
+    }
+
+    fn from_template(
+        &self,
+        exprs: &[Expr],
+        inputs: &[LogicalPlan],
+    ) -> Arc<dyn UserDefinedLogicalNode> {
+        assert_eq!(inputs.len(), 0, "input size inconsistent");
+        assert_eq!(exprs.len(), 0, "expr size inconsistent");
+        Arc::new(self.clone())
+    }
+}
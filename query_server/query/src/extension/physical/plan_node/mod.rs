@@ -1,3 +1,4 @@
 pub mod table_writer;
 pub mod tag_scan;
 pub mod topk;
+pub mod tskv_aggregate;
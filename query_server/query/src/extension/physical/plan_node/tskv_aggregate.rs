@@ -0,0 +1,665 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use datafusion::{
+    arrow::{
+        array::{new_empty_array, ArrayRef},
+        compute::cast,
+        datatypes::SchemaRef,
+        record_batch::RecordBatch,
+    },
+    error::{DataFusionError, Result},
+    execution::context::TaskContext,
+    physical_expr::PhysicalSortExpr,
+    physical_plan::{
+        metrics::{BaselineMetrics, ExecutionPlanMetricsSet},
+        stream::RecordBatchStreamAdapter,
+        DisplayFormatType, ExecutionPlan, Partitioning, SendableRecordBatchStream, Statistics,
+    },
+    scalar::ScalarValue,
+};
+use models::{
+    predicate::domain::PredicateRef,
+    schema::{ColumnType, TableColumn, TableSchemaRef},
+    utils::unite_id,
+    ColumnId, FieldId, Tag,
+};
+use trace::debug;
+use tskv::{
+    engine::EngineRef,
+    memcache::DataType,
+    tseries_family::{SuperVersion, TimeRange},
+    tsm::{BlockMeta, BlockStatistics, TsmReader},
+    ColumnFileId,
+};
+
+use crate::{
+    extension::logical::plan_node::tskv_aggregate::{
+        TskvAggregate, TskvAggregateFunction, TskvGroupBy,
+    },
+    iterator::filter_to_time_ranges,
+};
+
+/// Execution plan answering simple aggregates directly from tskv.
+///
+/// Blocks that lie fully inside the queried time range (and a single time bucket) are
+/// answered from the value statistics in the TSM index, only the other blocks are decoded.
+#[derive(Debug, Clone)]
+pub struct TskvAggregateExec {
+    table_schema: TableSchemaRef,
+    schema: SchemaRef,
+    predicate: PredicateRef,
+    group_by: Vec<TskvGroupBy>,
+    aggregates: Vec<TskvAggregate>,
+    engine: EngineRef,
+
+    /// Execution metrics
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl TskvAggregateExec {
+    pub(crate) fn new(
+        table_schema: TableSchemaRef,
+        schema: SchemaRef,
+        predicate: PredicateRef,
+        group_by: Vec<TskvGroupBy>,
+        aggregates: Vec<TskvAggregate>,
+        engine: EngineRef,
+    ) -> Self {
+        let metrics = ExecutionPlanMetricsSet::new();
+
+        Self {
+            table_schema,
+            schema,
+            predicate,
+            group_by,
+            aggregates,
+            engine,
+            metrics,
+        }
+    }
+
+    pub fn predicate(&self) -> PredicateRef {
+        self.predicate.clone()
+    }
+
+    /// Returns the field column aggregated by `aggregate`.
+    fn aggregate_column(&self, aggregate: &TskvAggregate) -> Result<TableColumn> {
+        let column = match &aggregate.field {
+            Some(name) => self.table_schema.column(name).cloned(),
+            None => self.table_schema.fields().into_iter().next(),
+        };
+        column.ok_or_else(|| {
+            DataFusionError::Internal(format!(
+                "field of aggregate {:?} not found in table {}",
+                aggregate, self.table_schema.name
+            ))
+        })
+    }
+
+    fn do_aggregate(&self) -> Result<RecordBatch> {
+        let filter = self
+            .predicate
+            .filter()
+            .translate_column(|c| self.table_schema.column(&c.name).cloned());
+        let time_filter = filter.translate_column(|e| match e.column_type {
+            ColumnType::Time => Some(e.name.clone()),
+            _ => None,
+        });
+        let tags_filter = filter.translate_column(|e| match e.column_type {
+            ColumnType::Tag => Some(e.name.clone()),
+            _ => None,
+        });
+        let time_ranges = filter_to_time_ranges(&time_filter);
+
+        let bucket = self.group_by.iter().find_map(|g| match g {
+            TskvGroupBy::TimeBucket { stride, origin } => Some((*stride, *origin)),
+            _ => None,
+        });
+
+        let mut columns: Vec<TableColumn> = Vec::with_capacity(self.aggregates.len());
+        for aggregate in self.aggregates.iter() {
+            let column = self.aggregate_column(aggregate)?;
+            if !columns.iter().any(|c| c.id == column.id) {
+                columns.push(column);
+            }
+        }
+
+        let mut groups: BTreeMap<Vec<GroupValue>, HashMap<ColumnId, FieldPartial>> =
+            BTreeMap::new();
+        if !time_ranges.is_empty() {
+            let db = &self.table_schema.db;
+            let versions = self
+                .engine
+                .get_db_versions(db, &time_ranges)
+                .map_err(|e| DataFusionError::External(Box::new(e)))?;
+            let series = self
+                .engine
+                .get_series_id_by_filter(db, &self.table_schema.name, &tags_filter)
+                .map_err(|e| DataFusionError::External(Box::new(e)))?;
+            debug!("TskvAggregateExec series number: {}", series.len());
+
+            let mut readers: HashMap<ColumnFileId, TsmReader> = HashMap::new();
+            for sid in series {
+                let key = match self
+                    .engine
+                    .get_series_key(db, sid)
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?
+                {
+                    Some(key) => key,
+                    None => continue,
+                };
+
+                for column in columns.iter() {
+                    let partials = aggregate_field(
+                        unite_id(column.id as u64, sid),
+                        &versions,
+                        &time_ranges,
+                        bucket,
+                        &mut readers,
+                    )
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?;
+
+                    for (bucket_ts, partial) in partials {
+                        let group_key = self
+                            .group_by
+                            .iter()
+                            .map(|g| match g {
+                                TskvGroupBy::Tag(name) => tag_group_value(key.tags(), name),
+                                TskvGroupBy::TimeBucket { .. } => {
+                                    GroupValue::Time(bucket_ts.unwrap_or_default())
+                                }
+                            })
+                            .collect::<Vec<_>>();
+                        groups
+                            .entry(group_key)
+                            .or_default()
+                            .entry(column.id)
+                            .or_default()
+                            .merge(partial.count, partial.stats);
+                    }
+                }
+            }
+        }
+
+        // Aggregate without group by always returns a row
+        if self.group_by.is_empty() && groups.is_empty() {
+            groups.insert(vec![], HashMap::new());
+        }
+
+        let fields = self.schema.fields();
+        let mut values: Vec<Vec<ScalarValue>> =
+            vec![Vec::with_capacity(groups.len()); fields.len()];
+        for (group_key, partials) in groups {
+            for (i, group_value) in group_key.into_iter().enumerate() {
+                values[i].push(match group_value {
+                    GroupValue::Tag(v) => ScalarValue::Utf8(v),
+                    GroupValue::Time(v) => ScalarValue::TimestampNanosecond(Some(v), None),
+                });
+            }
+            for (i, aggregate) in self.aggregates.iter().enumerate() {
+                let idx = self.group_by.len() + i;
+                let column = self.aggregate_column(aggregate)?;
+                let partial = partials.get(&column.id).copied().unwrap_or_default();
+                let value = match partial.output(aggregate.function) {
+                    Some(v) => v,
+                    None => ScalarValue::try_from(fields[idx].data_type())?,
+                };
+                values[idx].push(value);
+            }
+        }
+
+        let arrays = values
+            .into_iter()
+            .zip(fields.iter())
+            .map(|(values, field)| {
+                let array: ArrayRef = if values.is_empty() {
+                    new_empty_array(field.data_type())
+                } else {
+                    ScalarValue::iter_to_array(values)?
+                };
+                if array.data_type() == field.data_type() {
+                    Ok(array)
+                } else {
+                    Ok(cast(&array, field.data_type())?)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(RecordBatch::try_new(self.schema.clone(), arrays)?)
+    }
+}
+
+impl ExecutionPlan for TskvAggregateExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        Partitioning::UnknownPartitioning(1)
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        None
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(self.as_ref().clone()))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        debug!(
+            "Start TskvAggregateExec::execute for partition {} of context session_id {} and task_id {:?}",
+            partition,
+            context.session_id(),
+            context.task_id()
+        );
+
+        let metrics = BaselineMetrics::new(&self.metrics, partition);
+        let exec = self.clone();
+        let batch = async move {
+            let timer = metrics.elapsed_compute().timer();
+            // Reading TSM files blocks the thread, keep it off the async workers.
+            let batch = tokio::task::spawn_blocking(move || exec.do_aggregate())
+                .await
+                .map_err(|e| DataFusionError::External(Box::new(e)))??;
+            timer.done();
+            metrics.record_output(batch.num_rows());
+            Ok(batch)
+        };
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            futures::stream::once(batch),
+        )))
+    }
+
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default => {
+                write!(
+                    f,
+                    "TskvAggregateExec: groupBy={:?}, aggregates={:?}, predicate={:?}",
+                    self.group_by,
+                    self.aggregates,
+                    self.predicate.filter(),
+                )
+            }
+        }
+    }
+
+    fn statistics(&self) -> Statistics {
+        Statistics::default()
+    }
+
+    fn metrics(&self) -> Option<datafusion::physical_plan::metrics::MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum GroupValue {
+    /// Value of the tag, `None` if the series doesn't have the tag.
+    Tag(Option<String>),
+    Time(i64),
+}
+
+fn tag_group_value(tags: &[Tag], name: &str) -> GroupValue {
+    GroupValue::Tag(
+        tags.iter()
+            .find(|t| t.key == name.as_bytes())
+            .map(|t| String::from_utf8_lossy(&t.value).to_string()),
+    )
+}
+
+/// Partial aggregate state of a field
+#[derive(Debug, Default, Clone, Copy)]
+struct FieldPartial {
+    count: u64,
+    stats: Option<BlockStatistics>,
+}
+
+impl FieldPartial {
+    fn merge(&mut self, count: u64, stats: Option<BlockStatistics>) {
+        self.count += count;
+        self.stats = match (self.stats, stats) {
+            (Some(a), Some(b)) => Some(merge_statistics(a, b)),
+            (a, b) => a.or(b),
+        };
+    }
+
+    fn output(&self, function: TskvAggregateFunction) -> Option<ScalarValue> {
+        if function == TskvAggregateFunction::Count {
+            return Some(ScalarValue::Int64(Some(self.count as i64)));
+        }
+        let value = match (function, self.stats?) {
+            (TskvAggregateFunction::Min, BlockStatistics::U64 { min, .. }) => {
+                ScalarValue::UInt64(Some(min))
+            }
+            (TskvAggregateFunction::Max, BlockStatistics::U64 { max, .. }) => {
+                ScalarValue::UInt64(Some(max))
+            }
+            (TskvAggregateFunction::Sum, BlockStatistics::U64 { sum, .. }) => {
+                ScalarValue::UInt64(sum)
+            }
+            (TskvAggregateFunction::Min, BlockStatistics::I64 { min, .. }) => {
+                ScalarValue::Int64(Some(min))
+            }
+            (TskvAggregateFunction::Max, BlockStatistics::I64 { max, .. }) => {
+                ScalarValue::Int64(Some(max))
+            }
+            (TskvAggregateFunction::Sum, BlockStatistics::I64 { sum, .. }) => {
+                ScalarValue::Int64(sum)
+            }
+            (TskvAggregateFunction::Min, BlockStatistics::F64 { min, .. }) => {
+                ScalarValue::Float64(Some(min))
+            }
+            (TskvAggregateFunction::Max, BlockStatistics::F64 { max, .. }) => {
+                ScalarValue::Float64(Some(max))
+            }
+            (TskvAggregateFunction::Sum, BlockStatistics::F64 { sum, .. }) => {
+                ScalarValue::Float64(Some(sum))
+            }
+            (TskvAggregateFunction::Min, BlockStatistics::Bool { min, .. }) => {
+                ScalarValue::Boolean(Some(min))
+            }
+            (TskvAggregateFunction::Max, BlockStatistics::Bool { max, .. }) => {
+                ScalarValue::Boolean(Some(max))
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+fn merge_statistics(a: BlockStatistics, b: BlockStatistics) -> BlockStatistics {
+    use BlockStatistics::*;
+    match (a, b) {
+        (
+            U64 {
+                min: a_min,
+                max: a_max,
+                sum: a_sum,
+            },
+            U64 { min, max, sum },
+        ) => U64 {
+            min: a_min.min(min),
+            max: a_max.max(max),
+            sum: a_sum.zip(sum).map(|(a, b)| a.wrapping_add(b)),
+        },
+        (
+            I64 {
+                min: a_min,
+                max: a_max,
+                sum: a_sum,
+            },
+            I64 { min, max, sum },
+        ) => I64 {
+            min: a_min.min(min),
+            max: a_max.max(max),
+            sum: a_sum.zip(sum).map(|(a, b)| a.wrapping_add(b)),
+        },
+        (
+            F64 {
+                min: a_min,
+                max: a_max,
+                sum: a_sum,
+            },
+            F64 { min, max, sum },
+        ) => F64 {
+            min: a_min.min(min),
+            max: a_max.max(max),
+            sum: a_sum + sum,
+        },
+        (
+            Bool {
+                min: a_min,
+                max: a_max,
+                sum: a_sum,
+            },
+            Bool { min, max, sum },
+        ) => Bool {
+            min: a_min && min,
+            max: a_max || max,
+            sum: a_sum + sum,
+        },
+        (a, _) => a,
+    }
+}
+
+fn value_statistics(value: &DataType) -> Option<BlockStatistics> {
+    match *value {
+        DataType::U64(_, v) => Some(BlockStatistics::U64 {
+            min: v,
+            max: v,
+            sum: Some(v),
+        }),
+        DataType::I64(_, v) => Some(BlockStatistics::I64 {
+            min: v,
+            max: v,
+            sum: Some(v),
+        }),
+        DataType::F64(_, v) => Some(BlockStatistics::F64 {
+            min: v,
+            max: v,
+            sum: v,
+        }),
+        DataType::Bool(_, v) => Some(BlockStatistics::Bool {
+            min: v,
+            max: v,
+            sum: v as u64,
+        }),
+        DataType::Str(..) => None,
+    }
+}
+
+/// Same as `date_bin(stride, source, origin)` of DataFusion.
+fn date_bin(stride: i64, source: i64, origin: i64) -> i64 {
+    let time_diff = source.wrapping_sub(origin);
+    let time_delta = time_diff - (time_diff % stride);
+    let time_delta = if time_diff < 0 && stride > 1 {
+        time_delta.wrapping_sub(stride)
+    } else {
+        time_delta
+    };
+    origin.wrapping_add(time_delta)
+}
+
+/// Returns a flag for each of `ranges`, which is `true` if the range overlaps any other range.
+fn mark_overlapped(ranges: &[TimeRange]) -> Vec<bool> {
+    let mut sorted: Vec<usize> = (0..ranges.len()).collect();
+    sorted.sort_by_key(|i| (ranges[*i].min_ts, ranges[*i].max_ts));
+
+    let mut overlapped = vec![false; ranges.len()];
+    let mut prev_max_ts = None;
+    for (pos, i) in sorted.iter().enumerate() {
+        let range = &ranges[*i];
+        let overlaps_prev = matches!(prev_max_ts, Some(ts) if ts >= range.min_ts);
+        let overlaps_next =
+            matches!(sorted.get(pos + 1), Some(j) if ranges[*j].min_ts <= range.max_ts);
+        overlapped[*i] = overlaps_prev || overlaps_next;
+        prev_max_ts = Some(prev_max_ts.map_or(range.max_ts, |ts: i64| ts.max(range.max_ts)));
+    }
+    overlapped
+}
+
+/// Aggregates values of a field in `time_ranges`, returns partial aggregates for each
+/// time bucket, the bucket is `None` if not grouped by time.
+///
+/// A block is answered from its statistics if it is in a single time range and a single
+/// bucket, has no tombstone, and no other data of the field has an overlapped timestamp
+/// (which may overwrite values in the block), other blocks are decoded.
+fn aggregate_field(
+    field_id: FieldId,
+    versions: &[Arc<SuperVersion>],
+    time_ranges: &[TimeRange],
+    bucket: Option<(i64, i64)>,
+    readers: &mut HashMap<ColumnFileId, TsmReader>,
+) -> tskv::Result<HashMap<Option<i64>, FieldPartial>> {
+    let bucket_of = |ts: i64| bucket.map(|(stride, origin)| date_bin(stride, ts, origin));
+    let time_predicate = |ts: i64| time_ranges.iter().any(|tr| tr.contains(ts));
+
+    let mut mem_data: Vec<DataType> = Vec::new();
+    let mut blocks: Vec<(TsmReader, BlockMeta)> = Vec::new();
+    for version in versions.iter() {
+        version
+            .caches
+            .immut_cache
+            .iter()
+            .filter(|m| !m.read().flushed)
+            .for_each(|m| {
+                mem_data.append(&mut m.read().get_data(field_id, time_predicate, |_| true))
+            });
+        mem_data.append(&mut version.caches.mut_cache.read().get_data(
+            field_id,
+            time_predicate,
+            |_| true,
+        ));
+
+        for level in version.version.levels_info.iter().rev() {
+            for file in level.files.iter() {
+                if file.is_deleted() || !time_ranges.iter().any(|tr| file.overlap(tr)) {
+                    continue;
+                }
+                let reader = match readers.get(&file.file_id()) {
+                    Some(reader) => reader.clone(),
+                    None => {
+                        let reader = TsmReader::open(file.file_path())?;
+                        readers.insert(file.file_id(), reader.clone());
+                        reader
+                    }
+                };
+                for idx in reader.index_iterator_opt(field_id) {
+                    for blk in idx.block_iterator() {
+                        let blk_range = TimeRange::new(blk.min_ts(), blk.max_ts());
+                        if time_ranges.iter().any(|tr| tr.overlaps(&blk_range)) {
+                            blocks.push((reader.clone(), blk));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let ranges: Vec<TimeRange> = blocks
+        .iter()
+        .map(|(_, blk)| TimeRange::new(blk.min_ts(), blk.max_ts()))
+        .chain(
+            mem_data
+                .iter()
+                .map(|d| TimeRange::new(d.timestamp(), d.timestamp())),
+        )
+        .collect();
+    let overlapped = mark_overlapped(&ranges);
+
+    let mut partials: HashMap<Option<i64>, FieldPartial> = HashMap::new();
+    // Values of decoded blocks and caches, newer values overwrite older ones.
+    let mut values: BTreeMap<i64, DataType> = BTreeMap::new();
+    for (i, (reader, blk)) in blocks.iter().enumerate() {
+        let stats = blk.statistics().filter(|s| match s {
+            BlockStatistics::U64 { sum, .. } => sum.is_some(),
+            BlockStatistics::I64 { sum, .. } => sum.is_some(),
+            _ => true,
+        });
+        if let Some(stats) = stats {
+            if !overlapped[i]
+                && time_ranges.iter().any(|tr| tr.includes(&ranges[i]))
+                && bucket_of(blk.min_ts()) == bucket_of(blk.max_ts())
+                && reader.get_block_tombstone_time_ranges(blk).is_none()
+            {
+                partials
+                    .entry(bucket_of(blk.min_ts()))
+                    .or_default()
+                    .merge(blk.count() as u64, Some(stats));
+                continue;
+            }
+        }
+
+        let data_block = reader.get_data_block(blk)?;
+        for j in 0..data_block.len() {
+            if let Some(value) = data_block.get(j) {
+                if time_predicate(value.timestamp()) {
+                    values.insert(value.timestamp(), value);
+                }
+            }
+        }
+    }
+    for value in mem_data {
+        values.insert(value.timestamp(), value);
+    }
+    for (ts, value) in values {
+        partials
+            .entry(bucket_of(ts))
+            .or_default()
+            .merge(1, value_statistics(&value));
+    }
+
+    Ok(partials)
+}
+
+#[cfg(test)]
+mod test {
+    use models::Tag;
+    use tskv::tseries_family::TimeRange;
+
+    use super::{date_bin, mark_overlapped, tag_group_value, GroupValue};
+
+    #[test]
+    fn test_date_bin() {
+        assert_eq!(date_bin(10, 25, 0), 20);
+        assert_eq!(date_bin(10, 20, 0), 20);
+        assert_eq!(date_bin(10, 25, 3), 23);
+        assert_eq!(date_bin(10, -5, 0), -10);
+    }
+
+    #[test]
+    fn test_mark_overlapped() {
+        let ranges = vec![
+            TimeRange::new(1, 10),
+            TimeRange::new(11, 20),
+            TimeRange::new(15, 15),
+            TimeRange::new(21, 100),
+            TimeRange::new(30, 40),
+            TimeRange::new(101, 101),
+        ];
+        assert_eq!(
+            mark_overlapped(&ranges),
+            vec![false, true, true, true, true, false]
+        );
+    }
+
+    #[test]
+    fn test_tag_group_value() {
+        let tags = vec![
+            Tag::new(b"t0".to_vec(), b"a".to_vec()),
+            Tag::new(b"t1".to_vec(), b"".to_vec()),
+        ];
+        assert_eq!(
+            tag_group_value(&tags, "t0"),
+            GroupValue::Tag(Some("a".to_string()))
+        );
+        assert_eq!(
+            tag_group_value(&tags, "t1"),
+            GroupValue::Tag(Some("".to_string()))
+        );
+        assert_eq!(tag_group_value(&tags, "t2"), GroupValue::Tag(None));
+    }
+}
@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::{
+    execution::context::SessionState,
+    logical_expr::{LogicalPlan, UserDefinedLogicalNode},
+    physical_plan::{planner::ExtensionPlanner, ExecutionPlan, PhysicalPlanner},
+};
+
+use crate::extension::logical::plan_node::tskv_aggregate::TskvAggregatePlanNode;
+
+use datafusion::error::Result;
+
+/// Physical planner for TskvAggregate nodes
+pub struct TskvAggregatePlanner {}
+
+#[async_trait]
+impl ExtensionPlanner for TskvAggregatePlanner {
+    /// Create a physical plan for an extension node
+    async fn plan_extension(
+        &self,
+        _planner: &dyn PhysicalPlanner,
+        node: &dyn UserDefinedLogicalNode,
+        _logical_inputs: &[&LogicalPlan],
+        _physical_inputs: &[Arc<dyn ExecutionPlan>],
+        session_state: &SessionState,
+    ) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+        Ok(
+            if let Some(TskvAggregatePlanNode {
+                source,
+                filters,
+                group_by,
+                aggregates,
+                schema,
+                ..
+            }) = as_tskv_aggregate_plan_node(node)
+            {
+                let aggregate = source
+                    .aggregate(
+                        session_state,
+                        schema,
+                        filters,
+                        group_by.clone(),
+                        aggregates.clone(),
+                    )
+                    .await?;
+
+                Some(aggregate)
+            } else {
+                None
+            },
+        )
+    }
+}
+
+fn as_tskv_aggregate_plan_node(
+    node: &dyn UserDefinedLogicalNode,
+) -> Option<&TskvAggregatePlanNode> {
+    node.as_any().downcast_ref::<TskvAggregatePlanNode>()
+}
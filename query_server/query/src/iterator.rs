@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::ops::{Bound, RangeBounds};
 
@@ -20,7 +21,7 @@ use tskv::{
     error::IndexErrSnafu,
     memcache::DataType,
     tseries_family::{ColumnFile, SuperVersion, TimeRange},
-    tsm::{BlockMetaIterator, DataBlock, TsmReader},
+    tsm::{BlockMeta, BlockMetaIterator, BlockStatistics, DataBlock, TsmReader},
     ColumnFileId, Error,
 };
 
@@ -60,24 +61,37 @@ pub struct QueryOption {
 pub struct FieldFileLocation {
     reader: TsmReader,
     block_it: BlockMetaIterator,
+    /// Pushed-down predicate of the field, used to skip blocks by value statistics.
+    domain: Option<Arc<Domain>>,
 
     read_index: usize,
     data_block: DataBlock,
 }
 
 impl FieldFileLocation {
-    pub fn new(reader: TsmReader, block_it: BlockMetaIterator, vtype: ValueType) -> Self {
+    pub fn new(
+        reader: TsmReader,
+        block_it: BlockMetaIterator,
+        vtype: ValueType,
+        domain: Option<Arc<Domain>>,
+    ) -> Self {
         Self {
             reader,
             block_it,
+            domain,
             read_index: 0,
             data_block: DataBlock::new(0, vtype),
         }
     }
 
     pub fn peek(&mut self) -> Result<Option<DataType>, Error> {
-        if self.read_index >= self.data_block.len() {
+        while self.read_index >= self.data_block.len() {
             if let Some(meta) = self.block_it.next() {
+                if let Some(domain) = &self.domain {
+                    if !block_may_match(domain, &meta) {
+                        continue;
+                    }
+                }
                 self.read_index = 0;
                 self.data_block = self.reader.get_data_block(&meta)?;
             } else {
@@ -197,12 +211,19 @@ impl FieldCursor {
         vtype: ValueType,
         iterator: &mut RowIterator,
     ) -> Result<Self, Error> {
-        let version = match iterator.version.clone() {
-            Some(v) => v,
-            None => return Ok(Self::empty(vtype, name)),
-        };
+        let versions = iterator.versions.clone();
+        if versions.is_empty() {
+            return Ok(Self::empty(vtype, name));
+        }
 
         let time_ranges: Vec<TimeRange> = filter_to_time_ranges(&iterator.option.time_filter);
+        let domain = iterator
+            .option
+            .fields_filter
+            .domains()
+            .and_then(|domains| domains.get(&name))
+            .filter(|domain| !matches!(domain, Domain::All))
+            .map(|domain| Arc::new(domain.clone()));
 
         // get data from im_memcache and memcache
         let mut mem_data: Vec<DataType> = Vec::new();
@@ -213,20 +234,22 @@ impl FieldCursor {
                 .any(|time_range| time_range.is_boundless() || time_range.contains(ts))
         };
 
-        version
-            .caches
-            .immut_cache
-            .iter()
-            .filter(|m| !m.read().flushed)
-            .for_each(|m| {
-                mem_data.append(&mut m.read().get_data(field_id, time_predicate, |_| true))
-            });
-
-        mem_data.append(&mut version.caches.mut_cache.read().get_data(
-            field_id,
-            time_predicate,
-            |_| true,
-        ));
+        for version in versions.iter() {
+            version
+                .caches
+                .immut_cache
+                .iter()
+                .filter(|m| !m.read().flushed)
+                .for_each(|m| {
+                    mem_data.append(&mut m.read().get_data(field_id, time_predicate, |_| true))
+                });
+
+            mem_data.append(&mut version.caches.mut_cache.read().get_data(
+                field_id,
+                time_predicate,
+                |_| true,
+            ));
+        }
 
         mem_data.sort_by_key(|data| data.timestamp());
 
@@ -238,28 +261,35 @@ impl FieldCursor {
 
         // get data from levelinfo
         let mut locations = vec![];
-        for level in version.version.levels_info.iter().rev() {
-            for file in level.files.iter() {
-                if file.is_deleted() {
-                    continue;
-                }
-
-                for time_range in time_ranges.iter() {
-                    if !file.overlap(time_range) {
+        for version in versions.iter() {
+            for level in version.version.levels_info.iter().rev() {
+                for file in level.files.iter() {
+                    if file.is_deleted() {
                         continue;
                     }
 
-                    debug!(
-                        "build file data block id: {:02X}, len: {}",
-                        field_id,
-                        file.file_path().display()
-                    );
-
-                    let tsm_reader = iterator.get_tsm_reader(file.clone())?;
-                    for idx in tsm_reader.index_iterator_opt(field_id) {
-                        let block_it = idx.block_iterator_opt(time_range);
-                        let location = FieldFileLocation::new(tsm_reader.clone(), block_it, vtype);
-                        locations.push(location);
+                    for time_range in time_ranges.iter() {
+                        if !file.overlap(time_range) {
+                            continue;
+                        }
+
+                        debug!(
+                            "build file data block id: {:02X}, len: {}",
+                            field_id,
+                            file.file_path().display()
+                        );
+
+                        let tsm_reader = iterator.get_tsm_reader(file.clone())?;
+                        for idx in tsm_reader.index_iterator_opt(field_id) {
+                            let block_it = idx.block_iterator_opt(time_range);
+                            let location = FieldFileLocation::new(
+                                tsm_reader.clone(),
+                                block_it,
+                                vtype,
+                                domain.clone(),
+                            );
+                            locations.push(location);
+                        }
                     }
                 }
             }
@@ -344,6 +374,69 @@ impl Cursor for FieldCursor {
     }
 }
 
+/// Returns `false` if value statistics of the block show that no value in the block
+/// can match the domain, blocks without statistics are always considered matching.
+fn block_may_match(domain: &Domain, meta: &BlockMeta) -> bool {
+    let (min, max) = match meta.statistics() {
+        Some(BlockStatistics::U64 { min, max, .. }) => (
+            ScalarValue::UInt64(Some(min)),
+            ScalarValue::UInt64(Some(max)),
+        ),
+        Some(BlockStatistics::I64 { min, max, .. }) => {
+            (ScalarValue::Int64(Some(min)), ScalarValue::Int64(Some(max)))
+        }
+        Some(BlockStatistics::F64 { min, max, .. }) => (
+            ScalarValue::Float64(Some(min)),
+            ScalarValue::Float64(Some(max)),
+        ),
+        Some(BlockStatistics::Bool { min, max, .. }) => (
+            ScalarValue::Boolean(Some(min)),
+            ScalarValue::Boolean(Some(max)),
+        ),
+        None => return true,
+    };
+
+    // Values of different types can't be compared, take them as matching.
+    let ge = |a: &ScalarValue, b: &ScalarValue| !matches!(a.partial_cmp(b), Some(Ordering::Less));
+    let gt = |a: &ScalarValue, b: &ScalarValue| {
+        !matches!(a.partial_cmp(b), Some(Ordering::Less | Ordering::Equal))
+    };
+    let overlaps = |start: Bound<&ScalarValue>, end: Bound<&ScalarValue>| {
+        let after_start = match start {
+            Bound::Included(v) => ge(&max, v),
+            Bound::Excluded(v) => gt(&max, v),
+            Bound::Unbounded => true,
+        };
+        let before_end = match end {
+            Bound::Included(v) => ge(v, &min),
+            Bound::Excluded(v) => gt(v, &min),
+            Bound::Unbounded => true,
+        };
+        after_start && before_end
+    };
+
+    match domain {
+        Domain::Range(range_set) => range_set
+            .low_indexed_ranges()
+            .into_iter()
+            .any(|(_, range)| overlaps(range.start_bound(), range.end_bound())),
+        Domain::Equtable(vals) => {
+            if !vals.is_white_list() {
+                return true;
+            }
+            vals.entries().into_iter().any(|entry: &ValueEntry| {
+                overlaps(
+                    Bound::Included(entry.value()),
+                    Bound::Included(entry.value()),
+                )
+            })
+        }
+        // Values in blocks are not indexed by patterns.
+        Domain::Pattern(_) | Domain::All => true,
+        Domain::None => false,
+    }
+}
+
 pub fn filter_to_time_ranges(time_domain: &ColumnDomains<String>) -> Vec<TimeRange> {
     if time_domain.is_none() {
         // Does not contain any data, and returns an empty array directly
@@ -403,7 +496,7 @@ pub fn filter_to_time_ranges(time_domain: &ColumnDomains<String>) -> Vec<TimeRan
                     }
                 }
             }
-            Domain::All => time_ranges.push(TimeRange::all()),
+            Domain::Pattern(_) | Domain::All => time_ranges.push(TimeRange::all()),
             Domain::None => return vec![],
         }
     }
@@ -418,7 +511,7 @@ pub struct RowIterator {
     engine: EngineRef,
     option: QueryOption,
     columns: Vec<CursorPtr>,
-    version: Option<Arc<SuperVersion>>,
+    versions: Vec<Arc<SuperVersion>>,
 
     open_files: HashMap<ColumnFileId, TsmReader>,
 
@@ -432,7 +525,8 @@ impl RowIterator {
         option: QueryOption,
         batch_size: usize,
     ) -> Result<Self, Error> {
-        let version = engine.get_db_version(&option.table_schema.db)?;
+        let time_ranges = filter_to_time_ranges(&option.time_filter);
+        let versions = engine.get_db_versions(&option.table_schema.db, &time_ranges)?;
 
         let series = engine
             .get_series_id_by_filter(
@@ -448,7 +542,7 @@ impl RowIterator {
             series,
             engine,
             option,
-            version,
+            versions,
             batch_size,
 
             columns: vec![],
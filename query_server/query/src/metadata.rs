@@ -1,7 +1,8 @@
 use std::any::Any;
 
 use crate::catalog::{Database, UserCatalog, UserCatalogRef};
-use datafusion::arrow::array::{BooleanArray, StringArray};
+use async_trait::async_trait;
+use datafusion::arrow::array::{BooleanArray, StringArray, UInt64Array};
 use datafusion::arrow::datatypes::{DataType, Field, Schema};
 use datafusion::physical_plan::common::SizedRecordBatchStream;
 use datafusion::physical_plan::metrics::{ExecutionPlanMetricsSet, MemTrackingMetrics};
@@ -27,6 +28,7 @@ use spi::catalog::{
 };
 use spi::query::function::FuncMetaManagerRef;
 use std::sync::Arc;
+use tskv::backup::BackupManifest;
 use tskv::engine::EngineRef;
 
 /// remote meta
@@ -64,6 +66,26 @@ impl LocalCatalogMeta {
     }
 }
 
+impl LocalCatalogMeta {
+    /// Returns the database and the table of `table_name`, the database is the
+    /// current database if it's not specified.
+    fn resolve_optional_table(&self, table_name: &Option<String>) -> (String, Option<String>) {
+        match table_name {
+            None => (self.database_name.clone(), None),
+            Some(name) => {
+                let table: TableReference = name.as_str().into();
+                let table_ref =
+                    table.resolve(self.catalog_name.as_str(), self.database_name.as_str());
+                (
+                    table_ref.schema.to_string(),
+                    Some(table_ref.table.to_string()),
+                )
+            }
+        }
+    }
+}
+
+#[async_trait]
 impl MetaData for LocalCatalogMeta {
     fn as_any(&self) -> &dyn Any {
         self
@@ -176,6 +198,8 @@ impl MetaData for LocalCatalogMeta {
                     Field::new("VNODE_DURATION", DataType::Utf8, false),
                     Field::new("REPLICA", DataType::Utf8, false),
                     Field::new("PRECISION", DataType::Utf8, false),
+                    Field::new("MAX_SERIES_PER_DATABASE", DataType::Utf8, false),
+                    Field::new("MAX_SERIES_PER_TABLE", DataType::Utf8, false),
                 ]));
 
                 let ttl = db_cfg.config.ttl.to_string();
@@ -183,6 +207,15 @@ impl MetaData for LocalCatalogMeta {
                 let vnode_duration = db_cfg.config.vnode_duration.to_string();
                 let replica = db_cfg.config.replica.to_string();
                 let precision = db_cfg.config.precision.to_string();
+                // Unset limits are the defaults in config of the storage
+                let max_series_string = |max_series: Option<u64>| {
+                    max_series
+                        .map(|n| n.to_string())
+                        .unwrap_or_else(|| "DEFAULT".to_string())
+                };
+                let max_series_per_database =
+                    max_series_string(db_cfg.config.max_series_per_database);
+                let max_series_per_table = max_series_string(db_cfg.config.max_series_per_table);
 
                 let batch = RecordBatch::try_new(
                     schema,
@@ -192,6 +225,8 @@ impl MetaData for LocalCatalogMeta {
                         Arc::new(StringArray::from(vec![vnode_duration.as_str()])),
                         Arc::new(StringArray::from(vec![replica.as_str()])),
                         Arc::new(StringArray::from(vec![precision.as_str()])),
+                        Arc::new(StringArray::from(vec![max_series_per_database.as_str()])),
+                        Arc::new(StringArray::from(vec![max_series_per_table.as_str()])),
                     ],
                 )
                 .unwrap();
@@ -371,6 +406,100 @@ impl MetaData for LocalCatalogMeta {
             }
         }
     }
+
+    fn show_series_cardinality(&self, table_name: &Option<String>, exact: bool) -> Result<Output> {
+        let (database_name, table_name) = self.resolve_optional_table(table_name);
+        let cardinality = self
+            .engine
+            .get_series_cardinality(&database_name, table_name.as_deref(), exact)
+            .map_err(engine_error_to_metadata_error)?;
+        Ok(cardinality_output(cardinality))
+    }
+
+    fn show_tag_values_cardinality(
+        &self,
+        table_name: &Option<String>,
+        tag_key: &str,
+        exact: bool,
+    ) -> Result<Output> {
+        let (database_name, table_name) = self.resolve_optional_table(table_name);
+        let cardinality = self
+            .engine
+            .get_tag_values_cardinality(&database_name, table_name.as_deref(), tag_key, exact)
+            .map_err(engine_error_to_metadata_error)?;
+        Ok(cardinality_output(cardinality))
+    }
+
+    async fn backup_database(&self, name: &str, path: &str, since: Option<&str>) -> Result<Output> {
+        let manifest = self
+            .engine
+            .backup_database(name, path, since)
+            .await
+            .map_err(engine_error_to_metadata_error)?;
+        Ok(backup_manifest_output(path, &manifest))
+    }
+
+    async fn restore_database(&self, name: &str, path: &str) -> Result<Output> {
+        let manifest = self
+            .engine
+            .restore_database(name, path)
+            .await
+            .map_err(engine_error_to_metadata_error)?;
+        Ok(backup_manifest_output(path, &manifest))
+    }
+}
+
+fn engine_error_to_metadata_error(err: tskv::Error) -> MetadataError {
+    match err {
+        tskv::Error::DatabaseNotFound { database } => MetadataError::DatabaseNotExists {
+            database_name: database,
+        },
+        tskv::Error::DatabaseAlreadyExists { database } => MetadataError::DatabaseAlreadyExists {
+            database_name: database,
+        },
+        err => MetadataError::InternalError {
+            error_msg: err.to_string(),
+        },
+    }
+}
+
+fn cardinality_output(cardinality: u64) -> Output {
+    let schema = Arc::new(Schema::new(vec![Field::new(
+        "CARDINALITY",
+        DataType::UInt64,
+        false,
+    )]));
+
+    let batch =
+        RecordBatch::try_new(schema, vec![Arc::new(UInt64Array::from(vec![cardinality]))]).unwrap();
+
+    Output::StreamData(stream_from_batches(vec![Arc::new(batch)]))
+}
+
+fn backup_manifest_output(path: &str, manifest: &BackupManifest) -> Output {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("DATABASE", DataType::Utf8, false),
+        Field::new("ID", DataType::Utf8, false),
+        Field::new("PARENT", DataType::Utf8, true),
+        Field::new("PATH", DataType::Utf8, false),
+        Field::new("FILES", DataType::UInt64, false),
+        Field::new("SIZE", DataType::UInt64, false),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(StringArray::from(vec![manifest.database.name.as_str()])),
+            Arc::new(StringArray::from(vec![manifest.id.as_str()])),
+            Arc::new(StringArray::from(vec![manifest.parent.as_deref()])),
+            Arc::new(StringArray::from(vec![path])),
+            Arc::new(UInt64Array::from(vec![manifest.files.len() as u64])),
+            Arc::new(UInt64Array::from(vec![manifest.total_size()])),
+        ],
+    )
+    .unwrap();
+
+    Output::StreamData(stream_from_batches(vec![Arc::new(batch)]))
 }
 
 pub struct MetadataProvider {
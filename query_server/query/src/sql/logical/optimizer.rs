@@ -24,8 +24,8 @@ use spi::query::LogicalOptimizeSnafu;
 
 use crate::extension::logical::optimizer_rule::{
     implicit_type_conversion::ImplicitTypeConversion,
-    projection_push_down::ProjectionPushDownAdapter, reject_cross_join::RejectCrossJoin,
-    rewrite_tag_scan::RewriteTagScan,
+    projection_push_down::ProjectionPushDownAdapter, push_down_aggregate::PushDownAggregateToTskv,
+    reject_cross_join::RejectCrossJoin, rewrite_tag_scan::RewriteTagScan,
     transform_bottom_func_to_topk_node::TransformBottomFuncToTopkNodeRule,
     transform_topk_func_to_topk_node::TransformTopkFuncToTopkNodeRule,
 };
@@ -82,6 +82,7 @@ impl Default for DefaultLogicalOptimizer {
             // cnosdb rules
             Arc::new(TransformBottomFuncToTopkNodeRule {}),
             Arc::new(TransformTopkFuncToTopkNodeRule {}),
+            Arc::new(PushDownAggregateToTskv {}),
         ];
 
         Self { rules }
@@ -11,8 +11,9 @@ use datafusion::sql::sqlparser::{
 use models::codec::Encoding;
 use snafu::ResultExt;
 use spi::query::ast::{
-    ColumnOption, CreateDatabase, CreateTable, DatabaseOptions, DescribeDatabase, DescribeTable,
-    DropObject, ExtStatement, ObjectType,
+    BackupDatabase, ColumnOption, CreateDatabase, CreateTable, DatabaseOptions, DescribeDatabase,
+    DescribeTable, DropObject, ExtStatement, ObjectType, RestoreDatabase, ShowSeriesCardinality,
+    ShowTagValuesCardinality,
 };
 use spi::query::parser::Parser as CnosdbParser;
 use spi::query::ParserSnafu;
@@ -43,6 +44,26 @@ enum CnosKeyWord {
     REPLICA,
     #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
     PRECISION,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    MAX_SERIES_PER_DATABASE,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    MAX_SERIES_PER_TABLE,
+
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    BACKUP,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    RESTORE,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    INCREMENTAL,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    SINCE,
+
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    SERIES,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    CARDINALITY,
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    EXACT,
 }
 
 // impl CnosKeyWord {
@@ -75,7 +96,16 @@ impl FromStr for CnosKeyWord {
             "VNODE_DURATION" => Ok(CnosKeyWord::VNODE_DURATION),
             "REPLICA" => Ok(CnosKeyWord::REPLICA),
             "PRECISION" => Ok(CnosKeyWord::PRECISION),
+            "MAX_SERIES_PER_DATABASE" => Ok(CnosKeyWord::MAX_SERIES_PER_DATABASE),
+            "MAX_SERIES_PER_TABLE" => Ok(CnosKeyWord::MAX_SERIES_PER_TABLE),
             "DATABASES" => Ok(CnosKeyWord::DATABASES),
+            "BACKUP" => Ok(CnosKeyWord::BACKUP),
+            "RESTORE" => Ok(CnosKeyWord::RESTORE),
+            "INCREMENTAL" => Ok(CnosKeyWord::INCREMENTAL),
+            "SINCE" => Ok(CnosKeyWord::SINCE),
+            "SERIES" => Ok(CnosKeyWord::SERIES),
+            "CARDINALITY" => Ok(CnosKeyWord::CARDINALITY),
+            "EXACT" => Ok(CnosKeyWord::EXACT),
             _ => Err(ParserError::ParserError(format!(
                 "fail parse {} to CnosKeyWord",
                 s
@@ -183,6 +213,8 @@ impl<'a> ExtParser<'a> {
                     self.parser.next_token();
                     self.parse_create()
                 }
+                _ if self.parse_cnos_keyword(CnosKeyWord::BACKUP) => self.parse_backup(),
+                _ if self.parse_cnos_keyword(CnosKeyWord::RESTORE) => self.parse_restore(),
                 _ => Ok(ExtStatement::SqlStatement(Box::new(
                     self.parser.parse_statement()?,
                 ))),
@@ -207,9 +239,53 @@ impl<'a> ExtParser<'a> {
             self.parse_show_tables()
         } else if self.parse_cnos_keyword(CnosKeyWord::DATABASES) {
             self.parse_show_databases()
+        } else if self.parse_cnos_keyword(CnosKeyWord::SERIES) {
+            self.parse_show_series_cardinality()
+        } else if self.parse_cnos_keyword(CnosKeyWord::TAG) {
+            self.parse_show_tag_values_cardinality()
         } else {
-            self.expected("tables/databases", self.parser.peek_token())
+            self.expected("tables/databases/series/tag", self.parser.peek_token())
+        }
+    }
+
+    /// Parse `[EXACT] CARDINALITY [FROM table]` of SHOW SERIES and SHOW TAG VALUES
+    fn parse_cardinality(&mut self) -> Result<(bool, Option<ObjectName>)> {
+        let exact = self.parse_cnos_keyword(CnosKeyWord::EXACT);
+        if !self.parse_cnos_keyword(CnosKeyWord::CARDINALITY) {
+            return self.expected("CARDINALITY", self.parser.peek_token());
         }
+        let table_name = if self.parser.parse_keyword(Keyword::FROM) {
+            Some(self.parser.parse_object_name()?)
+        } else {
+            None
+        };
+        Ok((exact, table_name))
+    }
+
+    /// Parse a SQL SHOW SERIES [EXACT] CARDINALITY [FROM table] statement
+    fn parse_show_series_cardinality(&mut self) -> Result<ExtStatement> {
+        let (exact, table_name) = self.parse_cardinality()?;
+        Ok(ExtStatement::ShowSeriesCardinality(ShowSeriesCardinality {
+            table_name,
+            exact,
+        }))
+    }
+
+    /// Parse a SQL SHOW TAG VALUES [EXACT] CARDINALITY [FROM table] WITH KEY = key statement
+    fn parse_show_tag_values_cardinality(&mut self) -> Result<ExtStatement> {
+        self.parser.expect_keyword(Keyword::VALUES)?;
+        let (exact, table_name) = self.parse_cardinality()?;
+        self.parser
+            .expect_keywords(&[Keyword::WITH, Keyword::KEY])?;
+        self.parser.expect_token(&Token::Eq)?;
+        let tag_key = self.parser.parse_identifier()?;
+        Ok(ExtStatement::ShowTagValuesCardinality(
+            ShowTagValuesCardinality {
+                table_name,
+                tag_key,
+                exact,
+            },
+        ))
     }
 
     fn parse_show_databases(&mut self) -> Result<ExtStatement> {
@@ -470,6 +546,10 @@ impl<'a> ExtParser<'a> {
                     options.replica = Some(self.parse_u64()?);
                 } else if self.parse_cnos_keyword(CnosKeyWord::PRECISION) {
                     options.precision = Some(self.parse_string_value()?);
+                } else if self.parse_cnos_keyword(CnosKeyWord::MAX_SERIES_PER_DATABASE) {
+                    options.max_series_per_database = Some(self.parse_u64()?);
+                } else if self.parse_cnos_keyword(CnosKeyWord::MAX_SERIES_PER_TABLE) {
+                    options.max_series_per_table = Some(self.parse_u64()?);
                 } else {
                     return Ok(options);
                 }
@@ -526,6 +606,39 @@ impl<'a> ExtParser<'a> {
         }
     }
 
+    /// Parse a SQL BACKUP DATABASE statement
+    fn parse_backup(&mut self) -> Result<ExtStatement> {
+        self.parser.expect_keyword(Keyword::DATABASE)?;
+        let database_name = self.parser.parse_object_name()?;
+        self.parser.expect_keyword(Keyword::TO)?;
+        let path = self.parse_string_value()?;
+        let since = if self.parse_cnos_keyword(CnosKeyWord::INCREMENTAL) {
+            if !self.parse_cnos_keyword(CnosKeyWord::SINCE) {
+                return self.expected("SINCE after INCREMENTAL", self.parser.peek_token());
+            }
+            Some(self.parse_string_value()?)
+        } else {
+            None
+        };
+        Ok(ExtStatement::BackupDatabase(BackupDatabase {
+            database_name,
+            path,
+            since,
+        }))
+    }
+
+    /// Parse a SQL RESTORE DATABASE statement
+    fn parse_restore(&mut self) -> Result<ExtStatement> {
+        self.parser.expect_keyword(Keyword::DATABASE)?;
+        let database_name = self.parser.parse_object_name()?;
+        self.parser.expect_keyword(Keyword::FROM)?;
+        let path = self.parse_string_value()?;
+        Ok(ExtStatement::RestoreDatabase(RestoreDatabase {
+            database_name,
+            path,
+        }))
+    }
+
     /// Parse a SQL DROP statement
     fn parse_drop(&mut self) -> Result<ExtStatement> {
         let obj_type = if self.parser.parse_keyword(Keyword::TABLE) {
@@ -981,14 +1094,14 @@ mod tests {
 
     #[test]
     fn test_create_database() {
-        let sql = "CREATE DATABASE test WITH TTl '10d' SHARD 5 VNOdE_DURATiON '3d' REPLICA 10 pRECISIOn 'us';";
+        let sql = "CREATE DATABASE test WITH TTl '10d' SHARD 5 VNOdE_DURATiON '3d' REPLICA 10 pRECISIOn 'us' max_SERIES_PER_database 100000 MAX_SERIES_PER_TABLE 1000;";
         let statements = ExtParser::parse_sql(sql).unwrap();
         assert_eq!(statements.len(), 1);
         match statements[0] {
             ExtStatement::CreateDatabase(ref stmt) => {
                 let ans = format!("{:?}", stmt);
                 println!("{ans}");
-                let expectd = r#"CreateDatabase { name: ObjectName([Ident { value: "test", quote_style: None }]), if_not_exists: false, options: DatabaseOptions { ttl: Some("10d"), shard_num: Some(5), vnode_duration: Some("3d"), replica: Some(10), precision: Some("us") } }"#;
+                let expectd = r#"CreateDatabase { name: ObjectName([Ident { value: "test", quote_style: None }]), if_not_exists: false, options: DatabaseOptions { ttl: Some("10d"), shard_num: Some(5), vnode_duration: Some("3d"), replica: Some(10), precision: Some("us"), max_series_per_database: Some(100000), max_series_per_table: Some(1000) } }"#;
                 assert_eq!(ans, expectd);
             }
             _ => panic!("impossible"),
@@ -1000,4 +1113,95 @@ mod tests {
         let sql = "CREATE TABLE test0(TAGS(column6, column7));";
         ExtParser::parse_sql(sql).unwrap();
     }
+
+    #[test]
+    fn test_backup_restore_database() {
+        let sql = "BACKUP DATABASE test TO '/tmp/backup/test'";
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(statements.len(), 1);
+        match &statements[0] {
+            ExtStatement::BackupDatabase(stmt) => {
+                assert_eq!(stmt.database_name.to_string(), "test");
+                assert_eq!(stmt.path, "/tmp/backup/test");
+                assert_eq!(stmt.since, None);
+            }
+            _ => panic!("failed"),
+        }
+
+        let sql = "BACKUP DATABASE test TO '/tmp/backup/inc1' INCREMENTAL SINCE 'test'";
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        match &statements[0] {
+            ExtStatement::BackupDatabase(stmt) => {
+                assert_eq!(stmt.path, "/tmp/backup/inc1");
+                assert_eq!(stmt.since, Some("test".to_string()));
+            }
+            _ => panic!("failed"),
+        }
+        assert!(
+            ExtParser::parse_sql("BACKUP DATABASE test TO '/tmp/inc1' INCREMENTAL 'test'").is_err()
+        );
+
+        let sql = "restore database test from '/tmp/backup/test';";
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(statements.len(), 1);
+        match &statements[0] {
+            ExtStatement::RestoreDatabase(stmt) => {
+                assert_eq!(stmt.database_name.to_string(), "test");
+                assert_eq!(stmt.path, "/tmp/backup/test");
+            }
+            _ => panic!("failed"),
+        }
+
+        assert!(ExtParser::parse_sql("BACKUP DATABASE test '/tmp/backup/test'").is_err());
+        assert!(ExtParser::parse_sql("RESTORE DATABASE test TO '/tmp/backup/test'").is_err());
+    }
+
+    #[test]
+    fn test_show_cardinality() {
+        let sql = "SHOW SERIES CARDINALITY;";
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        assert_eq!(
+            statements[0],
+            ExtStatement::ShowSeriesCardinality(ShowSeriesCardinality {
+                table_name: None,
+                exact: false,
+            })
+        );
+
+        let sql = "show series exact cardinality from test.cpu";
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        match &statements[0] {
+            ExtStatement::ShowSeriesCardinality(stmt) => {
+                assert_eq!(stmt.table_name.as_ref().unwrap().to_string(), "test.cpu");
+                assert!(stmt.exact);
+            }
+            _ => panic!("failed"),
+        }
+
+        let sql = "SHOW TAG VALUES EXACT CARDINALITY FROM cpu WITH KEY = host";
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        match &statements[0] {
+            ExtStatement::ShowTagValuesCardinality(stmt) => {
+                assert_eq!(stmt.table_name.as_ref().unwrap().to_string(), "cpu");
+                assert_eq!(stmt.tag_key.value, "host");
+                assert!(stmt.exact);
+            }
+            _ => panic!("failed"),
+        }
+
+        let sql = "SHOW TAG VALUES CARDINALITY WITH KEY = \"Region\"";
+        let statements = ExtParser::parse_sql(sql).unwrap();
+        match &statements[0] {
+            ExtStatement::ShowTagValuesCardinality(stmt) => {
+                assert_eq!(stmt.table_name, None);
+                assert_eq!(stmt.tag_key.value, "Region");
+                assert!(!stmt.exact);
+            }
+            _ => panic!("failed"),
+        }
+
+        assert!(ExtParser::parse_sql("SHOW SERIES FROM cpu").is_err());
+        assert!(ExtParser::parse_sql("SHOW TAG VALUES CARDINALITY FROM cpu").is_err());
+        assert!(ExtParser::parse_sql("SHOW TAG VALUES CARDINALITY WITH KEY host").is_err());
+    }
 }
@@ -19,6 +19,7 @@ use spi::query::{session::IsiphoSessionCtx, PhysicalPlanerSnafu};
 
 use crate::extension::physical::transform_rule::{
     table_writer::TableWriterPlanner, tag_scan::TagScanPlanner, topk::TopKPlanner,
+    tskv_aggregate::TskvAggregatePlanner,
 };
 
 use super::optimizer::PhysicalOptimizer;
@@ -57,6 +58,7 @@ impl Default for DefaultPhysicalPlanner {
             Arc::new(TableWriterPlanner {}),
             Arc::new(TopKPlanner {}),
             Arc::new(TagScanPlanner {}),
+            Arc::new(TskvAggregatePlanner {}),
         ];
 
         let ext_physical_optimizer_rules: Vec<Arc<dyn PhysicalOptimizerRule + Send + Sync>> = vec![
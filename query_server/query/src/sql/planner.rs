@@ -21,13 +21,17 @@ use models::utils::SeqIdGenerator;
 use models::{ColumnId, ValueType};
 use snafu::ResultExt;
 use spi::query::ast::{
-    ColumnOption, CreateDatabase as ASTCreateDatabase, CreateTable as ASTCreateTable,
-    DatabaseOptions as ASTDatabaseOptions, DescribeDatabase as DescribeDatabaseOptions,
-    DescribeTable as DescribeTableOptions, DropObject, ExtStatement,
+    BackupDatabase as ASTBackupDatabase, ColumnOption, CreateDatabase as ASTCreateDatabase,
+    CreateTable as ASTCreateTable, DatabaseOptions as ASTDatabaseOptions,
+    DescribeDatabase as DescribeDatabaseOptions, DescribeTable as DescribeTableOptions, DropObject,
+    ExtStatement, RestoreDatabase as ASTRestoreDatabase,
+    ShowSeriesCardinality as ASTShowSeriesCardinality,
+    ShowTagValuesCardinality as ASTShowTagValuesCardinality,
 };
 use spi::query::logical_planner::{
-    self, affected_row_expr, CreateDatabase, CreateTable, DDLPlan, DescribeDatabase, DescribeTable,
-    DropPlan, ExternalSnafu, LogicalPlanner, LogicalPlannerError, Plan, QueryPlan,
+    self, affected_row_expr, BackupDatabase, CreateDatabase, CreateTable, DDLPlan,
+    DescribeDatabase, DescribeTable, DropPlan, ExternalSnafu, LogicalPlanner, LogicalPlannerError,
+    Plan, QueryPlan, RestoreDatabase, ShowSeriesCardinality, ShowTagValuesCardinality,
     MISMATCHED_COLUMNS, MISSING_COLUMN,
 };
 use spi::query::session::IsiphoSessionCtx;
@@ -66,6 +70,12 @@ impl<S: ContextProvider> SqlPlaner<S> {
             ExtStatement::DescribeDatabase(stmt) => self.database_to_describe(stmt),
             ExtStatement::ShowDatabases() => self.database_to_show(),
             ExtStatement::ShowTables(stmt) => self.table_to_show(stmt),
+            ExtStatement::ShowSeriesCardinality(stmt) => self.series_cardinality_to_show(stmt),
+            ExtStatement::ShowTagValuesCardinality(stmt) => {
+                self.tag_values_cardinality_to_show(stmt)
+            }
+            ExtStatement::BackupDatabase(stmt) => self.database_to_backup(stmt),
+            ExtStatement::RestoreDatabase(stmt) => self.database_to_restore(stmt),
         }
     }
 
@@ -339,6 +349,21 @@ impl<S: ContextProvider> SqlPlaner<S> {
         })))
     }
 
+    fn database_to_backup(&self, stmt: ASTBackupDatabase) -> Result<Plan> {
+        Ok(Plan::DDL(DDLPlan::BackupDatabase(BackupDatabase {
+            database_name: normalize_sql_object_name(&stmt.database_name),
+            path: stmt.path,
+            since: stmt.since,
+        })))
+    }
+
+    fn database_to_restore(&self, stmt: ASTRestoreDatabase) -> Result<Plan> {
+        Ok(Plan::DDL(DDLPlan::RestoreDatabase(RestoreDatabase {
+            database_name: normalize_sql_object_name(&stmt.database_name),
+            path: stmt.path,
+        })))
+    }
+
     fn database_to_show(&self) -> Result<Plan> {
         Ok(Plan::DDL(DDLPlan::ShowDatabases()))
     }
@@ -349,6 +374,25 @@ impl<S: ContextProvider> SqlPlaner<S> {
         )))
     }
 
+    fn series_cardinality_to_show(&self, stmt: ASTShowSeriesCardinality) -> Result<Plan> {
+        Ok(Plan::DDL(DDLPlan::ShowSeriesCardinality(
+            ShowSeriesCardinality {
+                table_name: stmt.table_name.map(|t| normalize_sql_object_name(&t)),
+                exact: stmt.exact,
+            },
+        )))
+    }
+
+    fn tag_values_cardinality_to_show(&self, stmt: ASTShowTagValuesCardinality) -> Result<Plan> {
+        Ok(Plan::DDL(DDLPlan::ShowTagValuesCardinality(
+            ShowTagValuesCardinality {
+                table_name: stmt.table_name.map(|t| normalize_sql_object_name(&t)),
+                tag_key: normalize_ident(&stmt.tag_key),
+                exact: stmt.exact,
+            },
+        )))
+    }
+
     fn database_to_plan(&self, stmt: ASTCreateDatabase) -> Result<Plan> {
         let ASTCreateDatabase {
             name,
@@ -377,6 +421,12 @@ impl<S: ContextProvider> SqlPlaner<S> {
         if let Some(vnode_duration) = options.vnode_duration {
             plan_options.vnode_duration = self.str_to_duration(&vnode_duration)?
         }
+        if let Some(max_series) = options.max_series_per_database {
+            plan_options.max_series_per_database = Some(max_series)
+        }
+        if let Some(max_series) = options.max_series_per_table {
+            plan_options.max_series_per_table = Some(max_series)
+        }
         if let Some(precision) = options.precision {
             plan_options.precision = match Precision::new(&precision) {
                 None => {
@@ -695,7 +745,7 @@ mod tests {
 
     #[test]
     fn test_create_database() {
-        let sql = "CREATE DATABASE test WITH TTL '10' SHARD 5 VNODE_DURATION '3d' REPLICA 10 PRECISION 'us';";
+        let sql = "CREATE DATABASE test WITH TTL '10' SHARD 5 VNODE_DURATION '3d' REPLICA 10 PRECISION 'us' MAX_SERIES_PER_TABLE 1000;";
         let mut statements = ExtParser::parse_sql(sql).unwrap();
         assert_eq!(statements.len(), 1);
         let test = MockContext {};
@@ -705,7 +755,7 @@ mod tests {
             .unwrap();
         if let Plan::DDL(DDLPlan::CreateDatabase(create)) = plan {
             let ans = format!("{:?}", create);
-            let expected = r#"CreateDatabase { name: "test", if_not_exists: false, options: DatabaseOptions { ttl: Duration { time_num: 10, unit: Day }, shard_num: 5, vnode_duration: Duration { time_num: 3, unit: Day }, replica: 10, precision: US } }"#;
+            let expected = r#"CreateDatabase { name: "test", if_not_exists: false, options: DatabaseOptions { ttl: Duration { time_num: 10, unit: Day }, shard_num: 5, vnode_duration: Duration { time_num: 3, unit: Day }, replica: 10, precision: US, max_series_per_database: None, max_series_per_table: Some(1000) } }"#;
             assert_eq!(ans, expected);
         } else {
             panic!("expected create table plan")
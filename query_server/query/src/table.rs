@@ -17,7 +17,12 @@ use tskv::engine::EngineRef;
 
 use crate::{
     data_source::tskv_sink::TskvRecordBatchSinkProvider,
-    extension::physical::plan_node::{table_writer::TableWriterExec, tag_scan::TagScanExec},
+    extension::{
+        logical::plan_node::tskv_aggregate::{TskvAggregate, TskvGroupBy},
+        physical::plan_node::{
+            table_writer::TableWriterExec, tag_scan::TagScanExec, tskv_aggregate::TskvAggregateExec,
+        },
+    },
     tskv_exec::TskvExec,
 };
 
@@ -85,6 +90,26 @@ impl ClusterTable {
         )))
     }
 
+    pub async fn aggregate(
+        &self,
+        _ctx: &SessionState,
+        schema: &DFSchemaRef,
+        filters: &[Expr],
+        group_by: Vec<TskvGroupBy>,
+        aggregates: Vec<TskvAggregate>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let filter = Arc::new(Predicate::default().push_down_filter(filters, &self.schema));
+
+        Ok(Arc::new(TskvAggregateExec::new(
+            Arc::new(self.schema.clone()),
+            Arc::new(schema.as_ref().into()),
+            filter,
+            group_by,
+            aggregates,
+            self.engine.clone(),
+        )))
+    }
+
     pub fn table_schema(&self) -> &TskvTableSchema {
         &self.schema
     }
@@ -1,5 +1,6 @@
 use crate::query::execution::Output;
 use crate::query::function::FuncMetaManagerRef;
+use async_trait::async_trait;
 use datafusion::catalog::catalog::CatalogProvider;
 use datafusion::catalog::TableReference;
 use models::schema::{DatabaseSchema, TableSchema};
@@ -15,6 +16,7 @@ pub type CatalogRef = Arc<dyn CatalogProvider>;
 pub const DEFAULT_DATABASE: &str = "public";
 pub const DEFAULT_CATALOG: &str = "cnosdb";
 
+#[async_trait]
 pub trait MetaData: Send + Sync {
     fn as_any(&self) -> &dyn Any;
     fn with_catalog(&self, catalog: &str) -> Arc<dyn MetaData + Send + Sync>;
@@ -32,6 +34,19 @@ pub trait MetaData: Send + Sync {
     fn describe_table(&self, table_name: &str) -> Result<Output>;
     fn show_databases(&self) -> Result<Output>;
     fn show_tables(&self, database_name: &Option<String>) -> Result<Output>;
+    /// Shows the number of series in the table, or in the current database if
+    /// `table_name` is None, it's estimated unless `exact` is set.
+    fn show_series_cardinality(&self, table_name: &Option<String>, exact: bool) -> Result<Output>;
+    /// Shows the number of distinct values of the tag in the table, or in the current
+    /// database if `table_name` is None, it's estimated unless `exact` is set.
+    fn show_tag_values_cardinality(
+        &self,
+        table_name: &Option<String>,
+        tag_key: &str,
+        exact: bool,
+    ) -> Result<Output>;
+    async fn backup_database(&self, name: &str, path: &str, since: Option<&str>) -> Result<Output>;
+    async fn restore_database(&self, name: &str, path: &str) -> Result<Output>;
 }
 
 #[derive(Debug, Snafu)]
@@ -22,6 +22,11 @@ pub enum ExtStatement {
     DescribeDatabase(DescribeDatabase),
     ShowDatabases(),
     ShowTables(Option<ObjectName>),
+    ShowSeriesCardinality(ShowSeriesCardinality),
+    ShowTagValuesCardinality(ShowTagValuesCardinality),
+
+    BackupDatabase(BackupDatabase),
+    RestoreDatabase(RestoreDatabase),
     //todo:  insert/update/alter
 }
 
@@ -75,6 +80,10 @@ pub struct DatabaseOptions {
     pub replica: Option<u64>,
     // timestamp percision
     pub precision: Option<String>,
+
+    pub max_series_per_database: Option<u64>,
+
+    pub max_series_per_table: Option<u64>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -87,11 +96,44 @@ pub struct DescribeDatabase {
     pub database_name: ObjectName,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupDatabase {
+    pub database_name: ObjectName,
+    /// Directory to put the backup in.
+    pub path: String,
+    /// Id of the backup that an incremental backup is based on.
+    pub since: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreDatabase {
+    pub database_name: ObjectName,
+    /// Directory of the backup.
+    pub path: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ShowTables {
     pub database_name: ObjectName,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShowSeriesCardinality {
+    /// Counts series in all tables of the database if not set.
+    pub table_name: Option<ObjectName>,
+    /// Counts series in the index instead of estimating.
+    pub exact: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShowTagValuesCardinality {
+    /// Counts values in all tables of the database if not set.
+    pub table_name: Option<ObjectName>,
+    pub tag_key: Ident,
+    /// Counts values in the index instead of estimating.
+    pub exact: bool,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ObjectType {
@@ -66,7 +66,15 @@ pub enum DDLPlan {
 
     ShowTables(Option<String>),
 
+    ShowSeriesCardinality(ShowSeriesCardinality),
+
+    ShowTagValuesCardinality(ShowTagValuesCardinality),
+
     ShowDatabases(),
+
+    BackupDatabase(BackupDatabase),
+
+    RestoreDatabase(RestoreDatabase),
 }
 
 #[derive(Debug, Clone)]
@@ -139,6 +147,19 @@ pub struct DescribeDatabase {
     pub database_name: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupDatabase {
+    pub database_name: String,
+    pub path: String,
+    pub since: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreDatabase {
+    pub database_name: String,
+    pub path: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DescribeTable {
     pub table_name: String,
@@ -149,6 +170,19 @@ pub struct ShowTables {
     pub database_name: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShowSeriesCardinality {
+    pub table_name: Option<String>,
+    pub exact: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShowTagValuesCardinality {
+    pub table_name: Option<String>,
+    pub tag_key: String,
+    pub exact: bool,
+}
+
 pub trait LogicalPlanner {
     fn create_logical_plan(
         &self,
@@ -0,0 +1,30 @@
+--#DATABASE=aggregate_push_down
+--#SORT=true
+drop database if exists aggregate_push_down;
+create database aggregate_push_down;
+
+drop table if exists m2;
+CREATE TABLE IF NOT EXISTS m2(f0 BIGINT , f1 DOUBLE , f2 STRING, f3 BOOLEAN, TAGS(t0, t1) );
+
+INSERT m2(TIME, f0, f1, f2, f3, t0, t1) VALUES(1000000, 111, 444, 'a', true, 'tag11', 'tag21'),
+(2000000, 222, 333, 'b', false, 'tag12', 'tag22'),
+(3000000, 333, 222, 'c', true, 'tag11', 'tag21'),
+(4000000, 444, 111, 'd', false, 'tag12', 'tag22'),
+(11000000, 555, 444, 'e', true, 'tag11', 'tag23'),
+(12000000, 666, 333, 'f', false, 'tag12', 'tag23'),
+(23000000, 777, 222, 'g', true, 'tag11', 'tag21'),
+(24000000, 888, 111, 'h', false, 'tag12', 'tag22');
+
+-- answered by tskv
+explain select count(f0), min(f1), max(f0), sum(f1) from m2;
+select count(f0), min(f1), max(f0), sum(f1), count(f2), min(f3), max(f3) from m2;
+select t0, count(f0), sum(f0) from m2 group by t0;
+select t0, t1, min(f1), max(f1) from m2 where time >= 2000000 and time < 24000000 group by t0, t1;
+select date_bin(interval '10 milliseconds', time, TIMESTAMP '1970-01-01T00:00:00') as b, count(f0), sum(f1) from m2 where t0 = 'tag11' group by b;
+select count(f0), sum(f0) from m2 where t0 = 'not_exists';
+select t0, count(f0) from m2 where t0 = 'not_exists' group by t0;
+
+-- not answered by tskv
+explain select count(f0) from m2 where f0 > 200;
+select count(f0) from m2 where f0 > 200;
+select avg(f0) from m2;
@@ -0,0 +1,517 @@
+//! Online backup and restore of a database.
+//!
+//! A backup is a directory with the layout:
+//! ```text
+//! <backup_dir>
+//! ├── manifest.json
+//! ├── summary-000000
+//! ├── index/...
+//! ├── data/<ts_family_id>/{tsm,delta}/...
+//! └── wal/_000001.wal
+//! ```
+//! - `manifest.json` is written at last, a backup without it is incomplete.
+//! - `summary-000000` contains `VersionEdit`s to rebuild versions of TseriesFamilies.
+//! - `index` is a copy of the sled index of the database.
+//! - `data` contains column files and their tombstones, files are hard-linked if possible,
+//!   data of offloaded files is fetched from the object store.
+//! - `wal` contains entries of the database not flushed when an incremental backup is taken.
+//!
+//! The id of a backup is the name of its directory. An incremental backup only contains
+//! column files added since its parent backup, which must be in the same directory as it,
+//! the summary, the index and tombstones are always complete. A full backup and its
+//! incremental backups make up a chain, restoring a backup looks up column files in the chain.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use models::codec::Encoding;
+use models::schema::DatabaseSchema;
+use models::utils::now_timestamp_nanos;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use crate::{
+    context::GlobalContext,
+    error::{self, Error, Result},
+    file_system::{file_manager, remote},
+    file_utils,
+    index::DBIndex,
+    kv_option::StorageOptions,
+    summary::{self, CompactMeta, VersionEdit},
+    tseries_family::{SuperVersion, VnodeInfo},
+    tsm::{codec::get_str_codec, DecodeSnafu},
+    wal::{self, WalEntryType},
+    TseriesFamilyId,
+};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+const INDEX_DIR: &str = "index";
+const DATA_DIR: &str = "data";
+const TSM_DIR: &str = "tsm";
+const DELTA_DIR: &str = "delta";
+const WAL_DIR: &str = "wal";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BackupManifest {
+    pub id: String,
+    /// Id of the backup that this incremental backup is based on.
+    #[serde(default)]
+    pub parent: Option<String>,
+    pub database: DatabaseSchema,
+    /// Time when the backup is taken, in nanoseconds.
+    pub created_at: i64,
+    pub files: Vec<BackupFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BackupFile {
+    /// Path relative to the backup directory.
+    pub path: String,
+    pub size: u64,
+}
+
+impl BackupManifest {
+    pub fn new(id: String, database: DatabaseSchema, parent: Option<String>) -> Self {
+        Self {
+            id,
+            parent,
+            database,
+            created_at: now_timestamp_nanos(),
+            files: vec![],
+        }
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.files.iter().map(|f| f.size).sum()
+    }
+
+    pub fn read(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let data = fs::read(&path).map_err(|e| Error::InvalidBackup {
+            path: dir.to_path_buf(),
+            reason: format!("failed to read '{}': {}", MANIFEST_FILE, e),
+        })?;
+        serde_json::from_slice(&data).map_err(|e| Error::InvalidBackup {
+            path: dir.to_path_buf(),
+            reason: format!("failed to parse '{}': {}", MANIFEST_FILE, e),
+        })
+    }
+
+    fn write(&self, dir: &Path) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self).map_err(|e| Error::InvalidBackup {
+            path: dir.to_path_buf(),
+            reason: format!("failed to encode '{}': {}", MANIFEST_FILE, e),
+        })?;
+        let tmp_path = dir.join(format!("{}.tmp", MANIFEST_FILE));
+        fs::write(&tmp_path, data).context(error::IOSnafu)?;
+        fs::rename(&tmp_path, dir.join(MANIFEST_FILE)).context(error::IOSnafu)
+    }
+
+    /// Checks that all files in the manifest exist with the recorded size.
+    pub fn verify(&self, dir: &Path) -> Result<()> {
+        for file in self.files.iter() {
+            let size = fs::metadata(dir.join(&file.path)).map(|m| m.len());
+            match size {
+                Ok(size) if size == file.size => {}
+                Ok(size) => {
+                    return Err(Error::InvalidBackup {
+                        path: dir.to_path_buf(),
+                        reason: format!(
+                            "size of '{}' is {}, expected {}",
+                            file.path, size, file.size
+                        ),
+                    })
+                }
+                Err(e) => {
+                    return Err(Error::InvalidBackup {
+                        path: dir.to_path_buf(),
+                        reason: format!("failed to stat '{}': {}", file.path, e),
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add_file(&mut self, dir: &Path, path: &Path) -> Result<()> {
+        let size = fs::metadata(path).context(error::IOSnafu)?.len();
+        let relative = path.strip_prefix(dir).unwrap_or(path);
+        self.files.push(BackupFile {
+            path: relative.to_string_lossy().to_string(),
+            size,
+        });
+        Ok(())
+    }
+}
+
+/// Returns the id of the backup in `backup_dir`.
+fn backup_id(backup_dir: &Path) -> Result<String> {
+    match backup_dir.file_name() {
+        Some(name) => Ok(name.to_string_lossy().to_string()),
+        None => Err(Error::InvalidBackup {
+            path: backup_dir.to_path_buf(),
+            reason: "backup directory must have a name".to_string(),
+        }),
+    }
+}
+
+/// Returns the directory of backup `parent_id`, which is in the same directory as `backup_dir`.
+pub(crate) fn parent_backup_dir(backup_dir: &Path, parent_id: &str) -> PathBuf {
+    match backup_dir.parent() {
+        Some(dir) => dir.join(parent_id),
+        None => PathBuf::from(parent_id),
+    }
+}
+
+/// Returns the WAL file of entries not flushed when the backup is taken.
+pub(crate) fn wal_tail_path(backup_dir: &Path) -> PathBuf {
+    file_utils::make_wal_file(backup_dir.join(WAL_DIR), 1)
+}
+
+/// Checks that there is no backup in `backup_dir` and creates the directory.
+pub(crate) fn prepare_backup_dir(backup_dir: &Path) -> Result<()> {
+    backup_id(backup_dir)?;
+    if file_manager::try_exists(backup_dir.join(MANIFEST_FILE)) {
+        return Err(Error::InvalidBackup {
+            path: backup_dir.to_path_buf(),
+            reason: "backup already exists".to_string(),
+        });
+    }
+    fs::create_dir_all(backup_dir).context(error::IOSnafu)
+}
+
+/// Reads manifests of the backup in `backup_dir` and all its parents, the newest first.
+///
+/// Returns error if a backup in the chain is missing, broken or of another database.
+pub(crate) fn read_backup_chain(backup_dir: &Path) -> Result<Vec<(PathBuf, BackupManifest)>> {
+    let mut chain: Vec<(PathBuf, BackupManifest)> = vec![];
+    let mut visited = HashSet::new();
+    let mut dir = backup_dir.to_path_buf();
+    loop {
+        let manifest = BackupManifest::read(&dir)?;
+        manifest.verify(&dir)?;
+        if let Some((_, child)) = chain.last() {
+            if manifest.database.name != child.database.name {
+                return Err(Error::InvalidBackup {
+                    path: dir,
+                    reason: format!("it is a backup of database '{}'", manifest.database.name),
+                });
+            }
+        }
+        if !visited.insert(manifest.id.clone()) {
+            return Err(Error::InvalidBackup {
+                path: backup_dir.to_path_buf(),
+                reason: format!("backup '{}' is in the chain twice", manifest.id),
+            });
+        }
+        let parent = manifest.parent.clone();
+        chain.push((dir.clone(), manifest));
+        match parent {
+            Some(parent_id) => dir = parent_backup_dir(&dir, &parent_id),
+            None => return Ok(chain),
+        }
+    }
+}
+
+/// Copies column files referenced by the pinned `versions`, the index directory
+/// and a summary snapshot of the database into `backup_dir`, which should have
+/// been prepared by `prepare_backup_dir`.
+///
+/// If `parent` is set, column files in the parent backup are not copied again.
+/// Data in caches is not in the backup, caches should be flushed or the WAL tail
+/// should be copied to `wal_tail_path` before.
+pub(crate) async fn backup_database(
+    schema: DatabaseSchema,
+    versions: &[Arc<SuperVersion>],
+    index: &DBIndex,
+    backup_dir: &Path,
+    parent: Option<&str>,
+) -> Result<BackupManifest> {
+    let mut parent_files = HashSet::new();
+    if let Some(parent_id) = parent {
+        let parent_dir = parent_backup_dir(backup_dir, parent_id);
+        for tsf in read_backup_ts_families(&parent_dir).await? {
+            parent_files.extend(tsf.files.iter().map(|f| (tsf.tsf_id, f.file_id)));
+        }
+    }
+
+    let id = backup_id(backup_dir)?;
+    let mut manifest = BackupManifest::new(id, schema, parent.map(|p| p.to_string()));
+    let mut edits = vec![];
+    for super_version in versions {
+        let version = &super_version.version;
+        let edit = version.snapshot_edit();
+        for meta in edit.add_files.iter() {
+            let src_dir = column_file_dir(&version.storage_opt(), version.database(), meta);
+            let dst_dir = backup_file_dir(backup_dir, version.tf_id(), meta);
+            fs::create_dir_all(&dst_dir).context(error::IOSnafu)?;
+
+            if !parent_files.contains(&(version.tf_id(), meta.file_id)) {
+                let src = column_file_name(&src_dir, meta);
+                let dst = column_file_name(&dst_dir, meta);
+                link_or_copy(&src, &dst)?;
+                manifest.add_file(backup_dir, &dst)?;
+            }
+
+            // Tombstones may be appended, so they are always copied.
+            let src = file_utils::make_tsm_tombstone_file_name(&src_dir, meta.file_id);
+            if file_manager::try_exists(&src) {
+                let dst = file_utils::make_tsm_tombstone_file_name(&dst_dir, meta.file_id);
+                fs::copy(&src, &dst).context(error::IOSnafu)?;
+                manifest.add_file(backup_dir, &dst)?;
+            }
+        }
+        edits.push(edit);
+    }
+
+    let summary_path = file_utils::make_summary_file(backup_dir, 0);
+    summary::write_summary_file(&summary_path, &edits).await?;
+    manifest.add_file(backup_dir, &summary_path)?;
+
+    // Files of a live index may be changed at any time, block writes while copying.
+    let index_files = index
+        .with_writes_blocked(|index_dir| copy_dir(index_dir, &backup_dir.join(INDEX_DIR)))
+        .context(error::IndexErrSnafu)??;
+    for path in index_files {
+        manifest.add_file(backup_dir, &path)?;
+    }
+
+    let wal_path = wal_tail_path(backup_dir);
+    if file_manager::try_exists(&wal_path) {
+        manifest.add_file(backup_dir, &wal_path)?;
+    }
+
+    manifest.write(backup_dir)?;
+    Ok(manifest)
+}
+
+/// Column files of a TseriesFamily in a backup.
+#[derive(Debug)]
+pub(crate) struct BackupTsFamily {
+    pub tsf_id: TseriesFamilyId,
+    pub vnode: VnodeInfo,
+    pub files: Vec<CompactMeta>,
+}
+
+/// Reads TseriesFamilies in the summary snapshot of a backup.
+pub(crate) async fn read_backup_ts_families(backup_dir: &Path) -> Result<Vec<BackupTsFamily>> {
+    let summary_path = file_utils::make_summary_file(backup_dir, 0);
+    let mut ts_families: Vec<BackupTsFamily> = vec![];
+    let mut files: HashMap<TseriesFamilyId, Vec<CompactMeta>> = HashMap::new();
+    for edit in summary::read_summary_file(&summary_path).await? {
+        if edit.add_tsf {
+            ts_families.push(BackupTsFamily {
+                tsf_id: edit.tsf_id,
+                vnode: edit.vnode,
+                files: vec![],
+            });
+        }
+        files.entry(edit.tsf_id).or_default().extend(edit.add_files);
+    }
+    for tsf in ts_families.iter_mut() {
+        tsf.files = files.remove(&tsf.tsf_id).unwrap_or_default();
+    }
+    Ok(ts_families)
+}
+
+/// Files copied from a backup to temporary paths next to their destinations, they are
+/// moved into place by `commit`, or removed if dropped before that.
+pub(crate) struct RestoreStaging {
+    database: String,
+    storage_opt: Arc<StorageOptions>,
+    /// (temporary path, destination path)
+    files: Vec<(PathBuf, PathBuf)>,
+    index_dir: Option<(PathBuf, PathBuf)>,
+}
+
+impl RestoreStaging {
+    pub fn new(database: &str, storage_opt: Arc<StorageOptions>) -> Self {
+        Self {
+            database: database.to_string(),
+            storage_opt,
+            files: vec![],
+            index_dir: None,
+        }
+    }
+
+    /// Stages column files of `tsf` in the backup chain (the newest first) into the
+    /// storage of database, files are renamed with new file ids, returns the `VersionEdit`
+    /// to add the files into TseriesFamily `new_tsf_id`.
+    pub fn stage_ts_family(
+        &mut self,
+        chain: &[PathBuf],
+        tsf: &BackupTsFamily,
+        new_tsf_id: TseriesFamilyId,
+        ctx: &GlobalContext,
+    ) -> Result<VersionEdit> {
+        let mut edit = VersionEdit::new();
+        let mut max_level_ts = i64::MIN;
+        let mut metas = Vec::with_capacity(tsf.files.len());
+        for meta in tsf.files.iter() {
+            let src_dir = chain
+                .iter()
+                .map(|dir| backup_file_dir(dir, tsf.tsf_id, meta))
+                .find(|dir| file_manager::try_exists(column_file_name(dir, meta)))
+                .ok_or_else(|| Error::InvalidBackup {
+                    path: chain[0].clone(),
+                    reason: format!("column file '{}' is missing", meta.file_id),
+                })?;
+            let mut new_meta = meta.clone();
+            new_meta.file_id = ctx.file_id_next();
+            new_meta.tsf_id = new_tsf_id;
+            new_meta.low_seq = 0;
+            new_meta.high_seq = 0;
+            let dst_dir = column_file_dir(&self.storage_opt, &self.database, &new_meta);
+            fs::create_dir_all(&dst_dir).context(error::IOSnafu)?;
+
+            self.stage_file(
+                &column_file_name(&src_dir, meta),
+                column_file_name(&dst_dir, &new_meta),
+            )?;
+            // Tombstones are always in the newest backup.
+            let tombstone_dir = backup_file_dir(&chain[0], tsf.tsf_id, meta);
+            let src = file_utils::make_tsm_tombstone_file_name(&tombstone_dir, meta.file_id);
+            if file_manager::try_exists(&src) {
+                let dst = file_utils::make_tsm_tombstone_file_name(&dst_dir, new_meta.file_id);
+                self.stage_file(&src, dst)?;
+            }
+
+            max_level_ts = max_level_ts.max(new_meta.max_ts);
+            metas.push(new_meta);
+        }
+        for meta in metas {
+            edit.add_file(meta, max_level_ts);
+        }
+        edit.set_tsf_id(new_tsf_id);
+        Ok(edit)
+    }
+
+    /// Files offloaded to the object store are restored with their data, so that the
+    /// restored file doesn't share the object with the file in the backup.
+    fn stage_file(&mut self, src: &Path, dst: PathBuf) -> Result<()> {
+        let tmp = restoring_path(&dst);
+        self.files.push((tmp.clone(), dst));
+        remote::copy_file(src, &tmp).context(error::IOSnafu)?;
+        Ok(())
+    }
+
+    /// Copies the index directory in the backup to a temporary directory of `index_dir`.
+    pub fn stage_index(&mut self, backup_dir: &Path, index_dir: &Path) -> Result<()> {
+        let tmp = restoring_path(index_dir);
+        if file_manager::try_exists(&tmp) {
+            fs::remove_dir_all(&tmp).context(error::IOSnafu)?;
+        }
+        self.index_dir = Some((tmp.clone(), index_dir.to_path_buf()));
+        copy_dir(&backup_dir.join(INDEX_DIR), &tmp)?;
+        Ok(())
+    }
+
+    /// Moves all staged files into place.
+    pub fn commit(mut self) -> Result<()> {
+        if let Some((tmp, index_dir)) = self.index_dir.take() {
+            if file_manager::try_exists(&index_dir) {
+                fs::remove_dir_all(&index_dir).context(error::IOSnafu)?;
+            }
+            fs::rename(&tmp, &index_dir).context(error::IOSnafu)?;
+        }
+        for (tmp, dst) in std::mem::take(&mut self.files) {
+            fs::rename(&tmp, &dst).context(error::IOSnafu)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RestoreStaging {
+    fn drop(&mut self) {
+        for (tmp, _) in self.files.iter() {
+            let _ = fs::remove_file(tmp);
+        }
+        if let Some((tmp, _)) = self.index_dir.as_ref() {
+            let _ = fs::remove_dir_all(tmp);
+        }
+    }
+}
+
+fn restoring_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".restoring");
+    path.with_file_name(name)
+}
+
+/// Reads points in the WAL tail of the backup, which need to be written again.
+pub(crate) fn read_wal_tail(backup_dir: &Path) -> Result<Vec<Vec<u8>>> {
+    let path = wal_tail_path(backup_dir);
+    if !file_manager::try_exists(&path) {
+        return Ok(vec![]);
+    }
+    let file = file_manager::open_file(&path)?;
+    if file.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut reader = wal::reader(file)?;
+    let mut points = vec![];
+    while let Some(entry) = reader.next_wal_entry()? {
+        if entry.typ != WalEntryType::Write {
+            continue;
+        }
+        let mut dst = Vec::new();
+        get_str_codec(Encoding::Zstd)
+            .decode(&entry.buf, &mut dst)
+            .context(DecodeSnafu)?;
+        points.extend(dst.into_iter().map(|p| p.to_vec()));
+    }
+    Ok(points)
+}
+
+fn column_file_dir(storage_opt: &StorageOptions, database: &str, meta: &CompactMeta) -> PathBuf {
+    let file_path = storage_opt.column_file_path(database, meta);
+    match file_path.parent() {
+        Some(dir) => dir.to_path_buf(),
+        None => file_path,
+    }
+}
+
+fn backup_file_dir(backup_dir: &Path, tsf_id: TseriesFamilyId, meta: &CompactMeta) -> PathBuf {
+    let dir = backup_dir.join(DATA_DIR).join(tsf_id.to_string());
+    if meta.is_delta {
+        dir.join(DELTA_DIR)
+    } else {
+        dir.join(TSM_DIR)
+    }
+}
+
+fn column_file_name(dir: &Path, meta: &CompactMeta) -> PathBuf {
+    if meta.is_delta {
+        file_utils::make_delta_file_name(dir, meta.file_id)
+    } else {
+        file_utils::make_tsm_file_name(dir, meta.file_id)
+    }
+}
+
+/// Column files are never modified, so hard links are enough for a backup. Stub files
+/// of offloaded files are not linked, the object is deleted with the column file.
+fn link_or_copy(src: &Path, dst: &Path) -> Result<()> {
+    if remote::is_remote_file(src) || fs::hard_link(src, dst).is_err() {
+        remote::copy_file(src, dst).context(error::IOSnafu)?;
+    }
+    Ok(())
+}
+
+/// Copies files in `src` into `dst` recursively, returns paths of the copied files.
+fn copy_dir(src: &Path, dst: &Path) -> Result<Vec<PathBuf>> {
+    let mut copied = vec![];
+    fs::create_dir_all(dst).context(error::IOSnafu)?;
+    for entry in fs::read_dir(src).context(error::IOSnafu)? {
+        let entry = entry.context(error::IOSnafu)?;
+        let dst_path = dst.join(entry.file_name());
+        if entry.file_type().context(error::IOSnafu)?.is_dir() {
+            copied.append(&mut copy_dir(&entry.path(), &dst_path)?);
+        } else {
+            fs::copy(entry.path(), &dst_path).context(error::IOSnafu)?;
+            copied.push(dst_path);
+        }
+    }
+    Ok(copied)
+}
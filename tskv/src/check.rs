@@ -0,0 +1,518 @@
+//! Offline integrity checking of a tskv storage directory.
+//!
+//! The summary file is replayed to get the column files of every TseriesFamily,
+//! then column files, tombstones and the series index of each database are
+//! verified. The storage must not be opened by a running server while checking.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use models::schema::DatabaseSchema;
+use models::utils::split_id;
+use models::FieldId;
+use serde::Serialize;
+use utils::BloomFilter;
+
+use crate::byte_utils::{decode_be_i64, decode_be_u32, decode_be_u64};
+use crate::file_system::file_manager;
+use crate::index::DBIndex;
+use crate::kv_option::{StorageOptions, DATA_PATH, DELTA_PATH, TSM_PATH};
+use crate::summary::{self, CompactMeta, VersionEdit};
+use crate::tseries_family::{TimeRange, VnodeInfo};
+use crate::tsm::{
+    self, decode_data_block, IndexIterator, BLOOM_FILTER_SIZE, FOOTER_SIZE, HEADER_SIZE,
+    TOMBSTONE_FILE_SUFFIX, TOMBSTONE_MAGIC, TSM_MAGIC, VERSION,
+};
+use crate::{file_utils, TseriesFamilyId};
+
+/// Max number of unknown series ids listed in a report issue.
+const MAX_LISTED_SERIES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// Summary file is missing or cannot be decoded.
+    Summary,
+    /// Column file referenced by the summary does not exist.
+    MissingFile,
+    /// File in data directories not referenced by the summary.
+    OrphanFile,
+    /// Header, footer, index or bloom filter of a column file is invalid.
+    ColumnFile,
+    /// Data block in a column file is broken.
+    Block,
+    /// Tombstone file is invalid.
+    Tombstone,
+    /// Series index of a database cannot be read.
+    Index,
+    /// Column file contains series not in the series index.
+    UnknownSeries,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckIssue {
+    pub kind: IssueKind,
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CheckReport {
+    pub path: PathBuf,
+    pub column_files: usize,
+    pub blocks: usize,
+    pub tombstones: usize,
+    pub issues: Vec<CheckIssue>,
+}
+
+impl CheckReport {
+    pub fn is_damaged(&self) -> bool {
+        !self.issues.is_empty()
+    }
+
+    fn add_issue(&mut self, kind: IssueKind, path: &Path, reason: impl Into<String>) {
+        self.issues.push(CheckIssue {
+            kind,
+            path: path.to_path_buf(),
+            reason: reason.into(),
+        });
+    }
+}
+
+/// TseriesFamily in a summary file.
+#[derive(Default)]
+pub(crate) struct TsFamilyFiles {
+    pub database: String,
+    pub vnode: VnodeInfo,
+    pub seq_no: u64,
+    pub files: HashMap<u64, CompactMeta>,
+}
+
+/// Checks the storage directory `storage.path` and root paths of levels in
+/// `storage.level_paths`, returns all found issues.
+pub fn check_storage(storage: &StorageOptions) -> CheckReport {
+    let mut report = CheckReport {
+        path: storage.path.clone(),
+        ..Default::default()
+    };
+
+    let summary_path = file_utils::make_summary_file(storage.summary_dir(), 0);
+    let ts_families = match read_summary(&summary_path) {
+        Ok(edits) => replay_summary(edits),
+        Err(reason) => {
+            report.add_issue(IssueKind::Summary, &summary_path, reason);
+            BTreeMap::new()
+        }
+    };
+
+    // Series ids used in column files of each database, files are
+    // checked in order of TseriesFamily id and file id.
+    let mut db_series: BTreeMap<String, Vec<(PathBuf, Vec<u64>)>> = BTreeMap::new();
+    let mut referenced: HashSet<PathBuf> = HashSet::new();
+    for (tsf_id, tsf) in ts_families.iter() {
+        let mut metas: Vec<&CompactMeta> = tsf.files.values().collect();
+        metas.sort_by_key(|m| (m.is_delta, m.file_id));
+        for meta in metas {
+            let meta = CompactMeta {
+                tsf_id: *tsf_id,
+                ..meta.clone()
+            };
+            let file_path = storage.column_file_path(&tsf.database, &meta);
+            let file_dir = file_path.parent().unwrap_or(&storage.path).to_path_buf();
+            referenced.insert(file_path.clone());
+            if !file_manager::try_exists(&file_path) {
+                report.add_issue(
+                    IssueKind::MissingFile,
+                    &file_path,
+                    format!("referenced by ts_family {} but not found", tsf_id),
+                );
+                continue;
+            }
+
+            let tombstone_path = file_utils::make_tsm_tombstone_file_name(&file_dir, meta.file_id);
+            let tombstones = if file_manager::try_exists(&tombstone_path) {
+                report.tombstones += 1;
+                match check_tombstone_file(&tombstone_path) {
+                    Ok(tombstones) => tombstones,
+                    Err(reason) => {
+                        report.add_issue(IssueKind::Tombstone, &tombstone_path, reason);
+                        vec![]
+                    }
+                }
+            } else {
+                vec![]
+            };
+
+            report.column_files += 1;
+            if let Some(fields) = check_column_file(&file_path, &mut report) {
+                // Series of deleted tables are removed from index and
+                // their data are covered by tombstones.
+                let mut series_ids: Vec<u64> = fields
+                    .iter()
+                    .filter(|(fid, time_range)| {
+                        !tombstones
+                            .iter()
+                            .any(|(t_fid, t_range)| t_fid == fid && t_range.includes(time_range))
+                    })
+                    .map(|(fid, _)| split_id(*fid).1)
+                    .collect();
+                series_ids.sort_unstable();
+                series_ids.dedup();
+                db_series
+                    .entry(tsf.database.clone())
+                    .or_default()
+                    .push((file_path, series_ids));
+            }
+        }
+    }
+
+    for data_path in storage.data_paths() {
+        check_orphan_files(&data_path.join(DATA_PATH), &referenced, &mut report);
+    }
+
+    for (database, files) in db_series {
+        check_series(
+            &storage.index_dir(&database),
+            &database,
+            &files,
+            &mut report,
+        );
+    }
+
+    report
+}
+
+fn read_summary(path: &Path) -> Result<Vec<VersionEdit>, String> {
+    if !file_manager::try_exists(path) {
+        return Err("summary file not found".to_string());
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+    runtime
+        .block_on(summary::read_summary_file(path))
+        .map_err(|e| e.to_string())
+}
+
+/// Applies edits in the same way as `Summary::recover_version()`.
+pub(crate) fn replay_summary(edits: Vec<VersionEdit>) -> BTreeMap<TseriesFamilyId, TsFamilyFiles> {
+    let mut ts_families: BTreeMap<TseriesFamilyId, TsFamilyFiles> = BTreeMap::new();
+    for ed in edits {
+        let tsf = if ed.add_tsf {
+            let tsf = ts_families.entry(ed.tsf_id).or_default();
+            tsf.database = ed.tsf_name;
+            tsf.vnode = ed.vnode;
+            tsf
+        } else if ed.del_tsf {
+            ts_families.remove(&ed.tsf_id);
+            continue;
+        } else if let Some(tsf) = ts_families.get_mut(&ed.tsf_id) {
+            tsf
+        } else {
+            continue;
+        };
+        if ed.has_seq_no {
+            tsf.seq_no = tsf.seq_no.max(ed.seq_no);
+        }
+        for m in ed.del_files {
+            tsf.files.remove(&m.file_id);
+        }
+        for m in ed.add_files {
+            tsf.files.insert(m.file_id, m);
+        }
+    }
+    ts_families
+}
+
+/// Checks header, footer, index, bloom filter and all data blocks of a column file,
+/// returns time ranges of all fields if the index is readable.
+pub(crate) fn check_column_file(
+    path: &Path,
+    report: &mut CheckReport,
+) -> Option<Vec<(FieldId, TimeRange)>> {
+    let file = match file_manager::open_column_file(path) {
+        Ok(f) => Arc::new(f),
+        Err(e) => {
+            report.add_issue(IssueKind::ColumnFile, path, e.to_string());
+            return None;
+        }
+    };
+    let file_len = file.len();
+    if file_len < (HEADER_SIZE + FOOTER_SIZE) as u64 {
+        report.add_issue(
+            IssueKind::ColumnFile,
+            path,
+            format!("file size {} is less than header and footer", file_len),
+        );
+        return None;
+    }
+
+    let mut header = [0_u8; HEADER_SIZE];
+    let mut footer = [0_u8; FOOTER_SIZE];
+    if let Err(e) = file
+        .read_at(0, &mut header)
+        .and_then(|_| file.read_at(file_len - FOOTER_SIZE as u64, &mut footer))
+    {
+        report.add_issue(IssueKind::ColumnFile, path, e.to_string());
+        return None;
+    }
+    let magic = decode_be_u32(&header[..4]);
+    if magic != TSM_MAGIC {
+        report.add_issue(
+            IssueKind::ColumnFile,
+            path,
+            format!("invalid magic number {:#010x}", magic),
+        );
+    }
+    let version = header[HEADER_SIZE - 1];
+    if version == 0 || version > VERSION {
+        report.add_issue(
+            IssueKind::ColumnFile,
+            path,
+            format!("unsupported version {}", version),
+        );
+        return None;
+    }
+    let index_offset = decode_be_u64(&footer[BLOOM_FILTER_SIZE..]);
+    if index_offset < HEADER_SIZE as u64 {
+        report.add_issue(
+            IssueKind::ColumnFile,
+            path,
+            format!("index offset {} is inside of the header", index_offset),
+        );
+        return None;
+    }
+
+    let index = match tsm::load_index(file.clone()) {
+        Ok(idx) => Arc::new(idx),
+        Err(e) => {
+            report.add_issue(IssueKind::ColumnFile, path, e.to_string());
+            return None;
+        }
+    };
+    let bloom_filter = BloomFilter::with_data(&footer[..BLOOM_FILTER_SIZE]);
+
+    let mut fields = Vec::with_capacity(index.field_ids().len());
+    let mut buf = vec![];
+    for idx in IndexIterator::new(index.clone(), index.field_ids().len(), 0) {
+        let field_id = idx.field_id();
+        if !bloom_filter.contains(&field_id.to_be_bytes()) {
+            report.add_issue(
+                IssueKind::ColumnFile,
+                path,
+                format!("field {} is not in the bloom filter", field_id),
+            );
+        }
+        let (min_ts, max_ts) = idx.time_range();
+        fields.push((field_id, TimeRange::new(min_ts, max_ts)));
+
+        for blk in idx.block_iterator() {
+            report.blocks += 1;
+            let (offset, size, val_off) = (blk.offset(), blk.size(), blk.val_off());
+            if offset < HEADER_SIZE as u64
+                || offset.saturating_add(size) > index_offset
+                || val_off < offset + 4
+                || val_off.saturating_add(4) > offset.saturating_add(size)
+            {
+                report.add_issue(
+                    IssueKind::Block,
+                    path,
+                    format!(
+                        "block of field {} has invalid offset {}, size {}, value offset {}",
+                        field_id, offset, size, val_off
+                    ),
+                );
+                continue;
+            }
+            buf.resize(size as usize, 0);
+            if let Err(e) = file.read_at(offset, &mut buf) {
+                report.add_issue(IssueKind::Block, path, e.to_string());
+                continue;
+            }
+            if let Err(reason) = check_block(&buf, &blk) {
+                report.add_issue(
+                    IssueKind::Block,
+                    path,
+                    format!("block of field {} at {}: {}", field_id, offset, reason),
+                );
+            }
+        }
+    }
+
+    Some(fields)
+}
+
+/// Checks the checksums of a data block in `buf`, and decodes it.
+fn check_block(buf: &[u8], blk: &tsm::BlockMeta) -> Result<(), String> {
+    let ts_len = (blk.val_off() - blk.offset()) as usize;
+    if decode_be_u32(&buf[..4]) != crc32fast::hash(&buf[4..ts_len]) {
+        return Err("timestamps checksum mismatch".to_string());
+    }
+    if decode_be_u32(&buf[ts_len..ts_len + 4]) != crc32fast::hash(&buf[ts_len + 4..]) {
+        return Err("values checksum mismatch".to_string());
+    }
+
+    // Codecs may panic on damaged data.
+    let block = panic::catch_unwind(AssertUnwindSafe(|| {
+        decode_data_block(buf, blk.field_type(), ts_len as u64)
+    }))
+    .map_err(|_| "panics while decoding".to_string())?
+    .map_err(|e| e.to_string())?;
+    if block.len() != blk.count() as usize {
+        return Err(format!(
+            "decoded {} values but block meta has {}",
+            block.len(),
+            blk.count()
+        ));
+    }
+    if block.time_range() != Some((blk.min_ts(), blk.max_ts())) {
+        return Err("decoded time range is different from block meta".to_string());
+    }
+    Ok(())
+}
+
+/// Checks format of a tombstone file, returns tombstones in it.
+fn check_tombstone_file(path: &Path) -> Result<Vec<(FieldId, TimeRange)>, String> {
+    const RECORD_SIZE: usize = 24;
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    if data.len() < 4 {
+        return Err(format!("file size {} is less than header", data.len()));
+    }
+    let magic = decode_be_u32(&data[..4]);
+    if magic != TOMBSTONE_MAGIC {
+        return Err(format!("invalid magic number {:#010x}", magic));
+    }
+    if (data.len() - 4) % RECORD_SIZE != 0 {
+        return Err(format!("file size {} has a truncated record", data.len()));
+    }
+    let mut tombstones = Vec::with_capacity((data.len() - 4) / RECORD_SIZE);
+    for record in data[4..].chunks(RECORD_SIZE) {
+        let field_id = decode_be_u64(&record[..8]);
+        let time_range = TimeRange::new(
+            decode_be_i64(&record[8..16]),
+            decode_be_i64(&record[16..24]),
+        );
+        if time_range.min_ts > time_range.max_ts {
+            return Err(format!(
+                "field {} has invalid time range ({}, {})",
+                field_id, time_range.min_ts, time_range.max_ts
+            ));
+        }
+        tombstones.push((field_id, time_range));
+    }
+    Ok(tombstones)
+}
+
+/// Reports files in `<data_dir>/<database>/{tsm,delta}/<ts_family_id>/` which are
+/// not column files in `referenced` or tombstones of them.
+fn check_orphan_files(data_dir: &Path, referenced: &HashSet<PathBuf>, report: &mut CheckReport) {
+    let mut files = vec![];
+    for db_dir in list_dirs(data_dir) {
+        for tsf_dir in [TSM_PATH, DELTA_PATH]
+            .iter()
+            .flat_map(|d| list_dirs(&db_dir.join(d)))
+        {
+            if let Ok(entries) = fs::read_dir(&tsf_dir) {
+                files.extend(entries.flatten().map(|e| e.path()).filter(|p| p.is_file()));
+            }
+        }
+    }
+    files.sort();
+
+    for path in files {
+        if referenced.contains(&path) {
+            continue;
+        }
+        let is_tombstone = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| n.ends_with(TOMBSTONE_FILE_SUFFIX))
+            .unwrap_or(false);
+        if is_tombstone {
+            if let (Some(dir), Ok(file_id)) =
+                (path.parent(), file_utils::get_tsm_file_id_by_path(&path))
+            {
+                if referenced.contains(&file_utils::make_tsm_file_name(dir, file_id))
+                    || referenced.contains(&file_utils::make_delta_file_name(dir, file_id))
+                {
+                    continue;
+                }
+            }
+        }
+        report.add_issue(IssueKind::OrphanFile, &path, "not referenced by summary");
+    }
+}
+
+pub(crate) fn list_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .collect(),
+        Err(_) => vec![],
+    };
+    dirs.sort();
+    dirs
+}
+
+/// Checks that series ids used in `files` of the database are in the series index.
+fn check_series(
+    index_dir: &Path,
+    database: &str,
+    files: &[(PathBuf, Vec<u64>)],
+    report: &mut CheckReport,
+) {
+    if !file_manager::try_exists(index_dir) {
+        report.add_issue(IssueKind::Index, index_dir, "index directory not found");
+        return;
+    }
+    // Opening index panics if it is locked or damaged.
+    let series_ids = panic::catch_unwind(|| {
+        DBIndex::new(index_dir, DatabaseSchema::new(database)).get_all_series_ids()
+    });
+    let series_ids: HashSet<u64> = match series_ids {
+        Ok(Ok(ids)) => ids.into_iter().map(|sid| split_id(sid).1).collect(),
+        Ok(Err(e)) => {
+            report.add_issue(IssueKind::Index, index_dir, e.to_string());
+            return;
+        }
+        Err(_) => {
+            report.add_issue(IssueKind::Index, index_dir, "unable to open index");
+            return;
+        }
+    };
+
+    for (path, sids) in files {
+        let unknown: Vec<u64> = sids
+            .iter()
+            .filter(|sid| !series_ids.contains(sid))
+            .copied()
+            .collect();
+        if !unknown.is_empty() {
+            let listed: Vec<String> = unknown
+                .iter()
+                .take(MAX_LISTED_SERIES)
+                .map(|sid| sid.to_string())
+                .collect();
+            report.add_issue(
+                IssueKind::UnknownSeries,
+                path,
+                format!(
+                    "{} series not found in index: {}{}",
+                    unknown.len(),
+                    listed.join(", "),
+                    if unknown.len() > MAX_LISTED_SERIES {
+                        ", ..."
+                    } else {
+                        ""
+                    }
+                ),
+            );
+        }
+    }
+}
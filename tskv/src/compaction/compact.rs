@@ -7,6 +7,8 @@ use std::{
 };
 
 use evmap::new;
+use metrics::incr_retention_purged_rows;
+use models::codec::Encoding;
 use models::{FieldId, Timestamp, ValueType};
 use snafu::ResultExt;
 use trace::{debug, error, info, trace};
@@ -23,8 +25,8 @@ use crate::{
     summary::{CompactMeta, VersionEdit},
     tseries_family::{ColumnFile, TimeRange},
     tsm::{
-        self, BlockMeta, BlockMetaIterator, ColumnReader, DataBlock, Index, IndexIterator,
-        IndexMeta, IndexReader, TsmReader, TsmWriter,
+        self, codec::DataBlockEncoding, BlockMeta, BlockMetaIterator, ColumnReader, DataBlock,
+        Index, IndexIterator, IndexMeta, IndexReader, TsmReader, TsmWriter,
     },
     Error, LevelId,
 };
@@ -99,12 +101,13 @@ impl CompactingBlock {
                     res.push(data_block);
                 }
                 CompactingBlock::Raw { meta, raw, .. } => {
-                    let data_block = tsm::decode_data_block(
+                    let mut data_block = tsm::decode_data_block(
                         &raw,
                         meta.field_type(),
                         meta.val_off() - meta.offset(),
                     )
                     .context(error::ReadTsmSnafu)?;
+                    restore_auto_encoding(&meta, &mut data_block);
                     res.push(data_block);
                 }
             }
@@ -112,6 +115,55 @@ impl CompactingBlock {
 
         Ok(res)
     }
+
+    /// Remove data before `expired_ts`, returns the remaining block (None if all data
+    /// is expired) and the number of removed values.
+    fn exclude_expired(self, expired_ts: Timestamp) -> Result<(Option<Self>, usize)> {
+        let (min_ts, max_ts, count) = match &self {
+            CompactingBlock::DataBlock { data_block, .. } => match data_block.time_range() {
+                Some((min_ts, max_ts)) => (min_ts, max_ts, data_block.len()),
+                None => return Ok((None, 0)),
+            },
+            CompactingBlock::Raw { meta, .. } => {
+                (meta.min_ts(), meta.max_ts(), meta.count() as usize)
+            }
+        };
+        if min_ts >= expired_ts {
+            return Ok((Some(self), 0));
+        }
+        if max_ts < expired_ts {
+            return Ok((None, count));
+        }
+
+        let (priority, field_id, mut data_block) = match self {
+            CompactingBlock::DataBlock {
+                priority,
+                field_id,
+                data_block,
+            } => (priority, field_id, data_block),
+            CompactingBlock::Raw {
+                priority,
+                meta,
+                raw,
+            } => {
+                let mut data_block =
+                    tsm::decode_data_block(&raw, meta.field_type(), meta.val_off() - meta.offset())
+                        .context(error::ReadTsmSnafu)?;
+                restore_auto_encoding(&meta, &mut data_block);
+                (priority, meta.field_id(), data_block)
+            }
+        };
+        data_block.exclude(&TimeRange::new(Timestamp::MIN, expired_ts - 1));
+        let excluded = count - data_block.len();
+        Ok((
+            Some(CompactingBlock::DataBlock {
+                priority,
+                field_id,
+                data_block,
+            }),
+            excluded,
+        ))
+    }
 }
 
 struct CompactIterator {
@@ -251,9 +303,10 @@ impl CompactIterator {
                     buf.resize(cbm.block_meta.size() as usize, 0);
                 }
                 if cbm.has_tombstone {
-                    let data_block = self.tsm_readers[cbm.readers_idx]
+                    let mut data_block = self.tsm_readers[cbm.readers_idx]
                         .get_data_block(&cbm.block_meta)
                         .context(error::ReadTsmSnafu)?;
+                    restore_auto_encoding(&cbm.block_meta, &mut data_block);
                     merging_blks.push(CompactingBlock::DataBlock {
                         priority: cbm.readers_idx + 1,
                         field_id,
@@ -274,9 +327,10 @@ impl CompactIterator {
                 (cbm.block_meta.min_ts(), cbm.block_meta.max_ts()),
             ) {
                 // 2.1
-                let data_block = self.tsm_readers[cbm.readers_idx]
+                let mut data_block = self.tsm_readers[cbm.readers_idx]
                     .get_data_block(&cbm.block_meta)
                     .context(error::ReadTsmSnafu)?;
+                restore_auto_encoding(&cbm.block_meta, &mut data_block);
                 merging_blks.push(CompactingBlock::DataBlock {
                     priority: cbm.readers_idx + 1,
                     field_id,
@@ -330,9 +384,10 @@ impl CompactIterator {
                         merged_blk_time_range.1 =
                             merged_blk_time_range.0.max(cbm.block_meta.max_ts());
                         if cbm.has_tombstone {
-                            let data_block = self.tsm_readers[cbm.readers_idx]
+                            let mut data_block = self.tsm_readers[cbm.readers_idx]
                                 .get_data_block(&cbm.block_meta)
                                 .context(error::ReadTsmSnafu)?;
+                            restore_auto_encoding(&cbm.block_meta, &mut data_block);
                             merging_blks.push(CompactingBlock::DataBlock {
                                 priority: cbm.readers_idx + 1,
                                 field_id,
@@ -350,9 +405,10 @@ impl CompactIterator {
                         }
                     } else {
                         // cbm.block_meta.count is less than max_datablock_values
-                        let data_block = self.tsm_readers[cbm.readers_idx]
+                        let mut data_block = self.tsm_readers[cbm.readers_idx]
                             .get_data_block(&cbm.block_meta)
                             .context(error::ReadTsmSnafu)?;
+                        restore_auto_encoding(&cbm.block_meta, &mut data_block);
                         merging_blks.push(CompactingBlock::DataBlock {
                             priority: cbm.readers_idx + 1,
                             field_id,
@@ -426,6 +482,14 @@ pub fn overlaps_tuples(r1: (i64, i64), r2: (i64, i64)) -> bool {
     r1.0 <= r2.1 && r1.1 >= r2.0
 }
 
+/// Decoded blocks keep codecs selected for the old blocks, codecs of auto encoded
+/// blocks are selected again when they are written.
+fn restore_auto_encoding(meta: &BlockMeta, data_block: &mut DataBlock) {
+    if meta.auto_encoded() {
+        data_block.set_encodings(DataBlockEncoding::of_column(Encoding::Auto));
+    }
+}
+
 pub fn run_compaction_job(
     request: CompactReq,
     kernel: Arc<GlobalContext>,
@@ -480,22 +544,30 @@ pub fn run_compaction_job(
         max_datablock_values: max_data_block_size,
         ..Default::default()
     };
-    let tsm_dir = storage_opt.tsm_dir(&request.database, tsf_id);
+    let tsm_dir = storage_opt.level_tsm_dir(&request.database, tsf_id, request.out_level);
     let mut tsm_writer = tsm::new_tsm_writer(&tsm_dir, kernel.file_id_next(), false, 0)?;
     info!("Compaction: File {} been created.", tsm_writer.sequence());
     let mut version_edit = VersionEdit::new();
     version_edit.tsf_id = tsf_id;
+    let mut expired_values = 0_usize;
     for next_blk in iter.flatten() {
         trace!("===============================");
+        let next_blk = match next_blk.exclude_expired(request.expired_ts)? {
+            (Some(blk), excluded) => {
+                expired_values += excluded;
+                blk
+            }
+            (None, excluded) => {
+                expired_values += excluded;
+                continue;
+            }
+        };
         let write_ret = match next_blk {
             CompactingBlock::DataBlock {
                 field_id: fid,
                 data_block: b,
                 ..
-            } => {
-                // TODO: let enc = b.encodings();
-                tsm_writer.write_block(fid, &b)
-            }
+            } => tsm_writer.write_block(fid, &b),
             CompactingBlock::Raw { meta, raw, .. } => tsm_writer.write_raw(&meta, &raw),
         };
         if let Err(e) = write_ret {
@@ -531,14 +603,36 @@ pub fn run_compaction_job(
 
     tsm_writer.write_index().context(error::WriteTsmSnafu)?;
     tsm_writer.finish().context(error::WriteTsmSnafu)?;
-    info!(
-        "Compaction: File: {} write finished (level: {}, {} B).",
-        tsm_writer.sequence(),
-        request.out_level,
-        tsm_writer.size()
-    );
-    let cm = new_compact_meta(&tsm_writer, request.out_level);
-    version_edit.add_file(cm, version.max_level_ts);
+    if tsm_writer.min_ts() > tsm_writer.max_ts() {
+        // All of the remaining data is expired, nothing was written to this file.
+        info!(
+            "Compaction: File: {} is empty, removing it.",
+            tsm_writer.sequence()
+        );
+        if let Err(e) = std::fs::remove_file(tsm_writer.path()) {
+            error!(
+                "Failed to remove empty file '{}': {}",
+                tsm_writer.path().display(),
+                e
+            );
+        }
+    } else {
+        info!(
+            "Compaction: File: {} write finished (level: {}, {} B).",
+            tsm_writer.sequence(),
+            request.out_level,
+            tsm_writer.size()
+        );
+        let cm = new_compact_meta(&tsm_writer, request.out_level);
+        version_edit.add_file(cm, version.max_level_ts);
+    }
+    if expired_values > 0 {
+        info!(
+            "Compaction: {} expired values before {} dropped.",
+            expired_values, request.expired_ts
+        );
+        incr_retention_purged_rows(&request.database, expired_values as u64);
+    }
     for file in request.files {
         version_edit.del_file(file.level(), file.file_id(), file.is_delta());
     }
@@ -579,17 +673,18 @@ mod test {
         },
     };
 
+    use models::codec::Encoding;
     use models::{FieldId, Timestamp, ValueType};
     use utils::BloomFilter;
 
     use crate::file_system::file_manager;
     use crate::{
-        compaction::{run_compaction_job, CompactReq},
+        compaction::{pick_tombstone_rewrite, run_compaction_job, CompactReq},
         context::GlobalContext,
         file_utils,
         kv_option::Options,
         summary::VersionEdit,
-        tseries_family::{ColumnFile, LevelInfo, TimeRange, Version},
+        tseries_family::{ColumnFile, LevelInfo, TimeRange, Version, VnodeInfo},
         tsm::{self, codec::DataBlockEncoding, DataBlock, Tombstone, TsmReader, TsmTombstone},
         TseriesFamilyId,
     };
@@ -697,6 +792,7 @@ mod test {
             1,
             LevelInfo::init_levels(database.clone(), opt.storage.clone()),
             1000,
+            VnodeInfo::default(),
         ));
         let compact_req = CompactReq {
             ts_family_id: 1,
@@ -705,6 +801,7 @@ mod test {
             files,
             version,
             out_level: 2,
+            expired_ts: Timestamp::MIN,
         };
         let kernel = Arc::new(GlobalContext::new());
         kernel.set_file_id(next_file_id);
@@ -1194,4 +1291,196 @@ mod test {
 
         check_column_file(dir, version_edit, expected_data);
     }
+
+    #[test]
+    fn test_compaction_expired() {
+        #[rustfmt::skip]
+        let data = vec![
+            HashMap::from([
+                (1, vec![DataBlock::I64 { ts: vec![1, 2, 3], val: vec![1, 2, 3], enc: DataBlockEncoding::default() }]),
+                (2, vec![DataBlock::I64 { ts: vec![1, 2, 3], val: vec![1, 2, 3], enc: DataBlockEncoding::default() }]),
+            ]),
+            HashMap::from([
+                (1, vec![DataBlock::I64 { ts: vec![4, 5, 6], val: vec![4, 5, 6], enc: DataBlockEncoding::default() }]),
+                (2, vec![DataBlock::I64 { ts: vec![4, 5, 6], val: vec![4, 5, 6], enc: DataBlockEncoding::default() }]),
+            ]),
+            HashMap::from([
+                (1, vec![DataBlock::I64 { ts: vec![7, 8, 9], val: vec![7, 8, 9], enc: DataBlockEncoding::default() }]),
+                (2, vec![DataBlock::I64 { ts: vec![7, 8, 9], val: vec![7, 8, 9], enc: DataBlockEncoding::default() }]),
+            ]),
+        ];
+        #[rustfmt::skip]
+        let expected_data = HashMap::from([
+            (1, vec![DataBlock::I64 { ts: vec![5, 6, 7, 8, 9], val: vec![5, 6, 7, 8, 9], enc: DataBlockEncoding::default() }]),
+            (2, vec![DataBlock::I64 { ts: vec![5, 6, 7, 8, 9], val: vec![5, 6, 7, 8, 9], enc: DataBlockEncoding::default() }]),
+        ]);
+
+        let dir = "/tmp/test/compaction/expired";
+        let database = "dba".to_string();
+        let opt = create_options(dir.to_string());
+        let dir = opt.storage.tsm_dir(&database, 1);
+
+        let (next_file_id, files) = write_data_blocks_to_column_file(&dir, data, 1, opt.clone());
+        let (mut compact_req, kernel) =
+            prepare_compact_req_and_kernel(database, opt, next_file_id, files);
+        compact_req.expired_ts = 5;
+        let version_edit = run_compaction_job(compact_req, kernel).unwrap().unwrap();
+        assert_eq!(version_edit.del_files.len(), 3);
+        check_column_file(dir, version_edit, expected_data);
+    }
+
+    #[test]
+    fn test_compaction_all_expired() {
+        #[rustfmt::skip]
+        let data = vec![
+            HashMap::from([
+                (1, vec![DataBlock::I64 { ts: vec![1, 2, 3], val: vec![1, 2, 3], enc: DataBlockEncoding::default() }]),
+            ]),
+            HashMap::from([
+                (1, vec![DataBlock::I64 { ts: vec![4, 5, 6], val: vec![4, 5, 6], enc: DataBlockEncoding::default() }]),
+            ]),
+        ];
+
+        let dir = "/tmp/test/compaction/all_expired";
+        let database = "dba".to_string();
+        let opt = create_options(dir.to_string());
+        let dir = opt.storage.tsm_dir(&database, 1);
+
+        let (next_file_id, files) = write_data_blocks_to_column_file(&dir, data, 1, opt.clone());
+        let (mut compact_req, kernel) =
+            prepare_compact_req_and_kernel(database, opt, next_file_id, files);
+        compact_req.expired_ts = 10;
+        let version_edit = run_compaction_job(compact_req, kernel).unwrap().unwrap();
+        assert!(version_edit.add_files.is_empty());
+        assert_eq!(version_edit.del_files.len(), 2);
+        assert!(!file_manager::try_exists(file_utils::make_tsm_file_name(
+            &dir,
+            next_file_id
+        )));
+    }
+
+    #[test]
+    fn test_compaction_tombstone_rewrite() {
+        #[rustfmt::skip]
+        let data = vec![
+            HashMap::from([
+                (1, vec![DataBlock::I64 { ts: vec![1, 2, 3, 4, 5, 6], val: vec![1, 2, 3, 4, 5, 6], enc: DataBlockEncoding::default() }]),
+                (2, vec![DataBlock::I64 { ts: vec![1, 2, 3, 4, 5, 6], val: vec![1, 2, 3, 4, 5, 6], enc: DataBlockEncoding::default() }]),
+            ]),
+        ];
+        #[rustfmt::skip]
+        let expected_data = HashMap::from([
+            (1, vec![DataBlock::I64 { ts: vec![5, 6], val: vec![5, 6], enc: DataBlockEncoding::default() }]),
+            (2, vec![DataBlock::I64 { ts: vec![5, 6], val: vec![5, 6], enc: DataBlockEncoding::default() }]),
+        ]);
+
+        let dir = "/tmp/test/compaction/tombstone_rewrite";
+        let _ = std::fs::remove_dir_all(dir);
+        let database = "dba".to_string();
+        let opt = create_options(dir.to_string());
+        let dir = opt.storage.tsm_dir(&database, 1);
+
+        let (next_file_id, files) = write_data_blocks_to_column_file(&dir, data, 1, opt.clone());
+        let file = files[0].clone();
+        let (tsm_path, tombstone_path) = (file.file_path(), file.tombstone_path());
+        file.add_tombstone(&[1, 2], &TimeRange::new(1, 2)).unwrap();
+        let ratio = file.tombstone_ratio().unwrap();
+        assert!((ratio - 2.0 / 6.0).abs() < 1e-6);
+        // Overlapped tombstones are counted once.
+        file.add_tombstone(&[1, 2], &TimeRange::new(2, 4)).unwrap();
+        let ratio = file.tombstone_ratio().unwrap();
+        assert!((ratio - 4.0 / 6.0).abs() < 1e-6);
+
+        let mut levels = LevelInfo::init_levels(database.clone(), opt.storage.clone());
+        levels[2].files = files;
+        let version = Arc::new(Version::new(
+            1,
+            database,
+            opt.storage.clone(),
+            1,
+            levels,
+            1000,
+            VnodeInfo::default(),
+        ));
+        let compact_req = pick_tombstone_rewrite(version.clone()).unwrap();
+        assert_eq!(compact_req.out_level, 2);
+        assert_eq!(compact_req.files.len(), 1);
+        assert!(pick_tombstone_rewrite(version.clone()).is_none());
+
+        let kernel = Arc::new(GlobalContext::new());
+        kernel.set_file_id(next_file_id);
+        let version_edit = run_compaction_job(compact_req, kernel).unwrap().unwrap();
+        check_column_file(&dir, version_edit, expected_data);
+
+        // Tombstone file is removed with the rewritten file.
+        file.mark_deleted();
+        drop(file);
+        drop(version);
+        assert!(!file_manager::try_exists(tsm_path));
+        assert!(!file_manager::try_exists(tombstone_path));
+    }
+
+    #[test]
+    fn test_compaction_auto_encoding() {
+        let dir = "/tmp/test/compaction/auto_encoding";
+        let _ = std::fs::remove_dir_all(dir);
+        let database = "dba_auto".to_string();
+        let opt = create_options(dir.to_string());
+        let tsm_dir = opt.storage.tsm_dir(&database, 1);
+
+        // Field 1 is auto encoded, field 2 is encoded by Gorilla. Sensor-like readings
+        // rounded to two decimals, of which Gorilla is not the smallest codec.
+        let (auto_fid, gorilla_fid) = (1, 2);
+        let block = |range: std::ops::Range<i64>, enc: DataBlockEncoding| DataBlock::F64 {
+            ts: range.clone().collect(),
+            val: range
+                .map(|i| ((20.0 + (i as f64 / 50.0).sin() * 5.0) * 100.0).round() / 100.0)
+                .collect(),
+            enc,
+        };
+        let auto = DataBlockEncoding::of_column(Encoding::Auto);
+        let gorilla = DataBlockEncoding::new(Encoding::Default, Encoding::Gorilla);
+        let data = vec![
+            HashMap::from([
+                (auto_fid, vec![block(0..500, auto)]),
+                (gorilla_fid, vec![block(0..500, gorilla)]),
+            ]),
+            HashMap::from([
+                (auto_fid, vec![block(500..1000, auto)]),
+                (gorilla_fid, vec![block(500..1000, gorilla)]),
+            ]),
+        ];
+        let (next_file_id, files) =
+            write_data_blocks_to_column_file(&tsm_dir, data, 1, opt.clone());
+        for file in files.iter() {
+            let tsm_reader = TsmReader::open(file.file_path()).unwrap();
+            for idx in tsm_reader.index_iterator() {
+                let auto_encoded = idx.field_id() == auto_fid;
+                assert!(idx
+                    .block_iterator()
+                    .all(|m| m.auto_encoded() == auto_encoded));
+            }
+        }
+        let (compact_req, kernel) =
+            prepare_compact_req_and_kernel(database, opt, next_file_id, files);
+        let version_edit = run_compaction_job(compact_req, kernel).unwrap().unwrap();
+
+        let path = get_result_file_path(&tsm_dir, version_edit);
+        let tsm_reader = TsmReader::open(&path).unwrap();
+        for idx in tsm_reader.index_iterator() {
+            let auto_encoded = idx.field_id() == auto_fid;
+            assert!(idx
+                .block_iterator()
+                .all(|m| m.auto_encoded() == auto_encoded));
+        }
+        let data = read_data_blocks_from_column_file(path);
+        let auto_blk = &data[&auto_fid][0];
+        let gorilla_blk = &data[&gorilla_fid][0];
+        // Codec of the auto encoded block is selected again for the merged block.
+        assert_ne!(auto_blk.encodings().split().1, Encoding::Gorilla);
+        assert_eq!(gorilla_blk.encodings().split().1, Encoding::Gorilla);
+        let expected = block(0..1000, gorilla);
+        assert_eq!(auto_blk, &expected);
+        assert_eq!(gorilla_blk, &expected);
+    }
 }
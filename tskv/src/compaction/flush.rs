@@ -1,6 +1,6 @@
 use std::{
     cmp::max,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     iter::Peekable,
     path::{Path, PathBuf},
     rc::Rc,
@@ -183,8 +183,7 @@ impl FlushTask {
             // Write the merged data into files.
             for (field_id, dlt_blks, tsm_blks) in merged_series_data {
                 let (table_field_id, _) = split_id(field_id);
-                let encoding = DataBlockEncoding::new(
-                    Encoding::Default,
+                let encoding = DataBlockEncoding::of_column(
                     field_id_code_type_map
                         .get(&table_field_id)
                         .copied()
@@ -369,6 +368,7 @@ pub fn run_flush_memtable_job(
     }
 
     let mut edits: Vec<VersionEdit> = vec![];
+    let mut databases = HashSet::new();
     for (tsf_id, caches) in tsf_caches.iter() {
         if caches.is_empty() {
             continue;
@@ -381,7 +381,8 @@ pub fn run_flush_memtable_job(
             let version = tsf_rlock.version();
             let database = tsf_rlock.database();
             drop(tsf_rlock);
-            let path_tsm = storage_opt.tsm_dir(&database, *tsf_id);
+            // Flushed tsm files are in level 1.
+            let path_tsm = storage_opt.level_tsm_dir(&database, *tsf_id, 1);
             let path_delta = storage_opt.delta_dir(&database, *tsf_id);
 
             FlushTask::new(
@@ -396,6 +397,17 @@ pub fn run_flush_memtable_job(
             if let Err(e) = compact_task_sender.send(*tsf_id) {
                 warn!("failed to send compact task, {}", e);
             }
+            databases.insert(database);
+        }
+    }
+
+    // Index changes which are not written to storage immediately, e.g. cardinality
+    // sketches, are stored along with flushing caches.
+    for database in databases {
+        if let Some(db) = version_set.read().get_db(&database) {
+            if let Err(e) = db.read().get_index().flush() {
+                warn!("failed to flush index of database '{}', {}", database, e);
+            }
         }
     }
 
@@ -430,7 +442,7 @@ pub mod flush_tests {
     use crate::file_utils;
     use crate::memcache::test::put_rows_to_cache;
     use crate::summary::{CompactMeta, VersionEdit};
-    use crate::tseries_family::{LevelInfo, Version};
+    use crate::tseries_family::{LevelInfo, Version, VnodeInfo};
     use crate::tsm::tsm_reader_tests::read_and_check;
     use crate::tsm::{codec::DataBlockEncoding, DataBlock, TsmReader};
     use crate::{
@@ -655,6 +667,7 @@ pub mod flush_tests {
             ts_family_id, database: database.clone(), storage_opt: options.storage.clone(),
             last_seq: 1, max_level_ts,
             levels_info: LevelInfo::init_levels(database, options.storage),
+            vnode: VnodeInfo::default(),
         });
         let flush_task = FlushTask::new(caches, 1, global_context, &tsm_dir, &delta_dir);
         let mut version_edits = vec![];
@@ -4,6 +4,7 @@ mod picker;
 
 pub use compact::*;
 pub use flush::*;
+use models::Timestamp;
 use parking_lot::RwLock;
 pub use picker::*;
 use std::sync::Arc;
@@ -24,6 +25,8 @@ pub struct CompactReq {
     files: Vec<Arc<ColumnFile>>,
     version: Arc<Version>,
     pub out_level: LevelId,
+    /// Data before this timestamp is expired by TTL and will not be written.
+    pub expired_ts: Timestamp,
 }
 
 #[derive(Debug)]
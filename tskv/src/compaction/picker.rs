@@ -1,7 +1,7 @@
 use std::fmt::Debug;
 use std::{
     cmp::Ordering,
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     ops::{Add, Div},
     sync::{
         atomic::{self, AtomicBool},
@@ -30,6 +30,29 @@ pub trait Picker: Send + Sync + Debug {
     fn pick_compaction(&self, version: Arc<Version>) -> Option<CompactReq>;
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactionPickerType {
+    Level,
+    TimeWindow,
+}
+
+impl CompactionPickerType {
+    /// Parse picker type from config, unknown types fall back to `Level`.
+    pub fn parse(text: &str) -> Self {
+        match text.to_lowercase().as_str() {
+            "level" => Self::Level,
+            "time_window" => Self::TimeWindow,
+            _ => {
+                error!(
+                    "Unknown compaction picker '{}', using 'level' instead.",
+                    text
+                );
+                Self::Level
+            }
+        }
+    }
+}
+
 /// Compaction picker for picking files in level
 #[derive(Debug)]
 pub struct LevelCompactionPicker {
@@ -150,6 +173,7 @@ impl Picker for LevelCompactionPicker {
             files: picking_files,
             version: version.clone(),
             out_level,
+            expired_ts: Timestamp::MIN,
         })
     }
 }
@@ -288,6 +312,160 @@ impl LevelCompactionPicker {
     }
 }
 
+/// Compaction picker for picking files in the same time window.
+///
+/// Files are grouped into time windows by their max timestamp, and only files in the
+/// same window are compacted together, so that old data will not be rewritten again
+/// and again by the compactions of the newer data.
+#[derive(Debug)]
+pub struct TimeWindowCompactionPicker {
+    time_window: i64,
+}
+
+impl Picker for TimeWindowCompactionPicker {
+    fn pick_compaction(&self, version: Arc<Version>) -> Option<CompactReq> {
+        //! 1. Group all files(`Vec<Arc<ColumnFile>>`) that not in compaction by their
+        //!    time window, which is calculated by max_ts of the files.
+        //! 2. Pick the window with the most files; the newest window must have at least
+        //!    `compact_trigger` files, other windows must have more than one file.
+        //! 3. Pick files in the window sorted by min_ts(ascending) until picking_files_size
+        //!    reaches max_compact_size.
+        //! 4. Build CompactReq using **version**, the highest level of picked files and
+        //!    picked files.
+
+        let mut windows: BTreeMap<i64, Vec<Arc<ColumnFile>>> = BTreeMap::new();
+        for lvl in version.levels_info().iter() {
+            for file in lvl.files.iter() {
+                if file.is_compacting() || file.is_deleted() {
+                    continue;
+                }
+                windows
+                    .entry(self.window_of(file.time_range().max_ts))
+                    .or_default()
+                    .push(file.clone());
+            }
+        }
+
+        let newest_window = *windows.keys().next_back()?;
+        let compact_trigger = (version.storage_opt.compact_trigger as usize).max(2);
+        let (window, mut files) = windows
+            .into_iter()
+            .filter(|(w, files)| {
+                files.len() >= compact_trigger || (*w != newest_window && files.len() > 1)
+            })
+            .max_by(|(w1, f1), (w2, f2)| f1.len().cmp(&f2.len()).then(w2.cmp(w1)))?;
+
+        let out_level = files.iter().map(|f| f.level()).max().unwrap_or(0).max(1);
+        let max_compact_size = version.storage_opt.level_file_size(out_level);
+        files.sort_by_key(|f| f.time_range().min_ts);
+        let mut picking_files: Vec<Arc<ColumnFile>> = Vec::new();
+        let mut picking_files_size = 0_u64;
+        for file in files {
+            picking_files_size += file.size();
+            if picking_files_size > max_compact_size && picking_files.len() > 1 {
+                break;
+            }
+            picking_files.push(file);
+        }
+        if picking_files.len() <= 1 {
+            info!("Picker: picked files: None");
+            return None;
+        }
+        for file in picking_files.iter() {
+            file.mark_compacting();
+        }
+
+        info!(
+            "Picker: Picked files in time window {}: [ {} ]",
+            window,
+            picking_files
+                .iter()
+                .map(|f| {
+                    format!(
+                        "{{ Level-{}, file_id: {}, time_range: {}-{} }}",
+                        f.level(),
+                        f.file_id(),
+                        f.time_range().min_ts,
+                        f.time_range().max_ts
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+
+        Some(CompactReq {
+            ts_family_id: version.ts_family_id,
+            database: version.database.clone(),
+            storage_opt: version.storage_opt.clone(),
+            files: picking_files,
+            version: version.clone(),
+            out_level,
+            expired_ts: Timestamp::MIN,
+        })
+    }
+}
+
+impl TimeWindowCompactionPicker {
+    /// Create a picker with `time_window` in the same unit as timestamps of the data.
+    pub fn new(time_window: i64) -> Self {
+        Self {
+            time_window: time_window.max(1),
+        }
+    }
+
+    fn window_of(&self, ts: Timestamp) -> i64 {
+        ts.div_euclid(self.time_window)
+    }
+}
+
+/// Pick a file whose deleted data reaches `tombstone_rewrite_percent` of the file, and
+/// rewrite it alone to free the space of deleted data. Files in level 0 are skipped,
+/// because they will soon be compacted with other files.
+pub fn pick_tombstone_rewrite(version: Arc<Version>) -> Option<CompactReq> {
+    let rewrite_percent = version.storage_opt.tombstone_rewrite_percent;
+    if rewrite_percent == 0 {
+        return None;
+    }
+    for lvl in version.levels_info().iter().skip(1) {
+        for file in lvl.files.iter() {
+            if file.is_compacting() || file.is_deleted() || !file.has_tombstone() {
+                continue;
+            }
+            let ratio = match file.tombstone_ratio() {
+                Ok(ratio) => ratio,
+                Err(e) => {
+                    error!(
+                        "Picker: failed to open file '{}': {}",
+                        file.file_path().display(),
+                        e
+                    );
+                    continue;
+                }
+            };
+            if ratio * 100.0 < rewrite_percent as f64 {
+                continue;
+            }
+            info!(
+                "Picker: picked file {} (Level-{}) to rewrite, {:.2}% of data is deleted",
+                file.file_id(),
+                file.level(),
+                ratio * 100.0
+            );
+            file.mark_compacting();
+            return Some(CompactReq {
+                ts_family_id: version.ts_family_id,
+                database: version.database.clone(),
+                storage_opt: version.storage_opt.clone(),
+                files: vec![file.clone()],
+                version: version.clone(),
+                out_level: file.level(),
+                expired_ts: Timestamp::MIN,
+            });
+        }
+    }
+    None
+}
+
 #[derive(Default)]
 struct LevelCompatContext {
     level_scores: Vec<(u32, f64)>,
@@ -401,10 +579,12 @@ mod test {
     use tokio::sync::mpsc;
 
     use crate::{
+        compaction::{Picker, TimeWindowCompactionPicker},
         file_utils::make_tsm_file_name,
         kv_option::{Options, StorageOptions},
         memcache::MemCache,
-        tseries_family::{ColumnFile, LevelInfo, TseriesFamily, Version},
+        tseries_family::{ColumnFile, LevelInfo, TseriesFamily, Version, VnodeInfo},
+        write_buffer::WriteBufferManager,
         TimeRange,
     };
 
@@ -478,6 +658,7 @@ mod test {
             1,
             level_infos,
             1000,
+            VnodeInfo::default(),
         ));
         let (flush_task_sender, flush_task_receiver) = mpsc::unbounded_channel();
         TseriesFamily::new(
@@ -488,6 +669,7 @@ mod test {
             opt.cache.clone(),
             opt.storage.clone(),
             flush_task_sender,
+            Arc::new(WriteBufferManager::new(0)),
         )
     }
 
@@ -531,4 +713,49 @@ mod test {
         assert_eq!(compact_req.out_level, 2);
         assert_eq!(compact_req.files.len(), 2);
     }
+
+    #[test]
+    fn test_time_window_pick() {
+        // Time window is 1000, files are in 3 windows by their max_ts:
+        // - Window 0: file 1, 3, 4
+        // - Window 1: file 2, 5
+        // - Window 2: file 6, the newest window, which has less than compact_trigger files.
+        // Window 0 will be picked first and compacted to Level 2, then Window 1 to Level 1.
+        let dir = "/tmp/test/pick/time_window";
+        let opt = create_options(dir.to_string());
+
+        #[rustfmt::skip]
+        let levels_sketch: LevelsSketch = vec![
+            // vec![( level, Timestamp_Begin, Timestamp_end, vec![(file_id, Timestamp_Begin, Timestamp_end, size, being_compact)] )]
+            (0_u32, 500_i64, 2100_i64, vec![
+                (4_u64, 500_i64, 800_i64, 100_u64, false),
+                (5, 1500, 1600, 100, false),
+                (6, 2001, 2100, 100, false),
+            ]),
+            (1, 1, 1999, vec![
+                (1, 1, 999, 1000, false),
+                (2, 1001, 1999, 1000, false),
+            ]),
+            (2, 1, 900, vec![
+                (3, 1, 900, 1000, false),
+            ]),
+        ];
+
+        let tsf = create_tseries_family("dba".to_string(), opt, levels_sketch);
+        let picker = TimeWindowCompactionPicker::new(1000);
+
+        let compact_req = picker.pick_compaction(tsf.version()).unwrap();
+        assert_eq!(compact_req.out_level, 2);
+        let mut file_ids: Vec<u64> = compact_req.files.iter().map(|f| f.file_id()).collect();
+        file_ids.sort_unstable();
+        assert_eq!(file_ids, vec![1, 3, 4]);
+
+        let compact_req = picker.pick_compaction(tsf.version()).unwrap();
+        assert_eq!(compact_req.out_level, 1);
+        let mut file_ids: Vec<u64> = compact_req.files.iter().map(|f| f.file_id()).collect();
+        file_ids.sort_unstable();
+        assert_eq!(file_ids, vec![2, 5]);
+
+        assert!(picker.pick_compaction(tsf.version()).is_none());
+    }
 }
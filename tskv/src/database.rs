@@ -6,6 +6,7 @@ use std::{
     sync::{atomic::AtomicU32, atomic::Ordering, Arc, Mutex},
 };
 
+use metrics::{incr_retention_dropped_files, incr_retention_purged_rows};
 use parking_lot::RwLock;
 use snafu::ResultExt;
 use tokio::sync::watch::Receiver;
@@ -13,14 +14,19 @@ use tokio::sync::{mpsc::UnboundedSender, oneshot};
 
 use ::models::{FieldInfo, InMemPoint, Tag, ValueType};
 use models::schema::{DatabaseSchema, TableSchema, TskvTableSchema};
-use models::utils::{split_id, unite_id};
+use models::utils::{now_timestamp_nanos, split_id, unite_id};
 use models::{ColumnId, SchemaId, SeriesId, SeriesKey, Timestamp};
-use protos::models::{Point, Points};
+use protos::models::{
+    Field, FieldArgs, Point, PointArgs, Points, PointsArgs, Tag as FbTag, TagArgs,
+};
 use trace::{debug, error, info};
 
-use crate::compaction::FlushReq;
-use crate::index::{index_manger, IndexError, IndexResult};
-use crate::tseries_family::LevelInfo;
+use crate::compaction::{
+    CompactionPickerType, FlushReq, LevelCompactionPicker, Picker, TimeWindowCompactionPicker,
+};
+use crate::index::{index_manger, IndexError, IndexResult, SeriesLimits};
+use crate::tseries_family::{LevelInfo, VnodeInfo};
+use crate::tsm::remove_orphan_tombstones;
 use crate::Error::InvalidPoint;
 use crate::{
     error::{self, IndexErrSnafu, Result},
@@ -30,13 +36,16 @@ use crate::{
 };
 use crate::{
     index::db_index,
-    kv_option::Options,
+    kv_option::{Options, DELTA_PATH, TSM_PATH},
     memcache::MemCache,
     summary::{CompactMeta, SummaryTask, VersionEdit},
     tseries_family::{TseriesFamily, Version},
+    write_buffer::WriteBufferManager,
 };
 
 pub type FlatBufferPoint<'a> = flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<Point<'a>>>;
+/// Rows to write grouped by vnode, and then by (series id, schema id).
+pub type VnodeWriteGroup = HashMap<VnodeInfo, HashMap<(SeriesId, SchemaId), RowGroup>>;
 
 #[derive(Debug)]
 pub struct Database {
@@ -44,10 +53,15 @@ pub struct Database {
     index: Arc<db_index::DBIndex>,
     ts_families: HashMap<TseriesFamilyId, Arc<RwLock<TseriesFamily>>>,
     opt: Arc<Options>,
+    write_buffer: Arc<WriteBufferManager>,
 }
 
 impl Database {
-    pub fn new(schema: DatabaseSchema, opt: Arc<Options>) -> Self {
+    pub fn new(
+        schema: DatabaseSchema,
+        opt: Arc<Options>,
+        write_buffer: Arc<WriteBufferManager>,
+    ) -> Self {
         Self {
             index: db_index::index_manger(opt.storage.index_base_dir())
                 .write()
@@ -55,6 +69,7 @@ impl Database {
             name: schema.name,
             ts_families: HashMap::new(),
             opt,
+            write_buffer,
         }
     }
 
@@ -64,8 +79,24 @@ impl Database {
         flush_task_sender: UnboundedSender<FlushReq>,
     ) {
         let opt = ver.storage_opt();
+        let tsf_id = ver.tf_id().to_string();
+        let removed: usize = opt
+            .database_dirs(ver.database())
+            .iter()
+            .map(|dir| {
+                remove_orphan_tombstones(dir.join(TSM_PATH).join(&tsf_id))
+                    + remove_orphan_tombstones(dir.join(DELTA_PATH).join(&tsf_id))
+            })
+            .sum();
+        if removed > 0 {
+            info!(
+                "Removed {} orphan tombstone files of ts_family {}",
+                removed,
+                ver.tf_id()
+            );
+        }
 
-        let tf = TseriesFamily::new(
+        let mut tf = TseriesFamily::new(
             ver.tf_id(),
             ver.database().to_string(),
             MemCache::new(ver.tf_id(), self.opt.cache.max_buffer_size, ver.last_seq),
@@ -73,7 +104,9 @@ impl Database {
             self.opt.cache.clone(),
             self.opt.storage.clone(),
             flush_task_sender,
+            self.write_buffer.clone(),
         );
+        tf.set_compact_picker(self.compaction_picker());
         self.ts_families
             .insert(ver.tf_id(), Arc::new(RwLock::new(tf)));
     }
@@ -96,6 +129,7 @@ impl Database {
         &mut self,
         tsf_id: u32,
         seq_no: u64,
+        vnode: VnodeInfo,
         summary_task_sender: UnboundedSender<SummaryTask>,
         flush_task_sender: UnboundedSender<FlushReq>,
     ) -> Arc<RwLock<TseriesFamily>> {
@@ -106,9 +140,10 @@ impl Database {
             seq_no,
             LevelInfo::init_levels(self.name.clone(), self.opt.storage.clone()),
             i64::MIN,
+            vnode,
         ));
 
-        let tf = TseriesFamily::new(
+        let mut tf = TseriesFamily::new(
             tsf_id,
             self.name.clone(),
             MemCache::new(tsf_id, self.opt.cache.max_buffer_size, seq_no),
@@ -116,12 +151,14 @@ impl Database {
             self.opt.cache.clone(),
             self.opt.storage.clone(),
             flush_task_sender,
+            self.write_buffer.clone(),
         );
+        tf.set_compact_picker(self.compaction_picker());
         let tf = Arc::new(RwLock::new(tf));
         self.ts_families.insert(tsf_id, tf.clone());
 
         let mut edit = VersionEdit::new();
-        edit.add_tsfamily(tsf_id, self.name.clone());
+        edit.add_tsfamily(tsf_id, self.name.clone(), vnode);
 
         let edits = vec![edit];
         let (task_state_sender, task_state_receiver) = oneshot::channel();
@@ -153,10 +190,12 @@ impl Database {
         }
     }
 
+    /// Builds rows of points grouped by vnode and series, points of new series
+    /// rejected by series limits are skipped and returned.
     pub fn build_write_group(
         &self,
         points: FlatBufferPoint,
-    ) -> Result<HashMap<(SeriesId, SchemaId), RowGroup>> {
+    ) -> Result<(VnodeWriteGroup, RejectedPoints)> {
         if self.opt.storage.strict_write {
             self.build_write_group_strict_mode(points)
         } else {
@@ -167,23 +206,35 @@ impl Database {
     pub fn build_write_group_strict_mode(
         &self,
         points: FlatBufferPoint,
-    ) -> Result<HashMap<(SeriesId, SchemaId), RowGroup>> {
-        // (series id, schema id) -> RowGroup
+    ) -> Result<(VnodeWriteGroup, RejectedPoints)> {
+        // vnode -> (series id, schema id) -> RowGroup
         let mut map = HashMap::new();
-        for point in points {
-            let sid = self.build_index(&point)?;
-            self.build_row_data(&mut map, point, sid)?
+        let mut rejected = RejectedPoints::default();
+        let (duration, shard_num) = self.vnode_options();
+        let limits = self.series_limits();
+        for (i, point) in points.iter().enumerate() {
+            let sid = match rejected.check(i, self.build_index(&point, limits))? {
+                Some(sid) => sid,
+                None => continue,
+            };
+            self.build_row_data(&mut map, point, sid, duration, shard_num)?
         }
-        Ok(map)
+        Ok((map, rejected))
     }
 
     pub fn build_write_group_loose_mode(
         &self,
         points: FlatBufferPoint,
-    ) -> Result<HashMap<(SeriesId, SchemaId), RowGroup>> {
+    ) -> Result<(VnodeWriteGroup, RejectedPoints)> {
         let mut map = HashMap::new();
-        for point in points {
-            let sid = self.build_index(&point)?;
+        let mut rejected = RejectedPoints::default();
+        let (duration, shard_num) = self.vnode_options();
+        let limits = self.series_limits();
+        for (i, point) in points.iter().enumerate() {
+            let sid = match rejected.check(i, self.build_index(&point, limits))? {
+                Some(sid) => sid,
+                None => continue,
+            };
             match self.index.check_field_type_from_cache(sid, &point) {
                 Ok(_) => {}
                 Err(_) => {
@@ -193,16 +244,35 @@ impl Database {
                 }
             }
 
-            self.build_row_data(&mut map, point, sid)?
+            self.build_row_data(&mut map, point, sid, duration, shard_num)?
         }
-        Ok(map)
+        Ok((map, rejected))
+    }
+
+    /// Returns limits of numbers of series, options of the database override defaults in config.
+    pub fn series_limits(&self) -> SeriesLimits {
+        let options = self.get_schema().config;
+        SeriesLimits {
+            max_series_per_database: options
+                .max_series_per_database
+                .unwrap_or(self.opt.storage.max_series_per_database),
+            max_series_per_table: options
+                .max_series_per_table
+                .unwrap_or(self.opt.storage.max_series_per_table),
+        }
+    }
+
+    fn vnode_options(&self) -> (i64, u64) {
+        vnode_options(&self.get_schema())
     }
 
     fn build_row_data(
         &self,
-        map: &mut HashMap<(SeriesId, SchemaId), RowGroup>,
+        map: &mut VnodeWriteGroup,
         point: Point,
         sid: u64,
+        duration: i64,
+        shard_num: u64,
     ) -> Result<()> {
         let table_name = String::from_utf8(point.tab().unwrap().to_vec()).unwrap();
         let table_schema = self
@@ -221,15 +291,21 @@ impl Database {
         let row = RowData::point_to_row_data(point, &table_schema);
         let schema_size = table_schema.size();
         let schema_id = table_schema.schema_id;
-        let entry = map.entry((sid, schema_id)).or_insert(RowGroup {
-            schema: TskvTableSchema::default(),
-            rows: vec![],
-            range: TimeRange {
-                min_ts: i64::MAX,
-                max_ts: i64::MIN,
-            },
-            size: size_of::<RowGroup>(),
-        });
+        let (series_hash, _) = split_id(sid);
+        let vnode = VnodeInfo::locate(series_hash as u64, row.ts, duration, shard_num);
+        let entry = map
+            .entry(vnode)
+            .or_default()
+            .entry((sid, schema_id))
+            .or_insert(RowGroup {
+                schema: TskvTableSchema::default(),
+                rows: vec![],
+                range: TimeRange {
+                    min_ts: i64::MAX,
+                    max_ts: i64::MIN,
+                },
+                size: size_of::<RowGroup>(),
+            });
         entry.schema = table_schema;
         entry.size += schema_size;
         entry.range.merge(&TimeRange {
@@ -242,7 +318,7 @@ impl Database {
         Ok(())
     }
 
-    fn build_index(&self, info: &Point) -> Result<u64> {
+    fn build_index(&self, info: &Point, limits: SeriesLimits) -> Result<u64> {
         if info.tags().ok_or(InvalidPoint)?.is_empty()
             || info.fields().ok_or(InvalidPoint)?.is_empty()
         {
@@ -258,38 +334,17 @@ impl Database {
 
         let id = self
             .index
-            .add_series_if_not_exists(info)
+            .add_series_if_not_exists(info, limits)
             .context(error::IndexErrSnafu)?;
 
         Ok(id)
     }
 
-    pub fn version_edit(&self, last_seq: u64) -> (Vec<VersionEdit>, Vec<VersionEdit>) {
-        let mut edits = vec![];
-        let mut files = vec![];
-
-        for (id, ts) in &self.ts_families {
-            //tsfamily edit
-            let mut edit = VersionEdit::new();
-            edit.add_tsfamily(*id, self.name.clone());
-            edits.push(edit);
-
-            // file edit
-            let mut edit = VersionEdit::new();
-            let version = ts.read().version().clone();
-            let max_level_ts = version.max_level_ts;
-            for files in version.levels_info.iter() {
-                for file in files.files.iter() {
-                    let mut meta = CompactMeta::from(file.as_ref());
-                    meta.tsf_id = files.tsf_id;
-                    meta.high_seq = last_seq;
-                    edit.add_file(meta, max_level_ts);
-                }
-            }
-            files.push(edit);
-        }
-
-        (edits, files)
+    pub fn version_edit(&self) -> Vec<VersionEdit> {
+        self.ts_families
+            .values()
+            .map(|ts| ts.read().version().snapshot_edit())
+            .collect()
     }
 
     pub fn get_series_key(&self, sid: u64) -> IndexResult<Option<SeriesKey>> {
@@ -308,6 +363,29 @@ impl Database {
         self.ts_families.get(&id)
     }
 
+    /// Returns the TseriesFamily which is responsible for the `vnode`.
+    pub fn get_tsfamily_by_vnode(&self, vnode: &VnodeInfo) -> Option<Arc<RwLock<TseriesFamily>>> {
+        self.ts_families
+            .values()
+            .find(|tsf| tsf.read().vnode().includes(vnode))
+            .cloned()
+    }
+
+    /// Returns TseriesFamilies whose time range overlaps any of the `time_ranges`.
+    pub fn get_tsfamilies_by_time_ranges(
+        &self,
+        time_ranges: &[TimeRange],
+    ) -> Vec<Arc<RwLock<TseriesFamily>>> {
+        self.ts_families
+            .values()
+            .filter(|tsf| {
+                let vnode = tsf.read().vnode();
+                time_ranges.iter().any(|tr| vnode.time_range.overlaps(tr))
+            })
+            .cloned()
+            .collect()
+    }
+
     pub fn tsf_num(&self) -> usize {
         self.ts_families.len()
     }
@@ -339,6 +417,213 @@ impl Database {
     pub fn get_schema(&self) -> DatabaseSchema {
         self.index.db_schema()
     }
+
+    /// Delete data expired by the database TTL in caches, returns `VersionEdit`s
+    /// to delete the vnodes and column files which are entirely expired.
+    pub fn drop_expired_data(&mut self) -> Vec<VersionEdit> {
+        let expired_ts = self.ttl_expired_ts();
+        let mut version_edits = Vec::new();
+        let (mut dropped_files, mut dropped_bytes, mut purged_rows) = (0_u64, 0_u64, 0_u64);
+
+        // Drop the whole vnodes whose time range is expired.
+        let expired_tsf_ids: Vec<TseriesFamilyId> = self
+            .ts_families
+            .iter()
+            .filter(|(_, tsf)| tsf.read().vnode().time_range.max_ts < expired_ts)
+            .map(|(tsf_id, _)| *tsf_id)
+            .collect();
+        for tsf_id in expired_tsf_ids {
+            if let Some(tsf) = self.ts_families.remove(&tsf_id) {
+                let version = tsf.read().version();
+                for level in version.levels_info.iter() {
+                    for file in level.files.iter() {
+                        file.mark_deleted();
+                        dropped_files += 1;
+                        dropped_bytes += file.size();
+                    }
+                }
+                info!(
+                    "Retention: database '{}' dropped vnode {} ({:?})",
+                    self.name, tsf_id, version.vnode
+                );
+            }
+            let mut edit = VersionEdit::new();
+            edit.del_tsfamily(tsf_id);
+            version_edits.push(edit);
+        }
+
+        for (tsf_id, tsf) in self.ts_families.iter() {
+            let tsf = tsf.read();
+            purged_rows += tsf.delete_expired_cache(expired_ts) as u64;
+
+            let expired_files = tsf.version().expired_column_files(expired_ts);
+            if expired_files.is_empty() {
+                continue;
+            }
+            let mut edit = VersionEdit::new();
+            edit.set_tsf_id(*tsf_id);
+            for file in expired_files {
+                // Prevent the file being picked by compaction.
+                file.mark_compacting();
+                edit.del_file(file.level(), file.file_id(), file.is_delta());
+                dropped_files += 1;
+                dropped_bytes += file.size();
+            }
+            version_edits.push(edit);
+        }
+
+        if dropped_files > 0 || purged_rows > 0 {
+            info!(
+                "Retention: database '{}' dropped {} files ({} B) and {} cached rows before {}",
+                self.name, dropped_files, dropped_bytes, purged_rows, expired_ts
+            );
+            incr_retention_dropped_files(&self.name, dropped_files, dropped_bytes);
+            incr_retention_purged_rows(&self.name, purged_rows);
+        }
+
+        version_edits
+    }
+
+    /// Releases the files marked by `drop_expired_data()` when its version edits
+    /// failed to apply, so that they can be compacted or dropped again.
+    pub fn unmark_expired_files(&self, version_edits: &[VersionEdit]) {
+        for edit in version_edits.iter().filter(|e| !e.del_tsf) {
+            let tsf = match self.ts_families.get(&edit.tsf_id) {
+                Some(tsf) => tsf,
+                None => continue,
+            };
+            let version = tsf.read().version();
+            for level in version.levels_info.iter() {
+                for file in level.files.iter() {
+                    if edit.del_files.iter().any(|f| f.file_id == file.file_id()) {
+                        file.unmark_compacting();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the compaction picker configured for the database.
+    fn compaction_picker(&self) -> Arc<dyn Picker> {
+        match self.opt.storage.compaction_picker(&self.name) {
+            CompactionPickerType::Level => Arc::new(LevelCompactionPicker::new()),
+            CompactionPickerType::TimeWindow => {
+                let time_window_nanos =
+                    (self.opt.storage.compaction_time_window as i64).saturating_mul(1_000_000_000);
+                let precision = self.get_schema().config.precision;
+                Arc::new(TimeWindowCompactionPicker::new(
+                    precision.convert_nanoseconds(time_window_nanos),
+                ))
+            }
+        }
+    }
+
+    /// Returns the timestamp before which data is expired by the database TTL.
+    pub fn ttl_expired_ts(&self) -> Timestamp {
+        let options = self.get_schema().config;
+        let now = options.precision.convert_nanoseconds(now_timestamp_nanos());
+        let ttl = options
+            .precision
+            .convert_nanoseconds(options.ttl.to_nanoseconds());
+        now.saturating_sub(ttl)
+    }
+
+    /// Returns the timestamp before which data is offloaded to the object store.
+    pub fn offload_expired_ts(&self, offload_age: u64) -> Timestamp {
+        let precision = self.get_schema().config.precision;
+        let now = precision.convert_nanoseconds(now_timestamp_nanos());
+        let age = precision.convert_nanoseconds((offload_age as i64).saturating_mul(1_000_000_000));
+        now.saturating_sub(age)
+    }
+}
+
+/// Points of new series rejected by series limits in a write batch.
+#[derive(Default)]
+pub struct RejectedPoints {
+    /// Indexes of the rejected points in the batch.
+    indexes: Vec<usize>,
+    error: Option<IndexError>,
+}
+
+impl RejectedPoints {
+    /// Returns `None` if the series of the point is rejected, other errors are returned.
+    fn check(&mut self, index: usize, build_index_result: Result<u64>) -> Result<Option<u64>> {
+        match build_index_result {
+            Ok(sid) => Ok(Some(sid)),
+            Err(Error::IndexErr {
+                source: source @ IndexError::SeriesLimitExceeded { .. },
+            }) => {
+                self.indexes.push(index);
+                self.error.get_or_insert(source);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+
+    /// Returns `points` without the rejected points, encoded as a new flatbuffer.
+    pub fn retain_accepted(&self, points: &Points) -> Vec<u8> {
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let mut point_offsets = vec![];
+        for (i, point) in points.points().into_iter().flatten().enumerate() {
+            if self.indexes.binary_search(&i).is_ok() {
+                continue;
+            }
+            let mut tags = vec![];
+            for tag in point.tags().into_iter().flatten() {
+                let args = TagArgs {
+                    key: tag.key().map(|v| fbb.create_vector(v)),
+                    value: tag.value().map(|v| fbb.create_vector(v)),
+                };
+                tags.push(FbTag::create(&mut fbb, &args));
+            }
+            let mut fields = vec![];
+            for field in point.fields().into_iter().flatten() {
+                let args = FieldArgs {
+                    name: field.name().map(|v| fbb.create_vector(v)),
+                    type_: field.type_(),
+                    value: field.value().map(|v| fbb.create_vector(v)),
+                };
+                fields.push(Field::create(&mut fbb, &args));
+            }
+            let args = PointArgs {
+                db: point.db().map(|v| fbb.create_vector(v)),
+                tab: point.tab().map(|v| fbb.create_vector(v)),
+                tags: Some(fbb.create_vector(&tags)),
+                fields: Some(fbb.create_vector(&fields)),
+                timestamp: point.timestamp(),
+            };
+            point_offsets.push(Point::create(&mut fbb, &args));
+        }
+        let args = PointsArgs {
+            db: points.db().map(|v| fbb.create_vector(v)),
+            points: Some(fbb.create_vector(&point_offsets)),
+        };
+        let points = Points::create(&mut fbb, &args);
+        fbb.finish(points, None);
+        fbb.finished_data().to_vec()
+    }
+
+    pub fn into_error(self) -> Option<Error> {
+        self.error.map(|source| Error::SeriesLimitExceeded {
+            points: self.indexes.len(),
+            source,
+        })
+    }
+}
+
+/// Returns the duration of a vnode in the precision of the database and the
+/// number of shards in a vnode time bucket.
+pub(crate) fn vnode_options(schema: &DatabaseSchema) -> (i64, u64) {
+    let options = &schema.config;
+    let duration = options
+        .precision
+        .convert_nanoseconds(options.vnode_duration.to_nanoseconds());
+    (duration, options.shard_num)
 }
 
 pub(crate) fn delete_table_async(
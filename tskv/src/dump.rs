@@ -0,0 +1,531 @@
+//! Exporting data in a TSM file as line protocol, CSV or Parquet.
+//!
+//! Series ids and column ids in field ids are resolved into series keys and
+//! field names by the index of the database if it's available, otherwise
+//! series are named as `series_<series_id>` and fields as `field_<column_id>`.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{
+    ArrayRef, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder, UInt64Builder,
+};
+use datafusion::arrow::datatypes::{DataType as ArrowDataType, Field, Schema, SchemaRef};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::parquet::arrow::ArrowWriter;
+use models::schema::{DatabaseSchema, TableSchema};
+use models::utils::split_id;
+use models::FieldId;
+use snafu::ResultExt;
+
+use crate::error::{self, Error, Result};
+use crate::file_system::file_manager;
+use crate::index::DBIndex;
+use crate::kv_option::{StorageOptions, DATA_PATH, INDEX_PATH};
+use crate::memcache::DataType;
+use crate::tseries_family::TimeRange;
+use crate::tsm::TsmReader;
+
+/// Max number of rows in a record batch of Parquet files.
+const PARQUET_BATCH_SIZE: usize = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    LineProtocol,
+    Csv,
+    Parquet,
+}
+
+impl FromStr for DumpFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "lp" => Ok(Self::LineProtocol),
+            "csv" => Ok(Self::Csv),
+            "parquet" => Ok(Self::Parquet),
+            _ => Err(format!(
+                "unknown dump format '{}', expected lp, csv or parquet",
+                s
+            )),
+        }
+    }
+}
+
+/// Series key and field name of a field id.
+struct FieldName {
+    table: String,
+    tags: Vec<(String, String)>,
+    field: String,
+}
+
+/// Resolves field ids into series keys and field names by index.
+struct FieldResolver {
+    index: Option<(DBIndex, HashMap<u64, u64>)>,
+    tables: HashMap<String, Option<TableSchema>>,
+}
+
+impl FieldResolver {
+    fn new(index_dir: Option<&Path>) -> Self {
+        let index = index_dir
+            .filter(|dir| file_manager::try_exists(dir))
+            .and_then(open_index);
+        Self {
+            index,
+            tables: HashMap::new(),
+        }
+    }
+
+    fn resolve(&mut self, field_id: FieldId) -> FieldName {
+        let (column_id, sid) = split_id(field_id);
+        let mut name = FieldName {
+            table: format!("series_{}", sid),
+            tags: vec![],
+            field: format!("field_{}", column_id),
+        };
+        let (index, series_ids) = match self.index.as_ref() {
+            Some(index) => index,
+            None => return name,
+        };
+        let series_key = match series_ids
+            .get(&sid)
+            .and_then(|sid| index.get_series_key(*sid).ok().flatten())
+        {
+            Some(key) => key,
+            None => return name,
+        };
+        name.table = series_key.table().clone();
+        name.tags = series_key
+            .tags()
+            .iter()
+            .map(|t| {
+                (
+                    String::from_utf8_lossy(&t.key).to_string(),
+                    String::from_utf8_lossy(&t.value).to_string(),
+                )
+            })
+            .collect();
+        let table_schema = self
+            .tables
+            .entry(name.table.clone())
+            .or_insert_with(|| index.get_table_schema(series_key.table()).ok().flatten());
+        if let Some(TableSchema::TsKvTableSchema(schema)) = table_schema {
+            if let Some(col) = schema.columns().iter().find(|c| c.id == column_id) {
+                name.field = col.name.clone();
+            }
+        }
+        name
+    }
+}
+
+fn open_index(index_dir: &Path) -> Option<(DBIndex, HashMap<u64, u64>)> {
+    let database = index_dir.file_name()?.to_str()?.to_string();
+    // Opening index panics if it is locked or damaged.
+    panic::catch_unwind(|| {
+        let index = DBIndex::new(index_dir, DatabaseSchema::new(&database));
+        // Field ids only contain the lower bits of series ids.
+        let series_ids = index
+            .get_all_series_ids()
+            .ok()?
+            .into_iter()
+            .map(|sid| (split_id(sid).1, sid))
+            .collect();
+        Some((index, series_ids))
+    })
+    .ok()
+    .flatten()
+}
+
+/// Returns the index directory of the database that the TSM file at `tsm_path`
+/// belongs to, which is `<root>/data/<database>/tsm/<ts_family_id>/<tsm_file>`.
+///
+/// `<root>` must be one of the root paths of column files in `storage`, it's taken
+/// as the storage path if `storage` is not set.
+pub fn default_index_dir(tsm_path: &Path, storage: Option<&StorageOptions>) -> Option<PathBuf> {
+    let db_dir = tsm_path.parent()?.parent()?.parent()?;
+    let data_dir = db_dir.parent()?;
+    if data_dir.file_name()?.to_str()? != DATA_PATH {
+        return None;
+    }
+    let root = data_dir.parent()?;
+    let database = db_dir.file_name()?.to_str()?;
+    match storage {
+        Some(storage) if storage.data_paths().contains(&root) => Some(storage.index_dir(database)),
+        Some(_) => None,
+        None => Some(root.join(INDEX_PATH).join(database)),
+    }
+}
+
+/// Writes data in the TSM file at `tsm_path` which are in `time_range` and not
+/// deleted by tombstones into `writer`, returns the number of written values.
+pub fn dump_tsm(
+    tsm_path: impl AsRef<Path>,
+    index_dir: Option<&Path>,
+    format: DumpFormat,
+    time_range: &TimeRange,
+    writer: impl Write + Send,
+) -> Result<usize> {
+    let reader = TsmReader::open(tsm_path)?;
+    let mut resolver = FieldResolver::new(index_dir);
+    let mut row_writer: Box<dyn RowWriter> = match format {
+        DumpFormat::LineProtocol => Box::new(LineProtocolWriter { writer }),
+        DumpFormat::Csv => Box::new(CsvWriter::new(writer)?),
+        DumpFormat::Parquet => Box::new(ParquetWriter::new(writer)?),
+    };
+
+    let mut rows = 0_usize;
+    for idx in reader.index_iterator() {
+        let mut name = None;
+        for blk in idx.block_iterator_opt(time_range) {
+            let data_block = reader.get_data_block(&blk).context(error::ReadTsmSnafu)?;
+            let name = name.get_or_insert_with(|| resolver.resolve(idx.field_id()));
+            for i in 0..data_block.len() {
+                let value = match data_block.get(i) {
+                    Some(v) if time_range.contains(v.timestamp()) => v,
+                    _ => continue,
+                };
+                row_writer.write_row(name, &value)?;
+                rows += 1;
+            }
+        }
+    }
+    row_writer.finish()?;
+
+    Ok(rows)
+}
+
+trait RowWriter {
+    fn write_row(&mut self, name: &FieldName, value: &DataType) -> Result<()>;
+
+    fn finish(&mut self) -> Result<()>;
+}
+
+/// Writes a line for each value.
+struct LineProtocolWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> RowWriter for LineProtocolWriter<W> {
+    fn write_row(&mut self, name: &FieldName, value: &DataType) -> Result<()> {
+        let mut line = escape(&name.table, &[',', ' ']);
+        for (k, v) in name.tags.iter() {
+            line.push(',');
+            line.push_str(&escape(k, &[',', '=', ' ']));
+            line.push('=');
+            line.push_str(&escape(v, &[',', '=', ' ']));
+        }
+        line.push(' ');
+        line.push_str(&escape(&name.field, &[',', '=', ' ']));
+        line.push('=');
+        match value {
+            DataType::F64(_, v) => line.push_str(&v.to_string()),
+            DataType::I64(_, v) => line.push_str(&format!("{}i", v)),
+            DataType::U64(_, v) => line.push_str(&format!("{}u", v)),
+            DataType::Bool(_, v) => line.push_str(&v.to_string()),
+            DataType::Str(_, v) => {
+                line.push('"');
+                line.push_str(&escape(&String::from_utf8_lossy(v), &['"', '\\']));
+                line.push('"');
+            }
+        }
+        writeln!(self.writer, "{} {}", line, value.timestamp()).context(error::IOSnafu)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush().context(error::IOSnafu)
+    }
+}
+
+/// Writes a record `measurement,tags,field,time,value` for each value, tags are
+/// formatted as `key=value` joined by `,`.
+struct CsvWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> CsvWriter<W> {
+    fn new(mut writer: W) -> Result<Self> {
+        writeln!(writer, "measurement,tags,field,time,value").context(error::IOSnafu)?;
+        Ok(Self { writer })
+    }
+}
+
+impl<W: Write> RowWriter for CsvWriter<W> {
+    fn write_row(&mut self, name: &FieldName, value: &DataType) -> Result<()> {
+        let value_str = match value {
+            DataType::F64(_, v) => v.to_string(),
+            DataType::I64(_, v) => v.to_string(),
+            DataType::U64(_, v) => v.to_string(),
+            DataType::Bool(_, v) => v.to_string(),
+            DataType::Str(_, v) => String::from_utf8_lossy(v).to_string(),
+        };
+        writeln!(
+            self.writer,
+            "{},{},{},{},{}",
+            csv_field(&name.table),
+            csv_field(&tags_string(&name.tags)),
+            csv_field(&name.field),
+            value.timestamp(),
+            csv_field(&value_str)
+        )
+        .context(error::IOSnafu)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush().context(error::IOSnafu)
+    }
+}
+
+/// Writes a row for each value, values are in the column of their types and
+/// the other value columns are null.
+struct ParquetWriter<W: Write + Send> {
+    writer: Option<ArrowWriter<W>>,
+    schema: SchemaRef,
+    rows: usize,
+    measurement: StringBuilder,
+    tags: StringBuilder,
+    field: StringBuilder,
+    time: Int64Builder,
+    float: Float64Builder,
+    integer: Int64Builder,
+    unsigned: UInt64Builder,
+    boolean: BooleanBuilder,
+    string: StringBuilder,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    fn new(writer: W) -> Result<Self> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("measurement", ArrowDataType::Utf8, false),
+            Field::new("tags", ArrowDataType::Utf8, false),
+            Field::new("field", ArrowDataType::Utf8, false),
+            Field::new("time", ArrowDataType::Int64, false),
+            Field::new("float", ArrowDataType::Float64, true),
+            Field::new("integer", ArrowDataType::Int64, true),
+            Field::new("unsigned", ArrowDataType::UInt64, true),
+            Field::new("boolean", ArrowDataType::Boolean, true),
+            Field::new("string", ArrowDataType::Utf8, true),
+        ]));
+        let writer =
+            ArrowWriter::try_new(writer, schema.clone(), None).map_err(|e| Error::Export {
+                reason: e.to_string(),
+            })?;
+        Ok(Self {
+            writer: Some(writer),
+            schema,
+            rows: 0,
+            measurement: StringBuilder::new(),
+            tags: StringBuilder::new(),
+            field: StringBuilder::new(),
+            time: Int64Builder::new(),
+            float: Float64Builder::new(),
+            integer: Int64Builder::new(),
+            unsigned: UInt64Builder::new(),
+            boolean: BooleanBuilder::new(),
+            string: StringBuilder::new(),
+        })
+    }
+
+    fn write_batch(&mut self) -> Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.measurement.finish()),
+            Arc::new(self.tags.finish()),
+            Arc::new(self.field.finish()),
+            Arc::new(self.time.finish()),
+            Arc::new(self.float.finish()),
+            Arc::new(self.integer.finish()),
+            Arc::new(self.unsigned.finish()),
+            Arc::new(self.boolean.finish()),
+            Arc::new(self.string.finish()),
+        ];
+        self.rows = 0;
+        let batch =
+            RecordBatch::try_new(self.schema.clone(), columns).map_err(|e| Error::Export {
+                reason: e.to_string(),
+            })?;
+        if let Some(writer) = self.writer.as_mut() {
+            writer.write(&batch).map_err(|e| Error::Export {
+                reason: e.to_string(),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write + Send> RowWriter for ParquetWriter<W> {
+    fn write_row(&mut self, name: &FieldName, value: &DataType) -> Result<()> {
+        self.measurement.append_value(&name.table);
+        self.tags.append_value(tags_string(&name.tags));
+        self.field.append_value(&name.field);
+        self.time.append_value(value.timestamp());
+        let (mut float, mut integer, mut unsigned, mut boolean, mut string) =
+            (None, None, None, None, None);
+        match value {
+            DataType::F64(_, v) => float = Some(*v),
+            DataType::I64(_, v) => integer = Some(*v),
+            DataType::U64(_, v) => unsigned = Some(*v),
+            DataType::Bool(_, v) => boolean = Some(*v),
+            DataType::Str(_, v) => string = Some(String::from_utf8_lossy(v).to_string()),
+        }
+        self.float.append_option(float);
+        self.integer.append_option(integer);
+        self.unsigned.append_option(unsigned);
+        self.boolean.append_option(boolean);
+        self.string.append_option(string);
+
+        self.rows += 1;
+        if self.rows >= PARQUET_BATCH_SIZE {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.write_batch()?;
+        if let Some(writer) = self.writer.take() {
+            writer.close().map_err(|e| Error::Export {
+                reason: e.to_string(),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Escapes `special` characters in `s` with backslashes.
+fn escape(s: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn tags_string(tags: &[(String, String)]) -> String {
+    tags.iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Quotes a CSV field if it contains delimiters, quotes or line breaks.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+
+    use config::get_config;
+    use datafusion::parquet::file::reader::{FileReader, SerializedFileReader};
+    use minivec::MiniVec;
+    use models::utils::unite_id;
+
+    use super::{default_index_dir, dump_tsm, DumpFormat};
+    use crate::kv_option::StorageOptions;
+    use crate::tseries_family::TimeRange;
+    use crate::tsm::{codec::DataBlockEncoding, DataBlock, TsmTombstone, TsmWriter};
+
+    #[test]
+    fn test_dump_tsm() {
+        let dir = Path::new("/tmp/test/dump");
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir_all(dir).unwrap();
+        let tsm_path = dir.join("_000001.tsm");
+        let mut writer = TsmWriter::open(&tsm_path, 1, false, 0).unwrap();
+        #[rustfmt::skip]
+        let blocks = [
+            (unite_id(0, 1), DataBlock::F64 { ts: vec![1, 2, 3], val: vec![1.5, 2.0, 3.5], enc: DataBlockEncoding::default() }),
+            (unite_id(1, 1), DataBlock::I64 { ts: vec![1, 2, 3], val: vec![-1, 2, 3], enc: DataBlockEncoding::default() }),
+            (unite_id(2, 2), DataBlock::Str { ts: vec![2, 3], val: vec![MiniVec::from("a \"b\"".as_bytes()), MiniVec::from("c,d".as_bytes())], enc: DataBlockEncoding::default() }),
+        ];
+        for (fid, blk) in blocks.iter() {
+            writer.write_block(*fid, blk).unwrap();
+        }
+        writer.write_index().unwrap();
+        writer.finish().unwrap();
+        let mut tombstone = TsmTombstone::open_for_write(dir, 1).unwrap();
+        tombstone
+            .add_range(&[unite_id(0, 1)], &TimeRange::new(2, 2))
+            .unwrap();
+        tombstone.flush().unwrap();
+
+        let dump = |format: DumpFormat, time_range: &TimeRange| {
+            let mut buf = vec![];
+            let rows = dump_tsm(&tsm_path, None, format, time_range, &mut buf).unwrap();
+            (rows, buf)
+        };
+
+        let (rows, buf) = dump(DumpFormat::LineProtocol, &TimeRange::all());
+        assert_eq!(rows, 7);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "series_1 field_0=1.5 1\n\
+             series_1 field_0=3.5 3\n\
+             series_1 field_1=-1i 1\n\
+             series_1 field_1=2i 2\n\
+             series_1 field_1=3i 3\n\
+             series_2 field_2=\"a \\\"b\\\"\" 2\n\
+             series_2 field_2=\"c,d\" 3\n"
+        );
+
+        let (rows, buf) = dump(DumpFormat::Csv, &TimeRange::new(3, 10));
+        assert_eq!(rows, 3);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "measurement,tags,field,time,value\n\
+             series_1,,field_0,3,3.5\n\
+             series_1,,field_1,3,3\n\
+             series_2,,field_2,3,\"c,d\"\n"
+        );
+
+        let parquet_path = dir.join("dump.parquet");
+        let (rows, buf) = dump(DumpFormat::Parquet, &TimeRange::all());
+        assert_eq!(rows, 7);
+        std::fs::write(&parquet_path, buf).unwrap();
+        let reader =
+            SerializedFileReader::new(std::fs::File::open(&parquet_path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 7);
+
+        assert_eq!(
+            default_index_dir(Path::new("/cnosdb/data/db/tsm/1/_000001.tsm"), None),
+            Some(Path::new("/cnosdb/index/db").to_path_buf())
+        );
+        assert_eq!(default_index_dir(&tsm_path, None), None);
+
+        // TSM files under root paths of levels use the index in the storage path.
+        let mut storage = StorageOptions::from(&get_config("../config/config.toml"));
+        storage.path = PathBuf::from("/cnosdb");
+        storage.level_paths = BTreeMap::from([(2, PathBuf::from("/cnosdb_cold"))]);
+        for tsm_path in [
+            "/cnosdb/data/db/tsm/1/_000001.tsm",
+            "/cnosdb_cold/data/db/tsm/1/_000001.tsm",
+        ] {
+            assert_eq!(
+                default_index_dir(Path::new(tsm_path), Some(&storage)),
+                Some(Path::new("/cnosdb/index/db").to_path_buf())
+            );
+        }
+        assert_eq!(
+            default_index_dir(
+                Path::new("/other/data/db/tsm/1/_000001.tsm"),
+                Some(&storage)
+            ),
+            None
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::backup::BackupManifest;
 use crate::error::Result;
 use crate::index::IndexResult;
 use crate::tseries_family::SuperVersion;
@@ -61,7 +62,38 @@ pub trait Engine: Send + Sync + Debug {
     ) -> IndexResult<Vec<u64>>;
     fn get_series_id_list(&self, db: &str, tab: &str, tags: &[Tag]) -> IndexResult<Vec<u64>>;
     fn get_series_key(&self, db: &str, sid: SeriesId) -> IndexResult<Option<SeriesKey>>;
-    fn get_db_version(&self, db: &str) -> Result<Option<Arc<SuperVersion>>>;
+    /// Returns the number of series in the table, or in the database if `tab` is None.
+    /// It's estimated unless `exact` is set.
+    fn get_series_cardinality(&self, db: &str, tab: Option<&str>, exact: bool) -> Result<u64>;
+    /// Returns the number of distinct values of the tag in the table, or in all tables
+    /// of the database if `tab` is None. It's estimated unless `exact` is set.
+    fn get_tag_values_cardinality(
+        &self,
+        db: &str,
+        tab: Option<&str>,
+        tag_key: &str,
+        exact: bool,
+    ) -> Result<u64>;
+    /// Returns super versions of vnodes in the database that overlap `time_ranges`.
+    fn get_db_versions(
+        &self,
+        db: &str,
+        time_ranges: &[TimeRange],
+    ) -> Result<Vec<Arc<SuperVersion>>>;
+
+    /// Copies files of the database into directory `path`. Caches are flushed for
+    /// a full backup, if `since` is set, only copies column files added since backup
+    /// `since` and entries of the database in WAL.
+    async fn backup_database(
+        &self,
+        database: &str,
+        path: &str,
+        since: Option<&str>,
+    ) -> Result<BackupManifest>;
+
+    /// Rebuilds the database from the backup in directory `path` and its parent
+    /// backups, the database must not exist.
+    async fn restore_database(&self, database: &str, path: &str) -> Result<BackupManifest>;
 }
 
 #[derive(Debug, Default)]
@@ -160,7 +192,46 @@ impl Engine for MockEngine {
         Ok(None)
     }
 
-    fn get_db_version(&self, db: &str) -> Result<Option<Arc<SuperVersion>>> {
+    fn get_series_cardinality(&self, db: &str, tab: Option<&str>, exact: bool) -> Result<u64> {
+        Ok(0)
+    }
+
+    fn get_tag_values_cardinality(
+        &self,
+        db: &str,
+        tab: Option<&str>,
+        tag_key: &str,
+        exact: bool,
+    ) -> Result<u64> {
+        Ok(0)
+    }
+
+    fn get_db_versions(
+        &self,
+        db: &str,
+        time_ranges: &[TimeRange],
+    ) -> Result<Vec<Arc<SuperVersion>>> {
         todo!()
     }
+
+    async fn backup_database(
+        &self,
+        database: &str,
+        path: &str,
+        since: Option<&str>,
+    ) -> Result<BackupManifest> {
+        Ok(BackupManifest::new(
+            path.to_string(),
+            DatabaseSchema::new(database),
+            since.map(|s| s.to_string()),
+        ))
+    }
+
+    async fn restore_database(&self, database: &str, path: &str) -> Result<BackupManifest> {
+        Ok(BackupManifest::new(
+            path.to_string(),
+            DatabaseSchema::new(database),
+            None,
+        ))
+    }
 }
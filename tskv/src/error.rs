@@ -1,11 +1,12 @@
 use std::path::{Path, PathBuf};
 
+use models::error_code::ErrorCode;
 use models::SeriesId;
 use snafu::Snafu;
 
 use crate::{
     tsm::{ReadTsmError, WriteTsmError},
-    wal,
+    wal, TseriesFamilyId,
 };
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -53,6 +54,9 @@ pub enum Error {
     #[snafu(display("wal truncated"))]
     WalTruncated,
 
+    #[snafu(display("wal corrupted: checksum mismatch at position {}", pos))]
+    WalCorrupted { pos: u64 },
+
     #[snafu(display("read record file block: {}", source))]
     LogRecordErr {
         source: crate::record_file::RecordFileError,
@@ -88,6 +92,12 @@ pub enum Error {
     #[snafu(display("database '{}' already exists", database))]
     DatabaseAlreadyExists { database: String },
 
+    #[snafu(display("invalid backup '{}': {}", path.display(), reason))]
+    InvalidBackup { path: PathBuf, reason: String },
+
+    #[snafu(display("export data error: {}", reason))]
+    Export { reason: String },
+
     #[snafu(display("invalid model: {}", source))]
     InvalidModel { source: models::Error },
 
@@ -114,4 +124,40 @@ pub enum Error {
 
     #[snafu(display("table not found for {}", table_name))]
     NotFoundTable { table_name: String },
+
+    #[snafu(display("{} points of new series are rejected: {}", points, source))]
+    SeriesLimitExceeded {
+        points: usize,
+        source: crate::index::IndexError,
+    },
+
+    #[snafu(display(
+        "write buffer is full, {} of {} bytes are used, retry later",
+        usage,
+        limit
+    ))]
+    WriteBufferFull { usage: u64, limit: u64 },
+
+    #[snafu(display(
+        "writes to ts_family {} are stopped, {} files in level 0 reached the limit {}, retry later",
+        ts_family,
+        files,
+        limit
+    ))]
+    WriteStalled {
+        ts_family: TseriesFamilyId,
+        files: u32,
+        limit: u32,
+    },
+}
+
+impl Error {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Error::SeriesLimitExceeded { .. } => ErrorCode::TskvSeriesLimitExceeded,
+            Error::WriteBufferFull { .. } => ErrorCode::TskvWriteBufferFull,
+            Error::WriteStalled { .. } => ErrorCode::TskvWriteStalled,
+            _ => ErrorCode::TskvUnknown,
+        }
+    }
 }
@@ -6,11 +6,18 @@ use std::{
 };
 
 use crate::file_system::file::*;
+use crate::file_system::remote::RemoteFile;
+
+enum ScopeFile {
+    Local(Arc<StdFile>),
+    /// Offloaded file, which is read-only.
+    Remote(Arc<RemoteFile>),
+}
 
 pub struct FileScope {
     scope_map: Weak<ScopeMap>,
     id: FileId,
-    file: Option<Arc<StdFile>>,
+    file: Option<ScopeFile>,
     len: AtomicU64,
 }
 
@@ -21,11 +28,32 @@ impl FileScope {
         file: StdFile,
         id: FileId,
         len: u64,
+    ) -> Result<ScopeHandle> {
+        Self::new_scope(cache, scope_map, ScopeFile::Local(Arc::new(file)), id, len)
+    }
+
+    /// Creates scope of an offloaded file, `id` is the id of it's stub file.
+    pub fn new_remote(
+        cache: &CacheHandle,
+        scope_map: &Arc<ScopeMap>,
+        file: RemoteFile,
+        id: FileId,
+    ) -> Result<ScopeHandle> {
+        let len = file.len();
+        Self::new_scope(cache, scope_map, ScopeFile::Remote(Arc::new(file)), id, len)
+    }
+
+    fn new_scope(
+        cache: &CacheHandle,
+        scope_map: &Arc<ScopeMap>,
+        file: ScopeFile,
+        id: FileId,
+        len: u64,
     ) -> Result<ScopeHandle> {
         let scope = Self {
             scope_map: Arc::downgrade(scope_map),
             id,
-            file: Some(Arc::new(file)),
+            file: Some(file),
             len: len.into(),
         };
 
@@ -61,17 +89,24 @@ impl FileScope {
     }
 
     pub fn sync_data(&self) -> Result<()> {
-        self.file().sync_data()
+        match self.file() {
+            ScopeFile::Local(file) => file.sync_data(),
+            ScopeFile::Remote(_) => Ok(()),
+        }
     }
 
     pub fn sync_all(&self) -> Result<()> {
-        self.file().sync_all()
+        match self.file() {
+            ScopeFile::Local(file) => file.sync_all(),
+            ScopeFile::Remote(_) => Ok(()),
+        }
     }
 
     pub fn sync_len(&self) -> Result<()> {
-        let file = self.file().clone();
-        let len = self.len();
-        Self::sync_len0(&file, len)
+        match self.file() {
+            ScopeFile::Local(file) => Self::sync_len0(file, self.len()),
+            ScopeFile::Remote(_) => Ok(()),
+        }
     }
 
     pub(crate) fn id(&self) -> FileId {
@@ -82,17 +117,18 @@ impl FileScope {
         file.set_len(len)
     }
 
-    fn file(&self) -> &Arc<StdFile> {
+    fn file(&self) -> &ScopeFile {
         self.file.as_ref().unwrap()
     }
 }
 
 impl Drop for FileScope {
     fn drop(&mut self) {
-        let file = self.file.take().unwrap();
-        let len = self.len();
-        // TODO log errors
-        let _ = Self::sync_len0(&file, len);
+        if let ScopeFile::Local(file) = self.file.take().unwrap() {
+            let len = self.len();
+            // TODO log errors
+            let _ = Self::sync_len0(&file, len);
+        }
 
         if let Some(scope_map) = self.scope_map.upgrade() {
             scope_map.remove(&self.id).unwrap();
@@ -104,7 +140,12 @@ impl Scope for FileScope {
     fn read(&self, id: PageId, buf: &mut [u8]) -> Result<()> {
         let (pos, len) = self.page_span(id, buf.len());
         if len > 0 {
-            let read = read_all_at(pos, len, buf, |pos, buf| read_at(self.file(), pos, buf))?;
+            let read = match self.file() {
+                ScopeFile::Local(file) => {
+                    read_all_at(pos, len, buf, |pos, buf| read_at(file, pos, buf))?
+                }
+                ScopeFile::Remote(file) => file.read_at(pos, &mut buf[..len])?,
+            };
             if read != buf.len() {
                 debug_assert!(read < buf.len());
                 let new_len = pos + read as u64;
@@ -116,8 +157,13 @@ impl Scope for FileScope {
 
     fn write(&self, id: PageId, buf: &[u8]) -> Result<()> {
         let pos = Self::page_pos(id, buf.len());
-        write_all_at(pos, buf, |pos, buf| write_at(self.file(), pos, buf))?;
-        Ok(())
+        match self.file() {
+            ScopeFile::Local(file) => write_all_at(pos, buf, |pos, buf| write_at(file, pos, buf)),
+            ScopeFile::Remote(_) => Err(std::io::Error::new(
+                ErrorKind::PermissionDenied,
+                "offloaded file is read-only",
+            )),
+        }
     }
 }
 
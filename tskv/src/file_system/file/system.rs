@@ -1,4 +1,5 @@
 use crate::file_system::file::*;
+use crate::file_system::remote::RemoteFile;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Options {
@@ -100,6 +101,25 @@ impl FileSystemCache {
         Ok(DmaFile::new(scope))
     }
 
+    /// Opens the file, it's opened as a read-only remote file if it's offloaded to
+    /// an object store, data of remote files are fetched lazily through the cache.
+    pub fn open_column(&self, path: impl AsRef<Path>) -> Result<DmaFile> {
+        let mut file = std::fs::File::open(&path)?;
+        let remote = match RemoteFile::open(&mut file)? {
+            Some(remote) => remote,
+            None => return self.open(path),
+        };
+        let (id, _) = FileId::of(&file)?;
+        if let Some(scope) = self.scope_map.get(&id) {
+            if let Some(scope) = scope.value().upgrade() {
+                return Ok(DmaFile::new(scope));
+            }
+        }
+
+        let scope = FileScope::new_remote(&self.cache, &self.scope_map, remote, id)?;
+        Ok(DmaFile::new(scope))
+    }
+
     pub fn open(&self, path: impl AsRef<Path>) -> Result<DmaFile> {
         self.open_with(path, OpenOptions::new().read(true).write(true))
     }
@@ -80,6 +80,15 @@ impl FileManager {
         })
     }
 
+    /// Opens the column file, which may be offloaded to an object store.
+    pub fn open_column_file(&self, path: impl AsRef<Path>) -> Result<file_system::DmaFile> {
+        self.file_system
+            .open_column(&path)
+            .context(error::OpenFileSnafu {
+                path: path.as_ref(),
+            })
+    }
+
     pub fn create_file(&self, path: impl AsRef<Path>) -> Result<file_system::DmaFile> {
         if let Some(p) = path.as_ref().parent() {
             if !try_exists(p) {
@@ -162,6 +171,11 @@ pub fn open_file(path: impl AsRef<Path>) -> Result<file_system::DmaFile> {
     get_file_manager().open_file(path)
 }
 
+#[inline(always)]
+pub fn open_column_file(path: impl AsRef<Path>) -> Result<file_system::DmaFile> {
+    get_file_manager().open_column_file(path)
+}
+
 #[inline(always)]
 pub fn create_file(path: impl AsRef<Path>) -> Result<file_system::DmaFile> {
     get_file_manager().create_file(path)
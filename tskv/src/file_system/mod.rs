@@ -6,6 +6,7 @@
 mod cache;
 mod file;
 pub mod file_manager;
+pub mod remote;
 
 pub use cache::PageId;
 pub use file::{
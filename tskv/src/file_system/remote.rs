@@ -0,0 +1,521 @@
+//! Files offloaded to an object store.
+//!
+//! An offloaded file is replaced by a local stub file, which has the location of the
+//! file in the object store and a copy of the tail of the file (the index and footer
+//! of tsm files), so only the other data is fetched from the object store when read.
+
+use std::{
+    cmp,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bytes::Bytes;
+use dashmap::DashMap;
+use futures::Future;
+use object_store::{
+    aws::AmazonS3Builder, local::LocalFileSystem, path::Path as ObjectPath, prefix::PrefixStore,
+    ObjectStore,
+};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::runtime::{Handle, Runtime, RuntimeFlavor};
+
+const REMOTE_FILE_MAGIC: [u8; 8] = *b"CNOSRMT1";
+const UPLOAD_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Object stores registered by url, stub files refer to object stores by url.
+static REMOTE_STORES: Lazy<DashMap<String, Arc<RemoteStore>>> = Lazy::new(DashMap::new);
+
+/// Requests to object stores run in this runtime, so that they can be waited by
+/// synchronous reads of the page cache.
+static REMOTE_RUNTIME: Lazy<Runtime> = Lazy::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .thread_name("remote-io")
+        .enable_all()
+        .build()
+        .expect("failed to build runtime of object stores")
+});
+
+fn other_error(e: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
+
+/// Runs the future in the runtime of object stores, and waits for the result.
+///
+/// Reads of the page cache may come from workers of a tokio runtime, the worker
+/// is handed over to other tasks while waiting if the runtime allows it.
+fn block_on<F>(f: F) -> io::Result<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let (tx, rx) = std::sync::mpsc::channel();
+    REMOTE_RUNTIME.spawn(async move {
+        let _ = tx.send(f.await);
+    });
+    let recv = || rx.recv().map_err(other_error);
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(recv)
+        }
+        _ => recv(),
+    }
+}
+
+/// Runs the future in the runtime of object stores.
+async fn spawn<F, T>(f: F) -> io::Result<T>
+where
+    F: Future<Output = io::Result<T>> + Send + 'static,
+    T: Send + 'static,
+{
+    REMOTE_RUNTIME.spawn(f).await.map_err(other_error)?
+}
+
+#[derive(Debug)]
+pub struct RemoteStore {
+    url: String,
+    store: Arc<dyn ObjectStore>,
+}
+
+impl RemoteStore {
+    pub fn new(url: impl Into<String>, store: Arc<dyn ObjectStore>) -> Self {
+        Self {
+            url: url.into(),
+            store,
+        }
+    }
+
+    /// Creates the object store by url, which is 's3://bucket[/prefix]' for
+    /// S3-compatible object stores, or 'file:///path' for local file systems.
+    pub fn open(
+        url: &str,
+        endpoint: &str,
+        region: &str,
+        access_key_id: &str,
+        secret_access_key: &str,
+    ) -> io::Result<Self> {
+        let store: Arc<dyn ObjectStore> = if let Some(bucket_path) = url.strip_prefix("s3://") {
+            let (bucket, prefix) = match bucket_path.split_once('/') {
+                Some((bucket, prefix)) => (bucket, prefix.trim_matches('/')),
+                None => (bucket_path, ""),
+            };
+            let mut builder = AmazonS3Builder::from_env().with_bucket_name(bucket);
+            if !endpoint.is_empty() {
+                builder = builder
+                    .with_endpoint(endpoint)
+                    .with_allow_http(endpoint.starts_with("http://"));
+            }
+            if !region.is_empty() {
+                builder = builder.with_region(region);
+            }
+            if !access_key_id.is_empty() {
+                builder = builder
+                    .with_access_key_id(access_key_id)
+                    .with_secret_access_key(secret_access_key);
+            }
+            let s3 = builder.build().map_err(other_error)?;
+            if prefix.is_empty() {
+                Arc::new(s3)
+            } else {
+                Arc::new(PrefixStore::new(s3, prefix))
+            }
+        } else if let Some(path) = url.strip_prefix("file://") {
+            fs::create_dir_all(path)?;
+            Arc::new(LocalFileSystem::new_with_prefix(path).map_err(other_error)?)
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported object store url '{}'", url),
+            ));
+        };
+        Ok(Self::new(url, store))
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Uploads the file at `path` as `key` by a multipart upload, the file is
+    /// streamed instead of being read into memory.
+    pub async fn put_file(&self, key: &str, path: impl AsRef<Path>) -> io::Result<()> {
+        let store = self.store.clone();
+        let location = ObjectPath::from(key);
+        let path = path.as_ref().to_path_buf();
+        spawn(async move {
+            let (upload_id, mut writer) =
+                store.put_multipart(&location).await.map_err(other_error)?;
+            let ret = async {
+                let file = tokio::fs::File::open(&path).await?;
+                let mut reader = BufReader::with_capacity(UPLOAD_BUFFER_SIZE, file);
+                tokio::io::copy_buf(&mut reader, &mut writer).await?;
+                writer.shutdown().await
+            }
+            .await;
+            if ret.is_err() {
+                let _ = store.abort_multipart(&location, &upload_id).await;
+            }
+            ret
+        })
+        .await
+    }
+
+    /// Deletes the object.
+    async fn delete_async(&self, key: &str) -> io::Result<()> {
+        let store = self.store.clone();
+        let location = ObjectPath::from(key);
+        spawn(async move { store.delete(&location).await.map_err(other_error) }).await
+    }
+
+    /// Deletes the object, waits until it's deleted.
+    pub fn delete(&self, key: &str) -> io::Result<()> {
+        let store = self.store.clone();
+        let location = ObjectPath::from(key);
+        block_on(async move { store.delete(&location).await })?.map_err(other_error)
+    }
+
+    /// Reads the range of the object, waits until it's read.
+    pub fn get_range(&self, key: &str, range: Range<usize>) -> io::Result<Bytes> {
+        let store = self.store.clone();
+        let location = ObjectPath::from(key);
+        block_on(async move { store.get_range(&location, range).await })?.map_err(other_error)
+    }
+}
+
+/// Registers the object store, so that files offloaded to it can be opened.
+pub fn register_remote_store(store: RemoteStore) -> Arc<RemoteStore> {
+    let store = Arc::new(store);
+    REMOTE_STORES.insert(store.url().to_string(), store.clone());
+    store
+}
+
+pub fn get_remote_store(url: &str) -> Option<Arc<RemoteStore>> {
+    REMOTE_STORES.get(url).map(|s| s.value().clone())
+}
+
+/// Location of an offloaded file, it's the header of the stub file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemoteFileMeta {
+    pub url: String,
+    pub key: String,
+    pub len: u64,
+    /// Data from this offset to the end of the file is in the stub file.
+    pub tail_offset: u64,
+}
+
+impl RemoteFileMeta {
+    /// Reads the header of the file, returns `None` if it's not a stub file.
+    pub fn read(file: &mut File) -> io::Result<Option<Self>> {
+        let mut magic = [0_u8; 8];
+        match file.read_exact(&mut magic) {
+            Ok(_) if magic == REMOTE_FILE_MAGIC => {}
+            Ok(_) => return Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let mut len = [0_u8; 4];
+        file.read_exact(&mut len)?;
+        let mut buf = vec![0_u8; u32::from_be_bytes(len) as usize];
+        file.read_exact(&mut buf)?;
+        let meta = bincode::deserialize(&buf).map_err(other_error)?;
+        Ok(Some(meta))
+    }
+
+    pub fn read_path(path: impl AsRef<Path>) -> io::Result<Option<Self>> {
+        Self::read(&mut File::open(path)?)
+    }
+}
+
+/// An offloaded file, reads data from the object store except for the tail.
+pub struct RemoteFile {
+    meta: RemoteFileMeta,
+    store: Arc<RemoteStore>,
+    tail: Vec<u8>,
+}
+
+impl RemoteFile {
+    /// Reads the stub file, returns `None` if it's not a stub file.
+    pub fn open(file: &mut File) -> io::Result<Option<Self>> {
+        let meta = match RemoteFileMeta::read(file)? {
+            Some(meta) => meta,
+            None => return Ok(None),
+        };
+        let store = get_remote_store(&meta.url).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("object store '{}' is not configured", meta.url),
+            )
+        })?;
+        let mut tail = Vec::with_capacity((meta.len - meta.tail_offset) as usize);
+        file.read_to_end(&mut tail)?;
+        if tail.len() as u64 != meta.len - meta.tail_offset {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stub file of offloaded file is truncated",
+            ));
+        }
+        Ok(Some(Self { meta, store, tail }))
+    }
+
+    pub fn len(&self) -> u64 {
+        self.meta.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meta.len == 0
+    }
+
+    pub fn read_at(&self, pos: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let end = cmp::min(pos + buf.len() as u64, self.meta.len);
+        if pos >= end {
+            return Ok(0);
+        }
+        let tail_offset = self.meta.tail_offset;
+        if pos < tail_offset {
+            let remote_end = cmp::min(end, tail_offset);
+            let data = self
+                .store
+                .get_range(&self.meta.key, pos as usize..remote_end as usize)?;
+            buf[..data.len()].copy_from_slice(&data);
+        }
+        if end > tail_offset {
+            let start = cmp::max(pos, tail_offset);
+            let data = &self.tail[(start - tail_offset) as usize..(end - tail_offset) as usize];
+            let offset = (start - pos) as usize;
+            buf[offset..offset + data.len()].copy_from_slice(data);
+        }
+        Ok((end - pos) as usize)
+    }
+}
+
+/// A file uploaded to the object store, of which the stub file is written but
+/// doesn't replace the file yet.
+pub struct OffloadedFile {
+    store: Arc<RemoteStore>,
+    key: String,
+    path: PathBuf,
+    stub_path: PathBuf,
+}
+
+impl OffloadedFile {
+    /// Replaces the file with the stub file.
+    pub fn commit(&self) -> io::Result<()> {
+        fs::rename(&self.stub_path, &self.path)
+    }
+
+    /// Removes the stub file and deletes the uploaded object, the file is kept.
+    pub async fn abort(self) -> io::Result<()> {
+        if let Err(e) = fs::remove_file(&self.stub_path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                return Err(e);
+            }
+        }
+        self.store.delete_async(&self.key).await
+    }
+}
+
+/// Uploads the file into the object store as `key`, and writes a stub file which
+/// keeps data from `tail_offset` to the end of the file. The file is replaced
+/// with the stub file by `OffloadedFile::commit()`.
+pub async fn offload_file(
+    store: &Arc<RemoteStore>,
+    path: impl AsRef<Path>,
+    key: &str,
+    tail_offset: u64,
+) -> io::Result<OffloadedFile> {
+    let path = path.as_ref();
+    let len = fs::metadata(path)?.len();
+    if tail_offset > len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("tail offset {} exceeds file length {}", tail_offset, len),
+        ));
+    }
+    store.put_file(key, path).await?;
+
+    let meta = RemoteFileMeta {
+        url: store.url().to_string(),
+        key: key.to_string(),
+        len,
+        tail_offset,
+    };
+    let offloaded = OffloadedFile {
+        store: store.clone(),
+        key: key.to_string(),
+        path: path.to_path_buf(),
+        stub_path: stub_tmp_path(path),
+    };
+    match write_stub_file(&offloaded.stub_path, path, &meta) {
+        Ok(()) => Ok(offloaded),
+        Err(e) => {
+            let _ = offloaded.abort().await;
+            Err(e)
+        }
+    }
+}
+
+fn write_stub_file(stub_path: &Path, path: &Path, meta: &RemoteFileMeta) -> io::Result<()> {
+    let meta_buf = bincode::serialize(meta).map_err(other_error)?;
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(meta.tail_offset))?;
+    let mut stub = File::create(stub_path)?;
+    stub.write_all(&REMOTE_FILE_MAGIC)?;
+    stub.write_all(&(meta_buf.len() as u32).to_be_bytes())?;
+    stub.write_all(&meta_buf)?;
+    let tail_len = io::copy(&mut file, &mut stub)?;
+    if tail_len != meta.len - meta.tail_offset {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "file is changed while being offloaded",
+        ));
+    }
+    stub.sync_all()
+}
+
+/// Deletes the object of the file if it's offloaded, returns true if it's deleted.
+pub fn delete_remote_object(path: impl AsRef<Path>) -> io::Result<bool> {
+    let meta = match RemoteFileMeta::read_path(path) {
+        Ok(Some(meta)) => meta,
+        Ok(None) => return Ok(false),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    let store = get_remote_store(&meta.url).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("object store '{}' is not configured", meta.url),
+        )
+    })?;
+    store.delete(&meta.key)?;
+    Ok(true)
+}
+
+/// Copies the file, data of an offloaded file is fetched from the object store, so
+/// the copy doesn't share the object with the file. Returns true if it's offloaded.
+pub fn copy_file(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<bool> {
+    let remote = match RemoteFile::open(&mut File::open(&src)?)? {
+        Some(remote) => remote,
+        None => {
+            fs::copy(src, dst)?;
+            return Ok(false);
+        }
+    };
+    let mut file = File::create(dst)?;
+    let mut buf = vec![0_u8; UPLOAD_BUFFER_SIZE];
+    let mut pos = 0;
+    while pos < remote.len() {
+        let len = remote.read_at(pos, &mut buf)?;
+        file.write_all(&buf[..len])?;
+        pos += len as u64;
+    }
+    file.sync_all()?;
+    Ok(true)
+}
+
+pub fn is_remote_file(path: impl AsRef<Path>) -> bool {
+    matches!(RemoteFileMeta::read_path(path), Ok(Some(_)))
+}
+
+fn stub_tmp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".offload");
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use object_store::memory::InMemory;
+
+    use super::{
+        copy_file, delete_remote_object, is_remote_file, offload_file, register_remote_store,
+        RemoteFile, RemoteStore,
+    };
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_offload_file() {
+        let dir = "/tmp/test/remote/offload_file";
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir_all(dir).unwrap();
+        let store = register_remote_store(RemoteStore::new(
+            "memory://test_offload_file",
+            Arc::new(InMemory::new()),
+        ));
+
+        let path = format!("{}/_000001.tsm", dir);
+        let data: Vec<u8> = (0..10000_u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+        assert!(!is_remote_file(&path));
+        let offloaded = offload_file(&store, &path, "db/1/_000001.tsm", 9000)
+            .await
+            .unwrap();
+        assert!(!is_remote_file(&path));
+        offloaded.commit().unwrap();
+        assert!(is_remote_file(&path));
+        assert!(std::fs::metadata(&path).unwrap().len() < 2000);
+
+        let mut stub = std::fs::File::open(&path).unwrap();
+        let remote = RemoteFile::open(&mut stub).unwrap().unwrap();
+        assert_eq!(remote.len(), 10000);
+        // Ranges in the object store, in the stub file, and across both.
+        for (pos, len) in [(0, 100), (9500, 100), (8990, 20), (9990, 100)] {
+            let mut buf = vec![0_u8; len];
+            let read = remote.read_at(pos as u64, &mut buf).unwrap();
+            let end = (pos + len).min(data.len());
+            assert_eq!(read, end - pos);
+            assert_eq!(&buf[..read], &data[pos..end]);
+        }
+
+        assert!(delete_remote_object(&path).unwrap());
+        let mut buf = vec![0_u8; 10];
+        assert!(remote.read_at(0, &mut buf).is_err());
+
+        // Aborted offloading keeps the file and deletes the object.
+        let path = format!("{}/_000002.tsm", dir);
+        std::fs::write(&path, &data).unwrap();
+        let offloaded = offload_file(&store, &path, "db/1/_000002.tsm", 9000)
+            .await
+            .unwrap();
+        assert!(store.get_range("db/1/_000002.tsm", 0..10).is_ok());
+        offloaded.abort().await.unwrap();
+        assert!(!is_remote_file(&path));
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        assert!(store.get_range("db/1/_000002.tsm", 0..10).is_err());
+        assert_eq!(std::fs::read_dir(dir).unwrap().count(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_copy_offloaded_file() {
+        let dir = "/tmp/test/remote/copy_offloaded_file";
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir_all(dir).unwrap();
+        let store = register_remote_store(RemoteStore::new(
+            "memory://test_copy_offloaded_file",
+            Arc::new(InMemory::new()),
+        ));
+
+        let path = format!("{}/_000001.tsm", dir);
+        let data: Vec<u8> = (0..3_000_000_u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+        let copy_path = format!("{}/_000002.tsm", dir);
+        assert!(!copy_file(&path, &copy_path).unwrap());
+        assert_eq!(std::fs::read(&copy_path).unwrap(), data);
+
+        let offloaded = offload_file(&store, &path, "db/1/_000001.tsm", 2_999_000)
+            .await
+            .unwrap();
+        offloaded.commit().unwrap();
+        let copy_path = format!("{}/_000003.tsm", dir);
+        assert!(copy_file(&path, &copy_path).unwrap());
+        assert!(!is_remote_file(&copy_path));
+        // The copy is still readable after the object is deleted.
+        assert!(delete_remote_object(&path).unwrap());
+        assert_eq!(std::fs::read(&copy_path).unwrap(), data);
+    }
+}
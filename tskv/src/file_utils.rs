@@ -26,6 +26,11 @@ pub fn make_summary_file_tmp(path: impl AsRef<Path>) -> PathBuf {
     path.as_ref().join(p)
 }
 
+pub fn make_summary_file_bak(path: impl AsRef<Path>) -> PathBuf {
+    let p = "summary.bak".to_string();
+    path.as_ref().join(p)
+}
+
 pub fn check_summary_file_name(file_name: &str) -> bool {
     SUMMARY_FILE_NAME_PATTERN.is_match(file_name)
 }
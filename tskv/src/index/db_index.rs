@@ -14,7 +14,8 @@ use datafusion::scalar::ScalarValue;
 use lazy_static::__Deref;
 use models::predicate::domain::{utf8_from, Domain, Marker, Range, ValueEntry};
 use once_cell::sync::OnceCell;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
 use sled::Error;
 use snafu::ResultExt;
 
@@ -23,14 +24,20 @@ use config::Config;
 use datafusion::arrow::datatypes::{DataType, ToByteSlice};
 use libc::read;
 use models::codec::Encoding;
-use models::schema::{ColumnType, DatabaseSchema, TableColumn, TableSchema, TskvTableSchema};
+use models::schema::{
+    ColumnType, DatabaseOptions, DatabaseSchema, Duration, Precision, TableColumn, TableSchema,
+    TskvTableSchema,
+};
 use models::{
     tag::TagFromParts, utils, ColumnId, FieldId, FieldInfo, SeriesId, SeriesKey, Tag, ValueType,
 };
 use protos::models::Point;
 use trace::{debug, error, info, warn};
 
-use super::utils::{decode_series_id_list, encode_inverted_index_key, encode_series_id_list};
+use super::utils::{
+    decode_inverted_index_tag_value, decode_series_id_list, encode_inverted_index_key,
+    encode_inverted_index_prefix, encode_series_id_list,
+};
 use super::*;
 use super::{errors, IndexEngine, IndexError, IndexResult};
 
@@ -38,6 +45,8 @@ const SERIES_KEY_PREFIX: &str = "_series_key_";
 const TABLE_SCHEMA_PREFIX: &str = "_table_schema_";
 const TIME_STAMP_NAME: &str = "time";
 const DATABASE_SCHEMA_PREFIX: &str = "_database_schema_";
+const SERIES_HLL_PREFIX: &str = "_series_hll_";
+const TAG_VALUES_HLL_PREFIX: &str = "_tag_values_hll_";
 
 #[derive(Debug, Clone)]
 pub struct IndexConfig {
@@ -82,11 +91,43 @@ impl DbIndexMgr {
         index.clone()
     }
 
+    /// Returns the directory of index of the database.
+    pub fn index_dir(&self, db_name: &str) -> PathBuf {
+        self.base_path.join(db_name)
+    }
+
     pub fn remove_db_index(&mut self, db_name: &str) {
         self.indexs.remove(db_name);
     }
 }
 
+/// Max numbers of series in a database and in a table, 0 means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SeriesLimits {
+    pub max_series_per_database: u64,
+    pub max_series_per_table: u64,
+}
+
+impl SeriesLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_series_per_database == 0 && self.max_series_per_table == 0
+    }
+}
+
+/// Numbers of series in a database and in each table of it.
+#[derive(Debug, Default)]
+struct SeriesCount {
+    database: u64,
+    tables: HashMap<String, u64>,
+}
+
+/// A cardinality sketch cached in memory, `dirty` if it's changed since it was stored.
+#[derive(Debug)]
+struct CachedSketch {
+    sketch: HyperLogLog,
+    dirty: bool,
+}
+
 #[derive(Debug)]
 pub struct DBIndex {
     path: PathBuf,
@@ -96,6 +137,14 @@ pub struct DBIndex {
     series_cache: RwLock<HashMap<u32, Vec<SeriesKey>>>,
     // TableName -> TableSchema
     table_schema: RwLock<HashMap<String, TableSchema>>,
+    // Loaded from storage when the first series is added with limits
+    series_count: RwLock<Option<SeriesCount>>,
+    // Storage key -> cardinality sketch of series of a table or values of a tag,
+    // changed sketches are stored by `flush()`
+    sketches: RwLock<HashMap<String, CachedSketch>>,
+    // Held when storing or deleting sketches, so that a sketch being stored won't
+    // be deleted in the meantime.
+    sketches_storing: Mutex<()>,
 }
 
 impl DBIndex {
@@ -109,7 +158,7 @@ impl DBIndex {
                     store_db_schema(&key, &db_schema, &storage);
                     db_schema
                 }
-                Some(v) => match bincode::deserialize::<DatabaseSchema>(&v) {
+                Some(v) => match decode_db_schema(&v) {
                     Ok(v) => v,
                     Err(e) => {
                         error!(
@@ -130,6 +179,9 @@ impl DBIndex {
             db_schema: schema,
             series_cache: RwLock::new(HashMap::new()),
             table_schema: RwLock::new(HashMap::new()),
+            series_count: RwLock::new(None),
+            sketches: RwLock::new(HashMap::new()),
+            sketches_storing: Mutex::new(()),
             path: path.into(),
         }
     }
@@ -147,7 +199,11 @@ impl DBIndex {
         Ok(None)
     }
 
-    pub fn add_series_if_not_exists(&self, info: &Point) -> IndexResult<u64> {
+    /// Adds the series of the point if it's not exists, returns the series id.
+    ///
+    /// Returns `IndexError::SeriesLimitExceeded` if the series is new and the number of
+    /// series in the database or the table reached `limits`.
+    pub fn add_series_if_not_exists(&self, info: &Point, limits: SeriesLimits) -> IndexResult<u64> {
         let mut series_key = SeriesKey::from_flatbuffer(info).map_err(|e| IndexError::FieldType)?;
 
         let (hash_id, _) = utils::split_id(series_key.hash());
@@ -173,20 +229,260 @@ impl DBIndex {
             return Ok(k.id());
         }
         //if not exist add it!
+        let mut series_count = self.series_count.write();
+        if series_count.is_none() && !limits.is_unlimited() {
+            *series_count = Some(self.load_series_count()?);
+        }
+        if let Some(count) = series_count.as_ref() {
+            let table_count = count.tables.get(series_key.table()).copied().unwrap_or(0);
+            if limits.max_series_per_database > 0
+                && count.database >= limits.max_series_per_database
+            {
+                return Err(IndexError::SeriesLimitExceeded {
+                    scope: format!("database '{}'", self.db_schema.name),
+                    limit: limits.max_series_per_database,
+                });
+            }
+            if limits.max_series_per_table > 0 && table_count >= limits.max_series_per_table {
+                return Err(IndexError::SeriesLimitExceeded {
+                    scope: format!(
+                        "table '{}' of database '{}'",
+                        series_key.table(),
+                        self.db_schema.name
+                    ),
+                    limit: limits.max_series_per_table,
+                });
+            }
+        }
+
         let id = utils::unite_id(hash_id as u64, self.storage.incr_id()?);
         series_key.set_id(id);
         keys.push(series_key.clone());
-        self.storage
-            .set(stroage_key.as_bytes(), &bincode::serialize(&keys).unwrap())?;
+        if let Err(e) = self
+            .storage
+            .set(stroage_key.as_bytes(), &bincode::serialize(&keys).unwrap())
+        {
+            keys.pop();
+            return Err(e.into());
+        }
+        // Counted after the series is stored, a failed insertion won't take the quota.
+        if let Some(count) = series_count.as_mut() {
+            count.database += 1;
+            *count.tables.entry(series_key.table().clone()).or_insert(0) += 1;
+        }
+        drop(series_count);
         drop(series_cache);
 
         for tag in series_key.tags() {
             let key = encode_inverted_index_key(series_key.table(), &tag.key, &tag.value);
             self.storage.push(&key, id.to_be_bytes().as_ref())?;
         }
+        self.update_sketches(&series_key, id)?;
         Ok(id)
     }
 
+    /// Adds the new series into the sketches of its table and tags, the sketches
+    /// are stored by the next `flush()`.
+    fn update_sketches(&self, series_key: &SeriesKey, sid: SeriesId) -> IndexResult<()> {
+        let tab = series_key.table();
+        let mut sketches = self.sketches.write();
+
+        let key = series_sketch_key(tab);
+        let cached = self.get_sketch(&mut sketches, &key, || self.build_series_sketch(tab))?;
+        if cached.sketch.insert(&sid.to_be_bytes()) {
+            cached.dirty = true;
+        }
+
+        for tag in series_key.tags() {
+            let key = tag_values_sketch_key(tab, &tag.key);
+            let cached = self.get_sketch(&mut sketches, &key, || {
+                self.build_tag_values_sketch(tab, &tag.key)
+            })?;
+            if cached.sketch.insert(&tag.value) {
+                cached.dirty = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads the sketch from storage if it's not cached, the sketch is built by
+    /// `build` if it's not stored either, e.g. the index is created by an old version.
+    fn get_sketch<'a>(
+        &self,
+        sketches: &'a mut HashMap<String, CachedSketch>,
+        key: &str,
+        build: impl FnOnce() -> IndexResult<HyperLogLog>,
+    ) -> IndexResult<&'a mut CachedSketch> {
+        if !sketches.contains_key(key) {
+            let cached = match self.storage.get(key.as_bytes())? {
+                Some(data) => CachedSketch {
+                    sketch: HyperLogLog::decode(&data).ok_or(IndexError::IndexStroage {
+                        msg: format!("decode sketch '{}' failed", key),
+                    })?,
+                    dirty: false,
+                },
+                None => CachedSketch {
+                    sketch: build()?,
+                    dirty: true,
+                },
+            };
+            sketches.insert(key.to_string(), cached);
+        }
+        Ok(sketches.get_mut(key).unwrap())
+    }
+
+    /// Stores the sketches changed since they were stored, the sketches lock is only
+    /// held to copy them.
+    fn store_sketches(&self) -> IndexResult<()> {
+        let _storing = self.sketches_storing.lock();
+        let changed: Vec<(String, Vec<u8>)> = self
+            .sketches
+            .write()
+            .iter_mut()
+            .filter(|(_, cached)| cached.dirty)
+            .map(|(key, cached)| {
+                cached.dirty = false;
+                (key.clone(), cached.sketch.encode())
+            })
+            .collect();
+        for (i, (key, data)) in changed.iter().enumerate() {
+            if let Err(e) = self.storage.set(key.as_bytes(), data) {
+                // Store the remaining sketches next time.
+                let mut sketches = self.sketches.write();
+                for (key, _) in changed[i..].iter() {
+                    if let Some(cached) = sketches.get_mut(key) {
+                        cached.dirty = true;
+                    }
+                }
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+
+    fn build_series_sketch(&self, tab: &str) -> IndexResult<HyperLogLog> {
+        let mut sketch = HyperLogLog::new();
+        for sid in self.get_series_id_list(tab, &[])? {
+            sketch.insert(&sid.to_be_bytes());
+        }
+        Ok(sketch)
+    }
+
+    fn build_tag_values_sketch(&self, tab: &str, tag_key: &[u8]) -> IndexResult<HyperLogLog> {
+        let mut sketch = HyperLogLog::new();
+        for value in self.get_tag_values(tab, tag_key)? {
+            sketch.insert(&value);
+        }
+        Ok(sketch)
+    }
+
+    /// Returns values of the tag which still have series.
+    fn get_tag_values(&self, tab: &str, tag_key: &[u8]) -> IndexResult<Vec<Vec<u8>>> {
+        let mut values = vec![];
+        let prefix = encode_inverted_index_prefix(tab, tag_key);
+        for kv in self.storage.prefix(&prefix) {
+            let (idx_key, sid_list) = kv?;
+            if sid_list.is_empty() {
+                continue;
+            }
+            if let Some(value) = decode_inverted_index_tag_value(&idx_key, &prefix) {
+                values.push(value);
+            }
+        }
+        Ok(values)
+    }
+
+    /// Returns names of tskv tables stored in the index.
+    fn get_tskv_tables(&self) -> IndexResult<Vec<String>> {
+        let mut tables = vec![];
+        for kv in self.storage.prefix(TABLE_SCHEMA_PREFIX.as_bytes()) {
+            let (key, value) = kv?;
+            if let Ok(TableSchema::TsKvTableSchema(schema)) =
+                serde_json::from_slice::<TableSchema>(&value)
+            {
+                tables.push(schema.name);
+            } else {
+                warn!(
+                    "skip table schema '{}' of non-tskv table or failed to decode",
+                    String::from_utf8_lossy(&key)
+                );
+            }
+        }
+        Ok(tables)
+    }
+
+    /// Returns the number of series in the table, or in the database if `tab` is None.
+    ///
+    /// The number is estimated by HyperLogLog sketches, which also count deleted series,
+    /// if `exact` is set, series in the index are counted instead.
+    pub fn series_cardinality(&self, tab: Option<&str>, exact: bool) -> IndexResult<u64> {
+        if exact {
+            let count = self.load_series_count()?;
+            return Ok(match tab {
+                Some(tab) => count.tables.get(tab).copied().unwrap_or(0),
+                None => count.database,
+            });
+        }
+
+        let tables = match tab {
+            Some(tab) => vec![tab.to_string()],
+            None => self.get_tskv_tables()?,
+        };
+        let mut sketches = self.sketches.write();
+        let mut merged = HyperLogLog::new();
+        for tab in tables.iter() {
+            let key = series_sketch_key(tab);
+            let cached = self.get_sketch(&mut sketches, &key, || self.build_series_sketch(tab))?;
+            merged.merge(&cached.sketch);
+        }
+        Ok(merged.estimate())
+    }
+
+    /// Returns the number of distinct values of the tag in the table, or in all tables
+    /// of the database if `tab` is None.
+    ///
+    /// The number is estimated by HyperLogLog sketches, which also count values of
+    /// deleted series, if `exact` is set, values in the index are counted instead.
+    pub fn tag_values_cardinality(
+        &self,
+        tab: Option<&str>,
+        tag_key: &str,
+        exact: bool,
+    ) -> IndexResult<u64> {
+        let tables = match tab {
+            Some(tab) => vec![tab.to_string()],
+            None => self.get_tskv_tables()?,
+        };
+        // Skip tables without the tag, so that no empty sketch is stored for them.
+        let mut tables_with_tag = Vec::with_capacity(tables.len());
+        for tab in tables {
+            if let Some(TableSchema::TsKvTableSchema(schema)) = self.get_table_schema(&tab)? {
+                if matches!(schema.column(tag_key), Some(c) if c.column_type == ColumnType::Tag) {
+                    tables_with_tag.push(tab);
+                }
+            }
+        }
+
+        if exact {
+            let mut values = HashSet::new();
+            for tab in tables_with_tag.iter() {
+                values.extend(self.get_tag_values(tab, tag_key.as_bytes())?);
+            }
+            return Ok(values.len() as u64);
+        }
+
+        let mut sketches = self.sketches.write();
+        let mut merged = HyperLogLog::new();
+        for tab in tables_with_tag.iter() {
+            let key = tag_values_sketch_key(tab, tag_key.as_bytes());
+            let cached = self.get_sketch(&mut sketches, &key, || {
+                self.build_tag_values_sketch(tab, tag_key.as_bytes())
+            })?;
+            merged.merge(&cached.sketch);
+        }
+        Ok(merged.estimate())
+    }
+
     pub fn check_field_type_from_cache(&self, series_id: u64, info: &Point) -> IndexResult<()> {
         let table_name = unsafe { String::from_utf8_unchecked(info.tab().unwrap().to_vec()) };
         if let Some(schema) = self.table_schema.read().get(&table_name) {
@@ -248,7 +544,7 @@ impl DBIndex {
                 schema.db = db_name;
                 let key = format!("{}{}", TABLE_SCHEMA_PREFIX, table_name);
                 if let Some(data) = self.storage.get(key.as_bytes())? {
-                    if let Ok(list) = bincode::deserialize(&data) {
+                    if let Ok(list) = serde_json::from_slice(&data) {
                         fields.insert(table_name.clone(), list);
                         schema = match fields
                             .get_mut(&table_name)
@@ -384,6 +680,23 @@ impl DBIndex {
     }
 
     pub fn del_table_schema(&self, tab: &str) -> IndexResult<()> {
+        let mut sketch_keys = vec![series_sketch_key(tab)];
+        if let Some(TableSchema::TsKvTableSchema(schema)) = self.get_table_schema(tab)? {
+            for column in schema.columns() {
+                if column.column_type == ColumnType::Tag {
+                    sketch_keys.push(tag_values_sketch_key(tab, column.name.as_bytes()));
+                }
+            }
+        }
+        let storing = self.sketches_storing.lock();
+        let mut sketches = self.sketches.write();
+        for key in sketch_keys {
+            sketches.remove(&key);
+            self.storage.delete(key.as_bytes())?;
+        }
+        drop(sketches);
+        drop(storing);
+
         self.table_schema.write().remove(tab);
 
         let key = format!("{}{}", TABLE_SCHEMA_PREFIX, tab);
@@ -393,10 +706,18 @@ impl DBIndex {
     }
 
     pub fn flush(&self) -> IndexResult<()> {
+        self.store_sketches()?;
         self.storage.flush();
         Ok(())
     }
 
+    /// Flushes the index and calls `f` with the index directory, writes to the index
+    /// are blocked until `f` returns.
+    pub fn with_writes_blocked<R>(&self, f: impl FnOnce(&Path) -> R) -> IndexResult<R> {
+        self.store_sketches()?;
+        Ok(self.storage.with_writes_blocked(f)?)
+    }
+
     pub fn get_series_key(&self, sid: u64) -> IndexResult<Option<SeriesKey>> {
         let (hash_id, _) = utils::split_id(sid);
         let stroage_key = format!("{}{}", SERIES_KEY_PREFIX, hash_id);
@@ -428,6 +749,39 @@ impl DBIndex {
         }
     }
 
+    /// Calls `f` with every series key stored in the index.
+    fn for_each_series_key(&self, mut f: impl FnMut(&SeriesKey)) -> IndexResult<()> {
+        for kv in self.storage.prefix(SERIES_KEY_PREFIX.as_bytes()) {
+            let kv = kv.map_err(|e| IndexError::IndexStroage {
+                msg: format!("scan prefix: {}", e),
+            })?;
+            let keys = bincode::deserialize::<Vec<SeriesKey>>(&kv.1).map_err(|_| {
+                IndexError::IndexStroage {
+                    msg: "deserialize failed".to_string(),
+                }
+            })?;
+            keys.iter().for_each(&mut f);
+        }
+        Ok(())
+    }
+
+    /// Counts series stored in the index.
+    fn load_series_count(&self) -> IndexResult<SeriesCount> {
+        let mut count = SeriesCount::default();
+        self.for_each_series_key(|key| {
+            count.database += 1;
+            *count.tables.entry(key.table().clone()).or_default() += 1;
+        })?;
+        Ok(count)
+    }
+
+    /// Returns ids of all series stored in the index.
+    pub fn get_all_series_ids(&self) -> IndexResult<Vec<SeriesId>> {
+        let mut series_ids = vec![];
+        self.for_each_series_key(|key| series_ids.push(key.id()))?;
+        Ok(series_ids)
+    }
+
     pub fn del_series_info(&self, sid: u64) -> IndexResult<()> {
         let (hash_id, _) = utils::split_id(sid);
         self.series_cache.write().remove(&hash_id);
@@ -451,6 +805,13 @@ impl DBIndex {
                         }
                     }
 
+                    if let Some(count) = self.series_count.write().as_mut() {
+                        count.database = count.database.saturating_sub(1);
+                        if let Some(c) = count.tables.get_mut(key.table()) {
+                            *c = c.saturating_sub(1);
+                        }
+                    }
+
                     let keys: Vec<&SeriesKey> = keys.iter().filter(|k| k.id() != sid).collect();
                     self.storage
                         .set(stroage_key.as_bytes(), &bincode::serialize(&keys).unwrap())?;
@@ -583,6 +944,31 @@ impl DBIndex {
                     series_ids = self.get_series_id_list(tab, &[])?;
                 }
             }
+            Domain::Pattern(pattern_set) => {
+                let matcher = match pattern_set.matcher() {
+                    Ok(m) => m,
+                    Err(e) => {
+                        // Patterns are checked when building the domain, take it as not filtered
+                        warn!("invalid tag pattern of '{}': {}", tag_key, e);
+                        return self.get_series_id_list(tab, &[]);
+                    }
+                };
+                // Iterate all values of the tag once, and save sids of values matching the patterns
+                let prefix = encode_inverted_index_prefix(tab, tag_key.as_bytes());
+                for kv in self.storage.prefix(&prefix) {
+                    let (idx_key, ori_sid_list) = kv?;
+                    let is_match = decode_inverted_index_tag_value(&idx_key, &prefix)
+                        .and_then(|v| String::from_utf8(v).ok())
+                        .map(|v| matcher.is_match(&v))
+                        .unwrap_or(false);
+                    if is_match {
+                        let sid_list = decode_series_id_list(&ori_sid_list)?;
+                        series_ids = utils::or_u64(&series_ids, &sid_list);
+                    }
+                }
+
+                debug!("pattern scan series_ids[{}]: {:?}", tag_key, series_ids);
+            }
             Domain::None => {
                 // Normally, it will not go here unless no judgment is made at the ColumnDomains level
                 // If you go here, you will directly return an empty series, because the tag condition in the map is' and '
@@ -603,6 +989,63 @@ impl DBIndex {
     }
 }
 
+fn series_sketch_key(tab: &str) -> String {
+    format!("{}{}", SERIES_HLL_PREFIX, tab)
+}
+
+fn tag_values_sketch_key(tab: &str, tag_key: &[u8]) -> String {
+    format!(
+        "{}{}.{}",
+        TAG_VALUES_HLL_PREFIX,
+        tab,
+        String::from_utf8_lossy(tag_key)
+    )
+}
+
+/// Layout of `DatabaseSchema` stored before series limits were added to the options.
+#[derive(Serialize, Deserialize)]
+struct DatabaseSchemaV1 {
+    name: String,
+    config: DatabaseOptionsV1,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DatabaseOptionsV1 {
+    ttl: Duration,
+    shard_num: u64,
+    vnode_duration: Duration,
+    replica: u64,
+    precision: Precision,
+}
+
+impl From<DatabaseSchemaV1> for DatabaseSchema {
+    fn from(schema: DatabaseSchemaV1) -> Self {
+        let options = schema.config;
+        DatabaseSchema {
+            name: schema.name,
+            config: DatabaseOptions {
+                ttl: options.ttl,
+                shard_num: options.shard_num,
+                vnode_duration: options.vnode_duration,
+                replica: options.replica,
+                precision: options.precision,
+                max_series_per_database: None,
+                max_series_per_table: None,
+            },
+        }
+    }
+}
+
+/// Decodes the stored `DatabaseSchema`, schema in the layout before series limits
+/// is decoded with the limits unset.
+///
+/// A schema in the old layout is shorter than the current one, so it always fails to
+/// be decoded in the current layout.
+fn decode_db_schema(data: &[u8]) -> bincode::Result<DatabaseSchema> {
+    bincode::deserialize::<DatabaseSchema>(data)
+        .or_else(|_| bincode::deserialize::<DatabaseSchemaV1>(data).map(DatabaseSchema::from))
+}
+
 fn store_db_schema(key: &str, db_schema: &DatabaseSchema, storage: &IndexEngine) {
     match bincode::serialize(db_schema) {
         Ok(v) => match storage.set(key.as_bytes(), &v) {
@@ -655,7 +1098,145 @@ pub fn tag_value_to_index_key(tab: &str, tag_key: &str, v: &ScalarValue) -> Vec<
 #[cfg(test)]
 mod test {
     use datafusion::arrow::datatypes::{DataType, Field, Schema};
-    use models::schema::ExternalTableSchema;
+    use models::codec::Encoding;
+    use models::schema::{
+        ColumnType, DatabaseSchema, Duration, DurationUnit, ExternalTableSchema, Precision,
+        TableColumn, TableSchema, TskvTableSchema,
+    };
+    use models::ValueType;
+    use protos::{models as fb_models, models_helper};
+
+    use super::{
+        series_sketch_key, tag_values_sketch_key, DBIndex, DatabaseOptionsV1, DatabaseSchemaV1,
+        HyperLogLog, IndexEngine, IndexError, SeriesLimits, DATABASE_SCHEMA_PREFIX,
+    };
+
+    #[test]
+    fn test_decode_v1_db_schema() {
+        let dir = "/tmp/test/index/v1_db_schema";
+        let _ = std::fs::remove_dir_all(dir);
+        let duration = |time_num| Duration {
+            time_num,
+            unit: DurationUnit::Day,
+        };
+        let schema_v1 = DatabaseSchemaV1 {
+            name: "db_v1".to_string(),
+            config: DatabaseOptionsV1 {
+                ttl: duration(30),
+                shard_num: 3,
+                vnode_duration: duration(7),
+                replica: 2,
+                precision: Precision::MS,
+            },
+        };
+        let key = format!("{}{}", DATABASE_SCHEMA_PREFIX, schema_v1.name);
+        let storage = IndexEngine::new(dir);
+        storage
+            .set(key.as_bytes(), &bincode::serialize(&schema_v1).unwrap())
+            .unwrap();
+        storage.flush();
+        drop(storage);
+
+        let index = DBIndex::new(dir, DatabaseSchema::new("db_v1"));
+        let schema = index.db_schema();
+        assert_eq!(schema.config.ttl, duration(30));
+        assert_eq!(schema.config.shard_num, 3);
+        assert_eq!(schema.config.vnode_duration, duration(7));
+        assert_eq!(schema.config.replica, 2);
+        assert_eq!(schema.config.precision, Precision::MS);
+        assert_eq!(schema.config.max_series_per_database, None);
+        assert_eq!(schema.config.max_series_per_table, None);
+    }
+
+    #[test]
+    fn test_check_field_type_with_stored_schema() {
+        let dir = "/tmp/test/index/stored_table_schema";
+        let _ = std::fs::remove_dir_all(dir);
+        let database = "db_stored_table_schema".to_string();
+        let index = DBIndex::new(dir, DatabaseSchema::new(&database));
+        let integer = ColumnType::Field(ValueType::Integer);
+        let float = ColumnType::Field(ValueType::Float);
+        let schema = TskvTableSchema::new(
+            database.clone(),
+            "cpu".to_string(),
+            vec![
+                TableColumn::new_time_column(0),
+                TableColumn::new_tag_column(1, "region".to_string()),
+                TableColumn::new_tag_column(2, "host".to_string()),
+                TableColumn::new(3, "cpu".to_string(), integer, Encoding::Default),
+                TableColumn::new(4, "mem".to_string(), float, Encoding::Default),
+            ],
+        );
+        index
+            .create_table(&TableSchema::TsKvTableSchema(schema))
+            .unwrap();
+        drop(index);
+
+        // The schema is not cached after reopening, it must be loaded from storage.
+        let index = DBIndex::new(dir, DatabaseSchema::new(&database));
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let points = models_helper::create_dev_ops_points(&mut fbb, 1, &database, "cpu");
+        fbb.finish(points, None);
+        let points = flatbuffers::root::<fb_models::Points>(fbb.finished_data()).unwrap();
+        let point = points.points().unwrap().get(0);
+        assert!(matches!(
+            index.check_field_type_or_else_add(0, &point),
+            Err(IndexError::FieldType)
+        ));
+    }
+
+    #[test]
+    fn test_store_sketches() {
+        let dir = "/tmp/test/index/store_sketches";
+        let _ = std::fs::remove_dir_all(dir);
+        let database = "db_store_sketches".to_string();
+        let index = DBIndex::new(dir, DatabaseSchema::new(&database));
+        let add_series = |index: &DBIndex, num: usize| {
+            let mut fbb = flatbuffers::FlatBufferBuilder::new();
+            let points = models_helper::create_dev_ops_points(&mut fbb, num, &database, "cpu");
+            fbb.finish(points, None);
+            let points = flatbuffers::root::<fb_models::Points>(fbb.finished_data()).unwrap();
+            for point in points.points().unwrap() {
+                index
+                    .add_series_if_not_exists(&point, SeriesLimits::default())
+                    .unwrap();
+            }
+        };
+        let stored_estimate = |index: &DBIndex, key: &str| {
+            index
+                .storage
+                .get(key.as_bytes())
+                .unwrap()
+                .map(|data| HyperLogLog::decode(&data).unwrap().estimate())
+        };
+        let (series_key, host_key) = (
+            series_sketch_key("cpu"),
+            tag_values_sketch_key("cpu", b"host"),
+        );
+
+        // Sketches are changed in memory, and stored when the index is flushed.
+        add_series(&index, 3);
+        assert_eq!(index.series_cardinality(Some("cpu"), false).unwrap(), 3);
+        assert_eq!(stored_estimate(&index, &series_key), None);
+        assert_eq!(stored_estimate(&index, &host_key), None);
+        index.flush().unwrap();
+        assert_eq!(stored_estimate(&index, &series_key), Some(3));
+        assert_eq!(stored_estimate(&index, &host_key), Some(3));
+
+        add_series(&index, 12);
+        assert_eq!(stored_estimate(&index, &series_key), Some(3));
+        index.flush().unwrap();
+        drop(index);
+
+        let index = DBIndex::new(dir, DatabaseSchema::new(&database));
+        assert_eq!(stored_estimate(&index, &series_key), Some(12));
+        assert_eq!(stored_estimate(&index, &host_key), Some(6));
+        assert_eq!(index.series_cardinality(Some("cpu"), false).unwrap(), 12);
+        assert_eq!(
+            stored_estimate(&index, &tag_values_sketch_key("cpu", b"region")),
+            Some(2)
+        );
+    }
 
     #[test]
     fn test_serde() {
@@ -4,12 +4,15 @@ use std::ops::Range;
 use std::ops::RangeBounds;
 use std::path::{Path, PathBuf};
 
+use parking_lot::RwLock;
 use sled;
 
 #[derive(Debug)]
 pub struct IndexEngine {
     db: sled::Db,
     dir: PathBuf,
+    /// Shared by writes, held exclusively while files of the index are read directly.
+    write_barrier: RwLock<()>,
 }
 
 impl IndexEngine {
@@ -30,10 +33,12 @@ impl IndexEngine {
         Self {
             db,
             dir: index_dir.into(),
+            write_barrier: RwLock::new(()),
         }
     }
 
     pub fn set(&self, key: &[u8], value: &[u8]) -> Result<(), sled::Error> {
+        let _barrier = self.write_barrier.read();
         self.db.insert(key, value)?;
 
         Ok(())
@@ -66,12 +71,14 @@ impl IndexEngine {
     }
 
     pub fn push(&self, key: &[u8], item: &[u8]) -> Result<(), sled::Error> {
+        let _barrier = self.write_barrier.read();
         self.db.merge(key, item)?;
 
         Ok(())
     }
 
     pub fn delete(&self, key: &[u8]) -> Result<(), sled::Error> {
+        let _barrier = self.write_barrier.read();
         self.db.remove(key)?;
 
         Ok(())
@@ -88,12 +95,14 @@ impl IndexEngine {
     }
 
     pub fn batch(&self, batch: sled::Batch) -> Result<(), sled::Error> {
+        let _barrier = self.write_barrier.read();
         self.db.apply_batch(batch)?;
 
         Ok(())
     }
 
     pub fn incr_id(&self) -> Result<u64, sled::Error> {
+        let _barrier = self.write_barrier.read();
         let id = self.db.generate_id()?;
 
         Ok(id)
@@ -103,6 +112,14 @@ impl IndexEngine {
         //todo:
         self.db.flush().unwrap();
     }
+
+    /// Flushes the index and calls `f` with the index directory, writes are blocked
+    /// until `f` returns, so that files in the directory are consistent.
+    pub fn with_writes_blocked<R>(&self, f: impl FnOnce(&Path) -> R) -> Result<R, sled::Error> {
+        let _barrier = self.write_barrier.write();
+        self.db.flush()?;
+        Ok(f(&self.dir))
+    }
 }
 
 fn concatenate_merge(
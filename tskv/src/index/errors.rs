@@ -46,6 +46,9 @@ pub enum IndexError {
 
     #[snafu(display("table '{}' not found", table))]
     TableNotFound { table: String },
+
+    #[snafu(display("number of series in {} reached the limit {}", scope, limit))]
+    SeriesLimitExceeded { scope: String, limit: u64 },
 }
 
 impl From<sled::Error> for IndexError {
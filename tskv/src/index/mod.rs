@@ -2,6 +2,7 @@ pub(crate) mod db_index;
 
 mod engine;
 mod errors;
+mod hll;
 mod tests;
 pub mod utils;
 
@@ -9,3 +10,4 @@ pub use ::utils::*;
 pub use db_index::*;
 pub use engine::*;
 pub use errors::*;
+pub use hll::*;
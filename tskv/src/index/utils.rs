@@ -16,6 +16,22 @@ pub fn encode_inverted_index_key(tab: &str, tag_key: &[u8], tag_val: &[u8]) -> V
     buf
 }
 
+/// Returns the prefix of inverted index keys of all values of the tag.
+pub fn encode_inverted_index_prefix(tab: &str, tag_key: &[u8]) -> Vec<u8> {
+    format!("{}.{:?}=", tab, tag_key).into_bytes()
+}
+
+/// Returns the tag value in an inverted index key starts with `prefix`,
+/// which is returned by `encode_inverted_index_prefix`.
+pub fn decode_inverted_index_tag_value(key: &[u8], prefix: &[u8]) -> Option<Vec<u8>> {
+    let tag_val = std::str::from_utf8(key.strip_prefix(prefix)?).ok()?;
+    let tag_val = tag_val.strip_prefix('[')?.strip_suffix(']')?;
+    if tag_val.is_empty() {
+        return Some(vec![]);
+    }
+    tag_val.split(", ").map(|b| b.parse::<u8>().ok()).collect()
+}
+
 pub fn decode_series_id_list(data: &[u8]) -> IndexResult<Vec<u64>> {
     if data.len() % 8 != 0 {
         return Err(IndexError::DecodeSeriesIDList);
@@ -1,17 +1,29 @@
 #![allow(dead_code)]
 
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use config::Config;
 use serde::{Deserialize, Serialize};
+use trace::error;
 
-use crate::{file_system, index::IndexConfig, summary};
+use crate::{
+    compaction::CompactionPickerType,
+    file_system, file_utils,
+    index::IndexConfig,
+    summary::{self, CompactMeta},
+    wal::WalRecoveryMode,
+    LevelId,
+};
 
-const SUMMARY_PATH: &str = "summary";
-const INDEX_PATH: &str = "index";
-const DATA_PATH: &str = "data";
-const TSM_PATH: &str = "tsm";
-const DELTA_PATH: &str = "delta";
+pub(crate) const SUMMARY_PATH: &str = "summary";
+pub(crate) const INDEX_PATH: &str = "index";
+pub(crate) const DATA_PATH: &str = "data";
+pub(crate) const TSM_PATH: &str = "tsm";
+pub(crate) const DELTA_PATH: &str = "delta";
 
 #[derive(Debug, Clone)]
 pub struct Options {
@@ -19,6 +31,7 @@ pub struct Options {
     pub wal: Arc<WalOptions>,
     pub cache: Arc<CacheOptions>,
     pub query: Arc<QueryOptions>,
+    pub object_store: Arc<ObjectStoreOptions>,
 }
 
 impl From<&Config> for Options {
@@ -28,6 +41,7 @@ impl From<&Config> for Options {
             wal: Arc::new(WalOptions::from(config)),
             cache: Arc::new(CacheOptions::from(config)),
             query: Arc::new(QueryOptions::from(config)),
+            object_store: Arc::new(ObjectStoreOptions::from(config)),
         }
     }
 }
@@ -44,6 +58,18 @@ pub struct StorageOptions {
     pub dio_max_non_resident: usize,
     pub dio_page_len_scale: usize,
     pub strict_write: bool,
+    pub retention_check_interval: u64,
+    pub compaction_picker: CompactionPickerType,
+    pub compaction_time_window: u64,
+    pub tombstone_rewrite_percent: u32,
+    pub max_series_per_database: u64,
+    pub max_series_per_table: u64,
+    pub l0_slowdown_trigger: u32,
+    pub l0_stop_trigger: u32,
+    pub database_compaction_pickers: HashMap<String, CompactionPickerType>,
+    /// Root paths of column files of levels, a level without a path uses the path
+    /// of the closest lower level, or `path` if there is none.
+    pub level_paths: BTreeMap<LevelId, PathBuf>,
 }
 
 impl StorageOptions {
@@ -51,6 +77,14 @@ impl StorageOptions {
         self.base_file_size * lvl as u64 * self.compact_trigger as u64
     }
 
+    /// Returns the compaction picker type of the database.
+    pub fn compaction_picker(&self, database: &str) -> CompactionPickerType {
+        self.database_compaction_pickers
+            .get(database)
+            .copied()
+            .unwrap_or(self.compaction_picker)
+    }
+
     pub fn summary_dir(&self) -> PathBuf {
         self.path.join(SUMMARY_PATH)
     }
@@ -67,18 +101,92 @@ impl StorageOptions {
         self.path.join(DATA_PATH).join(database)
     }
 
+    /// Returns the root path of column files of the level.
+    pub fn level_path(&self, level: LevelId) -> &Path {
+        self.level_paths
+            .range(..=level)
+            .next_back()
+            .map(|(_, path)| path.as_path())
+            .unwrap_or(&self.path)
+    }
+
+    /// Returns all distinct root paths of column files, the first one is `path`.
+    pub fn data_paths(&self) -> Vec<&Path> {
+        let mut paths = vec![self.path.as_path()];
+        for path in self.level_paths.values() {
+            if !paths.contains(&path.as_path()) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+
+    /// Returns the directories of column files of the database under all root paths.
+    pub fn database_dirs(&self, database: &str) -> Vec<PathBuf> {
+        self.data_paths()
+            .into_iter()
+            .map(|path| path.join(DATA_PATH).join(database))
+            .collect()
+    }
+
     pub fn tsm_dir(&self, database: &str, ts_family_id: u32) -> PathBuf {
         self.database_dir(database)
             .join(TSM_PATH)
             .join(ts_family_id.to_string())
     }
 
+    /// Returns the directory of tsm files of the level.
+    pub fn level_tsm_dir(&self, database: &str, ts_family_id: u32, level: LevelId) -> PathBuf {
+        self.level_path(level)
+            .join(DATA_PATH)
+            .join(database)
+            .join(TSM_PATH)
+            .join(ts_family_id.to_string())
+    }
+
+    /// Returns the directory of delta files, which are always in level 0.
     pub fn delta_dir(&self, database: &str, ts_family_id: u32) -> PathBuf {
-        self.database_dir(database)
+        self.level_path(0)
+            .join(DATA_PATH)
+            .join(database)
             .join(DELTA_PATH)
             .join(ts_family_id.to_string())
     }
 
+    /// Returns the path of the column file, the file is under the root path of it's
+    /// level, or under other root paths if `level_paths` changed after it's written.
+    pub fn column_file_path(&self, database: &str, meta: &CompactMeta) -> PathBuf {
+        let (sub_dir, level) = if meta.is_delta {
+            (DELTA_PATH, 0)
+        } else {
+            (TSM_PATH, meta.level)
+        };
+        let file_path = |root: &Path| {
+            let dir = root
+                .join(DATA_PATH)
+                .join(database)
+                .join(sub_dir)
+                .join(meta.tsf_id.to_string());
+            if meta.is_delta {
+                file_utils::make_delta_file_name(dir, meta.file_id)
+            } else {
+                file_utils::make_tsm_file_name(dir, meta.file_id)
+            }
+        };
+
+        let level_path = self.level_path(level);
+        let path = file_path(level_path);
+        if self.level_paths.is_empty() || file_system::file_manager::try_exists(&path) {
+            return path;
+        }
+        self.data_paths()
+            .into_iter()
+            .filter(|root| *root != level_path)
+            .map(file_path)
+            .find(|p| file_system::file_manager::try_exists(p))
+            .unwrap_or(path)
+    }
+
     pub fn direct_io_options(&self) -> file_system::Options {
         let mut opt = file_system::Options::default();
         opt.max_resident(self.dio_max_resident)
@@ -88,6 +196,35 @@ impl StorageOptions {
     }
 }
 
+/// Options of the default configuration file, used by tools working on a storage
+/// directory without a configuration file.
+impl Default for StorageOptions {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("data/db"),
+            max_summary_size: 128 * 1024 * 1024,
+            max_level: 4,
+            base_file_size: 16 * 1024 * 1024,
+            compact_trigger: 4,
+            max_compact_size: 2 * 1024 * 1024 * 1024,
+            dio_max_resident: 1024,
+            dio_max_non_resident: 1024,
+            dio_page_len_scale: 10,
+            strict_write: false,
+            retention_check_interval: 3600,
+            compaction_picker: CompactionPickerType::Level,
+            compaction_time_window: 86400,
+            tombstone_rewrite_percent: 30,
+            max_series_per_database: 0,
+            max_series_per_table: 0,
+            l0_slowdown_trigger: 20,
+            l0_stop_trigger: 36,
+            database_compaction_pickers: HashMap::new(),
+            level_paths: BTreeMap::new(),
+        }
+    }
+}
+
 impl From<&Config> for StorageOptions {
     fn from(config: &Config) -> Self {
         Self {
@@ -101,6 +238,35 @@ impl From<&Config> for StorageOptions {
             dio_max_non_resident: config.storage.dio_max_non_resident,
             dio_page_len_scale: config.storage.dio_page_len_scale,
             strict_write: config.storage.strict_write,
+            retention_check_interval: config.storage.retention_check_interval,
+            compaction_picker: CompactionPickerType::parse(&config.storage.compaction_picker),
+            compaction_time_window: config.storage.compaction_time_window,
+            tombstone_rewrite_percent: config.storage.tombstone_rewrite_percent,
+            max_series_per_database: config.storage.max_series_per_database,
+            max_series_per_table: config.storage.max_series_per_table,
+            l0_slowdown_trigger: config.storage.l0_slowdown_trigger,
+            l0_stop_trigger: config.storage.l0_stop_trigger,
+            database_compaction_pickers: config
+                .storage
+                .database_compaction_pickers
+                .iter()
+                .map(|(db, picker)| (db.clone(), CompactionPickerType::parse(picker)))
+                .collect(),
+            level_paths: config
+                .storage
+                .level_paths
+                .iter()
+                .filter_map(|(level, path)| match level.parse::<LevelId>() {
+                    Ok(level) => Some((level, PathBuf::from(path))),
+                    Err(_) => {
+                        error!(
+                            "Unknown level '{}' of level path '{}', ignored.",
+                            level, path
+                        );
+                        None
+                    }
+                })
+                .collect(),
         }
     }
 }
@@ -123,6 +289,10 @@ pub struct WalOptions {
     pub enabled: bool,
     pub path: PathBuf,
     pub sync: bool,
+    pub sync_interval: u64,
+    pub sync_every_n_bytes: u64,
+    pub max_file_size: u64,
+    pub recovery_mode: WalRecoveryMode,
 }
 
 impl From<&Config> for WalOptions {
@@ -131,6 +301,10 @@ impl From<&Config> for WalOptions {
             enabled: config.wal.enabled,
             path: PathBuf::from(config.wal.path.clone()),
             sync: config.wal.sync,
+            sync_interval: config.wal.sync_interval,
+            sync_every_n_bytes: config.wal.sync_every_n_bytes,
+            max_file_size: config.wal.max_file_size,
+            recovery_mode: WalRecoveryMode::parse(&config.wal.recovery_mode),
         }
     }
 }
@@ -139,6 +313,8 @@ impl From<&Config> for WalOptions {
 pub struct CacheOptions {
     pub max_buffer_size: u64,
     pub max_immutable_number: u16,
+    pub max_total_buffer_size: u64,
+    pub write_stall_timeout: u64,
 }
 
 impl From<&Config> for CacheOptions {
@@ -146,6 +322,105 @@ impl From<&Config> for CacheOptions {
         Self {
             max_buffer_size: config.cache.max_buffer_size,
             max_immutable_number: config.cache.max_immutable_number,
+            max_total_buffer_size: config.cache.max_total_buffer_size,
+            write_stall_timeout: config.cache.write_stall_timeout,
         }
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectStoreOptions {
+    pub url: String,
+    pub endpoint: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub offload_age: u64,
+    pub offload_check_interval: u64,
+}
+
+impl From<&Config> for ObjectStoreOptions {
+    fn from(config: &Config) -> Self {
+        Self {
+            url: config.object_store.url.clone(),
+            endpoint: config.object_store.endpoint.clone(),
+            region: config.object_store.region.clone(),
+            access_key_id: config.object_store.access_key_id.clone(),
+            secret_access_key: config.object_store.secret_access_key.clone(),
+            offload_age: config.object_store.offload_age,
+            offload_check_interval: config.object_store.offload_check_interval,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use config::get_config;
+
+    use super::StorageOptions;
+    use crate::file_utils;
+    use crate::summary::CompactMeta;
+
+    #[test]
+    fn test_default_storage_options() {
+        let global_config = get_config("../config/config.toml");
+        assert_eq!(
+            StorageOptions::default(),
+            StorageOptions::from(&global_config)
+        );
+    }
+
+    #[test]
+    fn test_level_paths() {
+        let dir = "/tmp/test/kv_option/level_paths";
+        let _ = std::fs::remove_dir_all(dir);
+        let mut global_config = get_config("../config/config.toml");
+        global_config.storage.path = format!("{}/hot", dir);
+        global_config
+            .storage
+            .level_paths
+            .insert("2".to_string(), format!("{}/warm", dir));
+        global_config
+            .storage
+            .level_paths
+            .insert("4".to_string(), format!("{}/cold", dir));
+        global_config
+            .storage
+            .level_paths
+            .insert("x".to_string(), format!("{}/unknown", dir));
+        let opt = StorageOptions::from(&global_config);
+
+        let hot = PathBuf::from(format!("{}/hot", dir));
+        let warm = PathBuf::from(format!("{}/warm", dir));
+        let cold = PathBuf::from(format!("{}/cold", dir));
+        assert_eq!(opt.level_path(0), hot);
+        assert_eq!(opt.level_path(1), hot);
+        assert_eq!(opt.level_path(2), warm);
+        assert_eq!(opt.level_path(3), warm);
+        assert_eq!(opt.level_path(4), cold);
+        assert_eq!(opt.data_paths(), vec![&hot, &warm, &cold]);
+        assert!(opt.level_tsm_dir("db", 1, 3).starts_with(&warm));
+        assert!(opt.delta_dir("db", 1).starts_with(&hot));
+
+        // Files are resolved under the root path of their level.
+        let meta = CompactMeta::new(1, 100, 1, 3, 1, 100, false);
+        let warm_file = opt.column_file_path("db", &meta);
+        assert_eq!(
+            warm_file,
+            file_utils::make_tsm_file_name(opt.level_tsm_dir("db", 1, 3), 1)
+        );
+
+        // Files written before the level path changed are still found.
+        let cold_file = file_utils::make_tsm_file_name(opt.level_tsm_dir("db", 1, 4), 1);
+        std::fs::create_dir_all(cold_file.parent().unwrap()).unwrap();
+        std::fs::write(&cold_file, b"").unwrap();
+        assert_eq!(opt.column_file_path("db", &meta), cold_file);
+        let delta_meta = CompactMeta::new(2, 100, 1, 0, 1, 100, true);
+        assert_eq!(
+            opt.column_file_path("db", &delta_meta),
+            file_utils::make_delta_file_name(opt.delta_dir("db", 1), 2)
+        );
+    }
+}
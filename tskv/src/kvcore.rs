@@ -1,3 +1,4 @@
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use std::{collections::HashMap, panic, sync::Arc};
 
@@ -36,8 +37,10 @@ use protos::{
 };
 use trace::{debug, error, info, trace, warn};
 
+use crate::backup::{self, BackupManifest};
 use crate::database::Database;
 use crate::file_system::file_manager::{self, init_file_manager, FileManager};
+use crate::file_system::remote::{self, register_remote_store, RemoteStore};
 use crate::file_system::Options as FileOptions;
 use crate::index::index_manger;
 use crate::{
@@ -53,22 +56,32 @@ use crate::{
     record_file::Reader,
     summary,
     summary::{Summary, SummaryProcessor, SummaryTask, VersionEdit},
-    tseries_family::{SuperVersion, TimeRange, Version},
-    tsm::{DataBlock, TsmTombstone, MAX_BLOCK_VALUES},
+    tseries_family::{SuperVersion, TimeRange, TseriesFamily, Version, VnodeInfo, WriteStallState},
+    tsm::{self, DataBlock, TsmTombstone, MAX_BLOCK_VALUES},
     version_set,
     version_set::VersionSet,
     wal::{self, WalEntryType, WalManager, WalTask},
+    write_buffer::WriteBufferManager,
     Error, Task, TseriesFamilyId,
 };
 
+/// Writes are delayed by a step for each file in level 0 over the slowdown trigger.
+const WRITE_STALL_DELAY: Duration = Duration::from_millis(10);
+const WRITE_STALL_MAX_STEPS: u32 = 100;
+
 #[derive(Debug)]
 pub struct TsKv {
     options: Arc<Options>,
     global_ctx: Arc<GlobalContext>,
     version_set: Arc<RwLock<VersionSet>>,
+    write_buffer: Arc<WriteBufferManager>,
+    remote_store: Option<Arc<RemoteStore>>,
 
     runtime: Arc<Runtime>,
     wal_sender: UnboundedSender<WalTask>,
+    /// Shared by a write from sending its data to WAL until the data is in caches, held
+    /// exclusively when computing the WAL checkpoint.
+    wal_write_lock: Arc<tokio::sync::RwLock<()>>,
     flush_task_sender: UnboundedSender<FlushReq>,
     compact_task_sender: UnboundedSender<TseriesFamilyId>,
     summary_task_sender: UnboundedSender<SummaryTask>,
@@ -84,6 +97,22 @@ impl TsKv {
                 .max_non_resident(shared_options.storage.dio_max_non_resident)
                 .page_len_scale(shared_options.storage.dio_page_len_scale),
         );
+        // The object store is registered even if offloading is disabled, so that
+        // offloaded files can still be read.
+        let object_store_opt = shared_options.object_store.clone();
+        let remote_store = if object_store_opt.url.is_empty() {
+            None
+        } else {
+            let store = RemoteStore::open(
+                &object_store_opt.url,
+                &object_store_opt.endpoint,
+                &object_store_opt.region,
+                &object_store_opt.access_key_id,
+                &object_store_opt.secret_access_key,
+            )
+            .context(error::IOSnafu)?;
+            Some(register_remote_store(store))
+        };
         let (flush_task_sender, flush_task_receiver) = mpsc::unbounded_channel();
         let (compact_task_sender, compact_task_receiver) = mpsc::unbounded_channel();
         let (wal_sender, wal_receiver) = mpsc::unbounded_channel();
@@ -92,11 +121,15 @@ impl TsKv {
         let (version_set, summary) =
             Self::recover_summary(shared_options.clone(), flush_task_sender.clone()).await;
         let wal_cfg = shared_options.wal.clone();
+        let write_buffer = version_set.read().write_buffer();
         let core = Self {
             version_set,
+            write_buffer,
+            remote_store,
             global_ctx: summary.global_context(),
             runtime,
             wal_sender,
+            wal_write_lock: Arc::new(tokio::sync::RwLock::new(())),
             options: shared_options,
             flush_task_sender: flush_task_sender.clone(),
             compact_task_sender: compact_task_sender.clone(),
@@ -104,7 +137,7 @@ impl TsKv {
             close_sender,
         };
 
-        let wal_manager = core.recover_wal().await;
+        let wal_manager = core.recover_wal().await?;
         core.run_wal_job(wal_manager, wal_receiver);
         core.run_flush_job(
             flush_task_receiver,
@@ -120,6 +153,8 @@ impl TsKv {
             summary_task_sender.clone(),
         );
         core.run_summary_job(summary, summary_task_receiver);
+        core.run_retention_job();
+        core.run_offload_job();
         Ok(core)
     }
 
@@ -131,6 +166,11 @@ impl TsKv {
         while let Some(_x) = rx.recv().await {
             continue;
         }
+        for (name, db) in self.version_set.read().get_all_db() {
+            if let Err(e) = db.read().get_index().flush() {
+                error!("Failed to flush index of database '{}': {:?}", name, e);
+            }
+        }
         info!("TsKv closed");
     }
 
@@ -157,34 +197,50 @@ impl TsKv {
         (version_set, summary)
     }
 
-    async fn recover_wal(&self) -> WalManager {
-        let wal_manager = WalManager::new(self.options.wal.clone());
+    async fn recover_wal(&self) -> Result<WalManager> {
+        let mut wal_manager = WalManager::new(self.options.wal.clone());
 
-        wal_manager
-            .recover(self, self.global_ctx.clone())
-            .await
-            .unwrap();
+        wal_manager.recover(self).await?;
+        delete_obsolete_wal_files(&mut wal_manager, &self.version_set);
 
-        wal_manager
+        Ok(wal_manager)
     }
 
     fn run_wal_job(&self, mut wal_manager: WalManager, mut receiver: UnboundedReceiver<WalTask>) {
         warn!("job 'WAL' starting.");
+        let version_set = self.version_set.clone();
+        let wal_sender = self.wal_sender.clone();
+        let wal_write_lock = self.wal_write_lock.clone();
+        let sync_interval = self.options.wal.sync_interval;
         let mut close_receiver = self.close_sender.subscribe();
         let f = async move {
+            let mut current_file_id = wal_manager.current_file_id();
+            let mut sync_ticker =
+                tokio::time::interval(Duration::from_millis(sync_interval.max(1)));
             loop {
                 tokio::select! {
                     wal_task = receiver.recv() => {
                         match wal_task {
-                            Some(WalTask::Write { points, cb }) => {
-                                // write wal
-                                let ret = wal_manager.write(WalEntryType::Write, &points).await;
-                                let send_ret = cb.send(ret);
-                                match send_ret {
-                                    Ok(wal_result) => {}
-                                    Err(err) => {
-                                        warn!("send WAL write result failed.")
-                                    }
+                            Some(wal_task) => {
+                                // Write all the queued tasks in a batch.
+                                let mut tasks = vec![wal_task];
+                                while let Ok(task) = receiver.try_recv() {
+                                    tasks.push(task);
+                                }
+                                wal_manager.write_tasks(tasks).await;
+                                // Check for obsolete files when the WAL file is rolled. The
+                                // checkpoint waits for writes in progress, which may wait for
+                                // this task, so it is computed in another task.
+                                if wal_manager.current_file_id() != current_file_id {
+                                    current_file_id = wal_manager.current_file_id();
+                                    let max_seq = wal_manager.current_seq_no();
+                                    let version_set = version_set.clone();
+                                    let wal_sender = wal_sender.clone();
+                                    let wal_write_lock = wal_write_lock.clone();
+                                    tokio::spawn(async move {
+                                        let min_seq = wal_checkpoint(&wal_write_lock, &version_set, max_seq).await;
+                                        let _ = wal_sender.send(WalTask::DeleteObsoleteFiles { min_seq });
+                                    });
                                 }
                             }
                             _ => {
@@ -192,6 +248,11 @@ impl TsKv {
                             }
                         }
                     }
+                    _ = sync_ticker.tick(), if sync_interval > 0 => {
+                        if let Err(e) = wal_manager.sync() {
+                            error!("Failed to sync WAL: {:?}", e);
+                        }
+                    }
                     close_task = close_receiver.recv() => {
                         info!("job 'WAL' closing.");
                         if let Err(e) = wal_manager.close().await {
@@ -250,7 +311,10 @@ impl TsKv {
                     info!("Starting compaction on ts_family {}", ts_family_id);
                     let start = Instant::now();
                     let compact_req = tsf.read().pick_compaction();
-                    if let Some(req) = compact_req {
+                    if let Some(mut req) = compact_req {
+                        if let Some(db) = version_set.read().get_db(&req.database) {
+                            req.expired_ts = db.read().ttl_expired_ts();
+                        }
                         let database = req.database.clone();
                         let compact_ts_family = req.ts_family_id;
                         let out_level = req.out_level;
@@ -301,6 +365,115 @@ impl TsKv {
         info!("Summary task handler started");
     }
 
+    fn run_retention_job(&self) {
+        let interval = self.options.storage.retention_check_interval;
+        if interval == 0 {
+            warn!("job 'retention' disabled.");
+            return;
+        }
+        let interval = Duration::from_secs(interval);
+        let version_set = self.version_set.clone();
+        let summary_task_sender = self.summary_task_sender.clone();
+        let mut close_receiver = self.close_sender.subscribe();
+        let f = async move {
+            let mut ticker = tokio::time::interval_at(Instant::now() + interval, interval);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        drop_expired_data(version_set.clone(), summary_task_sender.clone()).await;
+                    }
+                    close_task = close_receiver.recv() => {
+                        info!("job 'retention' closed.");
+                        if let Ok(tx) = close_task {
+                            if let Err(e) = tx.send(()) {
+                                error!("Failed to send retention closed signal: {:?}", e);
+                            }
+                        }
+                        break;
+                    }
+                }
+            }
+        };
+        self.runtime.spawn(f);
+        info!("job 'retention' started.");
+    }
+
+    fn run_offload_job(&self) {
+        let interval = self.options.object_store.offload_check_interval;
+        let offload_age = self.options.object_store.offload_age;
+        let store = match &self.remote_store {
+            Some(store) if interval > 0 && offload_age > 0 => store.clone(),
+            _ => {
+                warn!("job 'offload' disabled.");
+                return;
+            }
+        };
+        let interval = Duration::from_secs(interval);
+        let version_set = self.version_set.clone();
+        let mut close_receiver = self.close_sender.subscribe();
+        let f = async move {
+            let mut ticker = tokio::time::interval_at(Instant::now() + interval, interval);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        offload_cold_files(version_set.clone(), store.clone(), offload_age).await;
+                    }
+                    close_task = close_receiver.recv() => {
+                        info!("job 'offload' closed.");
+                        if let Ok(tx) = close_task {
+                            if let Err(e) = tx.send(()) {
+                                error!("Failed to send offload closed signal: {:?}", e);
+                            }
+                        }
+                        break;
+                    }
+                }
+            }
+        };
+        self.runtime.spawn(f);
+        info!("job 'offload' started.");
+    }
+
+    /// Offloads tsm files with data older than `offload_age` seconds of all
+    /// databases to the object store, returns the number of offloaded files.
+    pub async fn offload_cold_files(&self, offload_age: u64) -> usize {
+        match &self.remote_store {
+            Some(store) => {
+                offload_cold_files(self.version_set.clone(), store.clone(), offload_age).await
+            }
+            None => 0,
+        }
+    }
+
+    /// Drop data expired by TTL of all databases.
+    pub async fn drop_expired_data(&self) {
+        drop_expired_data(self.version_set.clone(), self.summary_task_sender.clone()).await;
+    }
+
+    /// Returns the TseriesFamily responsible for the `vnode`, creates it if not exists.
+    fn get_or_create_tsfamily(
+        &self,
+        db: &Arc<RwLock<Database>>,
+        vnode: &VnodeInfo,
+    ) -> Arc<RwLock<TseriesFamily>> {
+        if let Some(tsf) = db.read().get_tsfamily_by_vnode(vnode) {
+            return tsf;
+        }
+        let mut db = db.write();
+        match db.get_tsfamily_by_vnode(vnode) {
+            Some(tsf) => tsf,
+            // Nothing of a new TseriesFamily is flushed, but WAL entries not newer than
+            // the last sequence recovered from summary were all written before it existed.
+            None => db.add_tsfamily(
+                self.global_ctx.tsfamily_id_next(),
+                self.global_ctx.last_seq().saturating_sub(1),
+                *vnode,
+                self.summary_task_sender.clone(),
+                self.flush_task_sender.clone(),
+            ),
+        }
+    }
+
     // fn run_timer_job(&self, pub_sender: Sender<()>) {
     //     let f = async move {
     //         let interval = Duration::from_secs(1);
@@ -317,14 +490,136 @@ impl TsKv {
     //     Ok(None)
     // }
 
+    /// Flushes the largest caches of all TseriesFamilies if data in caches
+    /// approaches the memory limit.
+    fn flush_largest_caches(&self) {
+        if self.write_buffer.size_to_flush(0) == 0 {
+            return;
+        }
+        let dbs: Vec<Arc<RwLock<Database>>> = self
+            .version_set
+            .read()
+            .get_all_db()
+            .values()
+            .cloned()
+            .collect();
+        let mut ts_families = vec![];
+        let mut flushing = 0;
+        for db in dbs {
+            for tsf in db.read().ts_families().values() {
+                let (unflushed, tsf_flushing) = tsf.read().cache_sizes();
+                flushing += tsf_flushing;
+                if unflushed > 0 {
+                    ts_families.push((unflushed, tsf.clone()));
+                }
+            }
+        }
+
+        let mut size_to_flush = self.write_buffer.size_to_flush(flushing);
+        ts_families.sort_by_key(|(size, _)| std::cmp::Reverse(*size));
+        for (_, tsf) in ts_families {
+            if size_to_flush == 0 {
+                break;
+            }
+            size_to_flush = size_to_flush.saturating_sub(tsf.write().flush_caches());
+        }
+    }
+
+    /// Delays writes to the TseriesFamily if there are too many files in level 0,
+    /// writes are stopped until compaction reduces the files under the stop trigger.
+    async fn wait_for_write_stall(&self, tsf: &Arc<RwLock<TseriesFamily>>) -> Result<()> {
+        let (tf_id, state, l0_files) = {
+            let tsf = tsf.read();
+            (tsf.tf_id(), tsf.check_write_stall(), tsf.level0_file_num())
+        };
+        match state {
+            WriteStallState::Normal => Ok(()),
+            WriteStallState::Slowdown => {
+                // The more files over the trigger, the longer the delay.
+                let over = l0_files + 1 - self.options.storage.l0_slowdown_trigger;
+                let delay = WRITE_STALL_DELAY * over.min(WRITE_STALL_MAX_STEPS);
+                tokio::time::sleep(delay).await;
+                Ok(())
+            }
+            WriteStallState::Stop => {
+                let _ = self.compact_task_sender.send(tf_id);
+                let deadline =
+                    Instant::now() + Duration::from_millis(self.options.cache.write_stall_timeout);
+                loop {
+                    tokio::time::sleep(WRITE_STALL_DELAY).await;
+                    let tsf = tsf.read();
+                    if tsf.check_write_stall() != WriteStallState::Stop {
+                        return Ok(());
+                    }
+                    if Instant::now() >= deadline {
+                        return Err(Error::WriteStalled {
+                            ts_family: tf_id,
+                            files: tsf.level0_file_num(),
+                            limit: self.options.storage.l0_stop_trigger,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Flushes data in caches of the database to column files, and waits until
+    /// the flushed files are in versions of TseriesFamilies.
+    async fn flush_database(&self, db: &Arc<RwLock<Database>>) -> Result<()> {
+        let ts_families: Vec<Arc<RwLock<TseriesFamily>>> =
+            db.read().ts_families().values().cloned().collect();
+        let mut mems = vec![];
+        // The max WAL sequence in caches to flush of each TseriesFamily.
+        let mut flush_seqs = vec![];
+        for tsf in ts_families {
+            let mut tsf_wlock = tsf.write();
+            if !tsf_wlock.cache().read().is_empty() {
+                tsf_wlock.switch_to_immutable();
+            }
+            let mut max_seq = None;
+            for mem in tsf_wlock.im_cache() {
+                let mut mem_wlock = mem.write();
+                if mem_wlock.flushed || mem_wlock.cache_size() == 0 {
+                    continue;
+                }
+                max_seq = max_seq.max(Some(mem_wlock.seq_no()));
+                // Caches being flushed by flush job are just waited.
+                if !mem_wlock.flushing {
+                    mem_wlock.flushing = true;
+                    mems.push((tsf_wlock.tf_id(), mem.clone()));
+                }
+            }
+            drop(tsf_wlock);
+            if let Some(seq) = max_seq {
+                flush_seqs.push((tsf, seq));
+            }
+        }
+
+        run_flush_memtable_job(
+            FlushReq::new(mems),
+            self.global_ctx.clone(),
+            self.version_set.clone(),
+            self.summary_task_sender.clone(),
+            self.compact_task_sender.clone(),
+        )?;
+        for (tsf, seq) in flush_seqs {
+            while tsf.read().version().last_seq < seq {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+        Ok(())
+    }
+
     // Compact TSM files in database into bigger TSM files.
     pub fn compact(&self, database: &str) {
         let database = self.version_set.read().get_db(database);
         if let Some(db) = database {
             // TODO: stop current and prevent next flush and compaction.
+            let expired_ts = db.read().ttl_expired_ts();
             for (ts_family_id, ts_family) in db.read().ts_families() {
                 let compact_req = ts_family.read().pick_compaction();
-                if let Some(req) = compact_req {
+                if let Some(mut req) = compact_req {
+                    req.expired_ts = expired_ts;
                     match compaction::run_compaction_job(req, self.global_ctx.clone()) {
                         Ok(Some(version_edit)) => {
                             let (summary_tx, summary_rx) = oneshot::channel();
@@ -348,9 +643,153 @@ impl TsKv {
     }
 }
 
+async fn drop_expired_data(
+    version_set: Arc<RwLock<VersionSet>>,
+    summary_task_sender: UnboundedSender<SummaryTask>,
+) {
+    let dbs: Vec<Arc<RwLock<Database>>> =
+        version_set.read().get_all_db().values().cloned().collect();
+    for db in dbs {
+        let version_edits = db.write().drop_expired_data();
+        if version_edits.is_empty() {
+            continue;
+        }
+        let (summary_tx, summary_rx) = oneshot::channel();
+        if let Err(e) = summary_task_sender.send(SummaryTask {
+            edits: version_edits.clone(),
+            cb: summary_tx,
+        }) {
+            error!("Failed to send retention summary task: {:?}", e);
+            db.read().unmark_expired_files(&version_edits);
+            continue;
+        }
+        match summary_rx.await {
+            Ok(Err(e)) => {
+                error!("Failed to apply retention version edits: {:?}", e);
+                db.read().unmark_expired_files(&version_edits);
+            }
+            Err(e) => {
+                error!("Failed to receive retention summary result: {:?}", e);
+                db.read().unmark_expired_files(&version_edits);
+            }
+            _ => {}
+        }
+    }
+}
+
+async fn offload_cold_files(
+    version_set: Arc<RwLock<VersionSet>>,
+    store: Arc<RemoteStore>,
+    offload_age: u64,
+) -> usize {
+    let dbs: Vec<Arc<RwLock<Database>>> =
+        version_set.read().get_all_db().values().cloned().collect();
+    let mut offloaded = 0_usize;
+    for db in dbs {
+        let (expired_ts, ts_families) = {
+            let db = db.read();
+            let ts_families: Vec<Arc<RwLock<TseriesFamily>>> =
+                db.ts_families().values().cloned().collect();
+            (db.offload_expired_ts(offload_age), ts_families)
+        };
+        for tsf in ts_families {
+            let (database, tsf_id, files) = {
+                let tsf = tsf.read();
+                let files = tsf.version().cold_tsm_files(expired_ts);
+                (tsf.database(), tsf.tf_id(), files)
+            };
+            for file in files {
+                let path = file.file_path();
+                if remote::is_remote_file(&path) {
+                    continue;
+                }
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+                let key = format!("{}/{}/{}", database, tsf_id, file_name);
+                // The index and footer are kept in the local stub file.
+                let ret = match tsm::read_index_offset(&path) {
+                    Ok(offset) => remote::offload_file(&store, &path, &key, offset).await,
+                    Err(e) => Err(e),
+                };
+                let offloaded_file = match ret {
+                    Ok(f) => f,
+                    Err(e) => {
+                        error!("Failed to offload file '{}': {}", path.display(), e);
+                        continue;
+                    }
+                };
+                // The file may be picked by compaction or deleted while uploading,
+                // which is done under the lock of the TseriesFamily.
+                let committed = {
+                    let _tsf_wlock = tsf.write();
+                    if file.is_compacting() || file.is_deleted() {
+                        None
+                    } else {
+                        Some(offloaded_file.commit())
+                    }
+                };
+                match committed {
+                    Some(Ok(())) => {
+                        info!("Offloaded file '{}' to '{}'", path.display(), key);
+                        offloaded += 1;
+                        continue;
+                    }
+                    Some(Err(e)) => error!("Failed to offload file '{}': {}", path.display(), e),
+                    None => info!(
+                        "Cancelled offloading file '{}' being compacted or deleted",
+                        path.display()
+                    ),
+                }
+                if let Err(e) = offloaded_file.abort().await {
+                    error!("Failed to delete offloaded object '{}': {}", key, e);
+                }
+            }
+        }
+    }
+    offloaded
+}
+
+/// Returns the min sequence of WAL entries that may not be flushed to column files,
+/// `max_seq` is the max sequence of WAL entries written before calling this.
+async fn wal_checkpoint(
+    wal_write_lock: &tokio::sync::RwLock<()>,
+    version_set: &RwLock<VersionSet>,
+    max_seq: u64,
+) -> u64 {
+    // Entries with a sequence but not in caches yet are not counted by the version set.
+    let _guard = wal_write_lock.write().await;
+    version_set
+        .read()
+        .min_unflushed_seq()
+        .unwrap_or(max_seq + 1)
+}
+
+/// Deletes WAL files of which all data has been flushed to column files.
+fn delete_obsolete_wal_files(wal_manager: &mut WalManager, version_set: &RwLock<VersionSet>) {
+    let checkpoint = version_set
+        .read()
+        .min_unflushed_seq()
+        .unwrap_or(wal_manager.current_seq_no() + 1);
+    wal_manager.delete_obsolete_files(checkpoint);
+}
+
 #[async_trait::async_trait]
 impl Engine for TsKv {
     async fn write(&self, write_batch: WritePointsRpcRequest) -> Result<WritePointsRpcResponse> {
+        if self.write_buffer.should_stall() {
+            warn!(
+                "Write stalled, {} bytes of data in caches reached the limit",
+                self.write_buffer.memory_usage()
+            );
+            self.flush_largest_caches();
+            let timeout = Duration::from_millis(self.options.cache.write_stall_timeout);
+            if !self.write_buffer.wait_for_memory(timeout).await {
+                return Err(Error::WriteBufferFull {
+                    usage: self.write_buffer.memory_usage(),
+                    limit: self.write_buffer.max_size(),
+                });
+            }
+        }
+
         let points = Arc::new(write_batch.points);
         let fb_points = flatbuffers::root::<fb_models::Points>(&points)
             .context(error::InvalidFlatbufferSnafu)?;
@@ -366,8 +805,24 @@ impl Engine for TsKv {
                 .write()
                 .create_db(DatabaseSchema::new(&db_name)),
         };
-        let write_group = db.read().build_write_group(fb_points.points().unwrap())?;
+        let (write_group, rejected) = db.read().build_write_group(fb_points.points().unwrap())?;
+        if write_group.is_empty() && !rejected.is_empty() {
+            return Err(rejected.into_error().unwrap());
+        }
+        // Points of rejected series are not written into WAL.
+        let points = if rejected.is_empty() {
+            points
+        } else {
+            Arc::new(rejected.retain_accepted(&fb_points))
+        };
+        for vnode in write_group.keys() {
+            let tsf = db.read().get_tsfamily_by_vnode(vnode);
+            if let Some(tsf) = tsf {
+                self.wait_for_write_stall(&tsf).await?;
+            }
+        }
 
+        let wal_write_guard = self.wal_write_lock.read().await;
         let mut seq = 0;
         if self.options.wal.enabled {
             let (cb, rx) = oneshot::channel();
@@ -385,19 +840,16 @@ impl Engine for TsKv {
             seq = rx.await.context(error::ReceiveSnafu)??.0;
         }
 
-        let opt_tsf = db.read().get_tsfamily_random();
-        let tsf = match opt_tsf {
-            Some(v) => v,
-            None => db.write().add_tsfamily(
-                self.global_ctx.tsfamily_id_next(),
-                seq,
-                self.summary_task_sender.clone(),
-                self.flush_task_sender.clone(),
-            ),
-        };
-
-        tsf.read().put_points(seq, write_group);
-        tsf.write().check_to_flush();
+        for (vnode, group) in write_group {
+            let tsf = self.get_or_create_tsfamily(&db, &vnode);
+            tsf.read().put_points(seq, group);
+            tsf.write().check_to_flush();
+        }
+        drop(wal_write_guard);
+        self.flush_largest_caches();
+        if let Some(e) = rejected.into_error() {
+            return Err(e);
+        }
         Ok(WritePointsRpcResponse {
             version: 1,
             points: vec![],
@@ -421,20 +873,19 @@ impl Engine for TsKv {
             .write()
             .create_db(DatabaseSchema::new(&db_name));
 
-        let write_group = db.read().build_write_group(fb_points.points().unwrap())?;
-
-        let opt_tsf = db.read().get_tsfamily_random();
-        let tsf = match opt_tsf {
-            Some(v) => v,
-            None => db.write().add_tsfamily(
-                self.global_ctx.tsfamily_id_next(),
-                seq,
-                self.summary_task_sender.clone(),
-                self.flush_task_sender.clone(),
-            ),
-        };
+        let (write_group, rejected) = db.read().build_write_group(fb_points.points().unwrap())?;
+        if let Some(e) = rejected.into_error() {
+            warn!("Replaying WAL of sequence {}: {}", seq, e);
+        }
 
-        tsf.read().put_points(seq, write_group);
+        for (vnode, group) in write_group {
+            let tsf = self.get_or_create_tsfamily(&db, &vnode);
+            // Skip data already flushed to column files.
+            if tsf.read().version().last_seq >= seq {
+                continue;
+            }
+            tsf.read().put_points(seq, group);
+        }
 
         return Ok(WritePointsRpcResponse {
             version: 1,
@@ -501,9 +952,13 @@ impl Engine for TsKv {
         if let Err(e) = std::fs::remove_dir_all(&idx_dir) {
             error!("Failed to remove dir '{}', e: {}", idx_dir.display(), e);
         }
-        let db_dir = self.options.storage.database_dir(&database);
-        if let Err(e) = std::fs::remove_dir_all(&db_dir) {
-            error!("Failed to remove dir '{}', e: {}", db_dir.display(), e);
+        for db_dir in self.options.storage.database_dirs(&database) {
+            if !file_manager::try_exists(&db_dir) {
+                continue;
+            }
+            if let Err(e) = std::fs::remove_dir_all(&db_dir) {
+                error!("Failed to remove dir '{}', e: {}", db_dir.display(), e);
+            }
         }
 
         Ok(())
@@ -635,37 +1090,259 @@ impl Engine for TsKv {
         Ok(None)
     }
 
-    fn get_db_version(&self, db: &str) -> Result<Option<Arc<SuperVersion>>> {
-        let version_set = self.version_set.read();
-        if !version_set.db_exists(db) {
-            return Err(Error::DatabaseNotFound {
+    fn get_series_cardinality(&self, db: &str, tab: Option<&str>, exact: bool) -> Result<u64> {
+        if let Some(db) = self.version_set.read().get_db(db) {
+            db.read()
+                .get_index()
+                .series_cardinality(tab, exact)
+                .context(error::IndexErrSnafu)
+        } else {
+            Err(Error::DatabaseNotFound {
                 database: db.to_string(),
-            });
+            })
         }
-        if let Some(tsf) = version_set.get_tsfamily_by_name(db) {
-            Ok(Some(tsf.read().super_version()))
+    }
+
+    fn get_tag_values_cardinality(
+        &self,
+        db: &str,
+        tab: Option<&str>,
+        tag_key: &str,
+        exact: bool,
+    ) -> Result<u64> {
+        if let Some(db) = self.version_set.read().get_db(db) {
+            db.read()
+                .get_index()
+                .tag_values_cardinality(tab, tag_key, exact)
+                .context(error::IndexErrSnafu)
         } else {
-            warn!("ts_family with db name '{}' not found.", db);
-            Ok(None)
+            Err(Error::DatabaseNotFound {
+                database: db.to_string(),
+            })
         }
     }
+
+    fn get_db_versions(
+        &self,
+        db: &str,
+        time_ranges: &[TimeRange],
+    ) -> Result<Vec<Arc<SuperVersion>>> {
+        let database = match self.version_set.read().get_db(db) {
+            Some(database) => database,
+            None => {
+                return Err(Error::DatabaseNotFound {
+                    database: db.to_string(),
+                })
+            }
+        };
+        let versions = database
+            .read()
+            .get_tsfamilies_by_time_ranges(time_ranges)
+            .iter()
+            .map(|tsf| tsf.read().super_version())
+            .collect();
+        Ok(versions)
+    }
+
+    async fn backup_database(
+        &self,
+        database: &str,
+        path: &str,
+        since: Option<&str>,
+    ) -> Result<BackupManifest> {
+        let db = match self.version_set.read().get_db(database) {
+            Some(db) => db,
+            None => {
+                return Err(Error::DatabaseNotFound {
+                    database: database.to_string(),
+                })
+            }
+        };
+        let backup_dir = Path::new(path);
+        if let Some(parent_id) = since {
+            let parent_dir = backup::parent_backup_dir(backup_dir, parent_id);
+            let chain = backup::read_backup_chain(&parent_dir)?;
+            if chain[0].1.database.name != database {
+                return Err(Error::InvalidBackup {
+                    path: parent_dir,
+                    reason: format!("it is a backup of database '{}'", chain[0].1.database.name),
+                });
+            }
+        }
+        backup::prepare_backup_dir(backup_dir)?;
+        info!(
+            "Backup database '{}' to '{}' since '{:?}'",
+            database, path, since
+        );
+
+        if since.is_some() && self.options.wal.enabled {
+            // Snapshot WAL files before pinning versions, data in WAL files deleted
+            // after this is in column files of the pinned versions.
+            let min_seq = db
+                .read()
+                .ts_families()
+                .values()
+                .map(|tsf| tsf.read().version().last_seq)
+                .min()
+                .unwrap_or(0);
+            let (cb, rx) = oneshot::channel();
+            self.wal_sender
+                .send(WalTask::SnapshotFiles { min_seq, cb })
+                .map_err(|_| Error::Send)?;
+            let files = rx.await.context(error::ReceiveSnafu)??;
+            wal::copy_entries(
+                files,
+                database,
+                min_seq,
+                &backup::wal_tail_path(backup_dir),
+                self.options.wal.clone(),
+            )
+            .await?;
+        } else {
+            self.flush_database(&db).await?;
+        }
+
+        // Pin versions so that column files in the backup won't be deleted by compaction.
+        let (schema, index, versions) = {
+            let db_rlock = db.read();
+            let versions: Vec<Arc<SuperVersion>> = db_rlock
+                .ts_families()
+                .values()
+                .map(|tsf| tsf.read().super_version())
+                .collect();
+            (db_rlock.get_schema(), db_rlock.get_index(), versions)
+        };
+        backup::backup_database(schema, &versions, &index, backup_dir, since).await
+    }
+
+    async fn restore_database(&self, database: &str, path: &str) -> Result<BackupManifest> {
+        let backup_dir = Path::new(path);
+        let chain = backup::read_backup_chain(backup_dir)?;
+        let manifest = chain[0].1.clone();
+        if manifest.database.name != database {
+            return Err(Error::InvalidBackup {
+                path: backup_dir.to_path_buf(),
+                reason: format!("it is a backup of database '{}'", manifest.database.name),
+            });
+        }
+        if self.version_set.read().db_exists(database) {
+            return Err(Error::DatabaseAlreadyExists {
+                database: database.to_string(),
+            });
+        }
+        info!(
+            "Restore database '{}' from '{}' with {} backups",
+            database,
+            path,
+            chain.len()
+        );
+        let chain_dirs: Vec<PathBuf> = chain.into_iter().map(|(dir, _)| dir).collect();
+
+        // Files are copied to temporary paths first, nothing is left if it fails.
+        let ts_families = backup::read_backup_ts_families(backup_dir).await?;
+        let mut staging = backup::RestoreStaging::new(database, self.options.storage.clone());
+        let mut restored = Vec::with_capacity(ts_families.len());
+        let mut edits = Vec::with_capacity(ts_families.len());
+        for tsf in ts_families.iter() {
+            let tsf_id = self.global_ctx.tsfamily_id_next();
+            edits.push(staging.stage_ts_family(&chain_dirs, tsf, tsf_id, &self.global_ctx)?);
+            restored.push((tsf_id, tsf.vnode));
+        }
+        let index_dir = index_manger(self.options.storage.index_base_dir())
+            .read()
+            .index_dir(database);
+        staging.stage_index(backup_dir, &index_dir)?;
+        staging.commit()?;
+
+        let db = self
+            .version_set
+            .write()
+            .create_db(manifest.database.clone());
+        {
+            let mut db_wlock = db.write();
+            for (tsf_id, vnode) in restored {
+                // Data of restored TseriesFamilies is not in WAL.
+                db_wlock.add_tsfamily(
+                    tsf_id,
+                    0,
+                    vnode,
+                    self.summary_task_sender.clone(),
+                    self.flush_task_sender.clone(),
+                );
+            }
+        }
+
+        let (summary_tx, summary_rx) = oneshot::channel();
+        self.summary_task_sender
+            .send(SummaryTask {
+                edits,
+                cb: summary_tx,
+            })
+            .map_err(|_| Error::Send)?;
+        summary_rx.await.context(error::ReceiveSnafu)??;
+
+        // Data in the WAL tail of the backup is written again, points already
+        // in column files are overwritten by themselves.
+        for points in backup::read_wal_tail(backup_dir)? {
+            self.write(WritePointsRpcRequest { version: 1, points })
+                .await?;
+        }
+
+        Ok(manifest)
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use config::get_config;
+    use config::{get_config, Config};
     use flatbuffers::{FlatBufferBuilder, WIPOffset};
     use models::utils::now_timestamp;
     use models::{ColumnId, InMemPoint, SeriesId, SeriesKey, Timestamp};
-    use protos::{models::Points, models_helper};
+    use protos::models::{FieldType, Points, PointsArgs};
+    use protos::models_helper;
     use std::collections::HashMap;
     use std::sync::{atomic, Arc};
     use tokio::runtime::{self, Runtime};
 
-    use crate::{engine::Engine, error, tsm::DataBlock, Options, TimeRange, TsKv};
+    use datafusion::arrow::datatypes::DataType;
+    use datafusion::scalar::ScalarValue;
+    use models::error_code::ErrorCode;
+    use models::predicate::domain::{ColumnDomains, Domain};
+    use models::schema::DatabaseSchema;
+
+    use crate::backup::BackupManifest;
+    use crate::check;
+    use crate::file_system::remote;
+    use crate::index::IndexError;
+    use crate::kv_option::{StorageOptions, DATA_PATH};
+    use crate::repair;
+    use crate::tseries_family::WriteStallState;
+    use crate::tsm::TsmReader;
+    use crate::{engine::Engine, error, tsm::DataBlock, Error, Options, TimeRange, TsKv};
+
+    use super::wal_checkpoint;
+    use crate::tsm::codec::get_str_codec;
+    use crate::wal::{self, WalTask};
+    use models::codec::Encoding;
+    use protos::kv_service::WritePointsRpcRequest;
+    use protos::models as fb_models;
+    use std::path::{Path, PathBuf};
     use std::sync::atomic::{AtomicI64, Ordering};
     use tokio::sync::watch;
 
+    /// Opens a TsKv with WAL and storage in `dir`, after the directory is cleared.
+    fn open_tskv(dir: &str, configure: impl FnOnce(&mut Config)) -> (Arc<Runtime>, TsKv) {
+        let _ = std::fs::remove_dir_all(dir);
+        let mut global_config = get_config("../config/config.toml");
+        global_config.wal.path = format!("{}/wal", dir);
+        global_config.storage.path = format!("{}/data", dir);
+        configure(&mut global_config);
+        let opt = Options::from(&global_config);
+        let rt = Arc::new(Runtime::new().unwrap());
+        let tskv = rt.block_on(TsKv::open(opt, rt.clone())).unwrap();
+        (rt, tskv)
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_compact() {
@@ -677,4 +1354,912 @@ mod test {
             .unwrap();
         tskv.compact("public");
     }
+
+    #[test]
+    fn test_backup_restore() {
+        let dir = "/tmp/test/kvcore/backup";
+        let backup_dir = "/tmp/test/kvcore/backup/backup";
+        let (rt, tskv) = open_tskv(dir, |_| {});
+
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let points = models_helper::create_dev_ops_points(&mut fbb, 100, "db_backup", "cpu");
+        fbb.finish(points, None);
+        let points = fbb.finished_data().to_vec();
+        let request = WritePointsRpcRequest { version: 1, points };
+
+        rt.block_on(async {
+            tskv.write(request).await.unwrap();
+
+            let manifest = tskv
+                .backup_database("db_backup", backup_dir, None)
+                .await
+                .unwrap();
+            assert_eq!(manifest.database.name, "db_backup");
+            assert!(manifest.files.iter().any(|f| f.path.ends_with(".tsm")));
+            assert!(manifest.files.iter().any(|f| f.path.starts_with("index/")));
+            let read_manifest = BackupManifest::read(Path::new(backup_dir)).unwrap();
+            assert_eq!(read_manifest, manifest);
+            read_manifest.verify(Path::new(backup_dir)).unwrap();
+
+            // Backup directory can't be overwritten.
+            assert!(matches!(
+                tskv.backup_database("db_backup", backup_dir, None).await,
+                Err(Error::InvalidBackup { .. })
+            ));
+            // Database to restore must not exist.
+            assert!(matches!(
+                tskv.restore_database("db_backup", backup_dir).await,
+                Err(Error::DatabaseAlreadyExists { .. })
+            ));
+            assert!(matches!(
+                tskv.restore_database("db_1", backup_dir).await,
+                Err(Error::InvalidBackup { .. })
+            ));
+
+            let table_schema = tskv.get_table_schema("db_backup", "cpu").unwrap();
+            assert!(table_schema.is_some());
+            let column_files = |tskv: &TsKv| {
+                let mut files: Vec<(i64, i64, u64)> = tskv
+                    .get_db_versions("db_backup", &[TimeRange::all()])
+                    .unwrap()
+                    .iter()
+                    .flat_map(|v| {
+                        v.version
+                            .levels_info()
+                            .iter()
+                            .flat_map(|l| l.files.iter())
+                            .map(|f| {
+                                assert!(f.file_path().exists());
+                                (f.time_range().min_ts, f.time_range().max_ts, f.size())
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect();
+                files.sort_unstable();
+                files
+            };
+            let files = column_files(&tskv);
+            assert!(!files.is_empty());
+
+            tskv.drop_database("db_backup").unwrap();
+            let restored = tskv
+                .restore_database("db_backup", backup_dir)
+                .await
+                .unwrap();
+            assert_eq!(restored, manifest);
+            assert_eq!(
+                tskv.get_table_schema("db_backup", "cpu").unwrap(),
+                table_schema
+            );
+            assert_eq!(column_files(&tskv), files);
+        });
+    }
+
+    #[test]
+    fn test_incremental_backup_restore() {
+        let dir = "/tmp/test/kvcore/backup_incremental";
+        let full_dir = format!("{}/backup/full", dir);
+        let inc1_dir = format!("{}/backup/inc1", dir);
+        let inc2_dir = format!("{}/backup/inc2", dir);
+        let (rt, tskv) = open_tskv(dir, |_| {});
+
+        let write_request = || {
+            let mut fbb = flatbuffers::FlatBufferBuilder::new();
+            let points = models_helper::create_dev_ops_points(&mut fbb, 100, "db_inc", "cpu");
+            fbb.finish(points, None);
+            let points = fbb.finished_data().to_vec();
+            WritePointsRpcRequest { version: 1, points }
+        };
+        let column_files = |tskv: &TsKv| {
+            let mut files: Vec<(i64, i64, u64)> = tskv
+                .get_db_versions("db_inc", &[TimeRange::all()])
+                .unwrap()
+                .iter()
+                .flat_map(|v| {
+                    v.version
+                        .levels_info()
+                        .iter()
+                        .flat_map(|l| l.files.iter())
+                        .map(|f| (f.time_range().min_ts, f.time_range().max_ts, f.size()))
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            files.sort_unstable();
+            files
+        };
+        let cached_rows = |tskv: &TsKv| -> usize {
+            tskv.get_db_versions("db_inc", &[TimeRange::all()])
+                .unwrap()
+                .iter()
+                .flat_map(|v| {
+                    let mut caches = v.caches.immut_cache.clone();
+                    caches.push(v.caches.mut_cache.clone());
+                    caches
+                })
+                .filter(|c| !c.read().flushed)
+                .flat_map(|c| c.read().read_series_data())
+                .map(|(_, data)| {
+                    data.read()
+                        .flat_groups()
+                        .iter()
+                        .map(|(_, _, rows)| rows.len())
+                        .sum::<usize>()
+                })
+                .sum()
+        };
+        let tsm_files = |manifest: &BackupManifest| {
+            manifest
+                .files
+                .iter()
+                .filter(|f| f.path.ends_with(".tsm"))
+                .count()
+        };
+
+        rt.block_on(async {
+            tskv.write(write_request()).await.unwrap();
+            let full = tskv
+                .backup_database("db_inc", &full_dir, None)
+                .await
+                .unwrap();
+            assert_eq!(full.id, "full");
+            assert_eq!(full.parent, None);
+            let full_files = column_files(&tskv);
+            assert_eq!(tsm_files(&full), full_files.len());
+
+            // Data not flushed is in the WAL tail of the incremental backup.
+            tskv.write(write_request()).await.unwrap();
+            let inc1 = tskv
+                .backup_database("db_inc", &inc1_dir, Some("full"))
+                .await
+                .unwrap();
+            assert_eq!(inc1.parent, Some("full".to_string()));
+            assert_eq!(tsm_files(&inc1), 0);
+            assert!(inc1.files.iter().any(|f| f.path.starts_with("wal/")));
+
+            // Only column files added since the parent backup are copied.
+            let db = tskv.version_set.read().get_db("db_inc").unwrap();
+            tskv.flush_database(&db).await.unwrap();
+            tskv.write(write_request()).await.unwrap();
+            let inc2 = tskv
+                .backup_database("db_inc", &inc2_dir, Some("inc1"))
+                .await
+                .unwrap();
+            let files = column_files(&tskv);
+            assert_eq!(tsm_files(&inc2), files.len() - full_files.len());
+            assert_eq!(cached_rows(&tskv), 100);
+
+            assert!(matches!(
+                tskv.backup_database("db_inc", &format!("{}/backup/inc3", dir), Some("inc0"))
+                    .await,
+                Err(Error::InvalidBackup { .. })
+            ));
+
+            tskv.drop_database("db_inc").unwrap();
+            tskv.restore_database("db_inc", &inc2_dir).await.unwrap();
+            assert_eq!(column_files(&tskv), files);
+            assert_eq!(cached_rows(&tskv), 100);
+
+            tskv.drop_database("db_inc").unwrap();
+            tskv.restore_database("db_inc", &inc1_dir).await.unwrap();
+            assert_eq!(column_files(&tskv), full_files);
+            assert_eq!(cached_rows(&tskv), 100);
+
+            // A chain with a missing backup can't be restored.
+            tskv.drop_database("db_inc").unwrap();
+            std::fs::remove_dir_all(&inc1_dir).unwrap();
+            assert!(matches!(
+                tskv.restore_database("db_inc", &inc2_dir).await,
+                Err(Error::InvalidBackup { .. })
+            ));
+        });
+    }
+
+    /// Writes points into `database` of a TsKv in `dir` and flushes them, returns
+    /// the storage options of a copy of the storage. If `level_paths` is set, tsm
+    /// files of level 1 are put in `{dir}/cold` and copied to `{dir}/copy_cold`.
+    ///
+    /// Index of the running TsKv is locked, so checking and repairing are tested with
+    /// the copy, the index may be out of the storage as index manager is a singleton.
+    fn write_and_copy_storage(dir: &str, database: &str, level_paths: bool) -> StorageOptions {
+        let cold_dir = format!("{}/cold", dir);
+        let (rt, tskv) = open_tskv(dir, |config| {
+            if level_paths {
+                config
+                    .storage
+                    .level_paths
+                    .insert("1".to_string(), cold_dir.clone());
+            }
+        });
+
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let points = models_helper::create_dev_ops_points(&mut fbb, 100, database, "cpu");
+        fbb.finish(points, None);
+        let points = fbb.finished_data().to_vec();
+        let request = WritePointsRpcRequest { version: 1, points };
+        let index_dir = rt.block_on(async {
+            tskv.write(request).await.unwrap();
+            let db = tskv.version_set.read().get_db(database).unwrap();
+            tskv.flush_database(&db).await.unwrap();
+            let index = db.read().get_index();
+            index.flush().unwrap();
+            index.path()
+        });
+
+        let copy_dir = |src: &Path, dst: &Path| {
+            if !src.exists() {
+                return;
+            }
+            for entry in walkdir::WalkDir::new(src) {
+                let entry = entry.unwrap();
+                let path = dst.join(entry.path().strip_prefix(src).unwrap());
+                if entry.file_type().is_dir() {
+                    std::fs::create_dir_all(&path).unwrap();
+                } else {
+                    std::fs::copy(entry.path(), &path).unwrap();
+                }
+            }
+        };
+        let mut storage = tskv.options.storage.as_ref().clone();
+        storage.path = Path::new(dir).join("copy");
+        copy_dir(&tskv.options.storage.summary_dir(), &storage.summary_dir());
+        copy_dir(
+            &tskv.options.storage.path.join(DATA_PATH),
+            &storage.path.join(DATA_PATH),
+        );
+        copy_dir(&index_dir, &storage.index_dir(database));
+        for path in storage.level_paths.values_mut() {
+            let copy_path = Path::new(dir).join("copy_cold");
+            copy_dir(&path.join(DATA_PATH), &copy_path.join(DATA_PATH));
+            *path = copy_path;
+        }
+        storage
+    }
+
+    /// Finds a tsm file written by `write_and_copy_storage`.
+    fn find_tsm_file(storage: &StorageOptions) -> PathBuf {
+        storage
+            .data_paths()
+            .into_iter()
+            .flat_map(|path| walkdir::WalkDir::new(path.join(DATA_PATH)))
+            .flatten()
+            .map(|e| e.into_path())
+            .find(|p| p.extension().map(|e| e == "tsm").unwrap_or(false))
+            .unwrap()
+    }
+
+    fn check_storage(dir: &str, level_paths: bool) {
+        let storage = write_and_copy_storage(dir, "db_check", level_paths);
+        let report = check::check_storage(&storage);
+        assert!(!report.is_damaged(), "{:?}", report.issues);
+        assert!(report.column_files > 0);
+        assert!(report.blocks > 0);
+
+        let tsm_file = find_tsm_file(&storage);
+        if level_paths {
+            assert!(tsm_file.starts_with(Path::new(dir).join("copy_cold")));
+        }
+        let tsm_dir = tsm_file.parent().unwrap();
+        let issue_kinds = |report: &check::CheckReport| {
+            let mut kinds: Vec<check::IssueKind> = report.issues.iter().map(|i| i.kind).collect();
+            kinds.dedup();
+            kinds
+        };
+
+        // Damaged block, orphan file and broken tombstone. Damaged file is
+        // written into a new file as pages of the old one are cached.
+        let mut data = std::fs::read(&tsm_file).unwrap();
+        data[10] = !data[10];
+        let tmp_file = tsm_dir.join("damaged");
+        std::fs::write(&tmp_file, &data).unwrap();
+        std::fs::rename(&tmp_file, &tsm_file).unwrap();
+        std::fs::write(tsm_dir.join("_999999.tsm"), &data).unwrap();
+        let tombstone = crate::file_utils::make_tsm_tombstone_file_name(
+            tsm_dir,
+            crate::file_utils::get_tsm_file_id_by_path(&tsm_file).unwrap(),
+        );
+        std::fs::write(&tombstone, [0_u8; 3]).unwrap();
+        let report = check::check_storage(&storage);
+        assert_eq!(
+            issue_kinds(&report),
+            vec![
+                check::IssueKind::Tombstone,
+                check::IssueKind::Block,
+                check::IssueKind::OrphanFile
+            ]
+        );
+
+        // Index not found.
+        std::fs::remove_file(&tombstone).unwrap();
+        std::fs::remove_dir_all(storage.index_dir("db_check")).unwrap();
+        let report = check::check_storage(&storage);
+        assert_eq!(
+            issue_kinds(&report),
+            vec![
+                check::IssueKind::Block,
+                check::IssueKind::OrphanFile,
+                check::IssueKind::Index
+            ]
+        );
+
+        // File referenced by summary not found.
+        std::fs::remove_file(&tsm_file).unwrap();
+        let report = check::check_storage(&storage);
+        assert_eq!(
+            issue_kinds(&report),
+            vec![check::IssueKind::MissingFile, check::IssueKind::OrphanFile]
+        );
+    }
+
+    #[test]
+    fn test_check_storage() {
+        check_storage("/tmp/test/kvcore/check", false);
+    }
+
+    #[test]
+    fn test_check_storage_with_level_paths() {
+        check_storage("/tmp/test/kvcore/check_level_paths", true);
+    }
+
+    fn repair_storage(dir: &str, level_paths: bool) {
+        let storage = write_and_copy_storage(dir, "db_repair", level_paths);
+        let summary_dir = storage.summary_dir();
+        let summary_path = summary_dir.join("summary-000000");
+        let read_summary = || {
+            let rt = Runtime::new().unwrap();
+            let edits = rt
+                .block_on(crate::summary::read_summary_file(&summary_path))
+                .unwrap();
+            let mut ts_families: Vec<_> = check::replay_summary(edits)
+                .into_iter()
+                .map(|(tsf_id, tsf)| {
+                    let mut files: Vec<_> = tsf
+                        .files
+                        .values()
+                        .map(|m| (m.file_id, m.level, m.min_ts, m.max_ts, m.is_delta))
+                        .collect();
+                    files.sort_unstable();
+                    (tsf_id, tsf.database, tsf.vnode, files)
+                })
+                .collect();
+            ts_families.sort_by_key(|tsf| tsf.0);
+            ts_families
+        };
+        let ts_families = read_summary();
+        assert!(!ts_families.is_empty());
+
+        // Damaged summary file, TseriesFamilies in the readable part are kept.
+        let data = std::fs::read(&summary_path).unwrap();
+        std::fs::write(&summary_path, &data[..data.len() / 2]).unwrap();
+        let report = repair::repair_storage(&storage).unwrap();
+        assert_eq!(report.old_summary, Some(summary_dir.join("summary.bak")));
+        assert!(report.skipped_files.is_empty());
+        assert!(report.issues.is_empty());
+        assert_eq!(read_summary(), ts_families);
+        assert!(!check::check_storage(&storage).is_damaged());
+
+        // Lost summary file, vnodes are located by the index.
+        std::fs::remove_file(&summary_path).unwrap();
+        let report = repair::repair_storage(&storage).unwrap();
+        assert_eq!(report.old_summary, None);
+        assert!(report.ts_families.iter().all(|tsf| !tsf.in_old_summary));
+        assert_eq!(read_summary(), ts_families);
+        assert!(!check::check_storage(&storage).is_damaged());
+    }
+
+    #[test]
+    fn test_repair_storage() {
+        repair_storage("/tmp/test/kvcore/repair", false);
+    }
+
+    #[test]
+    fn test_repair_storage_with_level_paths() {
+        repair_storage("/tmp/test/kvcore/repair_level_paths", true);
+    }
+
+    #[test]
+    fn test_get_series_id_by_pattern_filter() {
+        let dir = "/tmp/test/kvcore/pattern_filter";
+        let database = "db_pattern";
+        let (rt, tskv) = open_tskv(dir, |_| {});
+
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let points = models_helper::create_dev_ops_points(&mut fbb, 100, database, "cpu");
+        fbb.finish(points, None);
+        let points = fbb.finished_data().to_vec();
+        let request = WritePointsRpcRequest { version: 1, points };
+        rt.block_on(tskv.write(request)).unwrap();
+
+        let get_series_ids = |tag: &str, domain: &Domain| {
+            let filter = ColumnDomains::of(tag.to_string(), domain);
+            let mut series_ids = tskv
+                .get_series_id_by_filter(database, "cpu", &filter)
+                .unwrap();
+            series_ids.sort_unstable();
+            series_ids
+        };
+        let get_series_ids_by_values = |tag: &str, values: &[&str]| {
+            let values: Vec<ScalarValue> = values
+                .iter()
+                .map(|v| ScalarValue::Utf8(Some(v.to_string())))
+                .collect();
+            let values: Vec<&ScalarValue> = values.iter().collect();
+            get_series_ids(tag, &Domain::of_values(&DataType::Utf8, true, &values))
+        };
+
+        let series_ids = get_series_ids(
+            "host",
+            &Domain::of_like("%.0.1", None, false, false).unwrap(),
+        );
+        assert!(!series_ids.is_empty());
+        assert_eq!(
+            series_ids,
+            get_series_ids_by_values("host", &["192.168.0.1"])
+        );
+
+        let series_ids = get_series_ids("region", &Domain::of_regex("^rg_[12]$", false).unwrap());
+        assert_eq!(
+            series_ids,
+            get_series_ids_by_values("region", &["rg_1", "rg_2"])
+        );
+
+        let series_ids = get_series_ids(
+            "host",
+            &Domain::of_like("192.168.0._", None, false, true).unwrap(),
+        );
+        assert!(series_ids.is_empty());
+    }
+
+    #[test]
+    fn test_series_limits() {
+        let dir = "/tmp/test/kvcore/series_limits";
+        let (rt, tskv) = open_tskv(dir, |config| {
+            config.storage.max_series_per_database = 5;
+        });
+
+        let mut schema = DatabaseSchema::new("db_series_limits");
+        schema.config.max_series_per_table = Some(3);
+        tskv.create_database(&schema).unwrap();
+
+        let write = |database: &str, num: usize| {
+            let mut fbb = flatbuffers::FlatBufferBuilder::new();
+            let points = models_helper::create_dev_ops_points(&mut fbb, num, database, "cpu");
+            fbb.finish(points, None);
+            let points = fbb.finished_data().to_vec();
+            let request = WritePointsRpcRequest { version: 1, points };
+            rt.block_on(tskv.write(request))
+        };
+        let series_num =
+            |database: &str| tskv.get_series_id_list(database, "cpu", &[]).unwrap().len();
+
+        // 36 series repeat in 100 points, points of the first 3 series are accepted.
+        let err = write("db_series_limits", 100).unwrap_err();
+        assert_eq!(err.error_code(), ErrorCode::TskvSeriesLimitExceeded);
+        assert!(matches!(
+            err,
+            Error::SeriesLimitExceeded {
+                points: 91,
+                source: IndexError::SeriesLimitExceeded { limit: 3, .. }
+            }
+        ));
+        assert_eq!(series_num("db_series_limits"), 3);
+        // Points of existing series are accepted.
+        write("db_series_limits", 3).unwrap();
+        let err = write("db_series_limits", 4).unwrap_err();
+        assert!(matches!(err, Error::SeriesLimitExceeded { points: 1, .. }));
+
+        // Limit of series in database is the default in config.
+        let err = write("db_default_limits", 100).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::SeriesLimitExceeded {
+                points: 85,
+                source: IndexError::SeriesLimitExceeded { limit: 5, .. }
+            }
+        ));
+        assert_eq!(series_num("db_default_limits"), 5);
+
+        // Points of rejected series are not written into WAL.
+        let wal_points_num = |database: &str| {
+            let (cb, rx) = tokio::sync::oneshot::channel();
+            let task = WalTask::SnapshotFiles { min_seq: 0, cb };
+            tskv.wal_sender.send(task).unwrap();
+            let files = rt.block_on(rx).unwrap().unwrap();
+            let dst = PathBuf::from(dir).join(database);
+            let wal_opt = tskv.options.wal.clone();
+            rt.block_on(wal::copy_entries(files, database, 0, &dst, wal_opt))
+                .unwrap();
+            let file = crate::file_system::file_manager::open_file(&dst).unwrap();
+            let mut reader = wal::reader(file).unwrap();
+            let mut num = 0;
+            while let Some(entry) = reader.next_wal_entry().unwrap() {
+                let mut buf = Vec::new();
+                get_str_codec(Encoding::Zstd)
+                    .decode(&entry.buf, &mut buf)
+                    .unwrap();
+                let points = flatbuffers::root::<fb_models::Points>(&buf[0]).unwrap();
+                num += points.points().unwrap().len();
+            }
+            num
+        };
+        assert_eq!(wal_points_num("db_series_limits"), 9 + 3 + 3);
+        assert_eq!(wal_points_num("db_default_limits"), 15);
+    }
+
+    #[test]
+    fn test_cardinality() {
+        let dir = "/tmp/test/kvcore/cardinality";
+        let database = "db_cardinality";
+        let (rt, tskv) = open_tskv(dir, |_| {});
+
+        // 36 series of 6 regions and 6 hosts.
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let points = models_helper::create_dev_ops_points(&mut fbb, 100, database, "cpu");
+        fbb.finish(points, None);
+        let points = fbb.finished_data().to_vec();
+        let request = WritePointsRpcRequest { version: 1, points };
+        rt.block_on(tskv.write(request)).unwrap();
+
+        let assert_estimate = |estimate: u64, exact: u64| {
+            assert!(
+                estimate.abs_diff(exact) <= 1,
+                "estimate {} of {}",
+                estimate,
+                exact
+            );
+        };
+        for tab in [None, Some("cpu")] {
+            assert_eq!(
+                tskv.get_series_cardinality(database, tab, true).unwrap(),
+                36
+            );
+            assert_estimate(
+                tskv.get_series_cardinality(database, tab, false).unwrap(),
+                36,
+            );
+            for tag in ["region", "host"] {
+                assert_eq!(
+                    tskv.get_tag_values_cardinality(database, tab, tag, true)
+                        .unwrap(),
+                    6
+                );
+                assert_estimate(
+                    tskv.get_tag_values_cardinality(database, tab, tag, false)
+                        .unwrap(),
+                    6,
+                );
+            }
+            for exact in [true, false] {
+                assert_eq!(
+                    tskv.get_tag_values_cardinality(database, tab, "usage_user", exact)
+                        .unwrap(),
+                    0
+                );
+            }
+        }
+        assert_eq!(
+            tskv.get_series_cardinality(database, Some("mem"), false)
+                .unwrap(),
+            0
+        );
+        assert!(matches!(
+            tskv.get_series_cardinality("db_not_exists", None, false),
+            Err(Error::DatabaseNotFound { .. })
+        ));
+
+        // Sketches of the table are removed with it.
+        tskv.drop_table(database, "cpu").unwrap();
+        for exact in [true, false] {
+            assert_eq!(
+                tskv.get_series_cardinality(database, Some("cpu"), exact)
+                    .unwrap(),
+                0
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_buffer_limit() {
+        let dir = "/tmp/test/kvcore/write_buffer_limit";
+        let max_total_buffer_size = 64 * 1024;
+        let (rt, tskv) = open_tskv(dir, |config| {
+            config.cache.max_total_buffer_size = max_total_buffer_size;
+            config.cache.write_stall_timeout = 100;
+        });
+
+        let write = |database: &str| {
+            let mut fbb = flatbuffers::FlatBufferBuilder::new();
+            let points = models_helper::create_dev_ops_points(&mut fbb, 100, database, "cpu");
+            fbb.finish(points, None);
+            let points = fbb.finished_data().to_vec();
+            let request = WritePointsRpcRequest { version: 1, points };
+            rt.block_on(tskv.write(request))
+        };
+
+        // Caches of all databases are flushed when they approach the limit.
+        write("db_write_buffer_1").unwrap();
+        let batch_size = tskv.write_buffer.memory_usage();
+        assert!(batch_size > 0);
+        for i in 0..30 {
+            write(&format!("db_write_buffer_{}", i % 3)).unwrap();
+        }
+        assert!(batch_size * 31 > 2 * max_total_buffer_size);
+        let start = std::time::Instant::now();
+        while tskv.write_buffer.memory_usage() >= max_total_buffer_size {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        // Writes fail with a retryable error if memory is not released in time.
+        tskv.write_buffer.reserve(max_total_buffer_size);
+        let err = write("db_write_buffer_1").unwrap_err();
+        assert!(matches!(err, Error::WriteBufferFull { .. }));
+        assert_eq!(err.error_code(), ErrorCode::TskvWriteBufferFull);
+        tskv.write_buffer.release(max_total_buffer_size);
+        write("db_write_buffer_1").unwrap();
+    }
+
+    #[test]
+    fn test_level0_write_stall() {
+        let dir = "/tmp/test/kvcore/level0_write_stall";
+        let (rt, tskv) = open_tskv(dir, |config| {
+            // Files in level 0 are not compacted.
+            config.storage.compact_trigger = 100;
+            config.storage.l0_slowdown_trigger = 1;
+            config.storage.l0_stop_trigger = 2;
+            config.cache.write_stall_timeout = 100;
+        });
+
+        let database = "db_write_stall";
+        // Data older than the data in column files is flushed into delta files in level 0.
+        let timestamp = now_timestamp() as i64;
+        let write = || {
+            let mut fbb = flatbuffers::FlatBufferBuilder::new();
+            let db = fbb.create_vector(database.as_bytes());
+            let table = fbb.create_vector("cpu".as_bytes());
+            let tags = models_helper::create_tags(&mut fbb, vec![("host", "192.168.0.1")]);
+            let value = 1.0_f64.to_be_bytes();
+            let fields = models_helper::create_fields(
+                &mut fbb,
+                vec![("usage", FieldType::Float, value.as_slice())],
+            );
+            let point = models_helper::create_point(&mut fbb, timestamp, db, table, tags, fields);
+            let points = fbb.create_vector(&[point]);
+            let points = Points::create(
+                &mut fbb,
+                &PointsArgs {
+                    db: Some(db),
+                    points: Some(points),
+                },
+            );
+            fbb.finish(points, None);
+            let points = fbb.finished_data().to_vec();
+            let request = WritePointsRpcRequest { version: 1, points };
+            rt.block_on(tskv.write(request))
+        };
+        let flush = || {
+            let db = tskv.version_set.read().get_db(database).unwrap();
+            rt.block_on(tskv.flush_database(&db)).unwrap();
+            let tsf = db.read().ts_families().values().next().cloned();
+            tsf.unwrap()
+        };
+
+        write().unwrap();
+        let tsf = flush();
+        assert_eq!(tsf.read().level0_file_num(), 0);
+        write().unwrap();
+        flush();
+        assert_eq!(tsf.read().level0_file_num(), 1);
+
+        // Writes are delayed but not rejected.
+        write().unwrap();
+        assert_eq!(tsf.read().check_write_stall(), WriteStallState::Slowdown);
+        flush();
+        assert_eq!(tsf.read().level0_file_num(), 2);
+
+        let err = write().unwrap_err();
+        assert!(matches!(
+            err,
+            Error::WriteStalled {
+                files: 2,
+                limit: 2,
+                ..
+            }
+        ));
+        assert_eq!(err.error_code(), ErrorCode::TskvWriteStalled);
+        assert_eq!(tsf.read().check_write_stall(), WriteStallState::Stop);
+    }
+
+    #[test]
+    fn test_wal_checkpoint() {
+        let dir = "/tmp/test/kvcore/wal_checkpoint";
+        let (rt, tskv) = open_tskv(dir, |_| {});
+
+        // The checkpoint waits for writes in progress.
+        let write_guard = rt.block_on(tskv.wal_write_lock.read());
+        let (wal_write_lock, version_set) = (tskv.wal_write_lock.clone(), tskv.version_set.clone());
+        let checkpoint =
+            rt.spawn(async move { wal_checkpoint(&wal_write_lock, &version_set, 10).await });
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(!checkpoint.is_finished());
+        drop(write_guard);
+        assert_eq!(rt.block_on(checkpoint).unwrap(), 11);
+
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let points = models_helper::create_dev_ops_points(&mut fbb, 10, "db_wal_checkpoint", "cpu");
+        fbb.finish(points, None);
+        let points = fbb.finished_data().to_vec();
+        rt.block_on(tskv.write(WritePointsRpcRequest { version: 1, points }))
+            .unwrap();
+        let checkpoint = rt.block_on(wal_checkpoint(&tskv.wal_write_lock, &tskv.version_set, 10));
+        assert_eq!(checkpoint, 1);
+    }
+
+    #[test]
+    fn test_level_paths() {
+        let dir = "/tmp/test/kvcore/level_paths";
+        let cold_dir = format!("{}/cold", dir);
+        let (rt, tskv) = open_tskv(dir, |config| {
+            config
+                .storage
+                .level_paths
+                .insert("1".to_string(), cold_dir.clone());
+        });
+
+        let database = "db_level_paths";
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let points = models_helper::create_dev_ops_points(&mut fbb, 10, database, "cpu");
+        fbb.finish(points, None);
+        let points = fbb.finished_data().to_vec();
+        rt.block_on(tskv.write(WritePointsRpcRequest { version: 1, points }))
+            .unwrap();
+        let db = tskv.version_set.read().get_db(database).unwrap();
+        rt.block_on(tskv.flush_database(&db)).unwrap();
+
+        // Flushed files of level 1 are under the path of level 1.
+        let mut files = vec![];
+        for tsf in db.read().ts_families().values() {
+            let version = tsf.read().version();
+            for file in version.levels_info()[1].files.iter() {
+                files.push(file.file_path());
+            }
+        }
+        assert!(!files.is_empty());
+        for file in files.iter() {
+            assert!(file.starts_with(&cold_dir));
+            assert!(file.exists());
+        }
+
+        drop(db);
+        tskv.drop_database(database).unwrap();
+        assert!(!Path::new(&cold_dir).join("data").join(database).exists());
+    }
+
+    #[test]
+    fn test_offload_cold_files() {
+        let dir = "/tmp/test/kvcore/offload_cold_files";
+        let remote_dir = format!("{}/remote", dir);
+        let (rt, tskv) = open_tskv(dir, |config| {
+            config.object_store.url = format!("file://{}", remote_dir);
+        });
+
+        let database = "db_offload";
+        let mut fbb = flatbuffers::FlatBufferBuilder::new();
+        let points = models_helper::create_dev_ops_points(&mut fbb, 100, database, "cpu");
+        fbb.finish(points, None);
+        let points = fbb.finished_data().to_vec();
+        rt.block_on(tskv.write(WritePointsRpcRequest { version: 1, points }))
+            .unwrap();
+        let db = tskv.version_set.read().get_db(database).unwrap();
+        rt.block_on(tskv.flush_database(&db)).unwrap();
+
+        let read_blocks = |path: &Path| {
+            let reader = TsmReader::open(path).unwrap();
+            let mut blocks = vec![];
+            for idx in reader.index_iterator() {
+                for blk_meta in idx.block_iterator() {
+                    blocks.push(reader.get_data_block(&blk_meta).unwrap());
+                }
+            }
+            blocks
+        };
+        let mut files = vec![];
+        for tsf in db.read().ts_families().values() {
+            for level in tsf.read().version().levels_info().iter() {
+                files.extend(level.files.iter().map(|f| f.file_path()));
+            }
+        }
+        assert!(!files.is_empty());
+        let blocks: Vec<Vec<DataBlock>> = files.iter().map(|f| read_blocks(f)).collect();
+        let mut file_sizes: Vec<u64> = files
+            .iter()
+            .map(|f| std::fs::metadata(f).unwrap().len())
+            .collect();
+        file_sizes.sort_unstable();
+
+        // Files with data newer than the offload age are kept.
+        assert_eq!(rt.block_on(tskv.offload_cold_files(3600)), 0);
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        assert_eq!(rt.block_on(tskv.offload_cold_files(0)), files.len());
+        assert_eq!(rt.block_on(tskv.offload_cold_files(0)), 0);
+
+        // Offloaded files are read from the object store transparently.
+        for (file, file_blocks) in files.iter().zip(blocks.iter()) {
+            assert!(remote::is_remote_file(file));
+            assert_eq!(&read_blocks(file), file_blocks);
+        }
+        let remote_files = walkdir::WalkDir::new(&remote_dir)
+            .into_iter()
+            .filter(|e| e.as_ref().unwrap().file_type().is_file())
+            .count();
+        assert_eq!(remote_files, files.len());
+        // Stub files are copied, so that they are opened again by check and repair,
+        // the index of the database is not under the storage path of this test.
+        let mut storage = tskv.options.storage.as_ref().clone();
+        storage.path = Path::new(dir).join("copy");
+        for entry in walkdir::WalkDir::new(&tskv.options.storage.path) {
+            let entry = entry.unwrap();
+            let relative = entry
+                .path()
+                .strip_prefix(&tskv.options.storage.path)
+                .unwrap();
+            if entry.file_type().is_dir() {
+                std::fs::create_dir_all(storage.path.join(relative)).unwrap();
+            } else {
+                std::fs::copy(entry.path(), storage.path.join(relative)).unwrap();
+            }
+        }
+        let report = check::check_storage(&storage);
+        assert!(
+            report
+                .issues
+                .iter()
+                .all(|i| i.kind == check::IssueKind::Index),
+            "{:?}",
+            report.issues
+        );
+        assert_eq!(report.column_files, files.len());
+        assert!(report.blocks > 0);
+        // Sizes of offloaded files in the repaired summary are not of the stub files.
+        repair::repair_storage(&storage).unwrap();
+        let summary_path = storage.summary_dir().join("summary-000000");
+        let edits = rt
+            .block_on(crate::summary::read_summary_file(&summary_path))
+            .unwrap();
+        let mut repaired_sizes: Vec<u64> = check::replay_summary(edits)
+            .values()
+            .flat_map(|tsf| tsf.files.values().map(|m| m.file_size))
+            .collect();
+        repaired_sizes.sort_unstable();
+        assert_eq!(repaired_sizes, file_sizes);
+
+        // Backups have data of offloaded files, which are kept after the
+        // objects are deleted with the database.
+        let backup_dir = format!("{}/backup", dir);
+        let manifest = rt
+            .block_on(tskv.backup_database(database, &backup_dir, None))
+            .unwrap();
+        for file in manifest.files.iter().filter(|f| f.path.ends_with(".tsm")) {
+            assert!(!remote::is_remote_file(
+                Path::new(&backup_dir).join(&file.path)
+            ));
+        }
+        drop(db);
+        tskv.drop_database(database).unwrap();
+        rt.block_on(tskv.restore_database(database, &backup_dir))
+            .unwrap();
+        let db = tskv.version_set.read().get_db(database).unwrap();
+        let mut restored_blocks = vec![];
+        for tsf in db.read().ts_families().values() {
+            for level in tsf.read().version().levels_info().iter() {
+                for file in level.files.iter() {
+                    assert!(!remote::is_remote_file(file.file_path()));
+                    restored_blocks.push(read_blocks(&file.file_path()));
+                }
+            }
+        }
+        restored_blocks.sort_by_key(|b| format!("{:?}", b));
+        let mut blocks = blocks;
+        blocks.sort_by_key(|b| format!("{:?}", b));
+        assert_eq!(restored_blocks, blocks);
+    }
 }
@@ -3,10 +3,13 @@
 #![allow(unreachable_patterns)]
 #![allow(unused_imports, unused_variables)]
 
+pub mod backup;
 mod byte_utils;
+pub mod check;
 mod compaction;
 mod context;
 pub mod database;
+pub mod dump;
 pub mod engine;
 pub mod error;
 pub mod file_system;
@@ -17,11 +20,13 @@ mod kvcore;
 pub mod memcache;
 mod reader;
 mod record_file;
+pub mod repair;
 mod summary;
 pub mod tseries_family;
 pub mod tsm;
 mod version_set;
 mod wal;
+pub mod write_buffer;
 
 pub use error::{Error, Result};
 pub use kv_option::Options;
@@ -1,16 +1,47 @@
 use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+
+use tskv::dump::{self, DumpFormat};
+use tskv::file_system::remote::{self, RemoteStore};
+use tskv::kv_option::StorageOptions;
+use tskv::tseries_family::TimeRange;
 
 const ARG_PRINT: &str = "print"; // To print something
+const ARG_CHECK: &str = "check"; // To check storage directories of a server
+const ARG_REPAIR: &str = "repair"; // To rebuild summary of storage directories of a server
+const ARG_DUMP: &str = "dump"; // To export data in a .tsm file
 const ARG_TSM: &str = "--tsm"; // To print a .tsm file
 const ARG_TOMBSTONE: &str = "--tombstone"; // To print a .tsm file with tombsotne
 const ARG_SUMMARY: &str = "--summary"; // To print a summary file
+const ARG_FORMAT: &str = "--format"; // Format of exported data
+const ARG_TIME_RANGE: &str = "--time-range"; // Time range of exported data
+const ARG_INDEX: &str = "--index"; // Index directory to resolve series keys
+const ARG_OUTPUT: &str = "--output"; // File to write exported data
+const ARG_CONFIG: &str = "--config"; // Configuration file of the server
 
 /// # Example
 /// tskv print [--tsm <tsm_path>] [--tombstone]
 /// tskv print [--summary <summary_path>]
+/// tskv check <data_dir> [--config <config_path>]
+/// tskv repair <data_dir> [--config <config_path>]
+/// tskv dump --tsm <tsm_path> --format lp|csv|parquet [--time-range <min_ts>,<max_ts>]
+///     [--index <index_path>] [--config <config_path>] [--output <output_path>]
 ///
 /// - --tsm <tsm_path> print statistics for .tsm file at <tsm_path> .
 /// - --tombstone also print tombstone for every field_id in .tsm file.
+/// - check <data_dir> print a JSON report of damages in storage directory <data_dir>
+///   (storage.path in config), exits with code 1 if there are any, root paths of levels
+///   (storage.level_paths) are also checked if --config is set.
+/// - repair <data_dir> rebuild summary file from column files in storage directory <data_dir>
+///   and root paths of levels in --config, the old summary file is copied to summary.bak .
+/// - dump --tsm <tsm_path> export data in .tsm file which are not deleted by tombstone,
+///   series keys and field names are resolved by index of the database if it exists,
+///   the index is located by storage directories in --config if --index is not set,
+///   data are written to stdout if --output is not set.
+/// - --config <config_path> also registers the object store (object_store.url) in the
+///   configuration file, which is needed to read offloaded column files.
 fn main() {
     let mut args = env::args().peekable();
 
@@ -21,6 +52,18 @@ fn main() {
     let mut show_summary = false;
     let mut summary_path: Option<String> = None;
 
+    let mut check_path: Option<String> = None;
+    let mut repair_path: Option<String> = None;
+    let mut config_path: Option<String> = None;
+
+    let mut dump = false;
+    let mut dump_tsm_path: Option<String> = None;
+    let mut dump_format: Option<String> = None;
+    let mut dump_time_range: Option<String> = None;
+    let mut dump_index_path: Option<String> = None;
+    let mut dump_config_path: Option<String> = None;
+    let mut dump_output_path: Option<String> = None;
+
     while let Some(arg) = args.peek() {
         // --print [--tsm <path>]
         if arg.as_str() == ARG_PRINT {
@@ -31,6 +74,7 @@ fn main() {
                         tsm_path = args.next();
                         if tsm_path.is_none() {
                             println!("Invalid arguments: --tsm <tsm_path>");
+                            std::process::exit(1);
                         }
                     }
                     ARG_TOMBSTONE => {
@@ -40,12 +84,52 @@ fn main() {
                         show_summary = true;
                         summary_path = args.next();
                         if summary_path.is_none() {
-                            println!("Invalid arguments: --summary <summary_path>")
+                            println!("Invalid arguments: --summary <summary_path>");
+                            std::process::exit(1);
                         }
                     }
                     _ => {}
                 }
             }
+        } else if arg.as_str() == ARG_CHECK {
+            args.next();
+            check_path = args.next();
+            if check_path.is_none() {
+                println!("Invalid arguments: check <data_dir> [--config <config_path>]");
+                std::process::exit(1);
+            }
+            continue;
+        } else if arg.as_str() == ARG_REPAIR {
+            args.next();
+            repair_path = args.next();
+            if repair_path.is_none() {
+                println!("Invalid arguments: repair <data_dir> [--config <config_path>]");
+                std::process::exit(1);
+            }
+            continue;
+        } else if arg.as_str() == ARG_CONFIG {
+            args.next();
+            config_path = args.next();
+            if config_path.is_none() {
+                println!("Invalid arguments: --config <config_path>");
+                std::process::exit(1);
+            }
+            continue;
+        } else if arg.as_str() == ARG_DUMP {
+            args.next();
+            dump = true;
+            while let Some(dump_arg) = args.next() {
+                match dump_arg.as_str() {
+                    ARG_TSM => dump_tsm_path = args.next(),
+                    ARG_FORMAT => dump_format = args.next(),
+                    ARG_TIME_RANGE => dump_time_range = args.next(),
+                    ARG_INDEX => dump_index_path = args.next(),
+                    ARG_CONFIG => dump_config_path = args.next(),
+                    ARG_OUTPUT => dump_output_path = args.next(),
+                    _ => {}
+                }
+            }
+            continue;
         }
         args.next();
     }
@@ -63,4 +147,131 @@ fn main() {
             tskv::print_summary_statistics(p);
         }
     }
+
+    if let Some(p) = check_path {
+        let report = tskv::check::check_storage(&data_dir_options(&p, config_path.as_deref()));
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => println!("Failed to serialize report: {}", e),
+        }
+        if report.is_damaged() {
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(p) = repair_path {
+        match tskv::repair::repair_storage(&data_dir_options(&p, config_path.as_deref())) {
+            Ok(report) => match serde_json::to_string_pretty(&report) {
+                Ok(json) => println!("{}", json),
+                Err(e) => println!("Failed to serialize report: {}", e),
+            },
+            Err(e) => {
+                println!("Failed to repair: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if dump {
+        let tsm_path = match dump_tsm_path {
+            Some(p) => PathBuf::from(p),
+            None => {
+                println!("Invalid arguments: dump --tsm <tsm_path>");
+                std::process::exit(1);
+            }
+        };
+        let format = match dump_format
+            .as_deref()
+            .unwrap_or_default()
+            .parse::<DumpFormat>()
+        {
+            Ok(f) => f,
+            Err(e) => {
+                println!("Invalid arguments: dump --format lp|csv|parquet: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let time_range = match dump_time_range.as_deref().map(parse_time_range) {
+            Some(Some(tr)) => tr,
+            Some(None) => {
+                println!("Invalid arguments: dump --time-range <min_ts>,<max_ts>");
+                std::process::exit(1);
+            }
+            None => TimeRange::all(),
+        };
+        let storage = dump_config_path.as_deref().map(storage_options);
+        let index_path = dump_index_path
+            .map(PathBuf::from)
+            .or_else(|| dump::default_index_dir(&tsm_path, storage.as_ref()));
+
+        let result = match dump_output_path {
+            Some(p) => File::create(&p).map_err(|e| e.to_string()).and_then(|f| {
+                dump::dump_tsm(
+                    &tsm_path,
+                    index_path.as_deref(),
+                    format,
+                    &time_range,
+                    BufWriter::new(f),
+                )
+                .map_err(|e| e.to_string())
+            }),
+            None => dump::dump_tsm(
+                &tsm_path,
+                index_path.as_deref(),
+                format,
+                &time_range,
+                BufWriter::new(io::stdout()),
+            )
+            .map_err(|e| e.to_string()),
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to dump: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Reads storage options from the configuration file of a server, the object store
+/// in it is registered so that offloaded column files can be read.
+fn storage_options(config_path: &str) -> StorageOptions {
+    let config = config::get_config(config_path);
+    let object_store = &config.object_store;
+    if !object_store.url.is_empty() {
+        match RemoteStore::open(
+            &object_store.url,
+            &object_store.endpoint,
+            &object_store.region,
+            &object_store.access_key_id,
+            &object_store.secret_access_key,
+        ) {
+            Ok(store) => {
+                remote::register_remote_store(store);
+            }
+            Err(e) => {
+                println!("Failed to open object store '{}': {}", object_store.url, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    StorageOptions::from(&config)
+}
+
+/// Returns storage options of the storage directory `data_dir`, root paths of levels
+/// are read from the configuration file if it's set.
+fn data_dir_options(data_dir: &str, config_path: Option<&str>) -> StorageOptions {
+    let mut storage = match config_path {
+        Some(p) => storage_options(p),
+        None => StorageOptions::default(),
+    };
+    storage.path = PathBuf::from(data_dir);
+    storage
+}
+
+/// Parses time range in format `<min_ts>,<max_ts>`.
+fn parse_time_range(s: &str) -> Option<TimeRange> {
+    let (min_ts, max_ts) = s.split_once(',')?;
+    Some(TimeRange::new(
+        min_ts.trim().parse().ok()?,
+        max_ts.trim().parse().ok()?,
+    ))
 }
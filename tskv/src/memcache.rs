@@ -221,15 +221,20 @@ impl SeriesData {
         self.groups.push(group);
     }
 
-    pub fn delete_data(&mut self, range: &TimeRange) {
+    /// Delete rows in the time range, returns the number of deleted rows.
+    pub fn delete_data(&mut self, range: &TimeRange) -> usize {
         if range.max_ts < self.range.min_ts || range.min_ts > self.range.max_ts {
-            return;
+            return 0;
         }
 
+        let mut deleted = 0;
         for item in self.groups.iter_mut() {
+            let len = item.rows.len();
             item.rows
                 .retain(|row| row.ts < range.min_ts || row.ts > range.max_ts);
+            deleted += len - item.rows.len();
         }
+        deleted
     }
 
     pub fn read_data(
@@ -325,7 +330,7 @@ impl MemCache {
 
     pub fn write_group(&self, sid: SeriesId, seq: u64, group: RowGroup) {
         let (_, sid) = split_id(sid);
-        self.seq_no.store(seq, Ordering::Relaxed);
+        self.seq_no.fetch_max(seq, Ordering::Relaxed);
         self.cache_size
             .fetch_add(group.size as u64, Ordering::Relaxed);
 
@@ -378,6 +383,17 @@ impl MemCache {
         }
     }
 
+    /// Delete rows of all series in the time range, returns the number of deleted rows.
+    pub fn delete_data_by_time_range(&self, range: &TimeRange) -> usize {
+        let mut deleted = 0;
+        for part in self.partions.iter() {
+            for data in part.read().values() {
+                deleted += data.write().delete_data(range);
+            }
+        }
+        deleted
+    }
+
     pub fn read_series_data(&self) -> Vec<(SeriesId, Arc<RwLock<SeriesData>>)> {
         let mut ret = Vec::new();
         self.partions.iter().for_each(|p| {
@@ -0,0 +1,322 @@
+//! Rebuilding the summary of a tskv storage directory from column files.
+//!
+//! Column files in `data/<database>/{tsm,delta}/<ts_family_id>/` under the storage
+//! path and root paths of levels are scanned and added to a new summary file,
+//! levels, sequences and vnodes of the TseriesFamilies are taken from the readable
+//! part of the old summary file.
+//! If a TseriesFamily is not found in the old summary, data are taken as not
+//! flushed (WAL is replayed from the start) and the vnode is located by the
+//! database schema and series stored in the index.
+//!
+//! Column files only contain series ids, series keys are not in them, so series
+//! missing in the index can't be rebuilt, `check::check_storage()` lists them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+
+use models::schema::DatabaseSchema;
+use models::utils::split_id;
+use models::FieldId;
+use serde::Serialize;
+use snafu::ResultExt;
+
+use crate::check::{self, CheckIssue, CheckReport, TsFamilyFiles};
+use crate::database;
+use crate::error::{self, Result};
+use crate::file_system::file_manager;
+use crate::index::DBIndex;
+use crate::kv_option::{StorageOptions, DATA_PATH, DELTA_PATH, TSM_PATH};
+use crate::summary::{self, CompactMeta, VersionEdit};
+use crate::tseries_family::VnodeInfo;
+use crate::{file_utils, TseriesFamilyId};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairedTsFamily {
+    pub id: TseriesFamilyId,
+    pub database: String,
+    pub vnode: VnodeInfo,
+    /// If the TseriesFamily is found in the old summary file.
+    pub in_old_summary: bool,
+    pub seq_no: u64,
+    pub files: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RepairReport {
+    pub path: PathBuf,
+    /// The replaced summary file.
+    pub old_summary: Option<PathBuf>,
+    pub ts_families: Vec<RepairedTsFamily>,
+    /// Column files which are not added to the new summary file.
+    pub skipped_files: Vec<PathBuf>,
+    /// Damages found in scanned column files.
+    pub issues: Vec<CheckIssue>,
+}
+
+/// Database schema and series ids in an index, series ids are mapped from
+/// series ids in field ids.
+type IndexSeries = (DatabaseSchema, HashMap<u64, u64>);
+
+/// A column file found in the storage directory.
+struct ScannedFile {
+    path: PathBuf,
+    file_id: u64,
+    is_delta: bool,
+    fields: Vec<(FieldId, crate::TimeRange)>,
+}
+
+/// Rebuilds the summary file of the storage directory `storage.path` from column files
+/// in it and in root paths of levels, the old summary file is copied to `summary.bak`.
+pub fn repair_storage(storage: &StorageOptions) -> Result<RepairReport> {
+    let summary_dir = storage.summary_dir();
+    let summary_path = file_utils::make_summary_file(&summary_dir, 0);
+    let mut report = RepairReport {
+        path: storage.path.clone(),
+        ..Default::default()
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context(error::IOSnafu)?;
+    let old_edits = if file_manager::try_exists(&summary_path) {
+        runtime.block_on(summary::read_summary_file_prefix(&summary_path))
+    } else {
+        vec![]
+    };
+    let mut max_file_id = old_edits
+        .iter()
+        .filter(|e| e.has_file_id)
+        .map(|e| e.file_id)
+        .max()
+        .unwrap_or(0);
+    let old_ts_families = check::replay_summary(old_edits);
+
+    let mut check_report = CheckReport::default();
+    let mut ts_families: BTreeMap<TseriesFamilyId, (String, Vec<ScannedFile>)> = BTreeMap::new();
+    let db_dirs = storage
+        .data_paths()
+        .into_iter()
+        .flat_map(|path| check::list_dirs(&path.join(DATA_PATH)));
+    for db_dir in db_dirs {
+        let database = match db_dir.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        for (sub_dir, is_delta) in [(TSM_PATH, false), (DELTA_PATH, true)] {
+            for tsf_dir in check::list_dirs(&db_dir.join(sub_dir)) {
+                let files = scan_column_files(
+                    &tsf_dir,
+                    is_delta,
+                    &mut check_report,
+                    &mut report.skipped_files,
+                );
+                let tsf_id = tsf_dir
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|n| n.parse::<TseriesFamilyId>().ok());
+                let (db, tsf_files) = match tsf_id {
+                    Some(id) => ts_families
+                        .entry(id)
+                        .or_insert_with(|| (database.clone(), vec![])),
+                    None => {
+                        report
+                            .skipped_files
+                            .extend(files.into_iter().map(|f| f.path));
+                        continue;
+                    }
+                };
+                // TseriesFamily ids are unique in all databases.
+                if *db != database {
+                    report
+                        .skipped_files
+                        .extend(files.into_iter().map(|f| f.path));
+                    continue;
+                }
+                tsf_files.extend(files);
+            }
+        }
+    }
+    report.issues = check_report.issues;
+
+    let mut db_series: HashMap<String, Option<IndexSeries>> = HashMap::new();
+    let mut edits = vec![];
+    for (tsf_id, (database, mut files)) in ts_families {
+        if files.is_empty() {
+            continue;
+        }
+        files.sort_by_key(|f| f.file_id);
+        let old_tsf = old_ts_families
+            .get(&tsf_id)
+            .filter(|tsf| tsf.database == database);
+
+        let mut metas = Vec::with_capacity(files.len());
+        for file in files.iter() {
+            max_file_id = max_file_id.max(file.file_id);
+            metas.push(compact_meta(tsf_id, file, old_tsf));
+        }
+        let (vnode, seq_no) = match old_tsf {
+            Some(tsf) => (tsf.vnode, tsf.seq_no),
+            None => {
+                let series = db_series
+                    .entry(database.clone())
+                    .or_insert_with(|| read_series(&storage.index_dir(&database)));
+                (locate_vnode(&database, series.as_ref(), &files, &metas), 0)
+            }
+        };
+
+        report.ts_families.push(RepairedTsFamily {
+            id: tsf_id,
+            database: database.clone(),
+            vnode,
+            in_old_summary: old_tsf.is_some(),
+            seq_no,
+            files: files.iter().map(|f| f.file_id).collect(),
+        });
+
+        let max_level_ts = metas.iter().map(|m| m.max_ts).max().unwrap_or(i64::MIN);
+        let mut edit = VersionEdit::new();
+        for meta in metas {
+            edit.add_file(meta, max_level_ts);
+        }
+        edit.add_tsfamily(tsf_id, database, vnode);
+        edit.has_seq_no = true;
+        edit.seq_no = seq_no;
+        edits.push(edit);
+    }
+    for edit in edits.iter_mut() {
+        edit.has_file_id = true;
+        edit.file_id = max_file_id;
+    }
+
+    fs::create_dir_all(&summary_dir).context(error::IOSnafu)?;
+    if file_manager::try_exists(&summary_path) {
+        let bak_path = file_utils::make_summary_file_bak(&summary_dir);
+        fs::copy(&summary_path, &bak_path).context(error::IOSnafu)?;
+        report.old_summary = Some(bak_path);
+    }
+    runtime.block_on(summary::rewrite_summary_file(&summary_dir, &edits))?;
+
+    Ok(report)
+}
+
+/// Checks column files in `dir`, returns files of which the index is readable.
+fn scan_column_files(
+    dir: &Path,
+    is_delta: bool,
+    report: &mut CheckReport,
+    skipped: &mut Vec<PathBuf>,
+) -> Vec<ScannedFile> {
+    let extension = if is_delta { "delta" } else { "tsm" };
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && p.extension().map(|e| e == extension).unwrap_or(false))
+            .collect(),
+        Err(_) => vec![],
+    };
+    paths.sort();
+
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let file_id = match file_utils::get_tsm_file_id_by_path(&path) {
+            Ok(id) => id,
+            Err(_) => {
+                skipped.push(path);
+                continue;
+            }
+        };
+        match check::check_column_file(&path, report) {
+            Some(fields) if !fields.is_empty() => files.push(ScannedFile {
+                path,
+                file_id,
+                is_delta,
+                fields,
+            }),
+            _ => skipped.push(path),
+        }
+    }
+    files
+}
+
+fn compact_meta(
+    tsf_id: TseriesFamilyId,
+    file: &ScannedFile,
+    old_tsf: Option<&TsFamilyFiles>,
+) -> CompactMeta {
+    // The stub file of an offloaded file is smaller than the column file.
+    let file_size = file_manager::open_column_file(&file.path)
+        .map(|f| f.len())
+        .unwrap_or(0);
+    let min_ts = file.fields.iter().map(|(_, tr)| tr.min_ts).min();
+    let max_ts = file.fields.iter().map(|(_, tr)| tr.max_ts).max();
+    let mut meta = CompactMeta::new(
+        file.file_id,
+        file_size,
+        tsf_id,
+        if file.is_delta { 0 } else { 1 },
+        min_ts.unwrap_or_default(),
+        max_ts.unwrap_or_default(),
+        file.is_delta,
+    );
+    // Levels and sequences are not in column files, files not in the old summary
+    // are put into level 1, or level 0 for delta files.
+    if let Some(old_meta) = old_tsf
+        .and_then(|tsf| tsf.files.get(&file.file_id))
+        .filter(|m| m.is_delta == file.is_delta)
+    {
+        meta.level = old_meta.level;
+        meta.high_seq = old_meta.high_seq;
+        meta.low_seq = old_meta.low_seq;
+    }
+    meta
+}
+
+/// Reads the database schema and ids of series in the index at `index_dir`.
+fn read_series(index_dir: &Path) -> Option<IndexSeries> {
+    if !file_manager::try_exists(index_dir) {
+        return None;
+    }
+    let database = index_dir.file_name()?.to_str()?.to_string();
+    // Opening index panics if it is locked or damaged.
+    panic::catch_unwind(|| {
+        let index = DBIndex::new(index_dir, DatabaseSchema::new(&database));
+        let series_ids = index.get_all_series_ids().ok()?;
+        Some((
+            index.db_schema(),
+            series_ids
+                .into_iter()
+                .map(|sid| (split_id(sid).1, sid))
+                .collect(),
+        ))
+    })
+    .ok()
+    .flatten()
+}
+
+/// Locates the vnode of a TseriesFamily by the database schema and a series in it.
+fn locate_vnode(
+    database: &str,
+    series: Option<&IndexSeries>,
+    files: &[ScannedFile],
+    metas: &[CompactMeta],
+) -> VnodeInfo {
+    let min_ts = metas.iter().map(|m| m.min_ts).min().unwrap_or_default();
+    let (schema, series_hash) = match series {
+        Some((schema, series_ids)) => {
+            let series_hash = files
+                .iter()
+                .flat_map(|f| f.fields.iter())
+                .find_map(|(fid, _)| series_ids.get(&split_id(*fid).1))
+                .map(|sid| split_id(*sid).0 as u64)
+                .unwrap_or(0);
+            (schema.clone(), series_hash)
+        }
+        None => (DatabaseSchema::new(database), 0),
+    };
+    let (duration, shard_num) = database::vnode_options(&schema);
+    VnodeInfo::locate(series_hash, min_ts, duration, shard_num)
+}
@@ -1,7 +1,7 @@
 use std::cmp::max;
 use std::fmt::Display;
-use std::fs::{remove_file, rename};
-use std::path::Path;
+use std::fs::{remove_file, rename, File};
+use std::path::{Path, PathBuf};
 use std::{borrow::Borrow, collections::HashMap, sync::Arc};
 
 use futures::TryFutureExt;
@@ -9,6 +9,7 @@ use libc::write;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
 use tokio::sync::watch::Receiver;
 use tokio::sync::{mpsc::UnboundedSender, oneshot::Sender};
 
@@ -20,16 +21,20 @@ use crate::compaction::FlushReq;
 use crate::file_system::file_manager::try_exists;
 use crate::{
     context::GlobalContext,
-    error::{Error, Result},
+    error::{self, Error, Result},
     file_utils,
     kv_option::{Options, StorageOptions},
-    record_file::{Reader, RecordFileError, Writer},
-    tseries_family::{ColumnFile, LevelInfo, Version},
+    record_file::{Reader, Record, RecordFileError, Writer},
+    tseries_family::{ColumnFile, LevelInfo, Version, VnodeInfo},
     version_set::VersionSet,
     LevelId, TseriesFamilyId,
 };
 
 const MAX_BATCH_SIZE: usize = 64;
+/// Data version of summary records written before `VersionEdit` carried a `vnode`.
+const VERSION_EDIT_V1: u8 = 1;
+/// Data version of summary records written by this version.
+const VERSION_EDIT_V2: u8 = 2;
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct CompactMeta {
@@ -116,6 +121,43 @@ pub struct VersionEdit {
     pub add_tsf: bool,
     pub tsf_id: TseriesFamilyId,
     pub tsf_name: String,
+    pub vnode: VnodeInfo,
+}
+
+/// Layout of `VersionEdit` in records with data version `VERSION_EDIT_V1`.
+#[derive(Serialize, Deserialize)]
+struct VersionEditV1 {
+    has_seq_no: bool,
+    seq_no: u64,
+    has_file_id: bool,
+    file_id: u64,
+    max_level_ts: Timestamp,
+    add_files: Vec<CompactMeta>,
+    del_files: Vec<CompactMeta>,
+
+    del_tsf: bool,
+    add_tsf: bool,
+    tsf_id: TseriesFamilyId,
+    tsf_name: String,
+}
+
+impl From<VersionEditV1> for VersionEdit {
+    fn from(ve: VersionEditV1) -> Self {
+        Self {
+            has_seq_no: ve.has_seq_no,
+            seq_no: ve.seq_no,
+            has_file_id: ve.has_file_id,
+            file_id: ve.file_id,
+            max_level_ts: ve.max_level_ts,
+            add_files: ve.add_files,
+            del_files: ve.del_files,
+            del_tsf: ve.del_tsf,
+            add_tsf: ve.add_tsf,
+            tsf_id: ve.tsf_id,
+            tsf_name: ve.tsf_name,
+            vnode: VnodeInfo::default(),
+        }
+    }
 }
 
 impl Default for VersionEdit {
@@ -138,6 +180,7 @@ impl VersionEdit {
             has_seq_no: false,
             has_file_id: false,
             max_level_ts: i64::MIN,
+            vnode: VnodeInfo::default(),
         }
     }
 
@@ -148,6 +191,17 @@ impl VersionEdit {
         bincode::deserialize(buf).map_err(|e| Error::Decode { source: (e) })
     }
 
+    /// Decodes a summary record, upgrading edits written in an older layout.
+    pub fn decode_record(record: &Record) -> Result<Self> {
+        if record.data_version == VERSION_EDIT_V1 {
+            bincode::deserialize::<VersionEditV1>(&record.data)
+                .map(VersionEdit::from)
+                .map_err(|e| Error::Decode { source: (e) })
+        } else {
+            Self::decode(&record.data)
+        }
+    }
+
     pub fn add_file(&mut self, compact_meta: CompactMeta, max_level_ts: i64) {
         self.has_seq_no = true;
         self.seq_no = compact_meta.high_seq;
@@ -167,10 +221,11 @@ impl VersionEdit {
         });
     }
 
-    pub fn add_tsfamily(&mut self, tsf_id: u32, tsf_name: String) {
+    pub fn add_tsfamily(&mut self, tsf_id: u32, tsf_name: String, vnode: VnodeInfo) {
         self.add_tsf = true;
         self.tsf_name = tsf_name;
         self.tsf_id = tsf_id;
+        self.vnode = vnode;
     }
 
     pub fn del_tsfamily(&mut self, tsf_id: u32) {
@@ -205,6 +260,8 @@ pub struct Summary {
     ctx: Arc<GlobalContext>,
     writer: Writer,
     opt: Arc<Options>,
+    /// Size of the summary file after the last rewriting, 0 if not rewritten since open.
+    snapshot_size: u64,
 }
 
 impl Summary {
@@ -218,7 +275,7 @@ impl Summary {
             Writer::new(&file_utils::make_summary_file(opt.storage.summary_dir(), 0)).unwrap();
         let buf = db.encode()?;
         let _ = w
-            .write_record(1, EditType::SummaryEdit.into(), &buf)
+            .write_record(VERSION_EDIT_V2, EditType::SummaryEdit.into(), &buf)
             .map_err(|e| Error::LogRecordErr { source: (e) })
             .await?;
         w.hard_sync()
@@ -235,6 +292,7 @@ impl Summary {
             ctx: Arc::new(GlobalContext::default()),
             writer: w,
             opt,
+            snapshot_size: 0,
         })
     }
 
@@ -254,6 +312,7 @@ impl Summary {
             ctx,
             writer,
             opt,
+            snapshot_size: 0,
         })
     }
 
@@ -265,7 +324,7 @@ impl Summary {
         flush_task_sender: UnboundedSender<FlushReq>,
     ) -> Result<VersionSet> {
         let mut edits: HashMap<TseriesFamilyId, Vec<VersionEdit>> = HashMap::default();
-        let mut databases: HashMap<TseriesFamilyId, String> = HashMap::default();
+        let mut databases: HashMap<TseriesFamilyId, (String, VnodeInfo)> = HashMap::default();
 
         let mut tsf_id = 0;
         loop {
@@ -275,11 +334,12 @@ impl Summary {
                 .map_err(|e| Error::LogRecordErr { source: (e) });
             match res {
                 Ok(result) => {
-                    let ed = VersionEdit::decode(&result.data)?;
+                    let ed = VersionEdit::decode_record(&result)?;
                     if ed.add_tsf {
                         tsf_id = max(ed.tsf_id, tsf_id);
-                        edits.insert(ed.tsf_id, vec![]);
-                        databases.insert(ed.tsf_id, ed.tsf_name.clone());
+                        databases.insert(ed.tsf_id, (ed.tsf_name.clone(), ed.vnode));
+                        // Edits written by rewriting the summary file contain files.
+                        edits.entry(ed.tsf_id).or_default().push(ed);
                     } else if ed.del_tsf {
                         edits.remove(&ed.tsf_id);
                         databases.remove(&ed.tsf_id);
@@ -298,7 +358,7 @@ impl Summary {
         let mut has_file_id = false;
         let mut file_id = 0_u64;
         for (id, eds) in edits {
-            let database = databases.get(&id).unwrap().to_owned();
+            let (database, vnode) = databases.get(&id).unwrap().to_owned();
             // let cf_opts = cf_options.remove(cf_name).unwrap_or_default();
 
             let mut files: HashMap<u64, CompactMeta> = HashMap::new();
@@ -307,11 +367,11 @@ impl Summary {
             for e in eds {
                 if e.has_seq_no {
                     has_seq_no = true;
-                    seq_no = e.seq_no;
+                    seq_no = seq_no.max(e.seq_no);
                 }
                 if e.has_file_id {
                     has_file_id = true;
-                    file_id = e.file_id;
+                    file_id = file_id.max(e.file_id);
                 }
                 max_log = std::cmp::max(max_log, e.seq_no);
                 max_level_ts = std::cmp::max(max_level_ts, e.max_level_ts);
@@ -334,6 +394,7 @@ impl Summary {
                 max_log,
                 levels,
                 max_level_ts,
+                vnode,
             );
             versions.insert(id, Arc::new(ver));
         }
@@ -363,7 +424,7 @@ impl Summary {
             let buf = edit.encode()?;
             let _ = self
                 .writer
-                .write_record(1, EditType::SummaryEdit.into(), &buf)
+                .write_record(VERSION_EDIT_V2, EditType::SummaryEdit.into(), &buf)
                 .map_err(|e| Error::LogRecordErr { source: (e) })
                 .await?;
             self.writer
@@ -394,46 +455,53 @@ impl Summary {
         Ok(())
     }
 
+    /// Rewrites the summary file with a snapshot of the current versions if it
+    /// exceeds both `max_summary_size` and twice the size of the last snapshot, so
+    /// that it won't grow unbounded, and won't be rewritten on every edit if the
+    /// snapshot itself is larger than `max_summary_size`.
     async fn roll_summary_file(&mut self) -> Result<()> {
-        if self.writer.file_size() >= self.opt.storage.max_summary_size {
-            let mut edits = vec![];
-            let mut files = vec![];
-            {
-                let vs = self.version_set.read();
-                let dbs = vs.get_all_db();
-                for (name, db) in dbs {
-                    let (mut tsf, mut tmp_files) = db.read().version_edit(self.ctx.last_seq());
-                    edits.append(&mut tsf);
-                    files.append(&mut tmp_files);
-                }
+        let file_size = self.writer.file_size();
+        let threshold = self
+            .opt
+            .storage
+            .max_summary_size
+            .max(self.snapshot_size.saturating_mul(2));
+        if file_size < threshold {
+            return Ok(());
+        }
 
-                edits.append(&mut files);
-            }
+        let edits = self.snapshot_edits();
+        let summary_dir = self.opt.storage.summary_dir();
+        info!(
+            "Summary file size {} exceeds {}, rewriting with {} edits",
+            file_size,
+            threshold,
+            edits.len()
+        );
+        rewrite_summary_file(&summary_dir, &edits).await?;
+
+        let path = file_utils::make_summary_file(&summary_dir, self.file_no);
+        self.writer = Writer::new(&path).ok_or_else(|| Error::OpenFile {
+            path,
+            source: std::io::Error::new(std::io::ErrorKind::Other, "failed to open summary file"),
+        })?;
+        self.snapshot_size = self.writer.file_size();
+        Ok(())
+    }
 
-            let new_path = &file_utils::make_summary_file_tmp(self.opt.storage.summary_dir());
-            let old_path = &self.writer.path().clone();
-            if try_exists(new_path) {
-                match remove_file(new_path) {
-                    Ok(_) => (),
-                    Err(e) => {
-                        error!("failed remove file {:?}, in case {:?}", new_path, e);
-                    }
-                };
-            }
-            self.writer = Writer::new(new_path).unwrap();
-            self.write_summary(edits).await?;
-            match rename(new_path, old_path) {
-                Ok(_) => (),
-                Err(e) => {
-                    error!(
-                        "failed remove old file {:?}, and create new file {:?},in case {:?}",
-                        old_path, new_path, e
-                    );
-                }
-            };
-            self.writer = Writer::new(old_path).unwrap();
+    /// Returns `VersionEdit`s describing the current versions, one for each TseriesFamily.
+    fn snapshot_edits(&self) -> Vec<VersionEdit> {
+        let mut edits = vec![];
+        for db in self.version_set.read().get_all_db().values() {
+            edits.append(&mut db.read().version_edit());
         }
-        Ok(())
+        // File ids allocated for files not in any version must not be used again.
+        let max_file_id = self.ctx.file_id().saturating_sub(1);
+        for edit in edits.iter_mut() {
+            edit.has_file_id = true;
+            edit.file_id = edit.file_id.max(max_file_id);
+        }
+        edits
     }
 
     pub fn version_set(&self) -> Arc<RwLock<VersionSet>> {
@@ -445,6 +513,86 @@ impl Summary {
     }
 }
 
+/// Replaces the summary file in `summary_dir` with a new file of `edits`.
+///
+/// The edits are written into a temporary file which is renamed to the summary file
+/// after being synced, if it crashes before the rename, the temporary file is ignored
+/// when recovering and removed by the next rewriting.
+pub(crate) async fn rewrite_summary_file(summary_dir: &Path, edits: &[VersionEdit]) -> Result<()> {
+    let tmp_path = write_summary_tmp_file(summary_dir, edits).await?;
+    rename(&tmp_path, file_utils::make_summary_file(summary_dir, 0)).context(error::IOSnafu)?;
+    // Sync the directory to persist the rename.
+    File::open(summary_dir)
+        .and_then(|dir| dir.sync_all())
+        .context(error::IOSnafu)
+}
+
+/// Writes `edits` into the temporary summary file in `summary_dir`, returns it's path.
+async fn write_summary_tmp_file(summary_dir: &Path, edits: &[VersionEdit]) -> Result<PathBuf> {
+    let tmp_path = file_utils::make_summary_file_tmp(summary_dir);
+    if try_exists(&tmp_path) {
+        remove_file(&tmp_path).context(error::IOSnafu)?;
+    }
+    write_summary_file(&tmp_path, edits).await?;
+    Ok(tmp_path)
+}
+
+/// Writes `edits` into a new summary file at `path`.
+pub(crate) async fn write_summary_file(path: &Path, edits: &[VersionEdit]) -> Result<()> {
+    let mut writer = Writer::new(path).ok_or_else(|| Error::OpenFile {
+        path: path.to_path_buf(),
+        source: std::io::Error::new(std::io::ErrorKind::Other, "failed to open summary file"),
+    })?;
+    for edit in edits {
+        let buf = edit.encode()?;
+        writer
+            .write_record(VERSION_EDIT_V2, EditType::SummaryEdit.into(), &buf)
+            .map_err(|e| Error::LogRecordErr { source: (e) })
+            .await?;
+    }
+    writer
+        .hard_sync()
+        .map_err(|e| Error::LogRecordErr { source: e })
+        .await?;
+    writer
+        .close()
+        .map_err(|e| Error::LogRecordErr { source: e })
+        .await
+}
+
+/// Reads all `VersionEdit`s in the summary file at `path`.
+pub(crate) async fn read_summary_file(path: &Path) -> Result<Vec<VersionEdit>> {
+    let mut reader = Reader::new(path).ok_or_else(|| Error::OpenFile {
+        path: path.to_path_buf(),
+        source: std::io::Error::new(std::io::ErrorKind::Other, "failed to open summary file"),
+    })?;
+    let mut edits = vec![];
+    loop {
+        match reader.read_record().await {
+            Ok(record) => edits.push(VersionEdit::decode_record(&record)?),
+            Err(RecordFileError::Eof) => break,
+            Err(e) => return Err(Error::LogRecordErr { source: e }),
+        }
+    }
+    Ok(edits)
+}
+
+/// Reads `VersionEdit`s in the summary file at `path` until the first damaged record.
+pub(crate) async fn read_summary_file_prefix(path: &Path) -> Vec<VersionEdit> {
+    let mut reader = match Reader::new(path) {
+        Some(r) => r,
+        None => return vec![],
+    };
+    let mut edits = vec![];
+    while let Ok(record) = reader.read_record().await {
+        match VersionEdit::decode_record(&record) {
+            Ok(edit) => edits.push(edit),
+            Err(_) => break,
+        }
+    }
+    edits
+}
+
 pub fn print_summary_statistics(path: impl AsRef<Path>) {
     let runtime = tokio::runtime::Builder::new_current_thread()
         .enable_all()
@@ -457,7 +605,7 @@ pub fn print_summary_statistics(path: impl AsRef<Path>) {
         loop {
             match reader.read_record().await {
                 Ok(record) => {
-                    let ve = VersionEdit::decode(&record.data).unwrap();
+                    let ve = VersionEdit::decode_record(&record).unwrap();
                     println!("VersionEdit #{}", i);
                     println!("------------------------------------------------------------");
                     i += 1;
@@ -594,11 +742,16 @@ mod test {
     use models::schema::DatabaseSchema;
 
     use crate::file_system::file_manager;
-    use crate::tseries_family::LevelInfo;
+    use crate::tseries_family::{LevelInfo, VnodeInfo};
     use crate::{
-        error,
+        error, file_utils,
         kv_option::{Options, StorageOptions},
-        summary::{CompactMeta, EditType, Summary, VersionEdit},
+        record_file::Writer,
+        summary::{
+            rewrite_summary_file, write_summary_tmp_file, CompactMeta, EditType, Summary,
+            VersionEdit, VersionEditV1, VERSION_EDIT_V1,
+        },
+        LevelId, TseriesFamilyId,
     };
 
     #[tokio::test]
@@ -642,7 +795,7 @@ mod test {
             .await
             .unwrap();
         let mut edit = VersionEdit::new();
-        edit.add_tsfamily(100, "hello".to_string());
+        edit.add_tsfamily(100, "hello".to_string(), VnodeInfo::default());
         summary.apply_version_edit(vec![edit]).await.unwrap();
         let summary = Summary::recover(opt.clone(), flush_task_sender.clone())
             .await
@@ -662,7 +815,7 @@ mod test {
             .unwrap();
 
         let mut edit = VersionEdit::new();
-        edit.add_tsfamily(100, "hello".to_string());
+        edit.add_tsfamily(100, "hello".to_string(), VnodeInfo::default());
         summary.apply_version_edit(vec![edit]).await.unwrap();
         let mut summary = Summary::recover(opt.clone(), flush_task_sender.clone())
             .await
@@ -718,10 +871,15 @@ mod test {
             .write()
             .create_db(DatabaseSchema::new(&database));
         for i in 0..40 {
-            db.write()
-                .add_tsfamily(i, 0, summary_task_sender.clone(), flush_task_sender.clone());
+            db.write().add_tsfamily(
+                i,
+                0,
+                VnodeInfo::default(),
+                summary_task_sender.clone(),
+                flush_task_sender.clone(),
+            );
             let mut edit = VersionEdit::new();
-            edit.add_tsfamily(i, database.clone());
+            edit.add_tsfamily(i, database.clone(), VnodeInfo::default());
             edits.push(edit.clone());
         }
 
@@ -764,13 +922,14 @@ mod test {
         db.write().add_tsfamily(
             10,
             0,
+            VnodeInfo::default(),
             summary_task_sender.clone(),
             flush_task_sender.clone(),
         );
 
         let mut edits = vec![];
         let mut edit = VersionEdit::new();
-        edit.add_tsfamily(10, "hello".to_string());
+        edit.add_tsfamily(10, "hello".to_string(), VnodeInfo::default());
         edits.push(edit);
 
         for _ in 0..100 {
@@ -822,4 +981,243 @@ mod test {
         assert_eq!(tsf.read().version().levels_info[1].files[0].size(), 100);
         assert_eq!(summary.ctx.file_id(), 16);
     }
+
+    /// Returns (tsf_id, level, file_id) of all column files in the summary.
+    fn summary_files(summary: &Summary) -> Vec<(TseriesFamilyId, LevelId, u64)> {
+        let mut files = vec![];
+        let vs = summary.version_set.read();
+        for db in vs.get_all_db().values() {
+            for (tsf_id, tsf) in db.read().ts_families() {
+                for level in tsf.read().version().levels_info.iter() {
+                    for file in level.files.iter() {
+                        files.push((*tsf_id, level.level(), file.file_id()));
+                    }
+                }
+            }
+        }
+        files.sort_unstable();
+        files
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_summary() {
+        let base_dir = "/tmp/test/summary/rewrite".to_string();
+        let _ = fs::remove_dir_all(&base_dir);
+        let mut config = get_config("../config/config.toml");
+        config.storage.path = base_dir.clone();
+        config.storage.max_summary_size = 4096;
+        let opt = Arc::new(Options::from(&config));
+        let summary_dir = opt.storage.summary_dir();
+        fs::create_dir_all(&summary_dir).unwrap();
+        let summary_path = file_utils::make_summary_file(&summary_dir, 0);
+        let tmp_path = file_utils::make_summary_file_tmp(&summary_dir);
+
+        let (flush_task_sender, _) = mpsc::unbounded_channel();
+        let (summary_task_sender, _) = mpsc::unbounded_channel();
+        let mut summary = Summary::new(opt.clone(), flush_task_sender.clone())
+            .await
+            .unwrap();
+        let db = summary
+            .version_set
+            .write()
+            .create_db(DatabaseSchema::new("test"));
+        let mut edits = vec![];
+        for tsf_id in [1, 2] {
+            db.write().add_tsfamily(
+                tsf_id,
+                0,
+                VnodeInfo::default(),
+                summary_task_sender.clone(),
+                flush_task_sender.clone(),
+            );
+            let mut edit = VersionEdit::new();
+            edit.add_tsfamily(tsf_id, "test".to_string(), VnodeInfo::default());
+            edits.push(edit);
+        }
+        summary.apply_version_edit(edits).await.unwrap();
+
+        // Each file is added and then compacted into the next file, only the last
+        // file of each TseriesFamily is left.
+        let mut written = 0;
+        for file_id in 0..200_u64 {
+            let tsf_id = (file_id % 2) as TseriesFamilyId + 1;
+            let mut edit = VersionEdit::new();
+            edit.add_file(
+                CompactMeta {
+                    file_id,
+                    file_size: 100,
+                    tsf_id,
+                    level: 1,
+                    min_ts: file_id as i64,
+                    max_ts: file_id as i64,
+                    high_seq: file_id,
+                    ..Default::default()
+                },
+                file_id as i64,
+            );
+            if file_id >= 2 {
+                edit.del_file(1, file_id - 2, false);
+            }
+            let buf = edit.encode().unwrap();
+            written += buf.len() as u64;
+            summary.ctx.file_id_next();
+            summary.apply_version_edit(vec![edit]).await.unwrap();
+        }
+        // Allocated file ids are kept even if the files are not in any version.
+        summary.ctx.file_id_next();
+        let expected = vec![(1, 1, 198), (2, 1, 199)];
+        assert_eq!(summary_files(&summary), expected);
+        assert!(written > opt.storage.max_summary_size);
+        assert!(fs::metadata(&summary_path).unwrap().len() < opt.storage.max_summary_size);
+
+        let recovered = Summary::recover(opt.clone(), flush_task_sender.clone())
+            .await
+            .unwrap();
+        assert_eq!(summary_files(&recovered), expected);
+        {
+            let vs = recovered.version_set.read();
+            let tsf = vs.get_tsfamily_by_tf_id(2).unwrap();
+            assert_eq!(tsf.read().version().last_seq, 199);
+            assert_eq!(tsf.read().version().max_level_ts, 199);
+        }
+
+        // Crashed when writing the temporary file, the summary file is not changed.
+        fs::write(&tmp_path, b"broken summary").unwrap();
+        let recovered = Summary::recover(opt.clone(), flush_task_sender.clone())
+            .await
+            .unwrap();
+        assert_eq!(summary_files(&recovered), expected);
+
+        // Crashed after the temporary file is written but before it's renamed,
+        // the summary file is not changed.
+        let mut edits = summary.snapshot_edits();
+        edits[0].del_file(1, 198, false);
+        let path = write_summary_tmp_file(&summary_dir, &edits).await.unwrap();
+        assert_eq!(path, tmp_path);
+        let recovered = Summary::recover(opt.clone(), flush_task_sender.clone())
+            .await
+            .unwrap();
+        assert_eq!(summary_files(&recovered), expected);
+
+        // Crashed after the temporary file is renamed.
+        rewrite_summary_file(&summary_dir, &summary.snapshot_edits())
+            .await
+            .unwrap();
+        assert!(!file_manager::try_exists(&tmp_path));
+        let recovered = Summary::recover(opt.clone(), flush_task_sender.clone())
+            .await
+            .unwrap();
+        assert_eq!(summary_files(&recovered), expected);
+        assert_eq!(recovered.ctx.file_id(), 201);
+    }
+
+    #[tokio::test]
+    async fn test_roll_summary_threshold() {
+        let base_dir = "/tmp/test/summary/roll_threshold".to_string();
+        let _ = fs::remove_dir_all(&base_dir);
+        let mut config = get_config("../config/config.toml");
+        config.storage.path = base_dir.clone();
+        // The snapshot is always larger than max_summary_size.
+        config.storage.max_summary_size = 1;
+        let opt = Arc::new(Options::from(&config));
+        fs::create_dir_all(opt.storage.summary_dir()).unwrap();
+
+        let (flush_task_sender, _) = mpsc::unbounded_channel();
+        let (summary_task_sender, _) = mpsc::unbounded_channel();
+        let mut summary = Summary::new(opt.clone(), flush_task_sender.clone())
+            .await
+            .unwrap();
+        let db = summary
+            .version_set
+            .write()
+            .create_db(DatabaseSchema::new("test"));
+        db.write().add_tsfamily(
+            1,
+            0,
+            VnodeInfo::default(),
+            summary_task_sender,
+            flush_task_sender,
+        );
+        let mut edit = VersionEdit::new();
+        edit.add_tsfamily(1, "test".to_string(), VnodeInfo::default());
+        summary.apply_version_edit(vec![edit]).await.unwrap();
+        let snapshot_size = summary.snapshot_size;
+        assert!(snapshot_size > opt.storage.max_summary_size);
+
+        // Not rewritten until the summary file is twice the size of the snapshot.
+        let mut edits_num = 0;
+        loop {
+            let file_size = summary.writer.file_size();
+            let mut edit = VersionEdit::new();
+            edit.set_tsf_id(1);
+            summary.apply_version_edit(vec![edit]).await.unwrap();
+            edits_num += 1;
+            if summary.writer.file_size() < file_size {
+                assert!(file_size < snapshot_size * 2);
+                break;
+            }
+            assert_eq!(summary.snapshot_size, snapshot_size);
+        }
+        assert!(edits_num > 1);
+    }
+
+    #[tokio::test]
+    async fn test_recover_v1_version_edits() {
+        let base_dir = "/tmp/test/summary/v1_edits".to_string();
+        let _ = fs::remove_dir_all(&base_dir);
+        let mut config = get_config("../config/config.toml");
+        config.storage.path = base_dir.clone();
+        let opt = Arc::new(Options::from(&config));
+        let summary_dir = opt.storage.summary_dir();
+        fs::create_dir_all(&summary_dir).unwrap();
+
+        let edit_v1 = |add_tsf: bool, add_files: Vec<CompactMeta>| VersionEditV1 {
+            has_seq_no: !add_tsf,
+            seq_no: if add_tsf { 0 } else { 10 },
+            has_file_id: !add_tsf,
+            file_id: if add_tsf { 0 } else { 1 },
+            max_level_ts: 100,
+            add_files,
+            del_files: vec![],
+            del_tsf: false,
+            add_tsf,
+            tsf_id: 1,
+            tsf_name: "test".to_string(),
+        };
+        let edits = vec![
+            edit_v1(true, vec![]),
+            edit_v1(
+                false,
+                vec![CompactMeta {
+                    file_id: 1,
+                    file_size: 100,
+                    tsf_id: 1,
+                    level: 1,
+                    min_ts: 1,
+                    max_ts: 100,
+                    high_seq: 10,
+                    ..Default::default()
+                }],
+            ),
+        ];
+        let mut writer = Writer::new(&file_utils::make_summary_file(&summary_dir, 0)).unwrap();
+        for edit in edits {
+            let buf = bincode::serialize(&edit).unwrap();
+            writer
+                .write_record(VERSION_EDIT_V1, EditType::SummaryEdit.into(), &buf)
+                .await
+                .unwrap();
+        }
+        writer.hard_sync().await.unwrap();
+        drop(writer);
+
+        let (flush_task_sender, _) = mpsc::unbounded_channel();
+        let summary = Summary::recover(opt, flush_task_sender).await.unwrap();
+        assert_eq!(summary_files(&summary), vec![(1, 1, 1)]);
+        let vs = summary.version_set.read();
+        let tsf = vs.get_tsfamily_by_tf_id(1).unwrap();
+        assert_eq!(tsf.read().version().database(), "test");
+        assert_eq!(tsf.read().vnode(), VnodeInfo::default());
+        assert_eq!(tsf.read().version().last_seq, 10);
+    }
 }
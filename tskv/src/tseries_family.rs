@@ -8,31 +8,34 @@ use std::{
     path::{Path, PathBuf},
     rc::Rc,
     sync::{
-        atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicU8, Ordering},
         Arc,
     },
 };
 
 use lazy_static::lazy_static;
 use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::watch::Receiver;
 
 use config::get_config;
+use metrics::{incr_write_stalls, set_write_stall_state};
 use models::{FieldId, InMemPoint, SchemaId, SeriesId, Timestamp, ValueType};
 use trace::{debug, error, info, warn};
 use utils::BloomFilter;
 
-use crate::file_system::file_manager;
+use crate::file_system::{file_manager, remote};
 use crate::{
-    compaction::{CompactReq, FlushReq, LevelCompactionPicker, Picker},
+    compaction::{pick_tombstone_rewrite, CompactReq, FlushReq, LevelCompactionPicker, Picker},
     error::{Error, Result},
     file_system::{DmaFile, FileCursor},
-    file_utils::{make_delta_file_name, make_tsm_file_name},
+    file_utils::{self, make_delta_file_name, make_tsm_file_name},
     kv_option::{CacheOptions, Options, StorageOptions},
     memcache::{DataType, MemCache},
     summary::{CompactMeta, VersionEdit},
     tsm::{ColumnReader, DataBlock, IndexReader, TsmReader, TsmTombstone},
+    write_buffer::WriteBufferManager,
     ColumnFileId, LevelId, TseriesFamilyId,
 };
 use crate::{memcache::RowGroup, tsm::BlockMetaIterator};
@@ -41,21 +44,23 @@ lazy_static! {
     pub static ref FLUSH_REQ: Arc<Mutex<Vec<FlushReq>>> = Arc::new(Mutex::new(vec![]));
 }
 
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct TimeRange {
     pub min_ts: i64,
     pub max_ts: i64,
 }
 
 impl From<(Bound<i64>, Bound<i64>)> for TimeRange {
-    /// TODO 目前TimeRange只支持闭区间
+    /// TimeRange is a closed interval, excluded bounds are converted to the adjacent timestamps.
     fn from(range: (Bound<i64>, Bound<i64>)) -> Self {
         let min_ts = match range.0 {
-            Bound::Excluded(v) | Bound::Included(v) => v,
+            Bound::Excluded(v) => v.saturating_add(1),
+            Bound::Included(v) => v,
             _ => Timestamp::MIN,
         };
         let max_ts = match range.1 {
-            Bound::Excluded(v) | Bound::Included(v) => v,
+            Bound::Excluded(v) => v.saturating_sub(1),
+            Bound::Included(v) => v,
             _ => Timestamp::MAX,
         };
 
@@ -117,6 +122,49 @@ impl From<TimeRange> for (Timestamp, Timestamp) {
     }
 }
 
+/// The time bucket and shard that a TseriesFamily (vnode) is responsible for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VnodeInfo {
+    pub time_range: TimeRange,
+    pub shard_id: u64,
+}
+
+impl VnodeInfo {
+    pub fn new(time_range: TimeRange, shard_id: u64) -> Self {
+        Self {
+            time_range,
+            shard_id,
+        }
+    }
+
+    /// Returns the vnode that contains the timestamp `ts` of the series, whose
+    /// time bucket is `duration` long and there are `shard_num` shards in a bucket.
+    pub fn locate(series_hash: u64, ts: Timestamp, duration: i64, shard_num: u64) -> Self {
+        let shard_id = series_hash % shard_num.max(1);
+        if duration <= 0 {
+            return Self::new(TimeRange::all(), shard_id);
+        }
+        let min_ts = ts.div_euclid(duration).saturating_mul(duration);
+        let max_ts = min_ts.saturating_add(duration - 1);
+        Self::new(TimeRange::new(min_ts, max_ts), shard_id)
+    }
+
+    /// Returns true if the vnode is responsible for `other`.
+    pub fn includes(&self, other: &VnodeInfo) -> bool {
+        self.shard_id == other.shard_id && self.time_range.includes(&other.time_range)
+    }
+}
+
+/// TseriesFamilies created before vnodes were introduced take all the data.
+impl Default for VnodeInfo {
+    fn default() -> Self {
+        Self {
+            time_range: TimeRange::all(),
+            shard_id: 0,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ColumnFile {
     file_id: ColumnFileId,
@@ -127,6 +175,7 @@ pub struct ColumnFile {
     field_id_bloom_filter: BloomFilter,
     deleted: AtomicBool,
     compacting: AtomicBool,
+    tombstone_ratio: Mutex<Option<f64>>,
 
     path: PathBuf,
 }
@@ -150,6 +199,7 @@ impl ColumnFile {
             field_id_bloom_filter: BloomFilter::new(512),
             deleted: AtomicBool::new(false),
             compacting: AtomicBool::new(false),
+            tombstone_ratio: Mutex::new(None),
             path: path.as_ref().into(),
         }
     }
@@ -206,14 +256,36 @@ impl ColumnFile {
         false
     }
 
+    pub fn tombstone_path(&self) -> PathBuf {
+        let dir = self.path.parent().expect("file has parent");
+        file_utils::make_tsm_tombstone_file_name(dir, self.file_id)
+    }
+
+    pub fn has_tombstone(&self) -> bool {
+        file_manager::try_exists(self.tombstone_path())
+    }
+
     pub fn add_tombstone(&self, field_ids: &[FieldId], time_range: &TimeRange) -> Result<()> {
         let dir = self.path.parent().expect("file has parent");
         // TODO flock tombstone file.
         let mut tombstone = TsmTombstone::open_for_write(dir, self.file_id)?;
         tombstone.add_range(field_ids, time_range)?;
         tombstone.flush()?;
+        *self.tombstone_ratio.lock() = None;
         Ok(())
     }
+
+    /// Returns the estimated fraction of values deleted by tombstones, which is
+    /// computed once and cached until a tombstone is added.
+    pub fn tombstone_ratio(&self) -> Result<f64> {
+        let mut cached = self.tombstone_ratio.lock();
+        if let Some(ratio) = *cached {
+            return Ok(ratio);
+        }
+        let ratio = TsmReader::open(&self.path)?.tombstone_ratio();
+        *cached = Some(ratio);
+        Ok(ratio)
+    }
 }
 
 impl ColumnFile {
@@ -232,6 +304,10 @@ impl ColumnFile {
     pub fn mark_compacting(&self) {
         self.compacting.store(true, Ordering::Release);
     }
+
+    pub fn unmark_compacting(&self) {
+        self.compacting.store(false, Ordering::Release);
+    }
 }
 
 impl Drop for ColumnFile {
@@ -239,6 +315,14 @@ impl Drop for ColumnFile {
         debug!("Removing file {}", self.file_id);
         if self.is_deleted() {
             let path = self.file_path();
+            if let Err(e) = remote::delete_remote_object(&path) {
+                error!(
+                    "Error when deleting offloaded file {} of '{}': {}",
+                    self.file_id,
+                    path.display(),
+                    e
+                );
+            }
             if let Err(e) = std::fs::remove_file(&path) {
                 error!(
                     "Error when removing file {} at '{}': {}",
@@ -248,6 +332,18 @@ impl Drop for ColumnFile {
                 );
             }
             info!("Removed file {} at '{}", self.file_id, path.display());
+
+            let tombstone_path = self.tombstone_path();
+            if file_manager::try_exists(&tombstone_path) {
+                if let Err(e) = std::fs::remove_file(&tombstone_path) {
+                    error!(
+                        "Error when removing tombstone of file {} at '{}': {}",
+                        self.file_id,
+                        tombstone_path.display(),
+                        e.to_string()
+                    );
+                }
+            }
         }
     }
 }
@@ -323,18 +419,14 @@ impl LevelInfo {
     }
 
     pub fn push_compact_meta(&mut self, compact_meta: &CompactMeta) {
-        let file_path = if compact_meta.is_delta {
-            let base_dir = self.storage_opt.delta_dir(&self.database, self.tsf_id);
-            make_delta_file_name(base_dir, compact_meta.file_id)
-        } else {
-            let base_dir = self.storage_opt.tsm_dir(&self.database, self.tsf_id);
-            make_tsm_file_name(base_dir, compact_meta.file_id)
-        };
+        self.tsf_id = compact_meta.tsf_id;
+        let file_path = self
+            .storage_opt
+            .column_file_path(&self.database, compact_meta);
         self.files.push(Arc::new(ColumnFile::with_compact_data(
             compact_meta,
             file_path,
         )));
-        self.tsf_id = compact_meta.tsf_id;
         self.cur_size += compact_meta.file_size;
         self.time_range.max_ts = self.time_range.max_ts.max(compact_meta.max_ts);
         self.time_range.min_ts = self.time_range.min_ts.min(compact_meta.min_ts);
@@ -413,9 +505,12 @@ pub struct Version {
     /// The max timestamp of write batch in wal flushed to column file.
     pub max_level_ts: i64,
     pub levels_info: [LevelInfo; 5],
+    /// The time bucket and shard of this TseriesFamily.
+    pub vnode: VnodeInfo,
 }
 
 impl Version {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         ts_family_id: TseriesFamilyId,
         database: String,
@@ -423,6 +518,7 @@ impl Version {
         last_seq: u64,
         levels_info: [LevelInfo; 5],
         max_level_ts: i64,
+        vnode: VnodeInfo,
     ) -> Self {
         Self {
             ts_family_id,
@@ -431,6 +527,7 @@ impl Version {
             last_seq,
             max_level_ts,
             levels_info,
+            vnode,
         }
     }
 
@@ -484,14 +581,36 @@ impl Version {
             ts_family_id: self.ts_family_id,
             database: self.database.clone(),
             storage_opt: self.storage_opt.clone(),
-            last_seq: last_seq.unwrap_or(self.last_seq),
+            // Edits of compaction don't carry a flushed seq, so never decrease it.
+            last_seq: last_seq.map_or(self.last_seq, |seq| seq.max(self.last_seq)),
             max_level_ts: self.max_level_ts,
             levels_info: new_levels,
+            vnode: self.vnode,
         };
         new_version.update_max_level_ts();
         new_version
     }
 
+    /// Returns a `VersionEdit` to rebuild this version from nothing, which adds the
+    /// TseriesFamily with all column files.
+    pub fn snapshot_edit(&self) -> VersionEdit {
+        let mut edit = VersionEdit::new();
+        for level in self.levels_info.iter() {
+            for file in level.files.iter() {
+                let mut meta = CompactMeta::from(file.as_ref());
+                meta.tsf_id = self.ts_family_id;
+                meta.high_seq = self.last_seq;
+                edit.add_file(meta, self.max_level_ts);
+            }
+        }
+        edit.add_tsfamily(self.ts_family_id, self.database.clone(), self.vnode);
+        // Keep the flushed WAL sequence even if there are no files.
+        edit.has_seq_no = true;
+        edit.seq_no = self.last_seq;
+
+        edit
+    }
+
     fn update_max_level_ts(&mut self) {
         if self.levels_info.is_empty() {
             return;
@@ -517,6 +636,10 @@ impl Version {
         &self.database
     }
 
+    pub fn vnode(&self) -> &VnodeInfo {
+        &self.vnode
+    }
+
     pub fn levels_info(&self) -> &[LevelInfo; 5] {
         &self.levels_info
     }
@@ -542,6 +665,38 @@ impl Version {
             .collect()
     }
 
+    /// Returns column files that all of the data is before `expired_ts`,
+    /// files being compacted are skipped.
+    pub fn expired_column_files(&self, expired_ts: Timestamp) -> Vec<Arc<ColumnFile>> {
+        self.levels_info
+            .iter()
+            .flat_map(|level| {
+                level
+                    .files
+                    .iter()
+                    .filter(|f| f.time_range().max_ts < expired_ts && !f.is_compacting())
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Returns tsm files that all of the data is before `ts`, delta files and files
+    /// being compacted are skipped.
+    pub fn cold_tsm_files(&self, ts: Timestamp) -> Vec<Arc<ColumnFile>> {
+        self.levels_info
+            .iter()
+            .flat_map(|level| {
+                level.files.iter().filter(|f| {
+                    !f.is_delta()
+                        && f.time_range().max_ts < ts
+                        && !f.is_compacting()
+                        && !f.is_deleted()
+                })
+            })
+            .cloned()
+            .collect()
+    }
+
     // todo:
     pub fn get_ts_overlap(&self, level: u32, ts_min: i64, ts_max: i64) -> Vec<Arc<ColumnFile>> {
         vec![]
@@ -581,6 +736,45 @@ impl SuperVersion {
     }
 }
 
+/// Whether writes to a TseriesFamily should be delayed because compaction falls
+/// behind and level 0 has too many files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum WriteStallState {
+    Normal = 0,
+    Slowdown = 1,
+    Stop = 2,
+}
+
+impl WriteStallState {
+    /// Returns the state of `l0_files` files in level 0, a trigger of 0 is disabled.
+    pub fn new(l0_files: u32, slowdown_trigger: u32, stop_trigger: u32) -> Self {
+        if stop_trigger > 0 && l0_files >= stop_trigger {
+            WriteStallState::Stop
+        } else if slowdown_trigger > 0 && l0_files >= slowdown_trigger {
+            WriteStallState::Slowdown
+        } else {
+            WriteStallState::Normal
+        }
+    }
+
+    fn from_u8(v: u8) -> Self {
+        match v {
+            1 => WriteStallState::Slowdown,
+            2 => WriteStallState::Stop,
+            _ => WriteStallState::Normal,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WriteStallState::Normal => "normal",
+            WriteStallState::Slowdown => "slowdown",
+            WriteStallState::Stop => "stop",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct TseriesFamily {
     tf_id: TseriesFamilyId,
@@ -597,6 +791,8 @@ pub struct TseriesFamily {
     immut_ts_min: AtomicI64,
     mut_ts_max: AtomicI64,
     flush_task_sender: UnboundedSender<FlushReq>,
+    write_buffer: Arc<WriteBufferManager>,
+    write_stall_state: AtomicU8,
 }
 
 impl TseriesFamily {
@@ -609,6 +805,7 @@ impl TseriesFamily {
         cache_opt: Arc<CacheOptions>,
         storage_opt: Arc<StorageOptions>,
         flush_task_sender: UnboundedSender<FlushReq>,
+        write_buffer: Arc<WriteBufferManager>,
     ) -> Self {
         let mm = Arc::new(RwLock::new(cache));
         let seq = version.last_seq;
@@ -638,6 +835,8 @@ impl TseriesFamily {
             immut_ts_min: AtomicI64::new(max_level_ts),
             mut_ts_max: AtomicI64::new(i64::MIN),
             flush_task_sender,
+            write_buffer,
+            write_stall_state: AtomicU8::new(WriteStallState::Normal as u8),
         }
     }
 
@@ -665,32 +864,45 @@ impl TseriesFamily {
     /// then create new SuperVersion, update seq_no
     pub fn new_version(&mut self, new_version: Version) {
         let version = Arc::new(new_version);
+        // Data of flushed caches is in the new version.
+        self.remove_flushed_caches();
         self.new_super_version(version.clone());
         self.seq_no = version.last_seq;
         self.version = version;
     }
 
     pub fn switch_to_immutable(&mut self) {
+        // The new cache starts from the max seq_no written to this TseriesFamily.
+        let seq_no = self.mut_cache.read().seq_no().max(self.seq_no);
         self.immut_cache.push(self.mut_cache.clone());
         self.mut_cache = Arc::from(RwLock::new(MemCache::new(
             self.tf_id,
             self.cache_opt.max_buffer_size,
-            self.seq_no,
+            seq_no,
         )));
         self.new_super_version(self.version.clone());
     }
 
-    fn wrap_flush_req(&mut self) {
+    /// Removes flushed caches from immutable caches, and releases their memory,
+    /// returns true if any is removed.
+    fn remove_flushed_caches(&mut self) -> bool {
         let len = self.immut_cache.len();
         let mut imut = vec![];
         for i in self.immut_cache.iter() {
-            if !i.read().flushed {
+            let i_rlock = i.read();
+            if i_rlock.flushed {
+                self.write_buffer.release(i_rlock.cache_size());
+            } else {
                 imut.push(i.clone());
             }
         }
         self.immut_cache = imut;
 
-        if len != self.immut_cache.len() {
+        len != self.immut_cache.len()
+    }
+
+    fn wrap_flush_req(&mut self) {
+        if self.remove_flushed_caches() {
             self.new_super_version(self.version.clone());
         }
 
@@ -721,6 +933,7 @@ impl TseriesFamily {
 
     pub fn put_points(&self, seq: u64, points: HashMap<(SeriesId, SchemaId), RowGroup>) {
         for ((sid, schema_id), group) in points {
+            self.write_buffer.reserve(group.size as u64);
             let mem = self.super_version.caches.mut_cache.read();
             mem.write_group(sid, seq, group);
         }
@@ -744,6 +957,102 @@ impl TseriesFamily {
             }
         }
     }
+    /// Switches the mutable cache to immutable, and sends all immutable caches not
+    /// being flushed to flush, returns the size of data in the sent caches.
+    pub fn flush_caches(&mut self) -> u64 {
+        if !self.mut_cache.read().is_empty() {
+            self.switch_to_immutable();
+        }
+        let mut size = 0;
+        let mut req_mem = vec![];
+        for mem in self.immut_cache.iter() {
+            let mut mem_wlock = mem.write();
+            if mem_wlock.flushing || mem_wlock.flushed || mem_wlock.cache_size() == 0 {
+                continue;
+            }
+            mem_wlock.flushing = true;
+            size += mem_wlock.cache_size();
+            req_mem.push((self.tf_id, mem.clone()));
+        }
+        if !req_mem.is_empty() {
+            info!(
+                "flush {} caches of ts_family {} to release memory",
+                req_mem.len(),
+                self.tf_id
+            );
+            self.flush_task_sender
+                .send(FlushReq { mems: req_mem })
+                .expect("error send flush req to kvcore");
+        }
+        size
+    }
+
+    /// Returns the size of data in caches not being flushed, and the size of data
+    /// in caches being flushed.
+    pub fn cache_sizes(&self) -> (u64, u64) {
+        let (mut unflushed, mut flushing) = (self.mut_cache.read().cache_size(), 0);
+        for mem in self.immut_cache.iter() {
+            let mem_rlock = mem.read();
+            if mem_rlock.flushed {
+                continue;
+            } else if mem_rlock.flushing {
+                flushing += mem_rlock.cache_size();
+            } else {
+                unflushed += mem_rlock.cache_size();
+            }
+        }
+        (unflushed, flushing)
+    }
+
+    pub fn level0_file_num(&self) -> u32 {
+        self.version.levels_info[0].files.len() as u32
+    }
+
+    /// Checks the number of files in level 0 against the triggers in `StorageOptions`,
+    /// the state is logged and recorded in metrics when it changes.
+    pub fn check_write_stall(&self) -> WriteStallState {
+        let l0_files = self.level0_file_num();
+        let state = WriteStallState::new(
+            l0_files,
+            self.storage_opt.l0_slowdown_trigger,
+            self.storage_opt.l0_stop_trigger,
+        );
+        let prev =
+            WriteStallState::from_u8(self.write_stall_state.swap(state as u8, Ordering::AcqRel));
+        if prev != state {
+            let tf_id = self.tf_id.to_string();
+            set_write_stall_state(&self.database, &tf_id, state as i64);
+            if state == WriteStallState::Normal {
+                info!(
+                    "Write stall of ts_family {} in database '{}' is over, {} files in level 0",
+                    self.tf_id, self.database, l0_files
+                );
+            } else {
+                incr_write_stalls(&self.database, &tf_id, state.as_str());
+                warn!(
+                    "Write stall state of ts_family {} in database '{}' changed from {} to {}, {} files in level 0",
+                    self.tf_id,
+                    self.database,
+                    prev.as_str(),
+                    state.as_str(),
+                    l0_files
+                );
+            }
+        }
+        state
+    }
+
+    /// Returns the min WAL sequence of data in caches that has not been flushed to
+    /// column files, returns `None` if all data is flushed.
+    pub fn min_unflushed_seq(&self) -> Option<u64> {
+        let last_seq = self.version.last_seq;
+        if self.mut_cache.read().seq_no() > last_seq {
+            Some(last_seq + 1)
+        } else {
+            None
+        }
+    }
+
     pub fn delete_cache(&self, field_ids: &[FieldId], time_range: &TimeRange) {
         self.mut_cache.read().delete_data(field_ids, time_range);
         for memcache in self.immut_cache.iter() {
@@ -751,8 +1060,24 @@ impl TseriesFamily {
         }
     }
 
+    /// Delete data before `expired_ts` in caches, returns the number of deleted rows.
+    pub fn delete_expired_cache(&self, expired_ts: Timestamp) -> usize {
+        let time_range = TimeRange::new(Timestamp::MIN, expired_ts.saturating_sub(1));
+        let mut deleted = self.mut_cache.read().delete_data_by_time_range(&time_range);
+        for memcache in self.immut_cache.iter() {
+            deleted += memcache.read().delete_data_by_time_range(&time_range);
+        }
+        deleted
+    }
+
     pub fn pick_compaction(&self) -> Option<CompactReq> {
-        self.compact_picker.pick_compaction(self.version.clone())
+        self.compact_picker
+            .pick_compaction(self.version.clone())
+            .or_else(|| pick_tombstone_rewrite(self.version.clone()))
+    }
+
+    pub fn set_compact_picker(&mut self, compact_picker: Arc<dyn Picker>) {
+        self.compact_picker = compact_picker;
     }
 
     pub fn tf_id(&self) -> TseriesFamilyId {
@@ -779,11 +1104,25 @@ impl TseriesFamily {
         self.version.clone()
     }
 
+    pub fn vnode(&self) -> VnodeInfo {
+        self.version.vnode
+    }
+
     pub fn storage_opt(&self) -> Arc<StorageOptions> {
         self.storage_opt.clone()
     }
 }
 
+impl Drop for TseriesFamily {
+    fn drop(&mut self) {
+        let mut size = self.mut_cache.read().cache_size();
+        for mem in self.immut_cache.iter() {
+            size += mem.read().cache_size();
+        }
+        self.write_buffer.release(size);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::collections::hash_map;
@@ -808,6 +1147,7 @@ mod test {
         tseries_family::{TimeRange, TseriesFamily, Version},
         tsm::TsmTombstone,
         version_set::VersionSet,
+        write_buffer::WriteBufferManager,
         TseriesFamilyId,
     };
     use config::get_config;
@@ -815,7 +1155,7 @@ mod test {
     use models::{Timestamp, ValueType};
     use trace::info;
 
-    use super::{ColumnFile, LevelInfo};
+    use super::{ColumnFile, LevelInfo, VnodeInfo, WriteStallState};
 
     #[test]
     fn test_version_apply_version_edits_1() {
@@ -876,6 +1216,7 @@ mod test {
                 LevelInfo::init(database.clone(), 3, opt.storage.clone()),
                 LevelInfo::init(database, 4, opt.storage.clone()),
             ],
+            vnode: VnodeInfo::default(),
         };
         let mut version_edits = Vec::new();
         let mut ve = VersionEdit::new();
@@ -971,6 +1312,7 @@ mod test {
                 LevelInfo::init(database.clone(), 3, opt.storage.clone()),
                 LevelInfo::init(database, 4, opt.storage.clone()),
             ],
+            vnode: VnodeInfo::default(),
         };
         let mut version_edits = Vec::new();
         let mut ve = VersionEdit::new();
@@ -1052,10 +1394,12 @@ mod test {
                 0,
                 LevelInfo::init_levels(database, opt.storage.clone()),
                 0,
+                VnodeInfo::default(),
             )),
             opt.cache.clone(),
             opt.storage.clone(),
             flush_task_sender,
+            Arc::new(WriteBufferManager::new(0)),
         );
 
         let row_group = RowGroup {
@@ -1096,6 +1440,242 @@ mod test {
             .is_empty());
     }
 
+    #[test]
+    fn test_version_expired_column_files() {
+        //! There is a Version with two levels:
+        //! - Lv.1: [ (3, 3001~3100) ]
+        //! - Lv.2: [ (1, 1~1000), (2, 1001~2000) ]
+        //!
+        //! File 2 is being compacted, so data before 2500 will only expire file 1.
+        let global_config = get_config("../config/config.toml");
+        let opt = Arc::new(Options::from(&global_config));
+        let database = "test".to_string();
+        let ts_family_id = 1;
+        let tsm_dir = opt.storage.tsm_dir(&database, ts_family_id);
+        #[rustfmt::skip]
+        let version = Version {
+            ts_family_id,
+            database: database.clone(),
+            storage_opt: opt.storage.clone(),
+            last_seq: 1,
+            max_level_ts: 3100,
+            levels_info: [
+                LevelInfo::init(database.clone(), 0, opt.storage.clone()),
+                LevelInfo {
+                    files: vec![
+                        Arc::new(ColumnFile::new(3, 1, TimeRange::new(3001, 3100), 100, false, make_tsm_file_name(&tsm_dir, 3))),
+                    ],
+                    database: database.clone(),
+                    tsf_id: 1,
+                    storage_opt: opt.storage.clone(),
+                    level: 1,
+                    cur_size: 100,
+                    max_size: 1000,
+                    time_range: TimeRange::new(3001, 3100),
+                },
+                LevelInfo {
+                    files: vec![
+                        Arc::new(ColumnFile::new(1, 2, TimeRange::new(1, 1000), 1000, false, make_tsm_file_name(&tsm_dir, 1))),
+                        Arc::new(ColumnFile::new(2, 2, TimeRange::new(1001, 2000), 1000, false, make_tsm_file_name(&tsm_dir, 2))),
+                    ],
+                    database: database.clone(),
+                    tsf_id: 1,
+                    storage_opt: opt.storage.clone(),
+                    level: 2,
+                    cur_size: 2000,
+                    max_size: 10000,
+                    time_range: TimeRange::new(1, 2000),
+                },
+                LevelInfo::init(database.clone(), 3, opt.storage.clone()),
+                LevelInfo::init(database, 4, opt.storage.clone()),
+            ],
+            vnode: VnodeInfo::default(),
+        };
+        version.levels_info[2].files[1].mark_compacting();
+
+        let files = version.expired_column_files(2500);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].file_id(), 1);
+
+        assert!(version.expired_column_files(1000).is_empty());
+        assert!(version.expired_column_files(Timestamp::MIN).is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn test_tsf_delete_expired_cache() {
+        let (flush_task_sender, _) = mpsc::unbounded_channel();
+        let global_config = get_config("../config/config.toml");
+        let opt = Arc::new(Options::from(&global_config));
+        let database = "db".to_string();
+        let tsf = TseriesFamily::new(
+            0,
+            database.clone(),
+            MemCache::new(0, 500, 0),
+            Arc::new(Version::new(
+                0,
+                database.clone(),
+                opt.storage.clone(),
+                0,
+                LevelInfo::init_levels(database, opt.storage.clone()),
+                0,
+                VnodeInfo::default(),
+            )),
+            opt.cache.clone(),
+            opt.storage.clone(),
+            flush_task_sender,
+            Arc::new(WriteBufferManager::new(0)),
+        );
+
+        let row_group = RowGroup {
+            schema: default_with_field_id(vec![0, 1, 2]),
+            range: TimeRange {
+                min_ts: 10,
+                max_ts: 110,
+            },
+            rows: vec![
+                RowData {
+                    ts: 10,
+                    fields: vec![Some(FieldVal::Integer(11)), None, None],
+                },
+                RowData {
+                    ts: 110,
+                    fields: vec![Some(FieldVal::Integer(111)), None, None],
+                },
+            ],
+            size: size_of::<RowGroup>()
+                + 3 * size_of::<u32>()
+                + 2 * size_of::<Option<FieldVal>>()
+                + 16,
+        };
+        let mut points = HashMap::new();
+        points.insert((0, 0), row_group);
+        tsf.put_points(0, points);
+
+        assert_eq!(tsf.delete_expired_cache(10), 0);
+        assert_eq!(tsf.delete_expired_cache(100), 1);
+        let data = tsf.mut_cache.read().get_data(0, |_| true, |_| true);
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0].timestamp(), 110);
+    }
+
+    #[test]
+    fn test_vnode_info_locate() {
+        let vnode = VnodeInfo::locate(7, 150, 100, 3);
+        assert_eq!(vnode, VnodeInfo::new(TimeRange::new(100, 199), 1));
+        let vnode = VnodeInfo::locate(7, -1, 100, 3);
+        assert_eq!(vnode, VnodeInfo::new(TimeRange::new(-100, -1), 1));
+        let vnode = VnodeInfo::locate(7, 150, 0, 0);
+        assert_eq!(vnode, VnodeInfo::new(TimeRange::all(), 0));
+
+        assert!(VnodeInfo::default().includes(&VnodeInfo::locate(6, 150, 100, 3)));
+        assert!(!VnodeInfo::default().includes(&VnodeInfo::locate(7, 150, 100, 3)));
+        assert!(!VnodeInfo::locate(7, 50, 100, 3).includes(&VnodeInfo::locate(7, 150, 100, 3)));
+    }
+
+    #[tokio::test]
+    pub async fn test_database_vnodes() {
+        let (summary_task_sender, _summary_task_receiver) = mpsc::unbounded_channel();
+        let (flush_task_sender, _) = mpsc::unbounded_channel();
+        let mut global_config = get_config("../config/config.toml");
+        global_config.storage.path = "/tmp/test/ts_family/database_vnodes".to_string();
+        let opt = Arc::new(Options::from(&global_config));
+        let database = "test_database_vnodes".to_string();
+        let version_set: Arc<RwLock<VersionSet>> = Arc::new(RwLock::new(VersionSet::new(
+            opt.clone(),
+            HashMap::new(),
+            flush_task_sender.clone(),
+        )));
+        let db = version_set
+            .write()
+            .create_db(DatabaseSchema::new(&database));
+        for (tsf_id, min_ts) in [(0, 0), (1, 100)] {
+            db.write().add_tsfamily(
+                tsf_id,
+                0,
+                VnodeInfo::new(TimeRange::new(min_ts, min_ts + 99), 0),
+                summary_task_sender.clone(),
+                flush_task_sender.clone(),
+            );
+        }
+
+        let tsf = db
+            .read()
+            .get_tsfamily_by_vnode(&VnodeInfo::locate(0, 150, 100, 1))
+            .unwrap();
+        assert_eq!(tsf.read().tf_id(), 1);
+        assert!(db
+            .read()
+            .get_tsfamily_by_vnode(&VnodeInfo::locate(0, 250, 100, 1))
+            .is_none());
+
+        let tsfs = db
+            .read()
+            .get_tsfamilies_by_time_ranges(&[TimeRange::new(50, 60)]);
+        assert_eq!(tsfs.len(), 1);
+        assert_eq!(tsfs[0].read().tf_id(), 0);
+        let tsfs = db.read().get_tsfamilies_by_time_ranges(&[TimeRange::all()]);
+        assert_eq!(tsfs.len(), 2);
+
+        // Both vnodes are far older than the default TTL.
+        let edits = db.write().drop_expired_data();
+        assert_eq!(edits.len(), 2);
+        assert!(edits.iter().all(|e| e.del_tsf));
+        assert_eq!(db.read().tsf_num(), 0);
+    }
+
+    #[tokio::test]
+    pub async fn test_database_unmark_expired_files() {
+        let (summary_task_sender, _summary_task_receiver) = mpsc::unbounded_channel();
+        let (flush_task_sender, _) = mpsc::unbounded_channel();
+        let mut global_config = get_config("../config/config.toml");
+        global_config.storage.path = "/tmp/test/ts_family/unmark_expired_files".to_string();
+        let opt = Arc::new(Options::from(&global_config));
+        let database = "test_unmark_expired_files".to_string();
+        let version_set: Arc<RwLock<VersionSet>> = Arc::new(RwLock::new(VersionSet::new(
+            opt.clone(),
+            HashMap::new(),
+            flush_task_sender.clone(),
+        )));
+        let db = version_set
+            .write()
+            .create_db(DatabaseSchema::new(&database));
+        let tsf = db.write().add_tsfamily(
+            1,
+            0,
+            VnodeInfo::default(),
+            summary_task_sender,
+            flush_task_sender,
+        );
+        let mut edit = VersionEdit::new();
+        edit.add_file(
+            CompactMeta {
+                file_id: 1,
+                file_size: 1000,
+                tsf_id: 1,
+                level: 1,
+                min_ts: 1,
+                max_ts: 1000,
+                ..Default::default()
+            },
+            1000,
+        );
+        let version = tsf
+            .read()
+            .version()
+            .copy_apply_version_edits(vec![edit], None);
+        tsf.write().new_version(version);
+
+        let edits = db.write().drop_expired_data();
+        assert_eq!(edits.len(), 1);
+        let file = tsf.read().version().levels_info[1].files[0].clone();
+        assert!(file.is_compacting());
+
+        // The version edits failed to apply, the file is still in the version.
+        db.read().unmark_expired_files(&edits);
+        assert!(!file.is_compacting());
+        assert_eq!(db.write().drop_expired_data().len(), 1);
+    }
+
     // Util function for testing with summary modification.
     async fn update_ts_family_version(
         version_set: Arc<RwLock<VersionSet>>,
@@ -1180,7 +1760,13 @@ mod test {
 
         let ts_family_id = db
             .write()
-            .add_tsfamily(0, 0, summary_task_sender.clone(), flush_task_sender.clone())
+            .add_tsfamily(
+                0,
+                0,
+                VnodeInfo::default(),
+                summary_task_sender.clone(),
+                flush_task_sender.clone(),
+            )
             .read()
             .tf_id();
 
@@ -1224,4 +1810,15 @@ mod test {
             },
         );
     }
+
+    #[test]
+    fn test_write_stall_state() {
+        assert_eq!(WriteStallState::new(19, 20, 36), WriteStallState::Normal);
+        assert_eq!(WriteStallState::new(20, 20, 36), WriteStallState::Slowdown);
+        assert_eq!(WriteStallState::new(36, 20, 36), WriteStallState::Stop);
+        // A trigger of 0 is disabled.
+        assert_eq!(WriteStallState::new(100, 0, 36), WriteStallState::Stop);
+        assert_eq!(WriteStallState::new(100, 20, 0), WriteStallState::Slowdown);
+        assert_eq!(WriteStallState::new(100, 0, 0), WriteStallState::Normal);
+    }
 }
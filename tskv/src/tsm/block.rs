@@ -2,7 +2,7 @@ use minivec::MiniVec;
 use std::cmp::min;
 use std::{fmt::Display, mem::size_of, ops::Index};
 
-use models::{Timestamp, ValueType};
+use models::{codec::Encoding, Timestamp, ValueType};
 use protos::models::FieldType;
 use trace::error;
 
@@ -10,9 +10,13 @@ use crate::{
     compaction::overlaps_tuples,
     memcache::DataType,
     tseries_family::TimeRange,
-    tsm::codec::{
-        get_bool_codec, get_f64_codec, get_i64_codec, get_str_codec, get_ts_codec, get_u64_codec,
-        DataBlockEncoding,
+    tsm::{
+        codec::{
+            get_bool_codec, get_f64_codec, get_i64_codec, get_str_codec, get_ts_codec,
+            get_u64_codec, select_bool_encoding, select_f64_encoding, select_i64_encoding,
+            select_str_encoding, select_ts_encoding, select_u64_encoding, DataBlockEncoding,
+        },
+        BlockStatistics,
     },
 };
 
@@ -361,7 +365,7 @@ impl DataBlock {
 
     /// Merges one or many `DataBlock`s into some `DataBlock` with fixed length,
     /// sorted by timestamp, if many (timestamp, value) conflict with the same
-    /// timestamp, use the last value.
+    /// timestamp, use the last value. Merged blocks take encodings of the last block.
     pub fn merge_blocks(mut blocks: Vec<Self>, max_block_size: u32) -> Vec<Self> {
         if blocks.is_empty() {
             return vec![];
@@ -378,9 +382,15 @@ impl DataBlock {
         };
         let capacity = data_blocks.len();
         let field_type = data_blocks.field_type();
+        let encodings = blocks[blocks.len() - 1].encodings();
+        let new_block = || {
+            let mut blk = Self::new(capacity, field_type);
+            blk.set_encodings(encodings);
+            blk
+        };
 
         let mut res = vec![];
-        let mut blk = Self::new(capacity, field_type);
+        let mut blk = new_block();
         let mut buf = vec![None; blocks.len()];
         let mut offsets = vec![0_usize; blocks.len()];
         loop {
@@ -398,7 +408,7 @@ impl DataBlock {
                         blk.insert(it);
                         if max_block_size != 0 && blk.len() >= max_block_size as usize {
                             res.push(blk);
-                            blk = Self::new(capacity, field_type);
+                            blk = new_block();
                         }
                     }
                 }
@@ -465,13 +475,50 @@ impl DataBlock {
         if min_idx > max_idx || min_idx == max_idx && !has_min && !has_max {
             return;
         }
-        if max_idx < ts_sli.len() {
+        // If max_ts is not found, max_idx is already the position after it.
+        if has_max {
             max_idx += 1;
         }
 
         self.exclude_by_index(min_idx, max_idx);
     }
 
+    /// Returns min/max/sum of values in this `DataBlock`, returns `None` if this `DataBlock`
+    /// is empty, is a string block, or contains NaN.
+    pub fn statistics(&self) -> Option<BlockStatistics> {
+        if self.is_empty() {
+            return None;
+        }
+        match self {
+            DataBlock::U64 { val, .. } => Some(BlockStatistics::U64 {
+                min: *val.iter().min()?,
+                max: *val.iter().max()?,
+                sum: val.iter().try_fold(0_u64, |acc, v| acc.checked_add(*v)),
+            }),
+            DataBlock::I64 { val, .. } => Some(BlockStatistics::I64 {
+                min: *val.iter().min()?,
+                max: *val.iter().max()?,
+                sum: val.iter().try_fold(0_i64, |acc, v| acc.checked_add(*v)),
+            }),
+            DataBlock::F64 { val, .. } => {
+                if val.iter().any(|v| v.is_nan()) {
+                    return None;
+                }
+                Some(BlockStatistics::F64 {
+                    min: val.iter().copied().fold(f64::INFINITY, f64::min),
+                    max: val.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                    sum: val.iter().sum(),
+                })
+            }
+            DataBlock::Bool { val, .. } => Some(BlockStatistics::Bool {
+                min: val.iter().all(|v| *v),
+                max: val.iter().any(|v| *v),
+                sum: val.iter().filter(|v| **v).count() as u64,
+            }),
+            DataBlock::Str { .. } => None,
+        }
+    }
+
     /// Extract `DataBlock`s to `DataType`s,
     /// returns the minimum timestamp in a series of `DataBlock`s
     fn next_min(
@@ -495,8 +542,9 @@ impl DataBlock {
         min_ts
     }
 
-    // todo:
-    /// Encodes timestamps and values of this `DataBlock` to bytes.
+    /// Encodes timestamps and values in range [start, end) with the given
+    /// encodings, `Encoding::Auto` is replaced by the codec that produces
+    /// the smallest output for the range.
     pub fn encode(
         &self,
         start: usize,
@@ -504,6 +552,7 @@ impl DataBlock {
         encodings: DataBlockEncoding,
     ) -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error + Send + Sync>> {
         let (ts_enc, val_enc) = encodings.split();
+        let ts_enc = resolve_encoding(ts_enc, || select_ts_encoding(&self.ts()[start..end]));
         let ts_codec = get_ts_codec(ts_enc);
 
         let mut ts_buf = vec![];
@@ -511,27 +560,32 @@ impl DataBlock {
         match self {
             DataBlock::Bool { ts, val, .. } => {
                 ts_codec.encode(&ts[start..end], &mut ts_buf)?;
+                let val_enc = resolve_encoding(val_enc, || select_bool_encoding(&val[start..end]));
                 let val_codec = get_bool_codec(val_enc);
                 val_codec.encode(&val[start..end], &mut data_buf)?
             }
             DataBlock::U64 { ts, val, .. } => {
                 ts_codec.encode(&ts[start..end], &mut ts_buf)?;
+                let val_enc = resolve_encoding(val_enc, || select_u64_encoding(&val[start..end]));
                 let val_codec = get_u64_codec(val_enc);
                 val_codec.encode(&val[start..end], &mut data_buf)?
             }
             DataBlock::I64 { ts, val, .. } => {
                 ts_codec.encode(&ts[start..end], &mut ts_buf)?;
+                let val_enc = resolve_encoding(val_enc, || select_i64_encoding(&val[start..end]));
                 let val_codec = get_i64_codec(val_enc);
                 val_codec.encode(&val[start..end], &mut data_buf)?
             }
             DataBlock::Str { ts, val, .. } => {
                 ts_codec.encode(&ts[start..end], &mut ts_buf)?;
                 let strs: Vec<&[u8]> = val.iter().map(|str| &str[..]).collect();
+                let val_enc = resolve_encoding(val_enc, || select_str_encoding(&strs[start..end]));
                 let val_codec = get_str_codec(val_enc);
                 val_codec.encode(&strs[start..end], &mut data_buf)?
             }
             DataBlock::F64 { ts, val, .. } => {
                 ts_codec.encode(&ts[start..end], &mut ts_buf)?;
+                let val_enc = resolve_encoding(val_enc, || select_f64_encoding(&val[start..end]));
                 let val_codec = get_f64_codec(val_enc);
                 val_codec.encode(&val[start..end], &mut data_buf)?
             }
@@ -542,6 +596,14 @@ impl DataBlock {
     pub fn decode() {}
 }
 
+fn resolve_encoding(encoding: Encoding, select: impl FnOnce() -> Encoding) -> Encoding {
+    if encoding == Encoding::Auto {
+        select()
+    } else {
+        encoding
+    }
+}
+
 impl Display for DataBlock {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -651,13 +713,19 @@ fn exclude_slow(v: &mut Vec<MiniVec<u8>>, min_idx: usize, max_idx: usize) {
 
 #[cfg(test)]
 pub mod test {
-    use minivec::mini_vec;
+    use minivec::{mini_vec, MiniVec};
     use std::mem::size_of;
 
+    use models::codec::Encoding;
+
     use crate::{
         memcache::DataType,
         tseries_family::TimeRange,
-        tsm::{block::exclude_fast, codec::DataBlockEncoding, DataBlock},
+        tsm::{
+            block::exclude_fast,
+            codec::{get_encoding, get_f64_codec, get_str_codec, get_ts_codec, DataBlockEncoding},
+            DataBlock,
+        },
     };
 
     pub(crate) fn check_data_block(block: &DataBlock, pattern: &[DataType]) {
@@ -686,6 +754,67 @@ pub mod test {
         ]);
     }
 
+    #[test]
+    fn test_encode_auto() {
+        let auto = DataBlockEncoding::new(Encoding::Auto, Encoding::Auto);
+
+        let ts: Vec<i64> = (0..200).map(|i| 1_000_000 + i * 10).collect();
+        let val: Vec<f64> = (0..200)
+            .map(|i| ((20.0 + (i as f64 / 10.0).sin()) * 100.0).round() / 100.0)
+            .collect();
+        let blk = DataBlock::F64 {
+            ts: ts.clone(),
+            val: val.clone(),
+            enc: auto,
+        };
+        let (ts_buf, val_buf) = blk.encode(0, blk.len(), auto).unwrap();
+        let ts_enc = get_encoding(&ts_buf);
+        let val_enc = get_encoding(&val_buf);
+        assert!(ts_enc.is_timestamp_encoding() && ts_enc != Encoding::Auto);
+        assert!(val_enc.is_double_encoding() && val_enc != Encoding::Auto);
+
+        // The whole block is sampled, so no candidate can be smaller.
+        for enc in [Encoding::Gorilla, Encoding::Chimp, Encoding::Quantile] {
+            let (_, buf) = blk
+                .encode(0, blk.len(), DataBlockEncoding::new(Encoding::Default, enc))
+                .unwrap();
+            assert!(val_buf.len() <= buf.len());
+        }
+
+        let mut got_ts = vec![];
+        get_ts_codec(ts_enc).decode(&ts_buf, &mut got_ts).unwrap();
+        assert_eq!(got_ts, ts);
+        let mut got_val = vec![];
+        get_f64_codec(val_enc)
+            .decode(&val_buf, &mut got_val)
+            .unwrap();
+        assert_eq!(got_val, val);
+
+        // Low-cardinality strings use dictionary encoding.
+        let states = ["ok", "warning", "critical"];
+        let mut seed = 42_u64;
+        let val: Vec<MiniVec<u8>> = (0..200)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                MiniVec::from(states[(seed % 3) as usize].as_bytes())
+            })
+            .collect();
+        let blk = DataBlock::Str {
+            ts,
+            val: val.clone(),
+            enc: auto,
+        };
+        let (_, val_buf) = blk.encode(0, blk.len(), auto).unwrap();
+        assert_eq!(get_encoding(&val_buf), Encoding::Dictionary);
+        let mut got_val = vec![];
+        get_str_codec(Encoding::Dictionary)
+            .decode(&val_buf, &mut got_val)
+            .unwrap();
+        assert_eq!(got_val, val);
+    }
+
     #[test]
     fn test_data_block_exclude_1() {
         #[rustfmt::skip]
@@ -840,5 +969,20 @@ pub mod test {
                 enc: DataBlockEncoding::default()
             }
         );
+
+        #[rustfmt::skip]
+        let mut blk = DataBlock::U64 {
+            ts: vec![0, 2, 4, 6], val: vec![10, 12, 14, 16],
+            enc: DataBlockEncoding::default()
+        };
+        blk.exclude(&TimeRange::from((1, 3)));
+        assert_eq!(
+            blk,
+            DataBlock::U64 {
+                ts: vec![0, 4, 6],
+                val: vec![10, 14, 16],
+                enc: DataBlockEncoding::default()
+            }
+        );
     }
 }
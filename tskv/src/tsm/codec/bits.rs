@@ -0,0 +1,83 @@
+use std::error::Error;
+
+/// Writes values of arbitrary bit width into a byte buffer, most significant
+/// bit first.
+pub(super) struct BitWriter<'a> {
+    buf: &'a mut Vec<u8>,
+    // number of unused low bits in the last byte of buf.
+    free_bits: u32,
+}
+
+impl<'a> BitWriter<'a> {
+    pub fn new(buf: &'a mut Vec<u8>) -> Self {
+        Self { buf, free_bits: 0 }
+    }
+
+    /// Writes the lowest `n` bits of `value`, most significant bit first.
+    pub fn write_bits(&mut self, value: u64, mut n: u32) {
+        while n > 0 {
+            if self.free_bits == 0 {
+                self.buf.push(0);
+                self.free_bits = 8;
+            }
+            let take = n.min(self.free_bits);
+            let bits = ((value >> (n - take)) & ((1_u64 << take) - 1)) as u8;
+            if let Some(last) = self.buf.last_mut() {
+                *last |= bits << (self.free_bits - take);
+            }
+            self.free_bits -= take;
+            n -= take;
+        }
+    }
+}
+
+/// Reads values written by [`BitWriter`].
+pub(super) struct BitReader<'a> {
+    buf: &'a [u8],
+    // position in bits.
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub fn read_bits(&mut self, mut n: u32) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        if self.pos + n as usize > self.buf.len() * 8 {
+            return Err(From::from("unexpected end of block"));
+        }
+        let mut value = 0_u64;
+        while n > 0 {
+            let available = 8 - (self.pos % 8) as u32;
+            let take = n.min(available);
+            let bits = (self.buf[self.pos / 8] >> (available - take)) as u64 & ((1 << take) - 1);
+            value = (value << take) | bits;
+            self.pos += take as usize;
+            n -= take;
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BitReader, BitWriter};
+
+    #[test]
+    fn test_bits_round_trip() {
+        let values: Vec<(u64, u32)> = vec![(1, 1), (0b101, 3), (u64::MAX, 64), (0, 7), (300, 9)];
+        let mut buf = vec![];
+        let mut writer = BitWriter::new(&mut buf);
+        for (v, n) in values.iter() {
+            writer.write_bits(*v, *n);
+        }
+        assert_eq!(buf.len(), 11);
+
+        let mut reader = BitReader::new(&buf);
+        for (v, n) in values.iter() {
+            assert_eq!(reader.read_bits(*n).unwrap(), *v);
+        }
+        assert!(reader.read_bits(8).is_err());
+    }
+}
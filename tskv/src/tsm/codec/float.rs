@@ -1,4 +1,5 @@
 use crate::byte_utils::decode_be_f64;
+use crate::tsm::codec::bits::{BitReader, BitWriter};
 use crate::tsm::codec::Encoding;
 use q_compress::{auto_compress, auto_decompress, DEFAULT_COMPRESSION_LEVEL};
 use std::error::Error;
@@ -257,6 +258,109 @@ pub fn f64_q_compress_encode(
     Ok(())
 }
 
+// Chimp128 keeps a window of the last 128 values and XORs each value with
+// the previous value sharing the most trailing bits, which suits
+// decimal-rounded data better than Gorilla's single previous value.
+// See "Chimp: Efficient Lossless Floating Point Compression for Time Series
+// Databases" (Liakos et al., VLDB 2022).
+const CHIMP_PREVIOUS_VALUES: usize = 128;
+const CHIMP_PREVIOUS_VALUES_LOG2: u32 = 7;
+const CHIMP_THRESHOLD: u32 = 6 + CHIMP_PREVIOUS_VALUES_LOG2;
+const CHIMP_SET_LSB: u64 = (1 << (CHIMP_THRESHOLD + 1)) - 1;
+// Leading zero counts are rounded down to one of these and stored as 3 bits.
+const CHIMP_LEADING_ZEROS: [u32; 8] = [0, 8, 12, 16, 18, 20, 22, 24];
+
+fn chimp_round_leading_zeros(leading_zeros: u32) -> (u64, u32) {
+    let repr = CHIMP_LEADING_ZEROS
+        .iter()
+        .rposition(|lz| *lz <= leading_zeros)
+        .unwrap_or(0);
+    (repr as u64, CHIMP_LEADING_ZEROS[repr])
+}
+
+/// encode encodes a vector of floats into dst using Chimp128.
+///
+/// The block starts with the encoding type and the number of values as a
+/// big-endian u32, followed by the first value and a control-bit stream for
+/// the rest:
+/// - `00` + index: value equals a value in the window.
+/// - `01` + index + leading + length + bits: XOR with a window value that
+///   has more than `CHIMP_THRESHOLD` trailing zeros.
+/// - `10` + bits: XOR with the previous value, same leading zeros as before.
+/// - `11` + leading + bits: XOR with the previous value, new leading zeros.
+pub fn f64_chimp_encode(
+    src: &[f64],
+    dst: &mut Vec<u8>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    dst.clear();
+    if src.is_empty() {
+        return Ok(());
+    }
+    let len = u32::try_from(src.len()).map_err(|_| "too many values for a block")?;
+
+    dst.push(Encoding::Chimp as u8);
+    dst.extend_from_slice(&len.to_be_bytes());
+
+    let mut writer = BitWriter::new(dst);
+    let mut stored = [0_u64; CHIMP_PREVIOUS_VALUES];
+    let mut indices = vec![0_usize; CHIMP_SET_LSB as usize + 1];
+    // u32::MAX means the next XOR must write its leading zeros.
+    let mut stored_leading_zeros = u32::MAX;
+
+    let first = src[0].to_bits();
+    writer.write_bits(first, 64);
+    stored[0] = first;
+    indices[(first & CHIMP_SET_LSB) as usize] = 0;
+
+    for (index, v) in src.iter().enumerate().skip(1) {
+        let value = v.to_bits();
+        let key = (value & CHIMP_SET_LSB) as usize;
+        let prev_index = (index - 1) % CHIMP_PREVIOUS_VALUES;
+
+        let mut reference = prev_index;
+        let mut xor = stored[prev_index] ^ value;
+        let candidate = indices[key];
+        if index - candidate < CHIMP_PREVIOUS_VALUES {
+            let candidate = candidate % CHIMP_PREVIOUS_VALUES;
+            let candidate_xor = stored[candidate] ^ value;
+            if candidate_xor.trailing_zeros() > CHIMP_THRESHOLD {
+                reference = candidate;
+                xor = candidate_xor;
+            }
+        }
+
+        let trailing_zeros = xor.trailing_zeros();
+        if xor == 0 {
+            writer.write_bits(0b00, 2);
+            writer.write_bits(reference as u64, CHIMP_PREVIOUS_VALUES_LOG2);
+            stored_leading_zeros = u32::MAX;
+        } else if trailing_zeros > CHIMP_THRESHOLD {
+            let (repr, leading_zeros) = chimp_round_leading_zeros(xor.leading_zeros());
+            let significant_bits = 64 - leading_zeros - trailing_zeros;
+            writer.write_bits(0b01, 2);
+            writer.write_bits(reference as u64, CHIMP_PREVIOUS_VALUES_LOG2);
+            writer.write_bits(repr, 3);
+            writer.write_bits(significant_bits as u64, 6);
+            writer.write_bits(xor >> trailing_zeros, significant_bits);
+            stored_leading_zeros = u32::MAX;
+        } else {
+            let (repr, leading_zeros) = chimp_round_leading_zeros(xor.leading_zeros());
+            if leading_zeros == stored_leading_zeros {
+                writer.write_bits(0b10, 2);
+            } else {
+                writer.write_bits(0b11, 2);
+                writer.write_bits(repr, 3);
+                stored_leading_zeros = leading_zeros;
+            }
+            writer.write_bits(xor, 64 - leading_zeros);
+        }
+
+        stored[index % CHIMP_PREVIOUS_VALUES] = value;
+        indices[key] = index;
+    }
+    Ok(())
+}
+
 pub fn f64_without_compress_encode(
     src: &[f64],
     dst: &mut Vec<u8>,
@@ -383,6 +487,61 @@ pub fn f64_q_compress_decode(
     Ok(())
 }
 
+/// decode decodes a Chimp128 encoded slice of bytes into a vector of floats.
+pub fn f64_chimp_decode(
+    src: &[u8],
+    dst: &mut Vec<f64>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if src.is_empty() {
+        return Ok(());
+    }
+    if src.len() < 5 {
+        return Err(From::from("unexpected end of block"));
+    }
+
+    let len = u32::from_be_bytes([src[1], src[2], src[3], src[4]]) as usize;
+    if len == 0 {
+        return Ok(());
+    }
+    dst.reserve(len);
+
+    let mut reader = BitReader::new(&src[5..]);
+    let mut stored = [0_u64; CHIMP_PREVIOUS_VALUES];
+    let mut stored_leading_zeros = 0;
+
+    let first = reader.read_bits(64)?;
+    stored[0] = first;
+    dst.push(f64::from_bits(first));
+
+    for index in 1..len {
+        let prev_index = (index - 1) % CHIMP_PREVIOUS_VALUES;
+        let value = match reader.read_bits(2)? {
+            0b00 => {
+                let reference = reader.read_bits(CHIMP_PREVIOUS_VALUES_LOG2)? as usize;
+                stored[reference]
+            }
+            0b01 => {
+                let reference = reader.read_bits(CHIMP_PREVIOUS_VALUES_LOG2)? as usize;
+                let leading_zeros = CHIMP_LEADING_ZEROS[reader.read_bits(3)? as usize];
+                let significant_bits = reader.read_bits(6)? as u32;
+                if significant_bits == 0 || leading_zeros + significant_bits > 64 {
+                    return Err(From::from("invalid chimp block"));
+                }
+                let trailing_zeros = 64 - leading_zeros - significant_bits;
+                stored[reference] ^ (reader.read_bits(significant_bits)? << trailing_zeros)
+            }
+            0b10 => stored[prev_index] ^ reader.read_bits(64 - stored_leading_zeros)?,
+            _ => {
+                stored_leading_zeros = CHIMP_LEADING_ZEROS[reader.read_bits(3)? as usize];
+                stored[prev_index] ^ reader.read_bits(64 - stored_leading_zeros)?
+            }
+        };
+        stored[index % CHIMP_PREVIOUS_VALUES] = value;
+        dst.push(f64::from_bits(value));
+    }
+    Ok(())
+}
+
 pub fn f64_without_compress_decode(
     src: &[u8],
     dst: &mut Vec<f64>,
@@ -583,8 +742,10 @@ mod tests {
     // use test_helpers::approximately_equal;
 
     use crate::tsm::codec::float::{
-        f64_gorilla_decode, f64_gorilla_encode, f64_q_compress_decode, f64_q_compress_encode,
+        f64_chimp_decode, f64_chimp_encode, f64_gorilla_decode, f64_gorilla_encode,
+        f64_q_compress_decode, f64_q_compress_encode, f64_without_compress_encode,
     };
+    use crate::tsm::codec::{get_encoding, Encoding};
 
     #[test]
     fn encode_no_values() {
@@ -1797,5 +1958,101 @@ mod tests {
             // verify got same values back
             assert_eq!(got, src, "{}", test.name);
         }
+
+        for test in tests.iter() {
+            let mut dst = vec![];
+            let src = test.input.clone();
+
+            f64_chimp_encode(&src, &mut dst).expect("failed to encode");
+
+            let mut got = vec![];
+            f64_chimp_decode(&dst, &mut got).expect("failed to decode");
+            // verify got same values back
+            assert_eq!(got, src, "{}", test.name);
+        }
+    }
+
+    #[test]
+    fn encode_special_value_chimp() {
+        let src: Vec<f64> = vec![
+            100.0,
+            222.12,
+            f64::from_bits(0x7ff8000000000001),
+            45.324,
+            f64::NAN,
+            2453.023,
+            -1234.235312132,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            9123419329123.1234,
+            f64::from_bits(0x7ff0000000000002),
+            -19292929929292929292.22,
+            -0.0000000000000000000000000092,
+            0.0,
+            -0.0,
+            f64::MIN_POSITIVE,
+            f64::MAX,
+            f64::MIN,
+        ];
+        let mut dst = vec![];
+
+        f64_chimp_encode(&src, &mut dst).expect("failed to encode src");
+        assert_eq!(get_encoding(&dst), Encoding::Chimp);
+
+        let mut got = vec![];
+        f64_chimp_decode(&dst, &mut got).expect("failed to decode");
+        assert_eq!(got.len(), src.len());
+        for (exp, v) in src.iter().zip(got.iter()) {
+            assert_eq!(exp.to_bits(), v.to_bits());
+        }
+
+        // Truncated blocks must fail instead of panicking.
+        let mut got = vec![];
+        assert!(f64_chimp_decode(&dst[..dst.len() / 2], &mut got).is_err());
+    }
+
+    #[test]
+    fn encode_chimp_window() {
+        // Values repeating with a period larger than one exercise the
+        // 128-value reference window, including wrap-around.
+        let src: Vec<f64> = (0..1000)
+            .map(|i| ((i % 37) as f64) * 0.25 + ((i / 300) as f64) * 1000.5)
+            .collect();
+        let mut dst = vec![];
+        f64_chimp_encode(&src, &mut dst).expect("failed to encode");
+
+        let mut got = vec![];
+        f64_chimp_decode(&dst, &mut got).expect("failed to decode");
+        assert_eq!(got, src);
+    }
+
+    #[test]
+    fn chimp_compression_ratio() {
+        // Sensor-like readings rounded to two decimals.
+        let src: Vec<f64> = (0..1000)
+            .map(|i| {
+                let v = 20.0 + (i as f64 / 50.0).sin() * 5.0 + ((i * 7919) % 13) as f64 * 0.01;
+                (v * 100.0).round() / 100.0
+            })
+            .collect();
+
+        let mut raw = vec![];
+        f64_without_compress_encode(&src, &mut raw).unwrap();
+        let mut gorilla = vec![];
+        f64_gorilla_encode(&src, &mut gorilla).unwrap();
+        let mut quantile = vec![];
+        f64_q_compress_encode(&src, &mut quantile).unwrap();
+        let mut chimp = vec![];
+        f64_chimp_encode(&src, &mut chimp).unwrap();
+
+        assert!(chimp.len() < raw.len());
+        assert!(chimp.len() < gorilla.len());
+        // Quantile compresses better as it encodes the whole block with the value
+        // distribution, Chimp is an XOR codec like Gorilla which is cheaper to encode.
+        assert!(quantile.len() < chimp.len());
+
+        let mut got = vec![];
+        f64_chimp_decode(&chimp, &mut got).unwrap();
+        assert_eq!(got, src);
     }
 }
@@ -3,17 +3,19 @@ use crate::tsm::codec::boolean::{
     bool_without_compress_encode,
 };
 use crate::tsm::codec::float::{
-    f64_gorilla_decode, f64_gorilla_encode, f64_q_compress_decode, f64_q_compress_encode,
-    f64_without_compress_decode, f64_without_compress_encode,
+    f64_chimp_decode, f64_chimp_encode, f64_gorilla_decode, f64_gorilla_encode,
+    f64_q_compress_decode, f64_q_compress_encode, f64_without_compress_decode,
+    f64_without_compress_encode,
 };
 use crate::tsm::codec::integer::{
     i64_q_compress_decode, i64_q_compress_encode, i64_without_compress_decode,
     i64_without_compress_encode, i64_zigzag_simple8b_decode, i64_zigzag_simple8b_encode,
 };
 use crate::tsm::codec::string::{
-    str_bzip_decode, str_bzip_encode, str_gzip_decode, str_gzip_encode, str_snappy_decode,
-    str_snappy_encode, str_without_compress_decode, str_without_compress_encode, str_zlib_decode,
-    str_zlib_encode, str_zstd_decode, str_zstd_encode,
+    str_bzip_decode, str_bzip_encode, str_dictionary_decode, str_dictionary_encode,
+    str_gzip_decode, str_gzip_encode, str_snappy_decode, str_snappy_encode,
+    str_without_compress_decode, str_without_compress_encode, str_zlib_decode, str_zlib_encode,
+    str_zstd_decode, str_zstd_encode,
 };
 use crate::tsm::codec::timestamp;
 use crate::tsm::codec::timestamp::{
@@ -153,6 +155,18 @@ impl FloatCodec for QuantileFloatCodec {
     }
 }
 
+struct ChimpFloatCodec();
+
+impl FloatCodec for ChimpFloatCodec {
+    fn encode(&self, src: &[f64], dst: &mut Vec<u8>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        f64_chimp_encode(src, dst)
+    }
+
+    fn decode(&self, src: &[u8], dst: &mut Vec<f64>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        f64_chimp_decode(src, dst)
+    }
+}
+
 pub trait UnsignedCodec {
     fn encode(&self, src: &[u64], dst: &mut Vec<u8>) -> Result<(), Box<dyn Error + Send + Sync>>;
     fn decode(&self, src: &[u8], dst: &mut Vec<u64>) -> Result<(), Box<dyn Error + Send + Sync>>;
@@ -328,6 +342,22 @@ impl StringCodec for ZlibStringCodec {
     }
 }
 
+struct DictionaryStringCodec();
+
+impl StringCodec for DictionaryStringCodec {
+    fn encode(&self, src: &[&[u8]], dst: &mut Vec<u8>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        str_dictionary_encode(src, dst)
+    }
+
+    fn decode(
+        &self,
+        src: &[u8],
+        dst: &mut Vec<MiniVec<u8>>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        str_dictionary_decode(src, dst)
+    }
+}
+
 pub fn get_encoding(src: &[u8]) -> Encoding {
     if src.is_empty() {
         return Encoding::Unknown;
@@ -367,6 +397,7 @@ pub fn get_f64_codec(algo: Encoding) -> Box<dyn FloatCodec + Send + Sync> {
         Encoding::Null => Box::new(NullFloatCodec()),
         Encoding::Gorilla => Box::new(GorillaFloatCodec()),
         Encoding::Quantile => Box::new(QuantileFloatCodec()),
+        Encoding::Chimp => Box::new(ChimpFloatCodec()),
         _ => Box::new(GorillaFloatCodec()),
     }
 }
@@ -379,6 +410,7 @@ pub fn get_str_codec(algo: Encoding) -> Box<dyn StringCodec + Send + Sync> {
         Encoding::Snappy => Box::new(SnappyStringCodec()),
         Encoding::Zstd => Box::new(ZstdStringCodec()),
         Encoding::Zlib => Box::new(ZlibStringCodec()),
+        Encoding::Dictionary => Box::new(DictionaryStringCodec()),
         _ => Box::new(SnappyStringCodec()),
     }
 }
@@ -390,3 +422,67 @@ pub fn get_bool_codec(algo: Encoding) -> Box<dyn BooleanCodec + Send + Sync> {
         _ => Box::new(BitPackBooleanCodec()),
     }
 }
+
+/// Max number of values `Encoding::Auto` encodes with every candidate codec
+/// to choose the codec for a block.
+const AUTO_SAMPLE_SIZE: usize = 256;
+
+const AUTO_TS_CANDIDATES: [Encoding; 2] = [Encoding::Delta, Encoding::Quantile];
+const AUTO_INTEGER_CANDIDATES: [Encoding; 2] = [Encoding::Delta, Encoding::Quantile];
+const AUTO_FLOAT_CANDIDATES: [Encoding; 3] =
+    [Encoding::Gorilla, Encoding::Chimp, Encoding::Quantile];
+const AUTO_STRING_CANDIDATES: [Encoding; 3] =
+    [Encoding::Snappy, Encoding::Dictionary, Encoding::Zstd];
+
+/// Encodes a sample of `src` with each candidate and returns the one with
+/// the smallest output, preferring earlier candidates on ties.
+fn select_encoding<T>(
+    src: &[T],
+    candidates: &[Encoding],
+    encode: impl Fn(Encoding, &[T], &mut Vec<u8>) -> Result<(), Box<dyn Error + Send + Sync>>,
+) -> Encoding {
+    let sample = &src[..src.len().min(AUTO_SAMPLE_SIZE)];
+    let mut buf = vec![];
+    let mut selected = (candidates[0], usize::MAX);
+    for encoding in candidates {
+        buf.clear();
+        if encode(*encoding, sample, &mut buf).is_ok() && buf.len() < selected.1 {
+            selected = (*encoding, buf.len());
+        }
+    }
+    selected.0
+}
+
+pub fn select_ts_encoding(src: &[i64]) -> Encoding {
+    select_encoding(src, &AUTO_TS_CANDIDATES, |encoding, src, dst| {
+        get_ts_codec(encoding).encode(src, dst)
+    })
+}
+
+pub fn select_i64_encoding(src: &[i64]) -> Encoding {
+    select_encoding(src, &AUTO_INTEGER_CANDIDATES, |encoding, src, dst| {
+        get_i64_codec(encoding).encode(src, dst)
+    })
+}
+
+pub fn select_u64_encoding(src: &[u64]) -> Encoding {
+    select_encoding(src, &AUTO_INTEGER_CANDIDATES, |encoding, src, dst| {
+        get_u64_codec(encoding).encode(src, dst)
+    })
+}
+
+pub fn select_f64_encoding(src: &[f64]) -> Encoding {
+    select_encoding(src, &AUTO_FLOAT_CANDIDATES, |encoding, src, dst| {
+        get_f64_codec(encoding).encode(src, dst)
+    })
+}
+
+pub fn select_str_encoding(src: &[&[u8]]) -> Encoding {
+    select_encoding(src, &AUTO_STRING_CANDIDATES, |encoding, src, dst| {
+        get_str_codec(encoding).encode(src, dst)
+    })
+}
+
+pub fn select_bool_encoding(_src: &[bool]) -> Encoding {
+    Encoding::BitPack
+}
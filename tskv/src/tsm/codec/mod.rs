@@ -1,3 +1,4 @@
+mod bits;
 mod boolean;
 mod float;
 mod instance;
@@ -34,4 +35,15 @@ impl DataBlockEncoding {
     pub fn split(&self) -> (Encoding, Encoding) {
         (self.ts_encoding, self.val_encoding)
     }
+
+    /// Encodings of a column with `val_encoding` in the table schema, timestamps
+    /// of auto encoded columns are auto encoded too.
+    pub fn of_column(val_encoding: Encoding) -> Self {
+        let ts_encoding = if val_encoding == Encoding::Auto {
+            Encoding::Auto
+        } else {
+            Encoding::Default
+        };
+        Self::new(ts_encoding, val_encoding)
+    }
 }
@@ -1,8 +1,11 @@
+use std::collections::HashMap;
 use std::io::Write;
 use std::{convert::TryInto, error::Error};
 
 use crate::byte_utils::{decode_be_i64, decode_be_u32, decode_be_u64};
+use crate::tsm::codec::bits::{BitReader, BitWriter};
 use crate::tsm::codec::Encoding;
+use crate::tsm::MAX_BLOCK_VALUES;
 use bzip2::write::{BzDecoder, BzEncoder};
 use bzip2::Compression as CompressionBzip;
 use flate2::write::{GzDecoder, GzEncoder};
@@ -24,6 +27,9 @@ const MAX_I32: usize = i32::MAX as usize;
 /// zstd compress level, select from -5 ~ 17
 const ZSTD_COMPRESS_LEVEL: i32 = 3;
 
+/// Max number of distinct values in a dictionary encoded block
+const DICTIONARY_MAX_SIZE: usize = 256;
+
 /// Encodes a slice of byte slices representing string data into a vector of
 /// bytes. Currently uses Snappy compression.
 pub fn str_snappy_encode(
@@ -83,7 +89,7 @@ pub fn str_snappy_encode(
     let actual_compressed_size = encoder.compress(data, compressed_data)?;
 
     dst.truncate(HEADER_LEN + actual_compressed_size);
-    dst.insert(0, Encoding::Gorilla as u8);
+    dst.insert(0, Encoding::Snappy as u8);
 
     Ok(())
 }
@@ -179,6 +185,65 @@ pub fn str_zlib_encode(
     Ok(())
 }
 
+/// Encodes a slice of byte slices as a per-block dictionary of the distinct
+/// values followed by bit-packed indexes into the dictionary. Blocks with more
+/// than `MAX_BLOCK_VALUES` values, more than `DICTIONARY_MAX_SIZE` distinct
+/// values, or with fewer than two values per dictionary entry, fall back to zstd.
+pub fn str_dictionary_encode(
+    src: &[&[u8]],
+    dst: &mut Vec<u8>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    dst.clear(); // reset buffer
+    if src.is_empty() {
+        return Ok(());
+    }
+    if src.len() > MAX_BLOCK_VALUES as usize {
+        return str_zstd_encode(src, dst);
+    }
+
+    let mut dictionary: Vec<&[u8]> = vec![];
+    let mut positions: HashMap<&[u8], u64> = HashMap::new();
+    let mut indexes = Vec::with_capacity(src.len());
+    for s in src {
+        let index = match positions.get(s) {
+            Some(index) => *index,
+            None => {
+                if dictionary.len() == DICTIONARY_MAX_SIZE {
+                    return str_zstd_encode(src, dst);
+                }
+                let index = dictionary.len() as u64;
+                dictionary.push(s);
+                positions.insert(s, index);
+                index
+            }
+        };
+        indexes.push(index);
+    }
+    if dictionary.len() * 2 > src.len() {
+        return str_zstd_encode(src, dst);
+    }
+
+    dst.push(Encoding::Dictionary as u8);
+    dst.extend_from_slice(&(src.len() as u64).encode_var_vec());
+    dst.extend_from_slice(&(dictionary.len() as u64).encode_var_vec());
+    for s in dictionary.iter() {
+        dst.extend_from_slice(&(s.len() as u64).encode_var_vec());
+        dst.extend_from_slice(s);
+    }
+
+    let width = dictionary_index_width(dictionary.len());
+    let mut writer = BitWriter::new(dst);
+    for index in indexes {
+        writer.write_bits(index, width);
+    }
+    Ok(())
+}
+
+/// Number of bits needed to store an index into a dictionary of `len` values.
+fn dictionary_index_width(len: usize) -> u32 {
+    usize::BITS - len.saturating_sub(1).leading_zeros()
+}
+
 pub fn str_without_compress_encode(
     src: &[&[u8]],
     dst: &mut Vec<u8>,
@@ -338,6 +403,55 @@ pub fn str_zlib_decode(
     Ok(())
 }
 
+pub fn str_dictionary_decode(
+    src: &[u8],
+    dst: &mut Vec<MiniVec<u8>>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if src.is_empty() {
+        return Ok(());
+    }
+    if src[0] == Encoding::Zstd as u8 {
+        return str_zstd_decode(src, dst);
+    }
+
+    let mut i = 1;
+    let (count, n) = u64::decode_var(&src[i..]).ok_or("invalid dictionary value count")?;
+    i += n;
+    if count > MAX_BLOCK_VALUES as u64 {
+        return Err(From::from("invalid dictionary value count"));
+    }
+    let (dictionary_len, n) = u64::decode_var(&src[i..]).ok_or("invalid dictionary length")?;
+    i += n;
+    let dictionary_len: usize = dictionary_len.try_into()?;
+    if dictionary_len == 0 || dictionary_len > DICTIONARY_MAX_SIZE {
+        return Err(From::from("invalid dictionary length"));
+    }
+
+    let mut dictionary = Vec::with_capacity(dictionary_len);
+    for _ in 0..dictionary_len {
+        let (len, n) = u64::decode_var(&src[i..]).ok_or("invalid dictionary value length")?;
+        i += n;
+        let len: usize = len.try_into()?;
+        let end = match i.checked_add(len) {
+            Some(end) if end <= src.len() => end,
+            _ => return Err(From::from("unexpected end of block")),
+        };
+        dictionary.push(&src[i..end]);
+        i = end;
+    }
+
+    let width = dictionary_index_width(dictionary_len);
+    let mut reader = BitReader::new(&src[i..]);
+    for _ in 0..count {
+        let index = reader.read_bits(width)? as usize;
+        let value = dictionary
+            .get(index)
+            .ok_or("dictionary index out of range")?;
+        dst.push(MiniVec::from(*value));
+    }
+    Ok(())
+}
+
 pub fn str_without_compress_decode(
     src: &[u8],
     dst: &mut Vec<MiniVec<u8>>,
@@ -556,5 +670,87 @@ mod tests {
         str_without_compress_encode(&data, &mut dst).unwrap();
         str_without_compress_decode(&dst, &mut got).unwrap();
         assert_eq!(data_exp, got);
+        dst.clear();
+        got.clear();
+
+        // Every value is distinct, so this falls back to zstd.
+        str_dictionary_encode(&data, &mut dst).unwrap();
+        assert_eq!(dst[0], Encoding::Zstd as u8);
+        str_dictionary_decode(&dst, &mut got).unwrap();
+        assert_eq!(data_exp, got);
+    }
+
+    #[test]
+    fn test_dictionary_encode_decode() {
+        let states = ["ok", "warning", "critical", ""];
+        let mut seed = 42_u64;
+        let data: Vec<&[u8]> = (0..1000)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                states[(seed % states.len() as u64) as usize].as_bytes()
+            })
+            .collect();
+        let data_exp: Vec<MiniVec<u8>> = data.iter().map(|s| MiniVec::from(*s)).collect();
+
+        let mut dst = vec![];
+        let mut got = vec![];
+        str_dictionary_encode(&data, &mut dst).unwrap();
+        assert_eq!(dst[0], Encoding::Dictionary as u8);
+        str_dictionary_decode(&dst, &mut got).unwrap();
+        assert_eq!(data_exp, got);
+
+        // 4 distinct values take 2 bits per index.
+        assert!(dst.len() < 1000 * 2 / 8 + 32);
+        let mut zstd = vec![];
+        str_zstd_encode(&data, &mut zstd).unwrap();
+        assert!(dst.len() < zstd.len());
+
+        // A single distinct value needs no index bits.
+        let data: Vec<&[u8]> = vec![b"running"; 10];
+        str_dictionary_encode(&data, &mut dst).unwrap();
+        assert_eq!(dst[0], Encoding::Dictionary as u8);
+        got.clear();
+        str_dictionary_decode(&dst, &mut got).unwrap();
+        assert_eq!(got, vec![MiniVec::from(&b"running"[..]); 10]);
+
+        // Too many distinct values fall back to zstd.
+        let strings: Vec<String> = (0..2000).map(|i| format!("v{}", i % 300)).collect();
+        let data: Vec<&[u8]> = strings.iter().map(|s| s.as_bytes()).collect();
+        str_dictionary_encode(&data, &mut dst).unwrap();
+        assert_eq!(dst[0], Encoding::Zstd as u8);
+        got.clear();
+        str_dictionary_decode(&dst, &mut got).unwrap();
+        assert_eq!(got.len(), 2000);
+        assert_eq!(got[299], MiniVec::from(&b"v299"[..]));
+
+        // Truncated blocks must fail instead of panicking.
+        let data: Vec<&[u8]> = (0..100).map(|i| states[i % 3].as_bytes()).collect();
+        str_dictionary_encode(&data, &mut dst).unwrap();
+        got.clear();
+        assert!(str_dictionary_decode(&dst[..dst.len() - 10], &mut got).is_err());
+
+        // Damaged value count or value length must fail instead of allocating
+        // or overflowing.
+        let mut damaged = vec![Encoding::Dictionary as u8];
+        damaged.extend_from_slice(&u64::MAX.encode_var_vec());
+        damaged.extend_from_slice(&1_u64.encode_var_vec());
+        damaged.extend_from_slice(&1_u64.encode_var_vec());
+        damaged.push(b'a');
+        assert!(str_dictionary_decode(&damaged, &mut got).is_err());
+        let mut damaged = vec![Encoding::Dictionary as u8];
+        damaged.extend_from_slice(&10_u64.encode_var_vec());
+        damaged.extend_from_slice(&1_u64.encode_var_vec());
+        damaged.extend_from_slice(&u64::MAX.encode_var_vec());
+        damaged.push(b'a');
+        assert!(str_dictionary_decode(&damaged, &mut got).is_err());
+
+        // Blocks with more values than a TSM block can store fall back to zstd.
+        let data: Vec<&[u8]> = (0..MAX_BLOCK_VALUES as usize + 1)
+            .map(|i| states[i % 3].as_bytes())
+            .collect();
+        str_dictionary_encode(&data, &mut dst).unwrap();
+        assert_eq!(dst[0], Encoding::Zstd as u8);
     }
 }
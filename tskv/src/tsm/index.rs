@@ -2,7 +2,7 @@ use std::{cmp, fmt::Display, io::SeekFrom, sync::Arc};
 
 use models::{FieldId, Timestamp, ValueType};
 
-use super::{BlockMetaIterator, BLOCK_META_SIZE, FOOTER_SIZE, INDEX_META_SIZE};
+use super::{block_meta_size, BlockMetaIterator, BLOCK_META_SIZE, FOOTER_SIZE, INDEX_META_SIZE};
 use crate::{
     byte_utils::{self, decode_be_i64, decode_be_u16, decode_be_u32, decode_be_u64},
     error::{Error, Result},
@@ -15,6 +15,8 @@ pub trait IndexT {}
 
 #[derive(Debug, Clone)]
 pub struct Index {
+    /// TSM file version, which decides the size of each block meta.
+    version: u8,
     /// In-memory index-block data
     ///
     /// ```text
@@ -34,14 +36,25 @@ pub struct Index {
 
 impl Index {
     #[inline(always)]
-    pub fn new(data: Vec<u8>, field_ids: Vec<FieldId>, offsets: Vec<u64>) -> Self {
+    pub fn new(version: u8, data: Vec<u8>, field_ids: Vec<FieldId>, offsets: Vec<u64>) -> Self {
         Self {
+            version,
             data,
             field_ids,
             offsets,
         }
     }
 
+    #[inline(always)]
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    #[inline(always)]
+    pub fn block_meta_size(&self) -> usize {
+        block_meta_size(self.version)
+    }
+
     #[inline(always)]
     pub fn data(&self) -> &[u8] {
         self.data.as_slice()
@@ -115,7 +128,8 @@ impl IndexMeta {
         }
         let first_blk_beg = self.index_ref.offsets()[self.index_idx] as usize + INDEX_META_SIZE;
         let min_ts = decode_be_i64(&self.index_ref.data[first_blk_beg..first_blk_beg + 8]);
-        let last_blk_beg = first_blk_beg + BLOCK_META_SIZE * (self.block_count as usize - 1);
+        let last_blk_beg =
+            first_blk_beg + self.index_ref.block_meta_size() * (self.block_count as usize - 1);
         let max_ts = decode_be_i64(&self.index_ref.data[last_blk_beg + 8..last_blk_beg + 16]);
         (min_ts, max_ts)
     }
@@ -229,6 +243,25 @@ impl BlockMeta {
     pub fn val_off(&self) -> u64 {
         decode_be_u64(&self.index_ref.data()[self.block_offset + 36..self.block_offset + 44])
     }
+
+    /// Returns value statistics of the block, `None` if the block has no statistics
+    /// (e.g. string blocks, or TSM files older than version 2).
+    pub fn statistics(&self) -> Option<BlockStatistics> {
+        if self.index_ref.version() < 2 {
+            return None;
+        }
+        BlockStatistics::decode(
+            self.field_type,
+            &self.index_ref.data()[self.block_offset + 44..self.block_offset + BLOCK_META_SIZE],
+        )
+    }
+
+    /// Returns true if codecs of the block were selected by `Encoding::Auto`.
+    pub fn auto_encoded(&self) -> bool {
+        self.index_ref.version() >= 2
+            && self.index_ref.data()[self.block_offset + 44] & BlockStatistics::FLAG_AUTO_ENCODED
+                != 0
+    }
 }
 
 impl Display for BlockMeta {
@@ -268,7 +301,7 @@ pub(crate) fn get_data_block_meta_unchecked(
     field_id: FieldId,
     field_type: ValueType,
 ) -> BlockMeta {
-    let base = index_offset + INDEX_META_SIZE + block_idx * BLOCK_META_SIZE;
+    let base = index_offset + INDEX_META_SIZE + block_idx * index.block_meta_size();
     BlockMeta::new(index, field_id, field_type, base)
 }
 
@@ -295,6 +328,110 @@ impl IndexEntry {
     }
 }
 
+/// Value statistics of a data block.
+///
+/// ```text
+/// +-------+---------+
+/// | flags | 1 bytes |
+/// | min   | 8 bytes |
+/// | max   | 8 bytes |
+/// | sum   | 8 bytes |
+/// +-------+---------+
+/// ```
+///
+/// `sum` of integer blocks is `None` if it overflowed, `sum` of boolean blocks
+/// is the number of `true` values. The flags byte also marks blocks of which
+/// codecs were selected by `Encoding::Auto`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockStatistics {
+    U64 {
+        min: u64,
+        max: u64,
+        sum: Option<u64>,
+    },
+    I64 {
+        min: i64,
+        max: i64,
+        sum: Option<i64>,
+    },
+    F64 {
+        min: f64,
+        max: f64,
+        sum: f64,
+    },
+    Bool {
+        min: bool,
+        max: bool,
+        sum: u64,
+    },
+}
+
+impl BlockStatistics {
+    const FLAG_MIN_MAX: u8 = 0b01;
+    const FLAG_SUM: u8 = 0b10;
+    const FLAG_AUTO_ENCODED: u8 = 0b100;
+
+    /// Encodes statistics into `buf`, all flags are cleared if `stats` is `None`.
+    fn encode(stats: Option<&Self>, buf: &mut [u8]) {
+        let (flags, min, max, sum) = match stats {
+            None => (0, 0_u64, 0_u64, None),
+            Some(Self::U64 { min, max, sum }) => (Self::FLAG_MIN_MAX, *min, *max, *sum),
+            Some(Self::I64 { min, max, sum }) => (
+                Self::FLAG_MIN_MAX,
+                *min as u64,
+                *max as u64,
+                sum.map(|s| s as u64),
+            ),
+            Some(Self::F64 { min, max, sum }) => (
+                Self::FLAG_MIN_MAX,
+                min.to_bits(),
+                max.to_bits(),
+                Some(sum.to_bits()),
+            ),
+            Some(Self::Bool { min, max, sum }) => {
+                (Self::FLAG_MIN_MAX, *min as u64, *max as u64, Some(*sum))
+            }
+        };
+        buf[0] = flags | if sum.is_some() { Self::FLAG_SUM } else { 0 };
+        buf[1..9].copy_from_slice(&min.to_be_bytes()[..]);
+        buf[9..17].copy_from_slice(&max.to_be_bytes()[..]);
+        buf[17..25].copy_from_slice(&sum.unwrap_or_default().to_be_bytes()[..]);
+    }
+
+    fn decode(field_type: ValueType, buf: &[u8]) -> Option<Self> {
+        let flags = buf[0];
+        if flags & Self::FLAG_MIN_MAX == 0 {
+            return None;
+        }
+        let min = decode_be_u64(&buf[1..9]);
+        let max = decode_be_u64(&buf[9..17]);
+        let sum = if flags & Self::FLAG_SUM == 0 {
+            None
+        } else {
+            Some(decode_be_u64(&buf[17..25]))
+        };
+        match field_type {
+            ValueType::Unsigned => Some(Self::U64 { min, max, sum }),
+            ValueType::Integer => Some(Self::I64 {
+                min: min as i64,
+                max: max as i64,
+                sum: sum.map(|s| s as i64),
+            }),
+            ValueType::Float => Some(Self::F64 {
+                min: f64::from_bits(min),
+                max: f64::from_bits(max),
+                sum: f64::from_bits(sum?),
+            }),
+            ValueType::Boolean => Some(Self::Bool {
+                min: min != 0,
+                max: max != 0,
+                sum: sum?,
+            }),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct BlockEntry {
     pub min_ts: Timestamp,
@@ -303,6 +440,8 @@ pub(crate) struct BlockEntry {
     pub offset: u64,
     pub size: u64,
     pub val_offset: u64,
+    pub statistics: Option<BlockStatistics>,
+    pub auto_encoded: bool,
 }
 
 impl BlockEntry {
@@ -314,5 +453,9 @@ impl BlockEntry {
         buf[20..28].copy_from_slice(&self.offset.to_be_bytes()[..]);
         buf[28..36].copy_from_slice(&self.size.to_be_bytes()[..]);
         buf[36..44].copy_from_slice(&self.val_offset.to_be_bytes()[..]);
+        BlockStatistics::encode(self.statistics.as_ref(), &mut buf[44..BLOCK_META_SIZE]);
+        if self.auto_encoded {
+            buf[44] |= BlockStatistics::FLAG_AUTO_ENCODED;
+        }
     }
 }
@@ -8,18 +8,32 @@ mod writer;
 pub use block::*;
 pub use index::*;
 pub use reader::*;
-pub use tombstone::{Tombstone, TsmTombstone};
+pub use tombstone::{remove_orphan_tombstones, Tombstone, TsmTombstone};
+pub(crate) use tombstone::{TOMBSTONE_FILE_SUFFIX, TOMBSTONE_MAGIC};
 pub use writer::*;
 
 // MAX_BLOCK_VALUES is the maximum number of values a TSM block can store.
 pub(crate) const MAX_BLOCK_VALUES: u32 = 1000;
 
-const HEADER_SIZE: usize = 5;
+// Version 2 appends value statistics to each block meta.
+pub(crate) const VERSION: u8 = 2;
+pub(crate) const HEADER_SIZE: usize = 5;
 const INDEX_META_SIZE: usize = 11;
-const BLOCK_META_SIZE: usize = 44;
-const BLOOM_FILTER_SIZE: usize = 64;
+const BLOCK_META_SIZE_V1: usize = 44;
+const BLOCK_META_SIZE: usize = 69;
+pub(crate) const BLOOM_FILTER_SIZE: usize = 64;
 const BLOOM_FILTER_BITS: u64 = 512; // 64 * 8
-const FOOTER_SIZE: usize = BLOOM_FILTER_SIZE + 8; // 72
+pub(crate) const FOOTER_SIZE: usize = BLOOM_FILTER_SIZE + 8; // 72
+
+/// Returns the size of a block meta in TSM files of the specified version,
+/// block metas older than version 2 have no value statistics.
+fn block_meta_size(version: u8) -> usize {
+    if version < 2 {
+        BLOCK_META_SIZE_V1
+    } else {
+        BLOCK_META_SIZE
+    }
+}
 
 pub trait BlockReader {
     fn decode(&mut self, block: &BlockMeta) -> crate::error::Result<DataBlock>;
@@ -1,6 +1,7 @@
 use minivec::MiniVec;
 use std::{
     borrow::Borrow,
+    collections::BTreeMap,
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -17,14 +18,15 @@ use crate::{
     file_utils,
     tseries_family::TimeRange,
     tsm::{
+        block_meta_size,
         codec::{
             get_bool_codec, get_encoding, get_f64_codec, get_i64_codec, get_str_codec,
             get_ts_codec, get_u64_codec, DataBlockEncoding,
         },
         get_data_block_meta_unchecked, get_index_meta_unchecked,
         tombstone::TsmTombstone,
-        BlockMeta, DataBlock, Index, IndexMeta, BLOCK_META_SIZE, FOOTER_SIZE, INDEX_META_SIZE,
-        MAX_BLOCK_VALUES,
+        BlockMeta, DataBlock, Index, IndexMeta, FOOTER_SIZE, HEADER_SIZE, INDEX_META_SIZE,
+        MAX_BLOCK_VALUES, VERSION,
     },
 };
 
@@ -156,18 +158,42 @@ impl IndexFile {
 pub fn print_tsm_statistics(path: impl AsRef<Path>, show_tombstone: bool) {
     let reader = TsmReader::open(path).unwrap();
     let mut points_cnt = 0_usize;
+    let mut codec_stats: BTreeMap<(String, &'static str), CodecStatistics> = BTreeMap::new();
+    let mut raw_buf = vec![];
     println!("============================================================");
     for idx in reader.index_iterator() {
         let tr = idx.time_range();
         let mut buffer = String::with_capacity(1024);
         let mut idx_points_cnt = 0_usize;
         for blk in idx.block_iterator() {
+            let size = reader.get_raw_data(&blk, &mut raw_buf).unwrap();
+            let raw_data = &raw_buf[..size];
+            let val_off = blk.val_off() - blk.offset();
+            let ts_encoding = get_encoding(&raw_data[4..val_off as usize]);
+            let val_encoding = get_encoding(&raw_data[val_off as usize + 4..]);
             buffer.push_str(
                 format!(
-                    "\tBlock | FieldId: {}, MinTime: {}, MaxTime: {}, Count: {}, Offset: {}, Size: {}, ValOffset: {}\n",
-                    blk.field_id(), blk.min_ts(), blk.max_ts(), blk.count(), blk.offset(), blk.size(), blk.val_off()
+                    "\tBlock | FieldId: {}, MinTime: {}, MaxTime: {}, Count: {}, Offset: {}, Size: {}, ValOffset: {}, TsEncoding: {}, ValEncoding: {}\n",
+                    blk.field_id(), blk.min_ts(), blk.max_ts(), blk.count(), blk.offset(), blk.size(), blk.val_off(),
+                    ts_encoding.as_str(), val_encoding.as_str()
                 ).as_str()
             );
+
+            let ts_stats = codec_stats
+                .entry(("Timestamp".to_string(), ts_encoding.as_str()))
+                .or_default();
+            ts_stats.blocks += 1;
+            ts_stats.size += val_off - 4;
+            ts_stats.raw_size += blk.count() as u64 * 8;
+            let val_stats = codec_stats
+                .entry((format!("{:?}", blk.field_type()), val_encoding.as_str()))
+                .or_default();
+            val_stats.blocks += 1;
+            val_stats.size += blk.size() - val_off - 4;
+            val_stats.raw_size += decode_data_block(raw_data, blk.field_type(), val_off)
+                .map(|b| raw_values_size(&b))
+                .unwrap_or_default();
+
             points_cnt += blk.count() as usize;
             idx_points_cnt += blk.count() as usize;
         }
@@ -207,17 +233,89 @@ pub fn print_tsm_statistics(path: impl AsRef<Path>, show_tombstone: bool) {
     println!("============================================================");
     println!("============================================================");
     println!("PointsCount: {}", points_cnt);
+    println!("------------------------------------------------------------");
+    for ((data_type, encoding), stats) in codec_stats.iter() {
+        println!(
+            "Codec | Type: {}, Encoding: {}, Blocks: {}, Size: {}, RawSize: {}, Ratio: {:.2}",
+            data_type,
+            encoding,
+            stats.blocks,
+            stats.size,
+            stats.raw_size,
+            stats.ratio()
+        );
+    }
+}
+
+/// Encoded and decoded sizes of the blocks written by a codec.
+#[derive(Default)]
+struct CodecStatistics {
+    blocks: usize,
+    size: u64,
+    raw_size: u64,
+}
+
+impl CodecStatistics {
+    fn ratio(&self) -> f64 {
+        if self.size == 0 {
+            return 0.0;
+        }
+        self.raw_size as f64 / self.size as f64
+    }
+}
+
+/// Returns the uncompressed size of the values of a data block.
+fn raw_values_size(block: &DataBlock) -> u64 {
+    match block {
+        DataBlock::U64 { val, .. } => val.len() as u64 * 8,
+        DataBlock::I64 { val, .. } => val.len() as u64 * 8,
+        DataBlock::F64 { val, .. } => val.len() as u64 * 8,
+        DataBlock::Str { val, .. } => val.iter().map(|v| v.len() as u64).sum(),
+        DataBlock::Bool { val, .. } => val.len() as u64,
+    }
+}
+
+/// Reads the offset of the index from the footer of the tsm file.
+pub fn read_index_offset(path: impl AsRef<Path>) -> std::io::Result<u64> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file = std::fs::File::open(path)?;
+    if file.metadata()?.len() < (HEADER_SIZE + FOOTER_SIZE) as u64 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "tsm file is too small",
+        ));
+    }
+    file.seek(SeekFrom::End(-8))?;
+    let mut buf = [0_u8; 8];
+    file.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
 }
 
 pub fn load_index(reader: Arc<DmaFile>) -> ReadTsmResult<Index> {
     let len = reader.len();
-    if len < FOOTER_SIZE as u64 {
+    if len < (HEADER_SIZE + FOOTER_SIZE) as u64 {
         return Err(ReadTsmError::Invalid {
-            reason: format!("TSM file size less than FOOTER_SIZE({})", FOOTER_SIZE),
+            reason: format!(
+                "TSM file size less than HEADER_SIZE + FOOTER_SIZE({})",
+                HEADER_SIZE + FOOTER_SIZE
+            ),
         });
     }
     let mut buf = [0u8; 8];
 
+    // Read version in header
+    reader
+        .read_at(0, &mut buf[..HEADER_SIZE])
+        .context(IOSnafu)?;
+    let version = buf[HEADER_SIZE - 1];
+    if version > VERSION {
+        return Err(ReadTsmError::Invalid {
+            reason: format!("TSM file version({}) is not supported", version),
+        });
+    }
+    let block_meta_size = block_meta_size(version);
+
     // Read index data offset
     reader.read_at(len - 8, &mut buf).context(IOSnafu)?;
     let offset = u64::from_be_bytes(buf);
@@ -236,9 +334,22 @@ pub fn load_index(reader: Arc<DmaFile>) -> ReadTsmResult<Index> {
     let mut field_ids = Vec::new();
     let mut pos = 0_usize;
     while pos < data_len {
+        if pos + INDEX_META_SIZE > data_len {
+            return Err(ReadTsmError::Invalid {
+                reason: format!("TSM index meta at {} is truncated", offset + pos as u64),
+            });
+        }
         offsets.push(pos as u64);
         field_ids.push(decode_be_u64(&data[pos..pos + 8]));
-        pos += INDEX_META_SIZE + BLOCK_META_SIZE * decode_be_u16(&data[pos + 9..pos + 11]) as usize;
+        pos += INDEX_META_SIZE + block_meta_size * decode_be_u16(&data[pos + 9..pos + 11]) as usize;
+        if pos > data_len {
+            return Err(ReadTsmError::Invalid {
+                reason: format!(
+                    "TSM block metas before {} are truncated",
+                    offset + pos as u64
+                ),
+            });
+        }
     }
 
     // Sort by field id
@@ -254,7 +365,7 @@ pub fn load_index(reader: Arc<DmaFile>) -> ReadTsmResult<Index> {
         offsets.swap(i, j);
     }
 
-    Ok(Index::new(data, field_ids, offsets))
+    Ok(Index::new(version, data, field_ids, offsets))
 }
 
 /// Memory-based index reader
@@ -369,13 +480,14 @@ impl BlockMetaIterator {
     /// Set iterator start & end position by time range
     pub(crate) fn filter_time_range(&mut self, time_range: &TimeRange) {
         let TimeRange { min_ts, max_ts } = *time_range;
+        let block_meta_size = self.index_ref.block_meta_size();
         let base = self.index_offset + INDEX_META_SIZE;
-        let sli = &self.index_ref.data()[base..base + self.block_count as usize * BLOCK_META_SIZE];
+        let sli = &self.index_ref.data()[base..base + self.block_count as usize * block_meta_size];
         let mut pos = 0_usize;
         let mut idx = 0_usize;
         while pos < sli.len() {
             if min_ts > decode_be_i64(&sli[pos + 8..pos + 16]) {
-                pos += BLOCK_META_SIZE;
+                pos += block_meta_size;
                 idx += 1;
             } else {
                 // First data block in time range
@@ -389,7 +501,7 @@ impl BlockMetaIterator {
         }
         self.block_meta_idx = idx;
         self.block_meta_idx_end = idx;
-        pos += BLOCK_META_SIZE;
+        pos += block_meta_size;
         while pos < sli.len() {
             if max_ts < decode_be_i64(&sli[pos..pos + 8]) {
                 return;
@@ -398,7 +510,7 @@ impl BlockMetaIterator {
                 return;
             } else {
                 self.block_meta_idx_end += 1;
-                pos += BLOCK_META_SIZE;
+                pos += block_meta_size;
             }
         }
     }
@@ -419,7 +531,7 @@ impl Iterator for BlockMetaIterator {
             self.field_type,
         ));
         self.block_meta_idx += 1;
-        self.block_offset += BLOCK_META_SIZE;
+        self.block_offset += self.index_ref.block_meta_size();
         ret
     }
 }
@@ -435,7 +547,7 @@ impl TsmReader {
     pub fn open(tsm_path: impl AsRef<Path>) -> Result<Self> {
         let path = tsm_path.as_ref().to_path_buf();
         let tsm_id = file_utils::get_tsm_file_id_by_path(&path)?;
-        let tsm = Arc::new(file_manager::open_file(tsm_path)?);
+        let tsm = Arc::new(file_manager::open_column_file(tsm_path)?);
         let tsm_idx = IndexReader::open(tsm.clone())?;
         let tombstone_path = path.parent().unwrap_or_else(|| Path::new("/"));
         let tombstone = TsmTombstone::new(tombstone_path, tsm_id)?;
@@ -487,6 +599,42 @@ impl TsmReader {
         !self.tombstone.read().is_empty()
     }
 
+    /// Returns the estimated fraction of values deleted by tombstones, values in a block
+    /// are assumed to be evenly distributed over the time range of the block.
+    pub fn tombstone_ratio(&self) -> f64 {
+        if !self.has_tombstone() {
+            return 0.0;
+        }
+        let mut total_values = 0_f64;
+        let mut deleted_values = 0_f64;
+        for idx in self.index_iterator() {
+            for blk in idx.block_iterator() {
+                let count = blk.count() as f64;
+                total_values += count;
+                if let Some(mut time_ranges) = self.get_block_tombstone_time_ranges(&blk) {
+                    let blk_len = (blk.max_ts() as f64 - blk.min_ts() as f64) + 1.0;
+                    // Tombstones may overlap, count every deleted timestamp once.
+                    time_ranges.sort_unstable_by_key(|tr| tr.min_ts);
+                    let mut deleted_len = 0_f64;
+                    let mut next_ts = blk.min_ts();
+                    for tr in time_ranges.iter() {
+                        let min_ts = tr.min_ts.max(next_ts);
+                        let max_ts = tr.max_ts.min(blk.max_ts());
+                        if min_ts <= max_ts {
+                            deleted_len += max_ts as f64 - min_ts as f64 + 1.0;
+                            next_ts = max_ts.saturating_add(1);
+                        }
+                    }
+                    deleted_values += count * (deleted_len / blk_len).min(1.0);
+                }
+            }
+        }
+        if total_values == 0.0 {
+            return 0.0;
+        }
+        deleted_values / total_values
+    }
+
     /// Returns all tombstone `TimeRange`s for a `BlockMeta`.
     /// Returns None if there is nothing to return, or `TimeRange`s is empty.
     pub fn get_block_tombstone_time_ranges(
@@ -660,7 +808,7 @@ pub mod tsm_reader_tests {
         sync::Arc,
     };
 
-    use models::{FieldId, Timestamp};
+    use models::{codec::Encoding, FieldId, Timestamp};
     use parking_lot::Mutex;
 
     use super::print_tsm_statistics;
@@ -759,6 +907,54 @@ pub mod tsm_reader_tests {
         read_and_check(&reader, expected_data);
     }
 
+    #[test]
+    fn test_tsm_reader_auto_encoding() {
+        let path = PathBuf::from("/tmp/test/tsm_reader/auto_encoding");
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        let tsm_file = file_utils::make_tsm_file_name(&path, 1);
+
+        let auto = DataBlockEncoding::new(Encoding::Auto, Encoding::Auto);
+        let states = ["ok", "warning", "critical"];
+        #[rustfmt::skip]
+        let ori_data: HashMap<FieldId, Vec<DataBlock>> = HashMap::from([
+            (1, vec![DataBlock::F64 {
+                ts: (1..=500).collect(),
+                val: (1..=500).map(|i| (i % 17) as f64 * 0.5).collect(),
+                enc: auto,
+            }]),
+            (2, vec![DataBlock::Str {
+                ts: (1..=500).collect(),
+                val: (1..=500_usize).map(|i| states[i * i % 7 % 3].as_bytes().into()).collect(),
+                enc: auto,
+            }]),
+            (3, vec![DataBlock::Bool {
+                ts: (1..=500).collect(),
+                val: (1..=500).map(|i| i % 3 == 0).collect(),
+                enc: auto,
+            }]),
+        ]);
+        let mut writer = TsmWriter::open(&tsm_file, 1, false, 0).unwrap();
+        for (fid, blks) in ori_data.iter() {
+            for blk in blks.iter() {
+                writer.write_block(*fid, blk).unwrap();
+            }
+        }
+        writer.write_index().unwrap();
+        writer.finish().unwrap();
+        print_tsm_statistics(&tsm_file, false);
+
+        let reader = TsmReader::open(&tsm_file).unwrap();
+        for idx in reader.index_iterator() {
+            for blk in idx.block_iterator() {
+                let (ts_enc, val_enc) = reader.get_data_block(&blk).unwrap().encodings().split();
+                assert_ne!(ts_enc, Encoding::Auto);
+                assert_ne!(val_enc, Encoding::Auto);
+            }
+        }
+        read_and_check(&reader, ori_data);
+    }
+
     pub(crate) fn read_opt_and_check(
         reader: &TsmReader,
         field_id: FieldId,
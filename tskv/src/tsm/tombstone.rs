@@ -12,6 +12,7 @@ use bytes::buf;
 use models::{FieldId, SeriesId, Timestamp, ValueType};
 use parking_lot::{Mutex, RwLock};
 use snafu::ResultExt;
+use trace::{error, info};
 
 use super::DataBlock;
 use crate::file_system::file_manager;
@@ -23,8 +24,8 @@ use crate::{
     Error, Result,
 };
 
-const TOMBSTONE_FILE_SUFFIX: &str = ".tombstone";
-const TOMBSTONE_MAGIC: u32 = 0x544F4D42;
+pub(crate) const TOMBSTONE_FILE_SUFFIX: &str = ".tombstone";
+pub(crate) const TOMBSTONE_MAGIC: u32 = 0x544F4D42;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Tombstone {
@@ -305,6 +306,40 @@ impl TsmTombstone {
     }
 }
 
+/// Remove tombstone files in `dir` whose tsm or delta file does not exist,
+/// returns the number of removed tombstone files.
+pub fn remove_orphan_tombstones(dir: impl AsRef<Path>) -> usize {
+    let dir = dir.as_ref();
+    let mut removed = 0_usize;
+    for file_name in file_manager::list_file_names(dir) {
+        if !file_name.ends_with(TOMBSTONE_FILE_SUFFIX) {
+            continue;
+        }
+        let file_id = match file_utils::get_tsm_file_id_by_path(&file_name) {
+            Ok(id) => id,
+            Err(_) => continue,
+        };
+        if file_manager::try_exists(file_utils::make_tsm_file_name(dir, file_id))
+            || file_manager::try_exists(file_utils::make_delta_file_name(dir, file_id))
+        {
+            continue;
+        }
+        let path = dir.join(&file_name);
+        match std::fs::remove_file(&path) {
+            Ok(_) => {
+                info!("Removed orphan tombstone file '{}'", path.display());
+                removed += 1;
+            }
+            Err(e) => error!(
+                "Failed to remove orphan tombstone file '{}': {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+    removed
+}
+
 #[cfg(test)]
 mod test {
     use std::{
@@ -313,7 +348,7 @@ mod test {
         sync::Arc,
     };
 
-    use super::TsmTombstone;
+    use super::{remove_orphan_tombstones, TsmTombstone};
     use crate::file_system::file_manager;
     use crate::{byte_utils, file_utils, tseries_family::TimeRange};
 
@@ -422,4 +457,32 @@ mod test {
             }
         ));
     }
+
+    #[test]
+    fn test_remove_orphan_tombstones() {
+        let dir = PathBuf::from("/tmp/test/tombstone/orphan".to_string());
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // Tombstone 1 has a tsm file, tombstone 2 has a delta file, tombstone 3 is orphan.
+        std::fs::write(file_utils::make_tsm_file_name(&dir, 1), b"").unwrap();
+        std::fs::write(file_utils::make_delta_file_name(&dir, 2), b"").unwrap();
+        for file_id in 1..=3 {
+            let path = file_utils::make_tsm_tombstone_file_name(&dir, file_id);
+            let mut tombstone = TsmTombstone::with_path(&path).unwrap();
+            tombstone.add_range(&[0], &TimeRange::new(0, 0)).unwrap();
+            tombstone.flush().unwrap();
+        }
+
+        assert_eq!(remove_orphan_tombstones(&dir), 1);
+        assert!(file_manager::try_exists(
+            file_utils::make_tsm_tombstone_file_name(&dir, 1)
+        ));
+        assert!(file_manager::try_exists(
+            file_utils::make_tsm_tombstone_file_name(&dir, 2)
+        ));
+        assert!(!file_manager::try_exists(
+            file_utils::make_tsm_tombstone_file_name(&dir, 3)
+        ));
+        assert_eq!(remove_orphan_tombstones(&dir), 0);
+    }
 }
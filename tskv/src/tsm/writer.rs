@@ -3,6 +3,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use models::codec::Encoding;
 use models::{FieldId, Timestamp, ValueType};
 use protos::kv_service::FieldType;
 use snafu::{ResultExt, Snafu};
@@ -15,7 +16,7 @@ use crate::{
     file_utils,
     tsm::{
         BlockEntry, BlockMeta, BlockMetaIterator, DataBlock, Index, IndexEntry, IndexMeta,
-        BLOCK_META_SIZE, BLOOM_FILTER_BITS, INDEX_META_SIZE, MAX_BLOCK_VALUES,
+        BLOCK_META_SIZE, BLOOM_FILTER_BITS, INDEX_META_SIZE, MAX_BLOCK_VALUES, VERSION,
     },
 };
 
@@ -49,6 +50,13 @@ use crate::{
 // │ 8 bytes │1 byte│2 bytes│ 8 bytes │ 8 bytes │4 bytes │8 bytes │8 bytes │8 bytes│
 // └─────────┴──────┴───────┴─────────┴─────────┴────────┴────────┴────────┴───────┘
 //
+// Since version 2 each block meta in the index is followed by value statistics:
+//
+// ┌───────┬─────────┬─────────┬─────────┐
+// │ Flags │   Min   │   Max   │   Sum   │
+// │1 byte │ 8 bytes │ 8 bytes │ 8 bytes │
+// └───────┴─────────┴─────────┴─────────┘
+//
 // ┌─────────────────────────┐
 // │ Footer                  │
 // ├───────────────┬─────────┤
@@ -57,8 +65,7 @@ use crate::{
 // └───────────────┴─────────┘
 
 const HEADER_LEN: u64 = 5;
-const TSM_MAGIC: u32 = 0x01346613;
-const VERSION: u8 = 1;
+pub(crate) const TSM_MAGIC: u32 = 0x01346613;
 
 pub type WriteTsmResult<T, E = WriteTsmError> = std::result::Result<T, E>;
 
@@ -382,6 +389,8 @@ fn write_raw_data_to(
             offset,
             size: block.len() as u64,
             val_offset: offset + ts_block_len,
+            statistics: block_meta.statistics(),
+            auto_encoded: block_meta.auto_encoded(),
         },
     );
 
@@ -451,6 +460,8 @@ fn write_block_to(
             offset,
             size: size as u64,
             val_offset: val_off,
+            statistics: block.statistics(),
+            auto_encoded: block.encodings().split().1 == Encoding::Auto,
         },
     );
 
@@ -479,6 +490,7 @@ mod test {
         sync::Arc,
     };
 
+    use minivec::MiniVec;
     use models::{FieldId, ValueType};
 
     use crate::file_system::file_manager::{self, get_file_manager, FileManager};
@@ -486,8 +498,9 @@ mod test {
         file_system::FileSync,
         memcache::FieldVal,
         tsm::{
-            codec::DataBlockEncoding, new_tsm_writer, ColumnReader, DataBlock, IndexReader,
-            TsmReader, TsmWriter,
+            codec::DataBlockEncoding, new_tsm_writer, BlockStatistics, ColumnReader, DataBlock,
+            IndexReader, TsmReader, TsmWriter, BLOCK_META_SIZE, BLOCK_META_SIZE_V1, FOOTER_SIZE,
+            HEADER_SIZE, INDEX_META_SIZE,
         },
     };
 
@@ -580,4 +593,81 @@ mod test {
         write_to_tsm(&dir, file_name, &data);
         check_tsm(dir.join(file_name), &data);
     }
+
+    #[test]
+    fn test_tsm_write_statistics() {
+        #[rustfmt::skip]
+        let data: HashMap<FieldId, Vec<DataBlock>> = HashMap::from([
+            (1, vec![DataBlock::I64 { ts: vec![1, 2, 3], val: vec![-5, 10, i64::MAX], enc: DataBlockEncoding::default() }]),
+            (2, vec![DataBlock::F64 { ts: vec![1, 2, 3], val: vec![1.5, -2.0, 4.0], enc: DataBlockEncoding::default() }]),
+            (3, vec![DataBlock::Bool { ts: vec![1, 2, 3], val: vec![false, true, true], enc: DataBlockEncoding::default() }]),
+            (4, vec![DataBlock::Str { ts: vec![1], val: vec![MiniVec::from("a".as_bytes())], enc: DataBlockEncoding::default() }]),
+        ]);
+        let dir = Path::new(TEST_PATH).join("statistics");
+        let file_name = "_000001.tsm";
+        write_to_tsm(&dir, file_name, &data);
+        let path = dir.join(file_name);
+        check_tsm(&path, &data);
+
+        let stats_of = |path: &Path| {
+            let reader = TsmReader::open(path).unwrap();
+            let mut stats = HashMap::new();
+            for idx in reader.index_iterator() {
+                for blk in idx.block_iterator() {
+                    stats.insert(idx.field_id(), blk.statistics());
+                }
+            }
+            stats
+        };
+        let stats = stats_of(&path);
+        assert_eq!(
+            stats[&1],
+            Some(BlockStatistics::I64 {
+                min: -5,
+                max: i64::MAX,
+                sum: None
+            })
+        );
+        assert_eq!(
+            stats[&2],
+            Some(BlockStatistics::F64 {
+                min: -2.0,
+                max: 4.0,
+                sum: 3.5
+            })
+        );
+        assert_eq!(
+            stats[&3],
+            Some(BlockStatistics::Bool {
+                min: false,
+                max: true,
+                sum: 2
+            })
+        );
+        assert_eq!(stats[&4], None);
+
+        // Rewrite the index into version 1 layout, which has no statistics.
+        let mut bytes = std::fs::read(&path).unwrap();
+        let len = bytes.len();
+        let index_offset = u64::from_be_bytes(bytes[len - 8..].try_into().unwrap()) as usize;
+        let footer = bytes.split_off(len - FOOTER_SIZE);
+        let index = bytes.split_off(index_offset);
+        bytes[HEADER_SIZE - 1] = 1;
+        let mut pos = 0;
+        while pos < index.len() {
+            let block_count = u16::from_be_bytes(index[pos + 9..pos + 11].try_into().unwrap());
+            bytes.extend_from_slice(&index[pos..pos + INDEX_META_SIZE]);
+            pos += INDEX_META_SIZE;
+            for _ in 0..block_count {
+                bytes.extend_from_slice(&index[pos..pos + BLOCK_META_SIZE_V1]);
+                pos += BLOCK_META_SIZE;
+            }
+        }
+        bytes.extend_from_slice(&footer);
+        let v1_path = dir.join("_000002.tsm");
+        std::fs::write(&v1_path, bytes).unwrap();
+
+        check_tsm(&v1_path, &data);
+        assert!(stats_of(&v1_path).values().all(|s| s.is_none()));
+    }
 }
@@ -19,6 +19,7 @@ use crate::{
     memcache::MemCache,
     summary::{SummaryTask, VersionEdit},
     tseries_family::{LevelInfo, TseriesFamily, Version},
+    write_buffer::WriteBufferManager,
     Options, TseriesFamilyId,
 };
 
@@ -27,6 +28,7 @@ pub struct VersionSet {
     opt: Arc<Options>,
     // DBName -> DB
     dbs: HashMap<String, Arc<RwLock<Database>>>,
+    write_buffer: Arc<WriteBufferManager>,
 }
 
 impl VersionSet {
@@ -36,6 +38,7 @@ impl VersionSet {
         flush_task_sender: UnboundedSender<FlushReq>,
     ) -> Self {
         let mut dbs = HashMap::new();
+        let write_buffer = Arc::new(WriteBufferManager::new(opt.cache.max_total_buffer_size));
 
         for (id, ver) in ver_set {
             let name = ver.database().to_string();
@@ -45,23 +48,38 @@ impl VersionSet {
                 Arc::new(RwLock::new(Database::new(
                     DatabaseSchema::new(&name),
                     opt.clone(),
+                    write_buffer.clone(),
                 )))
             });
 
             db.write().open_tsfamily(ver, flush_task_sender.clone());
         }
 
-        Self { dbs, opt }
+        Self {
+            dbs,
+            opt,
+            write_buffer,
+        }
     }
 
     pub fn options(&self) -> Arc<Options> {
         self.opt.clone()
     }
 
+    pub fn write_buffer(&self) -> Arc<WriteBufferManager> {
+        self.write_buffer.clone()
+    }
+
     pub fn create_db(&mut self, schema: DatabaseSchema) -> Arc<RwLock<Database>> {
         self.dbs
             .entry(schema.name.clone())
-            .or_insert_with(|| Arc::new(RwLock::new(Database::new(schema, self.opt.clone()))))
+            .or_insert_with(|| {
+                Arc::new(RwLock::new(Database::new(
+                    schema,
+                    self.opt.clone(),
+                    self.write_buffer.clone(),
+                )))
+            })
             .clone()
     }
 
@@ -111,6 +129,21 @@ impl VersionSet {
         None
     }
 
+    /// Returns the min WAL sequence of data that has not been flushed to column files
+    /// in all TseriesFamilies, returns `None` if all data is flushed.
+    pub fn min_unflushed_seq(&self) -> Option<u64> {
+        let mut min_seq: Option<u64> = None;
+        for db in self.dbs.values() {
+            for tsf in db.read().ts_families().values() {
+                if let Some(seq) = tsf.read().min_unflushed_seq() {
+                    min_seq = Some(min_seq.map_or(seq, |s| s.min(seq)));
+                }
+            }
+        }
+
+        min_seq
+    }
+
     // will delete in cluster version
     pub fn get_tsfamily_by_name(&self, name: &str) -> Option<Arc<RwLock<TseriesFamily>>> {
         if let Some(db) = self.dbs.get(name) {
@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeMap,
     io::SeekFrom,
     marker::PhantomData,
     path::{Path, PathBuf},
@@ -25,7 +26,6 @@ use crate::tsm::codec::get_str_codec;
 use crate::{
     byte_utils,
     compaction::FlushReq,
-    context::GlobalContext,
     error::{self, Error, Result},
     file_system::{DmaFile, FileCursor, FileSync},
     file_utils,
@@ -34,18 +34,70 @@ use crate::{
     version_set::VersionSet,
 };
 
+/// WAL file header: magic(4 bytes) + min_sequence(8 bytes) + max_sequence(8 bytes) + padding.
+///
+/// The sequences are only written when the file is created (`max_sequence = min_sequence - 1`)
+/// and closed, so the entries must be read to get the max sequence of an unclosed file.
 const SEGMENT_HEADER_SIZE: usize = 32;
-const SEGMENT_MAGIC: [u8; 4] = [0x57, 0x47, 0x4c, 0x00];
-const SEGMENT_SIZE: u64 = 1073741824; // 1 GiB
-
+const SEGMENT_MAGIC: [u8; 4] = [0x57, 0x47, 0x4c, 0x01];
+/// Magic of WAL files of which the entry crc is the checksum of the data only.
+const SEGMENT_MAGIC_V1: [u8; 4] = [0x57, 0x47, 0x4c, 0x00];
+
+/// WAL entry header: type(1 byte) + seq(8 bytes) + crc(4 bytes) + data_len(4 bytes).
+///
+/// The crc is the CRC32 checksum of type, seq, data_len and data, so a torn or
+/// corrupted entry can be found when recovering.
 const BLOCK_HEADER_SIZE: usize = 17;
 
+/// What to do if a torn or corrupted entry is found when recovering from WAL files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalRecoveryMode {
+    /// Stop replaying at the first torn or corrupted entry, the entries after it are dropped.
+    TolerateTailCorruption,
+    /// Fail to recover, the WAL files need to be repaired manually.
+    Fail,
+}
+
+impl WalRecoveryMode {
+    /// Parse recovery mode from config, unknown modes fall back to `TolerateTailCorruption`.
+    pub fn parse(text: &str) -> Self {
+        match text.to_lowercase().as_str() {
+            "tolerate_tail_corruption" => Self::TolerateTailCorruption,
+            "fail" => Self::Fail,
+            _ => {
+                error!(
+                    "Unknown WAL recovery mode '{}', using 'tolerate_tail_corruption' instead.",
+                    text
+                );
+                Self::TolerateTailCorruption
+            }
+        }
+    }
+}
+
+/// An opened WAL file, entries before `len` are complete and synced.
+pub struct WalFileSnapshot {
+    path: PathBuf,
+    file: DmaFile,
+    len: u64,
+}
+
+/// Callback of a write task, sends (seq_no, written_size).
+type WalWriteCallback = oneshot::Sender<Result<(u64, usize)>>;
+
 pub enum WalTask {
     Write {
         points: Arc<Vec<u8>>,
         // (seq_no, written_size)
-        cb: oneshot::Sender<Result<(u64, usize)>>,
+        cb: WalWriteCallback,
+    },
+    /// Opens WAL files to be copied, see `WalManager::snapshot_files`.
+    SnapshotFiles {
+        min_seq: u64,
+        cb: oneshot::Sender<Result<Vec<WalFileSnapshot>>>,
     },
+    /// Deletes old WAL files of which all entries are before `min_seq`.
+    DeleteObsoleteFiles { min_seq: u64 },
 }
 
 #[repr(u8)]
@@ -68,6 +120,19 @@ impl From<u8> for WalEntryType {
     }
 }
 
+/// Returns the checksum of a WAL entry, `legacy` for files with `SEGMENT_MAGIC_V1`.
+fn entry_crc(typ: u8, seq: u64, data: &[u8], legacy: bool) -> u32 {
+    if legacy {
+        return crc32fast::hash(data);
+    }
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&[typ]);
+    hasher.update(&seq.to_be_bytes());
+    hasher.update(&(data.len() as u32).to_be_bytes());
+    hasher.update(data);
+    hasher.finalize()
+}
+
 pub struct WalEntryBlock {
     pub typ: WalEntryType,
     pub seq: u64,
@@ -81,7 +146,7 @@ impl WalEntryBlock {
         Self {
             typ,
             seq: 0,
-            crc: crc32fast::hash(buf),
+            crc: entry_crc(typ as u8, 0, buf, false),
             len: buf.len() as u32,
             buf: buf.into(),
         }
@@ -117,6 +182,8 @@ struct WalWriter {
     header_buf: [u8; SEGMENT_HEADER_SIZE],
     min_sequence: u64,
     max_sequence: u64,
+    /// Whether the file was created with `SEGMENT_MAGIC_V1`.
+    legacy: bool,
 }
 
 impl WalWriter {
@@ -131,7 +198,13 @@ impl WalWriter {
         Ok(header_buf)
     }
 
-    pub fn open(id: u64, path: impl AsRef<Path>, config: Arc<WalOptions>) -> Result<Self> {
+    /// Opens a WAL file, `min_sequence` is the sequence of the first entry if it's a new file.
+    pub fn open(
+        id: u64,
+        path: impl AsRef<Path>,
+        min_sequence: u64,
+        config: Arc<WalOptions>,
+    ) -> Result<Self> {
         // TODO: Check path
         let path = path.as_ref();
 
@@ -149,13 +222,14 @@ impl WalWriter {
         };
 
         // Get metadata; if new file then write header
-        let min_sequence: u64;
+        let mut min_sequence = min_sequence;
         let max_sequence: u64;
         let mut header_buf = [0_u8; SEGMENT_HEADER_SIZE];
         if new_file {
-            min_sequence = 0;
-            max_sequence = 0;
+            max_sequence = min_sequence.saturating_sub(1);
             header_buf[..4].copy_from_slice(SEGMENT_MAGIC.as_slice());
+            header_buf[4..12].copy_from_slice(&min_sequence.to_be_bytes());
+            header_buf[12..20].copy_from_slice(&max_sequence.to_be_bytes());
             file.write_at(0, &header_buf)
                 .and_then(|_| file.sync_all(FileSync::Hard))
                 .context(error::IOSnafu)?;
@@ -165,6 +239,7 @@ impl WalWriter {
             min_sequence = byte_utils::decode_be_u64(&header_buf[4..12]);
             max_sequence = byte_utils::decode_be_u64(&header_buf[12..20]);
         }
+        let legacy = header_buf[..4] == SEGMENT_MAGIC_V1;
         let size = file.len();
 
         Ok(Self {
@@ -176,13 +251,15 @@ impl WalWriter {
             header_buf,
             min_sequence,
             max_sequence,
+            legacy,
         })
     }
 
+    /// Writes an entry, returns it's sequence and the written size.
     pub async fn write(&mut self, typ: WalEntryType, data: &[u8]) -> Result<(u64, usize)> {
         let typ = typ as u8;
         let mut pos = self.size;
-        let mut seq = self.max_sequence;
+        let seq = self.max_sequence + 1;
 
         self.file
             // write type
@@ -195,7 +272,7 @@ impl WalWriter {
             .and_then(|size| {
                 // write crc
                 pos += size as u64;
-                let crc = crc32fast::hash(data);
+                let crc = entry_crc(typ, seq, data, self.legacy);
                 self.file.write_at(pos, &crc.to_be_bytes())
             })
             .and_then(|size| {
@@ -209,20 +286,10 @@ impl WalWriter {
                 pos += size as u64;
                 self.file.write_at(pos, data)
             })
-            .and_then(|size| {
-                // sync
-                pos += size as u64;
-                if self.config.sync {
-                    self.file.sync_all(FileSync::Soft)
-                } else {
-                    Ok(())
-                }
-            })
+            .map(|size| pos += size as u64)
             .context(error::IOSnafu)?;
 
-        seq += 1;
-
-        // write & fsync succeed
+        // write succeed
         let written_size = (pos - self.size) as usize;
         self.size = pos;
         self.max_sequence = seq;
@@ -230,6 +297,11 @@ impl WalWriter {
         Ok((seq, written_size))
     }
 
+    /// Syncs the written entries to disk.
+    pub fn sync(&self) -> Result<()> {
+        self.file.sync_all(FileSync::Hard).context(error::IOSnafu)
+    }
+
     pub async fn flush(&mut self) -> Result<()> {
         // Write header
         self.header_buf[4..12].copy_from_slice(&self.min_sequence.to_be_bytes());
@@ -251,6 +323,12 @@ pub struct WalManager {
 
     current_dir: PathBuf,
     current_file: WalWriter,
+    /// Closed WAL files, file_id -> max sequence of the file.
+    old_files: BTreeMap<u64, u64>,
+    /// Size of entries written since the last sync.
+    unsynced_size: u64,
+    /// Write tasks waiting for the next sync to be acknowledged.
+    pending_acks: Vec<(WalWriteCallback, (u64, usize))>,
 }
 
 unsafe impl Send for WalManager {}
@@ -263,22 +341,34 @@ impl WalManager {
             std::fs::create_dir_all(&config.path).unwrap();
         }
 
+        let mut old_files = BTreeMap::new();
+        for file_name in file_manager::list_file_names(&config.path) {
+            let id = match file_utils::get_wal_file_id(&file_name) {
+                Ok(id) => id,
+                Err(_) => continue,
+            };
+            let path = config.path.join(&file_name);
+            let max_seq = read_max_sequence(&path).unwrap();
+            old_files.insert(id, max_seq);
+        }
+
         // Create a new wal file every time it starts.
-        let new_seq = match file_utils::get_max_sequence_file_name(
-            config.path.clone(),
-            file_utils::get_wal_file_id,
-        ) {
-            Some((_, seq)) => seq + 1,
+        let new_id = match old_files.keys().next_back() {
+            Some(id) => id + 1,
             None => 1,
         };
+        let max_seq = old_files.values().copied().max().unwrap_or(0);
 
-        let new_wal = file_utils::make_wal_file(config.path.clone(), new_seq);
-        let current_file = WalWriter::open(new_seq, new_wal, config.clone()).unwrap();
+        let new_wal = file_utils::make_wal_file(config.path.clone(), new_id);
+        let current_file = WalWriter::open(new_id, new_wal, max_seq + 1, config.clone()).unwrap();
         let current_dir = config.path.clone();
         WalManager {
             config,
             current_dir,
             current_file,
+            old_files,
+            unsynced_size: 0,
+            pending_acks: Vec::new(),
         }
     }
 
@@ -286,8 +376,12 @@ impl WalManager {
         self.current_file.max_sequence
     }
 
+    pub fn current_file_id(&self) -> u64 {
+        self.current_file.id
+    }
+
     async fn roll_wal_file(&mut self) -> Result<()> {
-        if self.current_file.size > SEGMENT_SIZE {
+        if self.current_file.size > self.config.max_file_size {
             info!(
                 "WAL '{}' is full at seq '{}', begin rolling.",
                 self.current_file.id, self.current_file.max_sequence
@@ -296,32 +390,173 @@ impl WalManager {
             let new_file_id = self.current_file.id + 1;
             let new_file_name = file_utils::make_wal_file(&self.config.path, new_file_id);
 
-            let new_file = WalWriter::open(new_file_id, new_file_name, self.config.clone())?;
+            let new_file = WalWriter::open(
+                new_file_id,
+                new_file_name,
+                self.current_file.max_sequence + 1,
+                self.config.clone(),
+            )?;
             let mut old_file = std::mem::replace(&mut self.current_file, new_file);
             old_file.flush().await?;
+            self.old_files.insert(old_file.id, old_file.max_sequence);
+            // Entries of the old file are synced by flush.
+            self.unsynced_size = 0;
 
             info!("WAL '{}' starts write", self.current_file.id);
         }
         Ok(())
     }
 
+    /// Writes an entry without syncing, returns it's sequence and the written size.
     pub async fn write(&mut self, typ: WalEntryType, data: &[u8]) -> Result<(u64, usize)> {
         self.roll_wal_file().await?;
-        self.current_file.write(typ, data).await
+        let ret = self.current_file.write(typ, data).await?;
+        self.unsynced_size += ret.1 as u64;
+        Ok(ret)
     }
 
-    pub async fn recover(
-        &self,
-        engine: &impl engine::Engine,
-        global_context: Arc<GlobalContext>,
-    ) -> Result<()> {
-        let min_log_seq = global_context.last_seq();
-        warn!("recovering version set from seq '{}'", &min_log_seq);
+    /// Writes a batch of tasks (group commit). If `WalOptions::sync` is set, the tasks
+    /// are acknowledged after the sync covering them, otherwise acknowledged immediately.
+    ///
+    /// The WAL file is synced after the batch if `sync_every_n_bytes` is reached, or
+    /// if `sync` is set and `sync_interval` is 0.
+    pub async fn write_tasks(&mut self, tasks: Vec<WalTask>) {
+        for task in tasks {
+            match task {
+                WalTask::Write { points, cb } => {
+                    match self.write(WalEntryType::Write, &points).await {
+                        Ok(ret) if self.config.sync => self.pending_acks.push((cb, ret)),
+                        ret => {
+                            if cb.send(ret).is_err() {
+                                warn!("send WAL write result failed.")
+                            }
+                        }
+                    }
+                }
+                WalTask::SnapshotFiles { min_seq, cb } => {
+                    let ret = self.snapshot_files(min_seq);
+                    if cb.send(ret).is_err() {
+                        warn!("send WAL snapshot result failed.")
+                    }
+                }
+                WalTask::DeleteObsoleteFiles { min_seq } => {
+                    self.delete_obsolete_files(min_seq);
+                }
+            }
+        }
+
+        let sync_by_size = self.config.sync_every_n_bytes > 0
+            && self.unsynced_size >= self.config.sync_every_n_bytes;
+        let sync_by_batch = self.config.sync && self.config.sync_interval == 0;
+        if sync_by_size || sync_by_batch {
+            if let Err(e) = self.sync() {
+                error!("Failed to sync WAL: {:?}", e);
+            }
+        }
+    }
+
+    /// Syncs the current WAL file if there are unsynced entries, and acknowledges
+    /// the tasks waiting for it.
+    pub fn sync(&mut self) -> Result<()> {
+        let ret = if self.unsynced_size > 0 {
+            self.current_file.sync()
+        } else {
+            Ok(())
+        };
+        if ret.is_ok() {
+            self.unsynced_size = 0;
+        }
+        self.release_pending_acks(&ret);
+        ret
+    }
+
+    fn release_pending_acks(&mut self, sync_result: &Result<()>) {
+        for (cb, ret) in self.pending_acks.drain(..) {
+            let ret = match sync_result {
+                Ok(_) => Ok(ret),
+                Err(e) => Err(Error::SyncFile {
+                    source: std::io::Error::new(std::io::ErrorKind::Other, e.to_string()),
+                }),
+            };
+            if cb.send(ret).is_err() {
+                warn!("send WAL write result failed.")
+            }
+        }
+    }
+
+    /// Syncs the current WAL file and opens WAL files which may contain entries with
+    /// a sequence greater than `min_seq`, the files can be read by `copy_entries` even
+    /// if they are deleted or written later.
+    pub fn snapshot_files(&mut self, min_seq: u64) -> Result<Vec<WalFileSnapshot>> {
+        // Entries in the current file must be synced before being read by another handle.
+        self.sync()?;
+
+        let file_ids = self
+            .old_files
+            .iter()
+            .filter(|(_, max_seq)| **max_seq > min_seq)
+            .map(|(id, _)| *id)
+            .chain(std::iter::once(self.current_file.id));
+        let mut files = Vec::new();
+        for id in file_ids {
+            let path = file_utils::make_wal_file(&self.current_dir, id);
+            let file = file_manager::get_file_manager().open_file(&path)?;
+            let len = if id == self.current_file.id {
+                self.current_file.size
+            } else {
+                file.len()
+            };
+            files.push(WalFileSnapshot { path, file, len });
+        }
+        Ok(files)
+    }
+
+    /// Deletes closed WAL files of which all entries have a sequence less than
+    /// `min_seq` (which means they have been flushed), returns the number of deleted files.
+    pub fn delete_obsolete_files(&mut self, min_seq: u64) -> usize {
+        let obsolete_ids: Vec<u64> = self
+            .old_files
+            .iter()
+            .filter(|(_, max_seq)| **max_seq < min_seq)
+            .map(|(id, _)| *id)
+            .collect();
+
+        let mut deleted = 0;
+        for id in obsolete_ids {
+            let path = file_utils::make_wal_file(&self.current_dir, id);
+            match std::fs::remove_file(&path) {
+                Ok(_) => {
+                    self.old_files.remove(&id);
+                    deleted += 1;
+                }
+                Err(e) => {
+                    error!("Failed to remove WAL file '{}': {:?}", path.display(), e);
+                }
+            }
+        }
+        if deleted > 0 {
+            info!(
+                "Deleted {} WAL files before checkpoint seq '{}'",
+                deleted, min_seq
+            );
+        }
+        deleted
+    }
 
-        let wal_files = file_manager::list_file_names(&self.current_dir);
-        for file_name in wal_files {
-            let id = file_utils::get_wal_file_id(&file_name)?;
-            let path = self.current_dir.join(file_name);
+    /// Replays entries of closed WAL files, entries already flushed to column files
+    /// are skipped by the engine.
+    ///
+    /// If a torn or corrupted entry is found at the tail of the last WAL file with entries,
+    /// truncates the file to the last good entry or returns the error according to
+    /// `WalOptions::recovery_mode`. Corrupted entries in other WAL files are always errors.
+    pub async fn recover(&self, engine: &impl engine::Engine) -> Result<()> {
+        warn!(
+            "recovering version set from {} WAL files",
+            self.old_files.len()
+        );
+
+        for (id, max_seq) in self.old_files.iter() {
+            let path = file_utils::make_wal_file(&self.current_dir, *id);
             if !file_manager::try_exists(&path) {
                 continue;
             }
@@ -329,18 +564,12 @@ impl WalManager {
             if file.is_empty() {
                 continue;
             }
-            let mut reader = WalReader::new(file.into())?;
-            if reader.max_sequence < min_log_seq {
-                // If this file is a new file, or empty file, continue.
-                continue;
-            }
+            let mut reader = WalReader::new(file.clone().into())?;
 
             loop {
+                let pos = reader.pos();
                 match reader.next_wal_entry() {
                     Ok(Some(e)) => {
-                        if e.seq < min_log_seq {
-                            continue;
-                        }
                         match e.typ {
                             WalEntryType::Write => {
                                 let decoder = get_str_codec(Encoding::Zstd);
@@ -362,9 +591,31 @@ impl WalManager {
                             _ => {}
                         };
                     }
-                    Ok(None) | Err(Error::WalTruncated) => {
+                    Ok(None) => {
                         break;
                     }
+                    Err(e @ (Error::WalTruncated | Error::WalCorrupted { .. })) => {
+                        // Files after the tail have no entries newer than it.
+                        let is_tail = self
+                            .old_files
+                            .range(id + 1..)
+                            .all(|(_, seq)| seq <= max_seq);
+                        if is_tail
+                            && self.config.recovery_mode == WalRecoveryMode::TolerateTailCorruption
+                        {
+                            warn!(
+                                "Truncating WAL '{}' at position {}: {}, the following entries are dropped.",
+                                path.display(),
+                                pos,
+                                e
+                            );
+                            file.set_len(pos);
+                            file.sync_all(FileSync::Hard).context(error::IOSnafu)?;
+                            break;
+                        }
+                        error!("Failed to recover from WAL '{}': {}", path.display(), e);
+                        return Err(e);
+                    }
                     Err(e) => {
                         panic!("Failed to recover from {}: {:?}", path.display(), e);
                     }
@@ -375,8 +626,82 @@ impl WalManager {
     }
 
     pub async fn close(&mut self) -> Result<()> {
-        self.current_file.flush().await
+        let ret = self.current_file.flush().await;
+        if ret.is_ok() {
+            self.unsynced_size = 0;
+        }
+        self.release_pending_acks(&ret);
+        ret
+    }
+}
+
+/// Copies `Write` entries of `database` with a sequence greater than `min_seq` in
+/// `files` into a new WAL file `dst`, returns the number of copied entries.
+///
+/// The entries are renumbered from 1 in the new file, returns error if an entry is
+/// torn or corrupted.
+pub async fn copy_entries(
+    files: Vec<WalFileSnapshot>,
+    database: &str,
+    min_seq: u64,
+    dst: &Path,
+    config: Arc<WalOptions>,
+) -> Result<usize> {
+    let mut writer = WalWriter::open(0, dst, 1, config)?;
+    let mut copied = 0;
+    for WalFileSnapshot { path, file, len } in files {
+        if len == 0 {
+            continue;
+        }
+        let mut reader = WalReader::new(file.into())?;
+        while reader.pos() < len {
+            let entry = match reader.next_wal_entry() {
+                Ok(Some(e)) => e,
+                Ok(None) => break,
+                Err(e) => {
+                    error!("Failed to copy WAL '{}': {}", path.display(), e);
+                    return Err(e);
+                }
+            };
+            if entry.typ != WalEntryType::Write || entry.seq <= min_seq {
+                continue;
+            }
+            let decoder = get_str_codec(Encoding::Zstd);
+            let mut dst = Vec::new();
+            decoder.decode(&entry.buf, &mut dst).context(DecodeSnafu)?;
+            let points = flatbuffers::root::<fb_models::Points>(&dst[0])
+                .context(error::InvalidFlatbufferSnafu)?;
+            if points.db() != Some(database.as_bytes()) {
+                continue;
+            }
+            writer.write(WalEntryType::Write, &entry.buf).await?;
+            copied += 1;
+        }
+    }
+    writer.flush().await?;
+    Ok(copied)
+}
+
+/// Returns the max sequence of a WAL file, reads all entries if it's not closed.
+fn read_max_sequence(path: impl AsRef<Path>) -> Result<u64> {
+    let file = file_manager::get_file_manager().open_file(path)?;
+    if file.is_empty() {
+        return Ok(0);
+    }
+    let mut reader = WalReader::new(file.into())?;
+    if reader.max_sequence >= reader.min_sequence {
+        return Ok(reader.max_sequence);
+    }
+
+    let mut max_sequence = reader.min_sequence.saturating_sub(1);
+    loop {
+        match reader.next_wal_entry() {
+            Ok(Some(e)) => max_sequence = max_sequence.max(e.seq),
+            Ok(None) | Err(Error::WalTruncated | Error::WalCorrupted { .. }) => break,
+            Err(e) => return Err(e),
+        }
     }
+    Ok(max_sequence)
 }
 
 pub fn reader(f: DmaFile) -> Result<WalReader> {
@@ -387,26 +712,47 @@ pub struct WalReader {
     cursor: FileCursor,
     header_buf: [u8; SEGMENT_HEADER_SIZE],
     block_header_buf: [u8; BLOCK_HEADER_SIZE],
+    min_sequence: u64,
     max_sequence: u64,
+    /// Whether the file was created with `SEGMENT_MAGIC_V1`.
+    legacy: bool,
     body_buf: Vec<u8>,
 }
 
 impl WalReader {
     pub fn new(mut cursor: FileCursor) -> Result<Self> {
         let header_buf = WalWriter::reade_header(&mut cursor)?;
+        let min_sequence = byte_utils::decode_be_u64(&header_buf[4..12]);
         let max_sequence = byte_utils::decode_be_u64(&header_buf[12..20]);
+        let legacy = header_buf[..4] == SEGMENT_MAGIC_V1;
 
         Ok(Self {
             cursor,
             header_buf,
+            min_sequence,
             max_sequence,
+            legacy,
             block_header_buf: [0_u8; BLOCK_HEADER_SIZE],
             body_buf: vec![],
         })
     }
 
+    /// Returns the position of the next entry.
+    pub fn pos(&self) -> u64 {
+        self.cursor.pos()
+    }
+
+    /// Reads the next entry, returns `None` at the end of file.
+    ///
+    /// Returns `Error::WalTruncated` if the entry is torn, and `Error::WalCorrupted`
+    /// if the checksum of the entry mismatches.
     pub fn next_wal_entry(&mut self) -> Result<Option<WalEntryBlock>> {
-        if self.cursor.len() - self.cursor.pos() < BLOCK_HEADER_SIZE as u64 {
+        let pos = self.cursor.pos();
+        let remaining = self.cursor.len() - pos;
+        if remaining == 0 {
+            return Ok(None);
+        }
+        if remaining < BLOCK_HEADER_SIZE as u64 {
             return Err(Error::WalTruncated);
         }
         let read_bytes = match self.cursor.read(&mut self.block_header_buf[..]) {
@@ -448,6 +794,9 @@ impl WalReader {
                 return Ok(None);
             }
         };
+        if entry_crc(typ, seq, buf, self.legacy) != crc {
+            return Err(Error::WalCorrupted { pos });
+        }
 
         Ok(Some(WalEntryBlock {
             typ: typ.into(),
@@ -462,12 +811,16 @@ impl WalReader {
 #[cfg(test)]
 mod test {
     use core::panic;
-    use std::{borrow::BorrowMut, path::PathBuf, sync::Arc};
+    use std::{
+        borrow::BorrowMut,
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
 
     use chrono::Utc;
     use flatbuffers::{self, Vector, WIPOffset};
     use lazy_static::lazy_static;
-    use tokio::runtime;
+    use tokio::{runtime, sync::oneshot};
 
     use config::get_config;
     use models::codec::Encoding;
@@ -479,10 +832,11 @@ mod test {
     use crate::tsm::codec::get_str_codec;
     use crate::{
         file_system::{DmaFile, FileCursor, FileSync},
+        file_utils,
         kv_option::WalOptions,
-        wal::{self, WalEntryBlock, WalEntryType, WalManager, WalReader},
+        wal::{self, WalEntryBlock, WalEntryType, WalManager, WalReader, WalRecoveryMode, WalTask},
     };
-    use crate::{kv_option, Error, TsKv};
+    use crate::{kv_option, Error, TimeRange, TsKv};
 
     impl From<&fb_models::Points<'_>> for WalEntryBlock {
         fn from(entry: &fb_models::Points) -> Self {
@@ -571,7 +925,12 @@ mod test {
                                     }
                                 };
                                 wrote_crcs.push(entry.crc);
-                                read_crcs.push(crc32fast::hash(&entry.buf[..entry.len as usize]));
+                                read_crcs.push(wal::entry_crc(
+                                    entry.typ as u8,
+                                    entry.seq,
+                                    &entry.buf[..entry.len as usize],
+                                    false,
+                                ));
                             }
                             WalEntryType::Delete => {
                                 // TODO delete a memcache entry
@@ -596,6 +955,30 @@ mod test {
         }
     }
 
+    /// Reads entries of a WAL file, returns sequences of the entries and the error if
+    /// stopped by an error.
+    fn read_wal_file(path: &Path) -> (Vec<u64>, Option<Error>) {
+        let file = file_manager::get_file_manager().open_file(path).unwrap();
+        let mut reader = WalReader::new(file.into()).unwrap();
+        let mut seqs = Vec::new();
+        loop {
+            match reader.next_wal_entry() {
+                Ok(Some(e)) => seqs.push(e.seq),
+                Ok(None) => return (seqs, None),
+                Err(e) => return (seqs, Some(e)),
+            }
+        }
+    }
+
+    /// Overwrites the last byte of a file, which is the data of the last WAL entry.
+    fn corrupt_last_byte(path: &Path) {
+        let file = file_manager::get_file_manager().open_file(path).unwrap();
+        let mut last_byte = [0_u8; 1];
+        file.read_at(file.len() - 1, &mut last_byte).unwrap();
+        file.write_at(file.len() - 1, &[!last_byte[0]]).unwrap();
+        file.sync_all(FileSync::Hard).unwrap();
+    }
+
     #[tokio::test]
     async fn test_read_and_write() {
         let dir = "/tmp/test/wal/1".to_string();
@@ -656,6 +1039,196 @@ mod test {
         check_wal_files(mgr.current_dir);
     }
 
+    #[tokio::test]
+    async fn test_delete_obsolete_files() {
+        init_default_global_tracing("tskv_log", "tskv.log", "debug");
+
+        let dir = "/tmp/test/wal/5".to_string();
+        let _ = std::fs::remove_dir_all(dir.clone()); // Ignore errors
+        let mut global_config = get_config("../config/config.toml");
+        global_config.wal.path = dir.clone();
+        global_config.wal.sync = false;
+        global_config.wal.max_file_size = 1024;
+        let wal_config = Arc::new(WalOptions::from(&global_config));
+
+        let database = "test_db".to_string();
+        let table = "test_table".to_string();
+        let mut mgr = WalManager::new(wal_config.clone());
+        let mut seqs = Vec::new();
+        for _i in 0..10 {
+            let mut fbb = flatbuffers::FlatBufferBuilder::new();
+            let points = models_helper::create_dev_ops_points(&mut fbb, 10, &database, &table);
+            fbb.finish(points, None);
+            let blk = WalEntryBlock::new(WalEntryType::Write, fbb.finished_data());
+            let mut enc_points = Vec::new();
+            let coder = get_str_codec(Encoding::Zstd);
+            coder
+                .encode(&[&blk.buf], &mut enc_points)
+                .map_err(|_| Error::Send)
+                .unwrap();
+            let (seq, _) = mgr.write(WalEntryType::Write, &enc_points).await.unwrap();
+            seqs.push(seq);
+        }
+        assert_eq!(seqs, (1..=10).collect::<Vec<u64>>());
+        let file_num = list_file_names(&dir).len();
+        assert!(file_num > 1);
+
+        // Entries before seq 1 are flushed, nothing to delete.
+        assert_eq!(mgr.delete_obsolete_files(1), 0);
+        // Entries before seq 6 are flushed.
+        let deleted = mgr.delete_obsolete_files(6);
+        assert!(deleted > 0);
+        assert_eq!(list_file_names(&dir).len(), file_num - deleted);
+        check_wal_files(PathBuf::from(&dir));
+        mgr.close().await.unwrap();
+
+        // Sequence continues after restart.
+        let mut mgr = WalManager::new(wal_config);
+        assert_eq!(mgr.current_seq_no(), 10);
+        let (seq, _) = mgr.write(WalEntryType::Write, &[0_u8; 8]).await.unwrap();
+        assert_eq!(seq, 11);
+        // All files except the current one are deleted.
+        mgr.delete_obsolete_files(11);
+        assert_eq!(list_file_names(&dir).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_copy_entries() {
+        let dir = "/tmp/test/wal/10".to_string();
+        let _ = std::fs::remove_dir_all(dir.clone()); // Ignore errors
+        let mut global_config = get_config("../config/config.toml");
+        global_config.wal.path = dir.clone();
+        global_config.wal.sync = false;
+        let wal_config = Arc::new(WalOptions::from(&global_config));
+
+        let mut mgr = WalManager::new(wal_config.clone());
+        let write = |database: &str| {
+            let mut fbb = flatbuffers::FlatBufferBuilder::new();
+            let points = models_helper::create_dev_ops_points(&mut fbb, 10, database, "cpu");
+            fbb.finish(points, None);
+            let mut enc_points = Vec::new();
+            get_str_codec(Encoding::Zstd)
+                .encode(&[fbb.finished_data()], &mut enc_points)
+                .unwrap();
+            enc_points
+        };
+        // Entries of db_a are at odd sequences.
+        for _i in 0..5 {
+            mgr.write(WalEntryType::Write, &write("db_a"))
+                .await
+                .unwrap();
+            mgr.write(WalEntryType::Write, &write("db_b"))
+                .await
+                .unwrap();
+        }
+        let files = mgr.snapshot_files(2).unwrap();
+        // Entries written after the snapshot are not copied.
+        mgr.write(WalEntryType::Write, &write("db_a"))
+            .await
+            .unwrap();
+        let dst = PathBuf::from(&dir).join("copy_1");
+        let copied = wal::copy_entries(files, "db_a", 2, &dst, wal_config.clone())
+            .await
+            .unwrap();
+        assert_eq!(copied, 4);
+        let (seqs, err) = read_wal_file(&dst);
+        assert_eq!(seqs, vec![1, 2, 3, 4]);
+        assert!(err.is_none());
+
+        // A corrupted entry fails the copy.
+        mgr.close().await.unwrap();
+        corrupt_last_byte(&file_utils::make_wal_file(&dir, mgr.current_file_id()));
+        let files = mgr.snapshot_files(0).unwrap();
+        let dst = PathBuf::from(&dir).join("copy_2");
+        let ret = wal::copy_entries(files, "db_a", 0, &dst, wal_config).await;
+        assert!(matches!(ret, Err(Error::WalCorrupted { .. })));
+    }
+
+    type WriteReceiver = oneshot::Receiver<crate::Result<(u64, usize)>>;
+
+    fn write_tasks(num: usize) -> (Vec<WalTask>, Vec<WriteReceiver>) {
+        (0..num)
+            .map(|_| {
+                let (cb, rx) = oneshot::channel();
+                let points = Arc::new(vec![1_u8; 64]);
+                (WalTask::Write { points, cb }, rx)
+            })
+            .unzip()
+    }
+
+    /// Returns sequences of the acknowledged tasks, `None` if not acknowledged.
+    fn acked_seqs(receivers: &mut [WriteReceiver]) -> Vec<Option<u64>> {
+        receivers
+            .iter_mut()
+            .map(|rx| rx.try_recv().ok().map(|ret| ret.unwrap().0))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_group_commit() {
+        let dir = "/tmp/test/wal/8".to_string();
+        let _ = std::fs::remove_dir_all(dir.clone()); // Ignore errors
+        let mut global_config = get_config("../config/config.toml");
+        global_config.wal.path = dir.clone();
+
+        // Acknowledged immediately without sync.
+        global_config.wal.sync = false;
+        let mut mgr = WalManager::new(Arc::new(WalOptions::from(&global_config)));
+        let (tasks, mut receivers) = write_tasks(5);
+        mgr.write_tasks(tasks).await;
+        assert_eq!(
+            acked_seqs(&mut receivers),
+            (1..=5).map(Some).collect::<Vec<_>>()
+        );
+        mgr.close().await.unwrap();
+
+        // Synced and acknowledged after every batch.
+        global_config.wal.sync = true;
+        let mut mgr = WalManager::new(Arc::new(WalOptions::from(&global_config)));
+        let (tasks, mut receivers) = write_tasks(5);
+        mgr.write_tasks(tasks).await;
+        assert_eq!(
+            acked_seqs(&mut receivers),
+            (6..=10).map(Some).collect::<Vec<_>>()
+        );
+        mgr.close().await.unwrap();
+
+        // Acknowledged after the next sync by interval.
+        global_config.wal.sync_interval = 100;
+        let mut mgr = WalManager::new(Arc::new(WalOptions::from(&global_config)));
+        let (tasks, mut receivers) = write_tasks(5);
+        mgr.write_tasks(tasks).await;
+        assert_eq!(acked_seqs(&mut receivers), vec![None; 5]);
+        mgr.sync().unwrap();
+        assert_eq!(
+            acked_seqs(&mut receivers),
+            (11..=15).map(Some).collect::<Vec<_>>()
+        );
+
+        mgr.close().await.unwrap();
+
+        // Acknowledged after the sync by size.
+        global_config.wal.sync_every_n_bytes = 64 * 8;
+        let mut mgr = WalManager::new(Arc::new(WalOptions::from(&global_config)));
+        let (tasks, mut receivers_1) = write_tasks(2);
+        mgr.write_tasks(tasks).await;
+        assert_eq!(acked_seqs(&mut receivers_1), vec![None; 2]);
+        let (tasks, mut receivers_2) = write_tasks(6);
+        mgr.write_tasks(tasks).await;
+        assert_eq!(acked_seqs(&mut receivers_1), vec![Some(16), Some(17)]);
+        assert_eq!(
+            acked_seqs(&mut receivers_2),
+            (18..=23).map(Some).collect::<Vec<_>>()
+        );
+
+        // Acknowledged when closing.
+        let (tasks, mut receivers) = write_tasks(1);
+        mgr.write_tasks(tasks).await;
+        assert_eq!(acked_seqs(&mut receivers), vec![None]);
+        mgr.close().await.unwrap();
+        assert_eq!(acked_seqs(&mut receivers), vec![Some(24)]);
+    }
+
     #[tokio::test]
     #[should_panic]
     async fn test_read_truncated() {
@@ -691,6 +1264,191 @@ mod test {
         check_wal_files(mgr.current_dir);
     }
 
+    #[tokio::test]
+    async fn test_read_corrupted() {
+        let dir = "/tmp/test/wal/6".to_string();
+        let _ = std::fs::remove_dir_all(dir.clone()); // Ignore errors
+        let mut global_config = get_config("../config/config.toml");
+        global_config.wal.path = dir.clone();
+        let wal_config = WalOptions::from(&global_config);
+
+        let mut mgr = WalManager::new(Arc::new(wal_config));
+        for _i in 0..10 {
+            mgr.write(WalEntryType::Write, &[1_u8; 64]).await.unwrap();
+        }
+        mgr.close().await.unwrap();
+        let path = file_utils::make_wal_file(&dir, mgr.current_file_id());
+        drop(mgr);
+
+        let (seqs, err) = read_wal_file(&path);
+        assert_eq!(seqs, (1..=10).collect::<Vec<u64>>());
+        assert!(err.is_none());
+
+        // Checksum of the last entry mismatches.
+        corrupt_last_byte(&path);
+        let (seqs, err) = read_wal_file(&path);
+        assert_eq!(seqs, (1..=9).collect::<Vec<u64>>());
+        assert!(matches!(err, Some(Error::WalCorrupted { .. })));
+
+        // The last entry is torn.
+        let file = file_manager::get_file_manager().open_file(&path).unwrap();
+        file.set_len(file.len() - 10);
+        file.sync_all(FileSync::Hard).unwrap();
+        drop(file);
+        let (seqs, err) = read_wal_file(&path);
+        assert_eq!(seqs, (1..=9).collect::<Vec<u64>>());
+        assert!(matches!(err, Some(Error::WalTruncated)));
+    }
+
+    #[test]
+    fn test_recover_from_corrupted_wal() {
+        init_default_global_tracing("tskv_log", "tskv.log", "debug");
+        let rt = Arc::new(runtime::Runtime::new().unwrap());
+        let dir = "/tmp/test/wal/7".to_string();
+        let dir_summary = "/tmp/test/wal/summary7".to_string();
+        let _ = std::fs::remove_dir_all(dir.clone()); // Ignore errors
+        let _ = std::fs::remove_dir_all(dir_summary.clone()); // Ignore errors
+        let mut global_config = get_config("../config/config.toml");
+        global_config.wal.path = dir.clone();
+        global_config.storage.path = dir_summary;
+        let wal_config = WalOptions::from(&global_config);
+        let mut mgr = WalManager::new(Arc::new(wal_config));
+        for _i in 0..10 {
+            let mut fbb = flatbuffers::FlatBufferBuilder::new();
+            let entry = wal_entry_block(&mut fbb);
+            let mut enc_points = Vec::new();
+            let coder = get_str_codec(Encoding::Zstd);
+            coder
+                .encode(&[&entry.buf], &mut enc_points)
+                .map_err(|_| Error::Send)
+                .unwrap();
+            rt.block_on(mgr.write(WalEntryType::Write, &enc_points))
+                .unwrap();
+        }
+        rt.block_on(mgr.close()).unwrap();
+        let path = file_utils::make_wal_file(&dir, mgr.current_file_id());
+        drop(mgr);
+        corrupt_last_byte(&path);
+
+        global_config.wal.recovery_mode = "fail".to_string();
+        let opt = kv_option::Options::from(&global_config);
+        assert_eq!(opt.wal.recovery_mode, WalRecoveryMode::Fail);
+        let res = rt.block_on(TsKv::open(opt, rt.clone()));
+        assert!(matches!(res, Err(Error::WalCorrupted { .. })));
+
+        global_config.wal.recovery_mode = "tolerate_tail_corruption".to_string();
+        let opt = kv_option::Options::from(&global_config);
+        let tskv = rt.block_on(TsKv::open(opt, rt.clone())).unwrap();
+        let vers = tskv.get_db_versions("db0", &[TimeRange::all()]).unwrap();
+        assert_eq!(vers.len(), 1);
+        // Each entry has 5 rows, rows of the last entry are dropped.
+        let data = format!("{:?}", vers[0].caches.mut_cache.read().read_series_data());
+        assert_eq!(data.matches("RowData").count(), 45);
+
+        // The corrupted entry is truncated.
+        let file = file_manager::get_file_manager().open_file(&path).unwrap();
+        let mut reader = WalReader::new(file.into()).unwrap();
+        let mut entries = 0;
+        while reader.next_wal_entry().unwrap().is_some() {
+            entries += 1;
+        }
+        assert_eq!(entries, 9);
+    }
+
+    #[test]
+    fn test_recover_from_corrupted_old_wal() {
+        init_default_global_tracing("tskv_log", "tskv.log", "debug");
+        let rt = Arc::new(runtime::Runtime::new().unwrap());
+        let dir = "/tmp/test/wal/9".to_string();
+        let dir_summary = "/tmp/test/wal/summary9".to_string();
+        let _ = std::fs::remove_dir_all(dir.clone()); // Ignore errors
+        let _ = std::fs::remove_dir_all(dir_summary.clone()); // Ignore errors
+        let mut global_config = get_config("../config/config.toml");
+        global_config.wal.path = dir.clone();
+        global_config.storage.path = dir_summary;
+        global_config.wal.recovery_mode = "tolerate_tail_corruption".to_string();
+        let wal_config = Arc::new(WalOptions::from(&global_config));
+
+        // Write 3 WAL files, each file has 2 entries.
+        let mut paths = vec![];
+        for _ in 0..3 {
+            let mut mgr = WalManager::new(wal_config.clone());
+            for _ in 0..2 {
+                let mut fbb = flatbuffers::FlatBufferBuilder::new();
+                let entry = wal_entry_block(&mut fbb);
+                let mut enc_points = Vec::new();
+                let coder = get_str_codec(Encoding::Zstd);
+                coder.encode(&[&entry.buf], &mut enc_points).unwrap();
+                rt.block_on(mgr.write(WalEntryType::Write, &enc_points))
+                    .unwrap();
+            }
+            rt.block_on(mgr.close()).unwrap();
+            paths.push(file_utils::make_wal_file(&dir, mgr.current_file_id()));
+        }
+        let len = file_manager::get_file_manager()
+            .open_file(&paths[1])
+            .unwrap()
+            .len();
+        corrupt_last_byte(&paths[1]);
+
+        // Only corruption at the tail of the last WAL file is tolerated.
+        let opt = kv_option::Options::from(&global_config);
+        let res = rt.block_on(TsKv::open(opt, rt.clone()));
+        assert!(matches!(res, Err(Error::WalCorrupted { .. })));
+        let file = file_manager::get_file_manager()
+            .open_file(&paths[1])
+            .unwrap();
+        assert_eq!(file.len(), len);
+    }
+
+    #[test]
+    fn test_recover_from_v1_wal() {
+        init_default_global_tracing("tskv_log", "tskv.log", "debug");
+        let rt = Arc::new(runtime::Runtime::new().unwrap());
+        let dir = "/tmp/test/wal/10".to_string();
+        let dir_summary = "/tmp/test/wal/summary10".to_string();
+        let _ = std::fs::remove_dir_all(dir.clone()); // Ignore errors
+        let _ = std::fs::remove_dir_all(dir_summary.clone()); // Ignore errors
+        let mut global_config = get_config("../config/config.toml");
+        global_config.wal.path = dir.clone();
+        global_config.storage.path = dir_summary;
+        global_config.wal.recovery_mode = "fail".to_string();
+
+        // Write a WAL file in the old format: the entry crc is the checksum of data,
+        // and the file is not closed so the max sequence in header is 0.
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = file_utils::make_wal_file(&dir, 1);
+        let mut buf = vec![0_u8; wal::SEGMENT_HEADER_SIZE];
+        buf[..4].copy_from_slice(&wal::SEGMENT_MAGIC_V1);
+        buf[4..12].copy_from_slice(&1_u64.to_be_bytes());
+        for seq in 1..=10_u64 {
+            let mut fbb = flatbuffers::FlatBufferBuilder::new();
+            let entry = wal_entry_block(&mut fbb);
+            let mut enc_points = Vec::new();
+            let coder = get_str_codec(Encoding::Zstd);
+            coder.encode(&[&entry.buf], &mut enc_points).unwrap();
+            buf.push(WalEntryType::Write as u8);
+            buf.extend_from_slice(&seq.to_be_bytes());
+            buf.extend_from_slice(&crc32fast::hash(&enc_points).to_be_bytes());
+            buf.extend_from_slice(&(enc_points.len() as u32).to_be_bytes());
+            buf.extend_from_slice(&enc_points);
+        }
+        std::fs::write(&path, &buf).unwrap();
+        let (seqs, err) = read_wal_file(&path);
+        assert_eq!(seqs, Vec::from_iter(1..=10));
+        assert!(err.is_none());
+
+        let opt = kv_option::Options::from(&global_config);
+        let tskv = rt.block_on(TsKv::open(opt, rt.clone())).unwrap();
+        let vers = tskv.get_db_versions("db0", &[TimeRange::all()]).unwrap();
+        assert_eq!(vers.len(), 1);
+        // Each entry has 5 rows.
+        let data = format!("{:?}", vers[0].caches.mut_cache.read().read_series_data());
+        assert_eq!(data.matches("RowData").count(), 50);
+        // The old WAL file is kept as it is.
+        assert_eq!(std::fs::read(&path).unwrap(), buf);
+    }
+
     #[test]
     fn test_recover_from_wal() {
         init_default_global_tracing("tskv_log", "tskv.log", "debug");
@@ -722,7 +1480,9 @@ mod test {
         check_wal_files(mgr.current_dir);
         let opt = kv_option::Options::from(&global_config);
         let tskv = rt.block_on(TsKv::open(opt, rt.clone())).unwrap();
-        let ver = tskv.get_db_version("db0").unwrap().unwrap();
+        let vers = tskv.get_db_versions("db0", &[TimeRange::all()]).unwrap();
+        assert_eq!(vers.len(), 1);
+        let ver = vers[0].clone();
         let expect = r#"range: TimeRange { min_ts: 1, max_ts: 1 }, rows: [RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }, RowData { ts: 1, fields: [Some(Integer(100)), Some(Float(4.94e-321))] }], size: 736 }] } })]"#;
         let ans = format!("{:?}", ver.caches.mut_cache.read().read_series_data());
         assert_eq!(&ans[622..], expect);
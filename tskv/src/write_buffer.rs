@@ -0,0 +1,132 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// Caches are flushed when memory usage reaches this ratio of the limit.
+const FLUSH_TRIGGER_RATIO: f64 = 0.875;
+
+/// Accounts memory of data in caches of all TseriesFamilies.
+///
+/// Memory is reserved when data is written into a cache, and released when the cache
+/// is removed from it's TseriesFamily, after it's flushed or the TseriesFamily is dropped.
+#[derive(Debug)]
+pub struct WriteBufferManager {
+    /// Max size of data in all caches, 0 means unlimited.
+    max_size: u64,
+    usage: AtomicU64,
+    released: Notify,
+}
+
+impl WriteBufferManager {
+    pub fn new(max_size: u64) -> Self {
+        Self {
+            max_size,
+            usage: AtomicU64::new(0),
+            released: Notify::new(),
+        }
+    }
+
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    pub fn memory_usage(&self) -> u64 {
+        self.usage.load(Ordering::Acquire)
+    }
+
+    pub fn reserve(&self, size: u64) {
+        self.usage.fetch_add(size, Ordering::AcqRel);
+    }
+
+    pub fn release(&self, size: u64) {
+        if size == 0 {
+            return;
+        }
+        let _ = self
+            .usage
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |u| {
+                Some(u.saturating_sub(size))
+            });
+        self.released.notify_waiters();
+    }
+
+    /// Returns the size of data that should be flushed to keep memory usage under
+    /// the flush trigger, `flushing` is the size of data being flushed.
+    pub fn size_to_flush(&self, flushing: u64) -> u64 {
+        if self.max_size == 0 {
+            return 0;
+        }
+        let trigger = (self.max_size as f64 * FLUSH_TRIGGER_RATIO) as u64;
+        let usage = self.memory_usage().saturating_sub(flushing);
+        if usage < trigger {
+            0
+        } else {
+            // Flush more than needed, so that flush is not triggered by every write.
+            usage - trigger / 2
+        }
+    }
+
+    /// Returns true if writes should be stalled until memory is released.
+    pub fn should_stall(&self) -> bool {
+        self.max_size > 0 && self.memory_usage() >= self.max_size
+    }
+
+    /// Waits until memory usage is under the limit, returns false if it's still
+    /// not after `timeout`.
+    pub async fn wait_for_memory(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            // Created before checking, so that releases after the check are not missed.
+            let released = self.released.notified();
+            if !self.should_stall() {
+                return true;
+            }
+            if tokio::time::timeout_at(deadline, released).await.is_err() {
+                return !self.should_stall();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::WriteBufferManager;
+
+    #[tokio::test]
+    async fn test_write_buffer_manager() {
+        let unlimited = WriteBufferManager::new(0);
+        unlimited.reserve(u64::MAX / 2);
+        assert_eq!(unlimited.size_to_flush(0), 0);
+        assert!(!unlimited.should_stall());
+
+        let manager = Arc::new(WriteBufferManager::new(800));
+        manager.reserve(600);
+        assert_eq!(manager.size_to_flush(0), 0);
+        manager.reserve(100);
+        // Usage 700 reached the trigger 700, flush to 350.
+        assert_eq!(manager.size_to_flush(0), 350);
+        assert_eq!(manager.size_to_flush(100), 0);
+        assert!(!manager.should_stall());
+
+        manager.reserve(100);
+        assert!(manager.should_stall());
+        assert!(!manager.wait_for_memory(Duration::from_millis(10)).await);
+
+        let m = manager.clone();
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            m.release(400);
+        });
+        assert!(manager.wait_for_memory(Duration::from_secs(10)).await);
+        release.await.unwrap();
+        assert_eq!(manager.memory_usage(), 400);
+
+        manager.release(1000);
+        assert_eq!(manager.memory_usage(), 0);
+    }
+}
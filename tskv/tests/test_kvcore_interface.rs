@@ -17,7 +17,7 @@ mod tests {
     use trace::{debug, error, info, init_default_global_tracing, warn};
     use tskv::engine::Engine;
     use tskv::file_system::file_manager;
-    use tskv::{kv_option, TsKv};
+    use tskv::{kv_option, TimeRange, TsKv};
 
     fn get_tskv() -> (Arc<Runtime>, TsKv) {
         let mut global_config = get_config("../config/config.toml");
@@ -28,6 +28,26 @@ mod tests {
         rt.block_on(async { (rt.clone(), TsKv::open(opt, rt.clone()).await.unwrap()) })
     }
 
+    fn now_timestamp() -> i64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as i64
+    }
+
+    /// Returns the directories of the TseriesFamilies storing points of database 'db' at `ts`.
+    fn tsfamily_dirs(tskv: &TsKv, dir: &str, ts: i64) -> Vec<String> {
+        tskv.get_db_versions("db", &[TimeRange::new(ts, ts)])
+            .unwrap()
+            .iter()
+            .map(|v| format!("data/db/data/db/{}/{}", dir, v.ts_family_id))
+            .collect()
+    }
+
+    fn any_exists(dirs: &[String]) -> bool {
+        dirs.iter().any(file_manager::try_exists)
+    }
+
     #[test]
     #[serial]
     fn test_kvcore_init() {
@@ -83,7 +103,7 @@ mod tests {
             tokio::time::sleep(Duration::from_secs(3)).await;
         });
 
-        assert!(file_manager::try_exists("data/db/data/db/tsm/0"))
+        assert!(any_exists(&tsfamily_dirs(&tskv, "tsm", now_timestamp())))
     }
 
     #[test]
@@ -134,8 +154,9 @@ mod tests {
             tokio::time::sleep(Duration::from_secs(3)).await;
         });
 
-        assert!(file_manager::try_exists("data/db/data/db/tsm/0"));
-        assert!(file_manager::try_exists("data/db/data/db/delta/0"));
+        assert!(any_exists(&tsfamily_dirs(&tskv, "tsm", now_timestamp())));
+        // Points at i64::MIN are routed to another vnode than the points at now.
+        assert!(any_exists(&tsfamily_dirs(&tskv, "delta", i64::MIN)));
     }
 
     #[tokio::test]